@@ -0,0 +1,46 @@
+#![feature(test)]
+
+extern crate test;
+extern crate rand;
+extern crate bellman_ce as bellman;
+extern crate franklin_crypto;
+
+use rand::{XorShiftRng, SeedableRng, Rand};
+use bellman::pairing::bls12_381::Bls12;
+use franklin_crypto::jubjub::{JubjubBls12, Unknown, edwards, fs::Fs};
+
+#[bench]
+fn bench_edwards_mul(b: &mut test::Bencher) {
+    let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+    let params = JubjubBls12::new();
+    let point = edwards::Point::<Bls12, Unknown>::rand(rng, &params);
+    let scalars: Vec<Fs> = (0..100).map(|_| Fs::rand(rng)).collect();
+
+    let mut count = 0;
+    b.iter(|| {
+        count = (count + 1) % scalars.len();
+        point.mul(scalars[count], &params)
+    });
+}
+
+#[bench]
+fn bench_edwards_into_xy_1000(b: &mut test::Bencher) {
+    let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+    let params = JubjubBls12::new();
+    let points: Vec<_> = (0..1000).map(|_| edwards::Point::<Bls12, Unknown>::rand(rng, &params).double(&params)).collect();
+
+    b.iter(|| {
+        points.iter().map(|p| p.into_xy()).collect::<Vec<_>>()
+    });
+}
+
+#[bench]
+fn bench_edwards_batch_normalize_1000(b: &mut test::Bencher) {
+    let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+    let params = JubjubBls12::new();
+    let points: Vec<_> = (0..1000).map(|_| edwards::Point::<Bls12, Unknown>::rand(rng, &params).double(&params)).collect();
+
+    b.iter(|| {
+        edwards::Point::batch_normalize(&points)
+    });
+}
@@ -0,0 +1,41 @@
+#![feature(test)]
+
+extern crate test;
+extern crate franklin_crypto;
+
+use franklin_crypto::jubjub::JubjubBls12;
+use franklin_crypto::alt_babyjubjub::AltJubjubBn256;
+
+#[bench]
+fn bench_jubjub_bls12_new(b: &mut test::Bencher) {
+    b.iter(|| {
+        JubjubBls12::new()
+    });
+}
+
+#[bench]
+fn bench_jubjub_bls12_read(b: &mut test::Bencher) {
+    let mut file = vec![];
+    JubjubBls12::new().write(&mut file).unwrap();
+
+    b.iter(|| {
+        JubjubBls12::read(&file[..]).unwrap()
+    });
+}
+
+#[bench]
+fn bench_alt_jubjub_bn256_new(b: &mut test::Bencher) {
+    b.iter(|| {
+        AltJubjubBn256::new()
+    });
+}
+
+#[bench]
+fn bench_alt_jubjub_bn256_read(b: &mut test::Bencher) {
+    let mut file = vec![];
+    AltJubjubBn256::new().write(&mut file).unwrap();
+
+    b.iter(|| {
+        AltJubjubBn256::read(&file[..]).unwrap()
+    });
+}
@@ -42,10 +42,18 @@ use bellman::pairing::ff::{
     PrimeField,
 };
 
-use group_hash::{baby_group_hash, generic_group_hash};
+use group_hash::{GeneratorSet, BlakeHasher};
 
 use constants;
 
+use std::io::{
+    self,
+    Read,
+    Write
+};
+
+use super::jubjub::params_file;
+
 use bellman::pairing::bn256::{
     Bn256,
     Fr
@@ -58,6 +66,10 @@ pub use super::jubjub::{
     ToUniform,
     JubjubEngine,
     JubjubParams,
+    TwistedEdwardsParams,
+    EdwardsCoordinates,
+    FixedBaseTable,
+    fixed_base_windows,
     edwards,
     montgomery
 };
@@ -83,6 +95,7 @@ impl JubjubEngine for Bn256 {
 }
 
 pub struct AltJubjubBn256 {
+    edwards_a: Fr,
     edwards_d: Fr,
     montgomery_a: Fr,
     montgomery_2a: Fr,
@@ -94,13 +107,19 @@ pub struct AltJubjubBn256 {
 
     fixed_base_generators: Vec<edwards::Point<Bn256, PrimeOrder>>,
     fixed_base_circuit_generators: Vec<Vec<Vec<(Fr, Fr)>>>,
+
+    fixed_base_tables: Vec<FixedBaseTable<Bn256>>,
 }
 
-impl JubjubParams<Bn256> for AltJubjubBn256 {
+impl TwistedEdwardsParams<Bn256> for AltJubjubBn256 {
+    fn edwards_a(&self) -> &Fr { &self.edwards_a }
     fn edwards_d(&self) -> &Fr { &self.edwards_d }
     fn montgomery_a(&self) -> &Fr { &self.montgomery_a }
     fn montgomery_2a(&self) -> &Fr { &self.montgomery_2a }
     fn scale(&self) -> &Fr { &self.scale }
+}
+
+impl JubjubParams<Bn256> for AltJubjubBn256 {
     fn pedersen_hash_generators(&self) -> &[edwards::Point<Bn256, PrimeOrder>] {
         &self.pedersen_hash_generators
     }
@@ -127,234 +146,58 @@ impl JubjubParams<Bn256> for AltJubjubBn256 {
     fn pedersen_hash_exp_window_size(&self) -> u32 {
         8
     }
+    fn fixed_base_tables(&self) -> &[FixedBaseTable<Bn256>] {
+        &self.fixed_base_tables
+    }
 }
 
 impl AltJubjubBn256 {
-    pub fn new() -> Self {
-        let montgomery_a = Fr::from_str("168698").unwrap();
-        let mut montgomery_2a = montgomery_a;
-        montgomery_2a.double();
-
-        let mut tmp_params = AltJubjubBn256 {
-            // d = -(168696/168700)
-            edwards_d: Fr::from_str("12181644023421730124874158521699555681764249180949974110617291017600649128846").unwrap(),
-            // A = 168698
-            montgomery_a: montgomery_a,
-            // 2A = 2.A
-            montgomery_2a: montgomery_2a,
-            // scaling factor = sqrt(4 / (a - d))
-            scale: Fr::from_str("6360561867910373094066688120553762416144456282423235903351243436111059670888").unwrap(),
-
-            // We'll initialize these below
-            pedersen_hash_generators: vec![],
-            pedersen_hash_exp: vec![],
-            pedersen_circuit_generators: vec![],
-            fixed_base_generators: vec![],
-            fixed_base_circuit_generators: vec![],
-        };
-
-        fn find_group_hash<E: JubjubEngine>(
-            m: &[u8],
-            personalization: &[u8; 8],
-            params: &E::Params
-        ) -> edwards::Point<E, PrimeOrder>
-        {
-            let mut tag = m.to_vec();
-            let i = tag.len();
-            tag.push(0u8);
-
-            loop {
-                let gh = baby_group_hash(
-                    &tag,
-                    personalization,
-                    params
-                );
-
-                // We don't want to overflow and start reusing generators
-                assert!(tag[i] != u8::max_value());
-                tag[i] += 1;
-
-                if let Some(gh) = gh {
-                    break gh;
-                }
-            }
-        }
-
-        // Create the bases for the Pedersen hashes
-        {
-            let mut pedersen_hash_generators = vec![];
-
-            for m in 0..5 {
-                use byteorder::{WriteBytesExt, LittleEndian};
-
-                let mut segment_number = [0u8; 4];
-                (&mut segment_number[0..4]).write_u32::<LittleEndian>(m).unwrap();
-
-                pedersen_hash_generators.push(
-                    find_group_hash(
-                        &segment_number,
-                        constants::PEDERSEN_HASH_GENERATORS_PERSONALIZATION,
-                        &tmp_params
-                    )
-                );
-            }
-
-            // Check for duplicates, far worse than spec inconsistencies!
-            for (i, p1) in pedersen_hash_generators.iter().enumerate() {
-                if p1 == &edwards::Point::zero() {
-                    panic!("Neutral element!");
-                }
-
-                for p2 in pedersen_hash_generators.iter().skip(i+1) {
-                    if p1 == p2 {
-                        panic!("Duplicate generator!");
-                    }
-                }
-            }
-
-            tmp_params.pedersen_hash_generators = pedersen_hash_generators;
-        }
-
-        // Create the exp table for the Pedersen hash generators
-        {
-            let mut pedersen_hash_exp = vec![];
-
-            for g in &tmp_params.pedersen_hash_generators {
-                let mut g = g.clone();
-
-                let window = tmp_params.pedersen_hash_exp_window_size();
-
-                let mut tables = vec![];
-
-                let mut num_bits = 0;
-                while num_bits <= fs::Fs::NUM_BITS {
-                    let mut table = Vec::with_capacity(1 << window);
-
-                    let mut base = edwards::Point::zero();
-
-                    for _ in 0..(1 << window) {
-                        table.push(base.clone());
-                        base = base.add(&g, &tmp_params);
-                    }
-
-                    tables.push(table);
-                    num_bits += window;
-
-                    for _ in 0..window {
-                        g = g.double(&tmp_params);
-                    }
-                }
-
-                pedersen_hash_exp.push(tables);
-            }
-
-            tmp_params.pedersen_hash_exp = pedersen_hash_exp;
-        }
-
-        // Create the bases for other parts of the protocol
-        {
-            let mut fixed_base_generators = vec![edwards::Point::zero(); FixedGenerators::Max as usize];
-
-            fixed_base_generators[FixedGenerators::ProofGenerationKey as usize] =
-                find_group_hash(&[], constants::PROOF_GENERATION_KEY_BASE_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::NoteCommitmentRandomness as usize] =
-                find_group_hash(b"r", constants::PEDERSEN_HASH_GENERATORS_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::NullifierPosition as usize] =
-                find_group_hash(&[], constants::NULLIFIER_POSITION_IN_TREE_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::ValueCommitmentValue as usize] =
-                find_group_hash(b"v", constants::VALUE_COMMITMENT_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::ValueCommitmentRandomness as usize] =
-                find_group_hash(b"r", constants::VALUE_COMMITMENT_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::SpendingKeyGenerator as usize] =
-                find_group_hash(&[], constants::SPENDING_KEY_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            // Check for duplicates, far worse than spec inconsistencies!
-            for (i, p1) in fixed_base_generators.iter().enumerate() {
-                if p1 == &edwards::Point::zero() {
-                    panic!("Neutral element!");
-                }
-
-                for p2 in fixed_base_generators.iter().skip(i+1) {
-                    if p1 == p2 {
-                        panic!("Duplicate generator!");
-                    }
-                }
-            }
-
-            tmp_params.fixed_base_generators = fixed_base_generators;
+    /// Adds window tables for application specific bases, so they can be
+    /// used for fixed-base exponentiation in the circuit. Tables are
+    /// available through `fixed_base_tables` in the same order as `bases`.
+    pub fn with_fixed_bases(mut self, bases: &[edwards::Point<Bn256, PrimeOrder>]) -> Self {
+        for base in bases {
+            let table = FixedBaseTable::new(base, &self);
+            self.fixed_base_tables.push(table);
         }
 
-        // Create the 2-bit window table lookups for each 4-bit
-        // "chunk" in each segment of the Pedersen hash
-        {
-            let mut pedersen_circuit_generators = vec![];
-
-            // Process each segment
-            for mut gen in tmp_params.pedersen_hash_generators.iter().cloned() {
-                let mut gen = montgomery::Point::from_edwards(&gen, &tmp_params);
-                let mut windows = vec![];
-                for _ in 0..tmp_params.pedersen_hash_chunks_per_generator() {
-                    // Create (x, y) coeffs for this chunk
-                    let mut coeffs = vec![];
-                    let mut g = gen.clone();
-
-                    // coeffs = g, g*2, g*3, g*4
-                    for _ in 0..4 {
-                        coeffs.push(g.into_xy().expect("cannot produce O"));
-                        g = g.add(&gen, &tmp_params);
-                    }
-                    windows.push(coeffs);
-
-                    // Our chunks are separated by 2 bits to prevent overlap.
-                    for _ in 0..4 {
-                        gen = gen.double(&tmp_params);
-                    }
-                }
-                pedersen_circuit_generators.push(windows);
-            }
-
-            tmp_params.pedersen_circuit_generators = pedersen_circuit_generators;
-        }
-
-        // Create the 3-bit window table lookups for fixed-base
-        // exp of each base in the protocol.
-        {
-            let mut fixed_base_circuit_generators = vec![];
+        self
+    }
 
-            for mut gen in tmp_params.fixed_base_generators.iter().cloned() {
-                let mut windows = vec![];
-                for _ in 0..tmp_params.fixed_base_chunks_per_generator() {
-                    let mut coeffs = vec![(Fr::zero(), Fr::one())];
-                    let mut g = gen.clone();
-                    for _ in 0..7 {
-                        coeffs.push(g.into_xy());
-                        g = g.add(&gen, &tmp_params);
-                    }
-                    windows.push(coeffs);
+    /// Writes the generators and all the precomputed tables, so that
+    /// the parameters can be loaded with `read` instead of recomputed
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        params_file::write::<Bn256, edwards::Point<Bn256, PrimeOrder>, _>(self, writer)
+    }
 
-                    // gen = gen * 8
-                    gen = g;
-                }
-                fixed_base_circuit_generators.push(windows);
-            }
+    /// Reads the parameters written by `write`, checking their integrity
+    /// and that they were computed for this curve
+    pub fn read<R: Read>(reader: R) -> io::Result<Self> {
+        let mut params = Self::curve();
+        let data = params_file::read::<Bn256, edwards::Point<Bn256, PrimeOrder>, _>(reader, &params)?;
 
-            tmp_params.fixed_base_circuit_generators = fixed_base_circuit_generators;
-        }
+        params.pedersen_hash_generators = data.pedersen_hash_generators;
+        params.pedersen_hash_exp = data.pedersen_hash_exp;
+        params.pedersen_circuit_generators = data.pedersen_circuit_generators;
+        params.fixed_base_generators = data.fixed_base_generators;
+        params.fixed_base_circuit_generators = data.fixed_base_circuit_generators;
+        params.fixed_base_tables = params_file::fixed_base_tables(data.fixed_base_tables);
 
-        tmp_params
+        Ok(params)
     }
 
-    pub fn new_with_hasher<H: GroupHasher>() -> Self {
+    /// Curve constants, without any generators or tables
+    fn curve() -> Self {
         let montgomery_a = Fr::from_str("168698").unwrap();
         let mut montgomery_2a = montgomery_a;
         montgomery_2a.double();
 
-        let mut tmp_params = AltJubjubBn256 {
+        let mut edwards_a = Fr::one();
+        edwards_a.negate();
+
+        AltJubjubBn256 {
+            // a = -1
+            edwards_a: edwards_a,
             // d = -(168696/168700)
             edwards_d: Fr::from_str("12181644023421730124874158521699555681764249180949974110617291017600649128846").unwrap(),
             // A = 168698
@@ -364,75 +207,27 @@ impl AltJubjubBn256 {
             // scaling factor = sqrt(4 / (a - d))
             scale: Fr::from_str("6360561867910373094066688120553762416144456282423235903351243436111059670888").unwrap(),
 
-            // We'll initialize these below
+            // Filled in by `from_generator_set` or `read`
             pedersen_hash_generators: vec![],
             pedersen_hash_exp: vec![],
             pedersen_circuit_generators: vec![],
             fixed_base_generators: vec![],
             fixed_base_circuit_generators: vec![],
-        };
-
-        fn find_group_hash<E: JubjubEngine, HH: GroupHasher>(
-            m: &[u8],
-            personalization: &[u8; 8],
-            params: &E::Params
-        ) -> edwards::Point<E, PrimeOrder>
-        {
-            let mut tag = m.to_vec();
-            let i = tag.len();
-            tag.push(0u8);
-
-            loop {
-                let gh = generic_group_hash::<_, HH>(
-                    &tag,
-                    personalization,
-                    params
-                );
-
-                // We don't want to overflow and start reusing generators
-                assert!(tag[i] != u8::max_value());
-                tag[i] += 1;
-
-                if let Some(gh) = gh {
-                    break gh;
-                }
-            }
+            fixed_base_tables: vec![],
         }
+    }
 
-        // Create the bases for the Pedersen hashes
-        {
-            let mut pedersen_hash_generators = vec![];
-
-            for m in 0..5 {
-                use byteorder::{WriteBytesExt, LittleEndian};
-
-                let mut segment_number = [0u8; 4];
-                (&mut segment_number[0..4]).write_u32::<LittleEndian>(m).unwrap();
-
-                pedersen_hash_generators.push(
-                    find_group_hash::<_, H>(
-                        &segment_number,
-                        constants::PEDERSEN_HASH_GENERATORS_PERSONALIZATION,
-                        &tmp_params
-                    )
-                );
-            }
+    pub fn new() -> Self {
+        Self::from_generator_set(&GeneratorSet::<BlakeHasher>::new())
+    }
 
-            // Check for duplicates, far worse than spec inconsistencies!
-            for (i, p1) in pedersen_hash_generators.iter().enumerate() {
-                if p1 == &edwards::Point::zero() {
-                    panic!("Neutral element!");
-                }
+    /// Constructs the parameters with the generators derived as
+    /// described by `generators`, instead of the Sapling ones.
+    pub fn from_generator_set<H: GroupHasher>(generators: &GeneratorSet<H>) -> Self {
+        let mut tmp_params = Self::curve();
 
-                for p2 in pedersen_hash_generators.iter().skip(i+1) {
-                    if p1 == p2 {
-                        panic!("Duplicate generator!");
-                    }
-                }
-            }
-
-            tmp_params.pedersen_hash_generators = pedersen_hash_generators;
-        }
+        // Create the bases for the Pedersen hashes
+        tmp_params.pedersen_hash_generators = generators.pedersen_hash_generators::<Bn256, _>(&tmp_params);
 
         // Create the exp table for the Pedersen hash generators
         {
@@ -471,42 +266,7 @@ impl AltJubjubBn256 {
         }
 
         // Create the bases for other parts of the protocol
-        {
-            let mut fixed_base_generators = vec![edwards::Point::zero(); FixedGenerators::Max as usize];
-
-            fixed_base_generators[FixedGenerators::ProofGenerationKey as usize] =
-                find_group_hash::<_, H>(&[], constants::PROOF_GENERATION_KEY_BASE_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::NoteCommitmentRandomness as usize] =
-                find_group_hash::<_, H>(b"r", constants::PEDERSEN_HASH_GENERATORS_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::NullifierPosition as usize] =
-                find_group_hash::<_, H>(&[], constants::NULLIFIER_POSITION_IN_TREE_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::ValueCommitmentValue as usize] =
-                find_group_hash::<_, H>(b"v", constants::VALUE_COMMITMENT_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::ValueCommitmentRandomness as usize] =
-                find_group_hash::<_, H>(b"r", constants::VALUE_COMMITMENT_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::SpendingKeyGenerator as usize] =
-                find_group_hash::<_, H>(&[], constants::SPENDING_KEY_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            // Check for duplicates, far worse than spec inconsistencies!
-            for (i, p1) in fixed_base_generators.iter().enumerate() {
-                if p1 == &edwards::Point::zero() {
-                    panic!("Neutral element!");
-                }
-
-                for p2 in fixed_base_generators.iter().skip(i+1) {
-                    if p1 == p2 {
-                        panic!("Duplicate generator!");
-                    }
-                }
-            }
-
-            tmp_params.fixed_base_generators = fixed_base_generators;
-        }
+        tmp_params.fixed_base_generators = generators.fixed_base_generators::<Bn256, _>(&tmp_params);
 
         // Create the 2-bit window table lookups for each 4-bit
         // "chunk" in each segment of the Pedersen hash
@@ -545,21 +305,10 @@ impl AltJubjubBn256 {
         {
             let mut fixed_base_circuit_generators = vec![];
 
-            for mut gen in tmp_params.fixed_base_generators.iter().cloned() {
-                let mut windows = vec![];
-                for _ in 0..tmp_params.fixed_base_chunks_per_generator() {
-                    let mut coeffs = vec![(Fr::zero(), Fr::one())];
-                    let mut g = gen.clone();
-                    for _ in 0..7 {
-                        coeffs.push(g.into_xy());
-                        g = g.add(&gen, &tmp_params);
-                    }
-                    windows.push(coeffs);
-
-                    // gen = gen * 8
-                    gen = g;
-                }
-                fixed_base_circuit_generators.push(windows);
+            for gen in tmp_params.fixed_base_generators.iter() {
+                fixed_base_circuit_generators.push(
+                    fixed_base_windows(gen, tmp_params.fixed_base_chunks_per_generator(), &tmp_params)
+                );
             }
 
             tmp_params.fixed_base_circuit_generators = fixed_base_circuit_generators;
@@ -567,6 +316,10 @@ impl AltJubjubBn256 {
 
         tmp_params
     }
+
+    pub fn new_with_hasher<H: GroupHasher>() -> Self {
+        Self::from_generator_set(&GeneratorSet::<H>::new())
+    }
 }
 
 #[test]
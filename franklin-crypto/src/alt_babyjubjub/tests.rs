@@ -1,6 +1,7 @@
 use super::{
     JubjubEngine,
     JubjubParams,
+    TwistedEdwardsParams,
     PrimeOrder,
     montgomery,
     edwards
@@ -2,6 +2,15 @@ use rand::{
     Rng
 };
 
+use byteorder::{
+    ReadBytesExt,
+    WriteBytesExt,
+    LittleEndian
+};
+
+use std::io::{self, Read, Write};
+use std::collections::HashMap;
+
 const EMPTY_STATE: usize = std::usize::MAX;
 
 // this is basically a grid of size x columns
@@ -13,7 +22,7 @@ pub struct AsWaksmanTopology {
 
 impl AsWaksmanTopology {
     pub fn new(size: usize) -> Self {
-        assert!(size > 1, "don't make strange moves");
+        assert!(size > 0, "don't make strange moves");
 
         let num_colunms = Self::num_colunms(size);
 
@@ -22,8 +31,10 @@ impl AsWaksmanTopology {
 
         let destinations: Vec<usize> = (0..size).collect();
 
-        // recursively iterate and construct the topology
-        Self::construct_inner(0, num_colunms-1, 0, size-1, &destinations, &mut topology);
+        // recursively iterate and construct the topology, a single element has no columns at all
+        if num_colunms > 0 {
+            Self::construct_inner(0, num_colunms-1, 0, size-1, &destinations, &mut topology);
+        }
 
         Self {
             topology,
@@ -271,7 +282,9 @@ impl AsWaksmanRoute {
 
         let inversed_permutation = permutation.inverse();
         assert!(inversed_permutation.inverse().elements == permutation.elements);
-        Self::construct_inner(0, num_columns-1, 0, size-1, permutation, &inversed_permutation, &mut assignments);
+        if num_columns > 0 {
+            Self::construct_inner(0, num_columns-1, 0, size-1, permutation, &inversed_permutation, &mut assignments);
+        }
 
         Self {
             switches: assignments,
@@ -584,7 +597,7 @@ impl AsWaksmanRoute {
         current_perm.elements == permutation.inverse().elements
     }
 
-    fn get_number_of_gates(&self) -> usize {
+    pub fn get_number_of_gates(&self) -> usize {
         let mut result = 0;
         for column in self.switches.iter() {
             result += column.len();
@@ -593,7 +606,7 @@ impl AsWaksmanRoute {
         result
     }
 
-    fn assign_switches(&mut self, switch_assignments: &[bool]) {
+    pub fn assign_switches(&mut self, switch_assignments: &[bool]) {
         let required_switches = self.get_number_of_gates();
         assert!(switch_assignments.len() == required_switches);
         let mut i = 0;
@@ -608,7 +621,7 @@ impl AsWaksmanRoute {
         }
     }
 
-    fn dump_assignments(&self) -> Vec<bool> {
+    pub fn dump_assignments(&self) -> Vec<bool> {
         let mut result = vec![false; self.get_number_of_gates()];
         let mut i = 0;
         for column in self.switches.iter() {
@@ -627,7 +640,7 @@ impl AsWaksmanRoute {
 
     // this function forwards newly created ordered set [0, n) into the permutation by switches
     // that were supplied to the router
-    fn calculate_permutation(&self) -> IntegerPermutation 
+    pub fn calculate_permutation(&self) -> IntegerPermutation 
     {
         let num_columns = AsWaksmanTopology::num_colunms(self.size);
         let topology = AsWaksmanTopology::new(self.size);
@@ -674,6 +687,124 @@ impl AsWaksmanRoute {
 
         permutation.inverse()
     }
+
+    // routes items through the switches, so the item number `i` goes into the place
+    // given by the routed permutation. Items can be anything, e.g. records of field elements
+    pub fn route<T: Clone>(&self, items: &[T]) -> Vec<T> {
+        assert_eq!(items.len(), self.size);
+        let permutation = self.calculate_permutation();
+
+        let mut result: Vec<Option<T>> = vec![None; self.size];
+        for (idx, item) in items.iter().enumerate() {
+            result[permutation.get(idx)] = Some(item.clone());
+        }
+
+        result.into_iter().map(|e| e.expect("routed permutation is valid")).collect()
+    }
+
+    pub fn witness(&self) -> AsWaksmanSwitchWitness {
+        AsWaksmanSwitchWitness {
+            size: self.size,
+            switches: self.dump_assignments()
+        }
+    }
+}
+
+// largest network size accepted when reading a serialized switch witness
+pub const MAX_SWITCH_WITNESS_SIZE: usize = 1 << 20;
+
+// number of switches in the network of the given size, without building the topology
+fn num_switches_for_size(size: usize, cache: &mut HashMap<usize, usize>) -> Option<usize> {
+    if size <= 1 {
+        return Some(0);
+    }
+    if size == 2 {
+        return Some(1);
+    }
+    if let Some(num) = cache.get(&size) {
+        return Some(*num);
+    }
+
+    // left and right columns, the last switch of the right column is fixed for an even size
+    let top = AsWaksmanTopology::calculate_top_height(size);
+    let num = top
+        .checked_add((size - 1) / 2)?
+        .checked_add(num_switches_for_size(top, cache)?)?
+        .checked_add(num_switches_for_size(size - top, cache)?)?;
+    cache.insert(size, num);
+
+    Some(num)
+}
+
+// switch settings of the network in the order of `AsWaksmanRoute::dump_assignments`,
+// so routing can be done once and then transferred to the machine that makes a proof
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsWaksmanSwitchWitness {
+    pub size: usize,
+    pub switches: Vec<bool>
+}
+
+impl AsWaksmanSwitchWitness {
+    pub fn new(permutation: &IntegerPermutation) -> Self {
+        AsWaksmanRoute::new(permutation).witness()
+    }
+
+    pub fn to_route(&self) -> io::Result<AsWaksmanRoute> {
+        if self.size == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "network can not be empty"));
+        }
+
+        let mut route = AsWaksmanRoute::new(&IntegerPermutation::new(self.size));
+        if route.get_number_of_gates() != self.switches.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "number of switches doesn't match the network size"));
+        }
+        route.assign_switches(&self.switches);
+
+        Ok(route)
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let size = reader.read_u64::<LittleEndian>()?;
+        if size == 0 || size > MAX_SWITCH_WITNESS_SIZE as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "network size is out of range"));
+        }
+        let size = size as usize;
+
+        // header must describe the network of this size before anything is allocated
+        let num_switches = reader.read_u64::<LittleEndian>()?;
+        let expected = num_switches_for_size(size, &mut HashMap::new())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "network size is out of range"))?;
+        if num_switches != expected as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "number of switches doesn't match the network size"));
+        }
+        let num_switches = expected;
+
+        // switches are packed into bytes, least significant bit first
+        let num_bytes = num_switches / 8 + if num_switches % 8 == 0 { 0 } else { 1 };
+        let mut packed = vec![0u8; num_bytes];
+        reader.read_exact(&mut packed)?;
+
+        let switches = (0..num_switches).map(|i| (packed[i / 8] >> (i % 8)) & 1 == 1).collect();
+
+        Ok(AsWaksmanSwitchWitness {
+            size,
+            switches
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u64::<LittleEndian>(self.size as u64)?;
+        writer.write_u64::<LittleEndian>(self.switches.len() as u64)?;
+
+        let mut packed = vec![0u8; (self.switches.len() + 7) / 8];
+        for (i, switch) in self.switches.iter().enumerate() {
+            if *switch {
+                packed[i / 8] |= 1 << (i % 8);
+            }
+        }
+
+        writer.write_all(&packed)
+    }
 }
 
 #[test]
@@ -869,4 +1000,104 @@ fn test_uniformity() {
     // // and P = 0.99
     // let alpha = 0.0015f64;
     // assert!(global_max < alpha);
-}
\ No newline at end of file
+}
+
+// every permutation of `0..size`, shared by the exhaustive tests of the network and the gadget
+#[cfg(test)]
+pub(crate) fn all_permutations(size: usize) -> Vec<Vec<usize>> {
+    if size == 0 {
+        return vec![vec![]];
+    }
+
+    // insert the largest element into every position of all smaller permutations
+    let mut result = vec![];
+    for smaller in all_permutations(size - 1).into_iter() {
+        for position in 0..size {
+            let mut permutation = smaller.clone();
+            permutation.insert(position, size - 1);
+            result.push(permutation);
+        }
+    }
+
+    result
+}
+
+#[test]
+fn test_exhaustive_routing_for_small_sizes() {
+    for size in 1..=8 {
+        let all = all_permutations(size);
+        assert_eq!(all.len(), (1..=size).product::<usize>());
+
+        for elements in all.into_iter() {
+            let mut permutation = IntegerPermutation::new(size);
+            permutation.elements = elements;
+            assert!(permutation.is_valid());
+
+            let router = AsWaksmanRoute::new(&permutation);
+            assert!(AsWaksmanRoute::validate_routing_for_permutation(&permutation, &router));
+            assert!(router.calculate_permutation().elements == permutation.elements);
+
+            // records are moved as a whole
+            let records: Vec<(usize, usize)> = (0..size).map(|i| (i, 100 + i)).collect();
+            let routed = router.route(&records);
+            for (i, record) in records.iter().enumerate() {
+                assert_eq!(routed[permutation.get(i)], *record);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_switch_witness_serialization() {
+    use rand::thread_rng;
+    let rng = &mut thread_rng();
+    for size in 1..64 {
+        let mut permutation = IntegerPermutation::new(size);
+        permutation.make_permutation(rng);
+
+        let witness = AsWaksmanSwitchWitness::new(&permutation);
+
+        let mut serialized = vec![];
+        witness.write(&mut serialized).unwrap();
+        assert_eq!(serialized.len(), 16 + (witness.switches.len() + 7) / 8);
+
+        let deserialized = AsWaksmanSwitchWitness::read(&serialized[..]).unwrap();
+        assert_eq!(deserialized, witness);
+
+        let route = deserialized.to_route().unwrap();
+        assert!(route.calculate_permutation().elements == permutation.elements);
+
+        // witness for a network of the other size
+        let mut wrong = witness.clone();
+        wrong.size += 1;
+        assert!(wrong.to_route().is_err());
+    }
+}
+
+#[test]
+fn test_switch_witness_header_validation() {
+    for size in 1..=256 {
+        let route = AsWaksmanRoute::new(&IntegerPermutation::new(size));
+        assert_eq!(num_switches_for_size(size, &mut HashMap::new()), Some(route.get_number_of_gates()));
+    }
+
+    let header = |size: u64, num_switches: u64| {
+        let mut serialized = vec![];
+        serialized.write_u64::<LittleEndian>(size).unwrap();
+        serialized.write_u64::<LittleEndian>(num_switches).unwrap();
+        serialized
+    };
+
+    // nothing is allocated for a hostile header
+    assert!(AsWaksmanSwitchWitness::read(&header(0, 0)[..]).is_err());
+    assert!(AsWaksmanSwitchWitness::read(&header(u64::max_value(), 0)[..]).is_err());
+    assert!(AsWaksmanSwitchWitness::read(&header(MAX_SWITCH_WITNESS_SIZE as u64 + 1, 0)[..]).is_err());
+    assert!(AsWaksmanSwitchWitness::read(&header(4, u64::max_value())[..]).is_err());
+    assert!(AsWaksmanSwitchWitness::read(&header(4, 4)[..]).is_err());
+
+    // header is right but the switches are missing
+    assert!(AsWaksmanSwitchWitness::read(&header(4, 5)[..]).is_err());
+    let mut serialized = header(4, 5);
+    serialized.push(0);
+    assert!(AsWaksmanSwitchWitness::read(&serialized[..]).is_ok());
+}
@@ -9,11 +9,15 @@ use bellman::pairing::ff::{
 use super::{
     JubjubEngine,
     JubjubParams,
+    TwistedEdwardsParams,
     Unknown,
     PrimeOrder,
     montgomery
 };
 
+use ::jubjub::{EdwardsCoordinates, EdwardsPoint, PrimeOrderPoint};
+use ::jubjub::edwards::{wnaf_mul, batch_normalize};
+
 use rand::{
     Rng
 };
@@ -39,6 +43,42 @@ pub struct Point<E: JubjubEngine, Subgroup> {
     _marker: PhantomData<Subgroup>
 }
 
+impl<E: JubjubEngine, Subgroup> EdwardsCoordinates<E> for Point<E, Subgroup> {
+    fn edwards_xy(&self) -> (E::Fr, E::Fr) {
+        self.into_xy()
+    }
+}
+
+impl<E: JubjubEngine, Subgroup> EdwardsPoint<E> for Point<E, Subgroup> {
+    type Params = E::Params;
+
+    fn zero() -> Self {
+        Point::zero()
+    }
+
+    fn into_projective(&self) -> (E::Fr, E::Fr, E::Fr) {
+        (self.x, self.y, self.z)
+    }
+
+    fn double(&self, params: &E::Params) -> Self {
+        Point::double(self, params)
+    }
+
+    fn add(&self, other: &Self, params: &E::Params) -> Self {
+        Point::add(self, other, params)
+    }
+
+    fn negate(&self) -> Self {
+        Point::negate(self)
+    }
+}
+
+impl<E: JubjubEngine> PrimeOrderPoint<E> for Point<E, PrimeOrder> {
+    fn read_mul_by_cofactor<R: Read>(reader: R, params: &E::Params) -> io::Result<Self> {
+        Ok(Point::<E, Unknown>::read(reader, params)?.mul_by_cofactor(params))
+    }
+}
+
 fn convert_subgroup<E: JubjubEngine, S1, S2>(from: &Point<E, S1>) -> Point<E, S2>
 {
     Point {
@@ -165,6 +205,36 @@ impl<E: JubjubEngine> Point<E, Unknown> {
         }
     }
 
+    pub fn from_xy(x: E::Fr, y: E::Fr, params: &E::Params) -> Option<Self>
+    {
+        // HERE it' different from jubjub
+        // check that a point is on curve
+        // a * x^2 + y^2 = 1 + d * x^2 * y^2
+
+        // tmp0 = x^2
+        let mut tmp0 = x;
+        tmp0.square();
+
+        // tmp1 = y^2
+        let mut tmp1 = y;
+        tmp1.square();
+
+        let mut lhs = tmp0;
+        lhs.mul_assign(params.edwards_a());
+        lhs.add_assign(&tmp1);
+
+        let mut rhs = tmp0;
+        rhs.mul_assign(&tmp1);
+        rhs.mul_assign(params.edwards_d());
+        rhs.add_assign(&E::Fr::one());
+
+        if rhs != lhs {
+            return None;
+        }
+
+        Some(Self::from_xy_unchecked(x, y))
+    }
+
     /// This guarantees the point is in the prime order subgroup
     #[must_use]
     pub fn mul_by_cofactor(&self, params: &E::Params) -> Point<E, PrimeOrder>
@@ -189,6 +259,22 @@ impl<E: JubjubEngine> Point<E, Unknown> {
 }
 
 impl<E: JubjubEngine, Subgroup> Point<E, Subgroup> {
+    /// Constructs the point from its affine coordinates without
+    /// checking that it is on the curve or in the subgroup. Only for
+    /// points that are known to be valid, e.g. from a checksummed file.
+    pub(crate) fn from_xy_unchecked(x: E::Fr, y: E::Fr) -> Self {
+        let mut t = x;
+        t.mul_assign(&y);
+
+        Point {
+            x: x,
+            y: y,
+            t: t,
+            z: E::Fr::one(),
+            _marker: PhantomData
+        }
+    }
+
     pub fn write<W: Write>(
         &self,
         writer: W
@@ -516,18 +602,12 @@ impl<E: JubjubEngine, Subgroup> Point<E, Subgroup> {
         params: &E::Params
     ) -> Self
     {
-        // Standard double-and-add scalar multiplication
-
-        let mut res = Self::zero();
-
-        for b in BitIterator::new(scalar.into()) {
-            res = res.double(params);
-
-            if b {
-                res = res.add(self, params);
-            }
-        }
+        wnaf_mul(self, scalar.into(), params)
+    }
 
-        res
+    /// Converts all the points into affine coordinates
+    /// at the cost of a single inversion
+    pub fn batch_normalize(points: &[Self]) -> Vec<(E::Fr, E::Fr)> {
+        batch_normalize(points)
     }
 }
@@ -29,15 +29,38 @@ use bellman::pairing::ff::{
     SqrtField
 };
 
-use baby_group_hash::group_hash;
+use group_hash::{GeneratorSet, GroupHasher, BlakeHasher};
 
 use constants;
 
+use std::io::{
+    self,
+    Read,
+    Write
+};
+
 use bellman::pairing::bn256::{
     Bn256,
     Fr
 };
 
+// Baby Jubjub keeps its own `JubjubEngine` and `JubjubParams`: `Bn256`
+// already implements the shared `jubjub::JubjubEngine` for alt Baby Jubjub,
+// the same curve in the `a = -1` form that the Jubjub point arithmetic is
+// written for, and an engine can implement it only once. What doesn't
+// depend on the engine trait is shared instead of copied: the curve
+// constants, the marker types and the fixed generators below, and the
+// point gadgets, which work with any `TwistedEdwardsParams`.
+use ::jubjub::params_file::{self, ParamsFilePoint};
+
+pub use ::jubjub::{
+    TwistedEdwardsParams,
+    EdwardsCoordinates,
+    Unknown,
+    PrimeOrder,
+    FixedGenerators
+};
+
 /// This is an implementation of the twisted Edwards Jubjub curve.
 pub mod edwards;
 
@@ -51,45 +74,6 @@ pub mod fs;
 #[cfg(test)]
 pub mod tests;
 
-/// Point of unknown order.
-pub enum Unknown { }
-
-/// Point of prime order.
-pub enum PrimeOrder { }
-
-/// Fixed generators of the Jubjub curve of unknown
-/// exponent.
-#[derive(Copy, Clone)]
-pub enum FixedGenerators {
-    /// The prover will demonstrate knowledge of discrete log
-    /// with respect to this base when they are constructing
-    /// a proof, in order to authorize proof construction.
-    ProofGenerationKey = 0,
-
-    /// The note commitment is randomized over this generator.
-    NoteCommitmentRandomness = 1,
-
-    /// The node commitment is randomized again by the position
-    /// in order to supply the nullifier computation with a
-    /// unique input w.r.t. the note being spent, to prevent
-    /// Faerie gold attacks.
-    NullifierPosition = 2,
-
-    /// The value commitment is used to check balance between
-    /// inputs and outputs. The value is placed over this
-    /// generator.
-    ValueCommitmentValue = 3,
-    /// The value commitment is randomized over this generator,
-    /// for privacy.
-    ValueCommitmentRandomness = 4,
-
-    /// The spender proves discrete log with respect to this
-    /// base at spend time.
-    SpendingKeyGenerator = 5,
-
-    Max = 6
-}
-
 pub trait ToUniform {
     fn to_uniform(digest: &[u8]) -> Self;
 }
@@ -106,17 +90,7 @@ pub trait JubjubEngine: Engine {
 
 /// The pre-computed parameters for Jubjub, including curve
 /// constants and various limits and window tables.
-pub trait JubjubParams<E: JubjubEngine>: Sized {
-    /// The `a` constant of the twisted Edwards curve.
-    fn edwards_a(&self) -> &E::Fr;
-    /// The `d` constant of the twisted Edwards curve.
-    fn edwards_d(&self) -> &E::Fr;
-    /// The `A` constant of the birationally equivalent Montgomery curve.
-    fn montgomery_a(&self) -> &E::Fr;
-    /// The `A` constant, doubled.
-    fn montgomery_2a(&self) -> &E::Fr;
-    /// The scaling factor used for conversion from the Montgomery form.
-    fn scale(&self) -> &E::Fr;
+pub trait JubjubParams<E: JubjubEngine>: TwistedEdwardsParams<E> + Sized {
     /// Returns the generators (for each segment) used in all Pedersen commitments.
     fn pedersen_hash_generators(&self) -> &[edwards::Point<E, PrimeOrder>];
     /// Returns the exp table for Pedersen hashes.
@@ -160,12 +134,15 @@ pub struct JubjubBn256 {
     fixed_base_circuit_generators: Vec<Vec<Vec<(Fr, Fr)>>>,
 }
 
-impl JubjubParams<Bn256> for JubjubBn256 {
+impl TwistedEdwardsParams<Bn256> for JubjubBn256 {
     fn edwards_a(&self) -> &Fr { &self.edwards_a }
     fn edwards_d(&self) -> &Fr { &self.edwards_d }
     fn montgomery_a(&self) -> &Fr { &self.montgomery_a }
     fn montgomery_2a(&self) -> &Fr { &self.montgomery_2a }
     fn scale(&self) -> &Fr { &self.scale }
+}
+
+impl JubjubParams<Bn256> for JubjubBn256 {
     fn pedersen_hash_generators(&self) -> &[edwards::Point<Bn256, PrimeOrder>] {
         &self.pedersen_hash_generators
     }
@@ -195,13 +172,68 @@ impl JubjubParams<Bn256> for JubjubBn256 {
     }
 }
 
+impl<E: JubjubEngine> ParamsFilePoint<E> for edwards::Point<E, PrimeOrder> {
+    fn from_xy_checked(x: E::Fr, y: E::Fr, params: &E::Params) -> Option<Self> {
+        edwards::Point::<E, Unknown>::from_xy(x, y, params).and_then(|p| p.as_prime_order(params))
+    }
+
+    fn from_xy_unchecked(x: E::Fr, y: E::Fr) -> Self {
+        edwards::Point::from_xy_unchecked(x, y)
+    }
+
+    fn montgomery_xy(&self, params: &E::Params) -> Option<(E::Fr, E::Fr)> {
+        montgomery::Point::from_edwards(self, params).into_xy()
+    }
+
+    fn scalar_bits() -> u32 {
+        E::Fs::NUM_BITS
+    }
+
+    fn pedersen_hash_generators(params: &E::Params) -> &[Self] {
+        params.pedersen_hash_generators()
+    }
+
+    fn pedersen_hash_exp_table(params: &E::Params) -> &[Vec<Vec<Self>>] {
+        params.pedersen_hash_exp_table()
+    }
+
+    fn pedersen_hash_exp_window_size(_: &E::Params) -> u32 {
+        E::Params::pedersen_hash_exp_window_size()
+    }
+
+    fn pedersen_hash_chunks_per_generator(params: &E::Params) -> usize {
+        params.pedersen_hash_chunks_per_generator()
+    }
+
+    fn pedersen_circuit_generators(params: &E::Params) -> &[Vec<Vec<(E::Fr, E::Fr)>>] {
+        params.pedersen_circuit_generators()
+    }
+
+    fn fixed_base_chunks_per_generator(params: &E::Params) -> usize {
+        params.fixed_base_chunks_per_generator()
+    }
+
+    fn generator(params: &E::Params, base: FixedGenerators) -> &Self {
+        params.generator(base)
+    }
+
+    fn circuit_generators(params: &E::Params, base: FixedGenerators) -> &[Vec<(E::Fr, E::Fr)>] {
+        params.circuit_generators(base)
+    }
+
+    fn fixed_base_tables(_: &E::Params) -> Vec<(&Self, &[Vec<(E::Fr, E::Fr)>])> {
+        vec![]
+    }
+}
+
 impl JubjubBn256 {
-    pub fn new() -> Self {
+    /// Curve constants, without any generators or tables
+    fn curve() -> Self {
         let montgomery_a = Fr::from_str("168698").unwrap();
         let mut montgomery_2a = montgomery_a;
         montgomery_2a.double();
 
-        let mut tmp_params = JubjubBn256 {
+        JubjubBn256 {
             // a = 168700
             edwards_a: Fr::from_str("168700").unwrap(),
             // d = 168696
@@ -213,75 +245,50 @@ impl JubjubBn256 {
             // scaling factor = sqrt(4 / (a - d))
             scale: Fr::from_str("1").unwrap(),
 
-            // We'll initialize these below
+            // Filled in by `from_generator_set` or `read`
             pedersen_hash_generators: vec![],
             pedersen_hash_exp: vec![],
             pedersen_circuit_generators: vec![],
             fixed_base_generators: vec![],
             fixed_base_circuit_generators: vec![],
-        };
-
-        fn find_group_hash<E: JubjubEngine>(
-            m: &[u8],
-            personalization: &[u8; 8],
-            params: &E::Params
-        ) -> edwards::Point<E, PrimeOrder>
-        {
-            let mut tag = m.to_vec();
-            let i = tag.len();
-            tag.push(0u8);
-
-            loop {
-                let gh = group_hash(
-                    &tag,
-                    personalization,
-                    params
-                );
-
-                // We don't want to overflow and start reusing generators
-                assert!(tag[i] != u8::max_value());
-                tag[i] += 1;
-
-                if let Some(gh) = gh {
-                    break gh;
-                }
-            }
         }
+    }
 
-        // Create the bases for the Pedersen hashes
-        {
-            let mut pedersen_hash_generators = vec![];
+    pub fn new() -> Self {
+        Self::from_generator_set(&GeneratorSet::<BlakeHasher>::default())
+    }
 
-            for m in 0..5 {
-                use byteorder::{WriteBytesExt, LittleEndian};
+    /// Writes the generators and all the precomputed tables, so that
+    /// the parameters can be loaded with `read` instead of recomputed
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        params_file::write::<Bn256, edwards::Point<Bn256, PrimeOrder>, _>(self, writer)
+    }
 
-                let mut segment_number = [0u8; 4];
-                (&mut segment_number[0..4]).write_u32::<LittleEndian>(m).unwrap();
+    /// Reads the parameters written by `write`, checking their integrity
+    /// and that they were computed for this curve
+    pub fn read<R: Read>(reader: R) -> io::Result<Self> {
+        let mut params = Self::curve();
+        let data = params_file::read::<Bn256, edwards::Point<Bn256, PrimeOrder>, _>(reader, &params)?;
+        if !data.fixed_base_tables.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Baby Jubjub parameters have no application fixed-base tables"));
+        }
 
-                pedersen_hash_generators.push(
-                    find_group_hash(
-                        &segment_number,
-                        constants::PEDERSEN_HASH_GENERATORS_PERSONALIZATION,
-                        &tmp_params
-                    )
-                );
-            }
+        params.pedersen_hash_generators = data.pedersen_hash_generators;
+        params.pedersen_hash_exp = data.pedersen_hash_exp;
+        params.pedersen_circuit_generators = data.pedersen_circuit_generators;
+        params.fixed_base_generators = data.fixed_base_generators;
+        params.fixed_base_circuit_generators = data.fixed_base_circuit_generators;
 
-            // Check for duplicates, far worse than spec inconsistencies!
-            for (i, p1) in pedersen_hash_generators.iter().enumerate() {
-                if p1 == &edwards::Point::zero() {
-                    panic!("Neutral element!");
-                }
+        Ok(params)
+    }
 
-                for p2 in pedersen_hash_generators.iter().skip(i+1) {
-                    if p1 == p2 {
-                        panic!("Duplicate generator!");
-                    }
-                }
-            }
+    /// Constructs the parameters with the generators derived as
+    /// described by `generators`, instead of the Sapling ones.
+    pub fn from_generator_set<H: GroupHasher>(generators: &GeneratorSet<H>) -> Self {
+        let mut tmp_params = Self::curve();
 
-            tmp_params.pedersen_hash_generators = pedersen_hash_generators;
-        }
+        // Create the bases for the Pedersen hashes
+        tmp_params.pedersen_hash_generators = generators.pedersen_hash_generators::<Bn256, _>(&tmp_params);
 
         // Create the exp table for the Pedersen hash generators
         {
@@ -320,42 +327,7 @@ impl JubjubBn256 {
         }
 
         // Create the bases for other parts of the protocol
-        {
-            let mut fixed_base_generators = vec![edwards::Point::zero(); FixedGenerators::Max as usize];
-
-            fixed_base_generators[FixedGenerators::ProofGenerationKey as usize] =
-                find_group_hash(&[], constants::PROOF_GENERATION_KEY_BASE_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::NoteCommitmentRandomness as usize] =
-                find_group_hash(b"r", constants::PEDERSEN_HASH_GENERATORS_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::NullifierPosition as usize] =
-                find_group_hash(&[], constants::NULLIFIER_POSITION_IN_TREE_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::ValueCommitmentValue as usize] =
-                find_group_hash(b"v", constants::VALUE_COMMITMENT_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::ValueCommitmentRandomness as usize] =
-                find_group_hash(b"r", constants::VALUE_COMMITMENT_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::SpendingKeyGenerator as usize] =
-                find_group_hash(&[], constants::SPENDING_KEY_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            // Check for duplicates, far worse than spec inconsistencies!
-            for (i, p1) in fixed_base_generators.iter().enumerate() {
-                if p1 == &edwards::Point::zero() {
-                    panic!("Neutral element!");
-                }
-
-                for p2 in fixed_base_generators.iter().skip(i+1) {
-                    if p1 == p2 {
-                        panic!("Duplicate generator!");
-                    }
-                }
-            }
-
-            tmp_params.fixed_base_generators = fixed_base_generators;
-        }
+        tmp_params.fixed_base_generators = generators.fixed_base_generators::<Bn256, _>(&tmp_params);
 
         // Create the 2-bit window table lookups for each 4-bit
         // "chunk" in each segment of the Pedersen hash
@@ -424,6 +396,75 @@ fn test_params() {
     tests::test_jubjub_params::<Bn256>(&params);
 }
 
+#[test]
+fn test_generator_set() {
+    use group_hash::Keccak256Hasher;
+    use baby_group_hash::group_hash;
+
+    let params = JubjubBn256::new();
+
+    // Same derivation as before the generator set was introduced
+    let mut tag = vec![b'v', 0];
+    let value_generator = loop {
+        if let Some(p) = group_hash::<Bn256>(&tag, constants::VALUE_COMMITMENT_GENERATOR_PERSONALIZATION, &params) {
+            break p;
+        }
+        tag[1] += 1;
+    };
+    assert!(params.generator(FixedGenerators::ValueCommitmentValue) == &value_generator);
+    assert_eq!(params.pedersen_hash_generators().len(), 5);
+
+    let set = GeneratorSet::<Keccak256Hasher>::default()
+        .pedersen_hash(b"MyApp_PH", 3);
+    let custom = JubjubBn256::from_generator_set(&set);
+    tests::test_jubjub_params::<Bn256>(&custom);
+
+    assert_eq!(custom.pedersen_hash_generators().len(), 3);
+    assert_eq!(custom.pedersen_circuit_generators().len(), 3);
+    for (custom, default) in custom.pedersen_hash_generators().iter().zip(params.pedersen_hash_generators().iter()) {
+        assert!(custom != default);
+    }
+    assert!(custom.generator(FixedGenerators::SpendingKeyGenerator) != params.generator(FixedGenerators::SpendingKeyGenerator));
+}
+
+#[test]
+fn test_params_file() {
+    use group_hash::Keccak256Hasher;
+    use jubjub::JubjubBls12;
+
+    let params = JubjubBn256::from_generator_set(&GeneratorSet::<Keccak256Hasher>::default().pedersen_hash(b"Custom__", 3));
+
+    let mut file = vec![];
+    params.write(&mut file).unwrap();
+
+    let loaded = JubjubBn256::read(&file[..]).unwrap();
+
+    assert!(loaded.pedersen_hash_generators == params.pedersen_hash_generators);
+    assert!(loaded.pedersen_hash_exp == params.pedersen_hash_exp);
+    assert_eq!(loaded.pedersen_circuit_generators, params.pedersen_circuit_generators);
+    assert!(loaded.fixed_base_generators == params.fixed_base_generators);
+    assert_eq!(loaded.fixed_base_circuit_generators, params.fixed_base_circuit_generators);
+
+    let mut rewritten = vec![];
+    loaded.write(&mut rewritten).unwrap();
+    assert!(rewritten == file);
+
+    for &position in [0, 20, 200, file.len() / 2, file.len() - 1].iter() {
+        let mut corrupted = file.clone();
+        corrupted[position] ^= 0x01;
+        assert!(JubjubBn256::read(&corrupted[..]).is_err());
+    }
+
+    // Lengths are checked before the tables are allocated
+    let mut corrupted = file.clone();
+    corrupted[176..180].copy_from_slice(&[0xff; 4]);
+    assert!(JubjubBn256::read(&corrupted[..]).is_err());
+
+    let mut other = vec![];
+    JubjubBls12::new().write(&mut other).unwrap();
+    assert!(JubjubBn256::read(&other[..]).is_err());
+}
+
 #[test]
 fn test_jubjub_bn256() {
     let params = JubjubBn256::new();
@@ -9,6 +9,7 @@ use bellman::pairing::ff::{
 use super::{
     JubjubEngine,
     JubjubParams,
+    TwistedEdwardsParams,
     Unknown,
     PrimeOrder,
     edwards
@@ -31,52 +31,111 @@ use super::boolean::{AllocatedBit, Boolean};
 /// and permuted elements can be anything. Caller be responsible for validity
 /// if elements are unique or not
 pub fn prove_shuffle<E, CS>(
-    mut cs: CS,
+    cs: CS,
     original: &[AllocatedNum<E>],
     permuted: &[AllocatedNum<E>],
     permuted_order: &IntegerPermutation,
 ) -> Result<(), SynthesisError>
     where CS: ConstraintSystem<E>,
           E: Engine
+{
+    let original: Vec<Vec<AllocatedNum<E>>> = original.iter().map(|e| vec![e.clone()]).collect();
+    let permuted: Vec<Vec<AllocatedNum<E>>> = permuted.iter().map(|e| vec![e.clone()]).collect();
+
+    prove_shuffle_of_records(cs, &original, &permuted, permuted_order)
+}
+
+/// Same as `prove_shuffle`, but every element is a record of several
+/// variables that are moved together by the same switches, so the
+/// records are permuted as a whole
+pub fn prove_shuffle_of_records<E, CS>(
+    mut cs: CS,
+    original: &[Vec<AllocatedNum<E>>],
+    permuted: &[Vec<AllocatedNum<E>>],
+    permuted_order: &IntegerPermutation,
+) -> Result<(), SynthesisError>
+    where CS: ConstraintSystem<E>,
+          E: Engine
 {
     assert_eq!(original.len(), permuted.len());
-    assert_eq!(original.len(), permuted_order.size());
-    // First make a topology
 
-    let topology = AsWaksmanTopology::new(original.len());
+    let routed = permute_records(
+        cs.namespace(|| "route records"),
+        original,
+        permuted_order
+    )?;
 
-    // now calculate the witness for gate assignments
+    // enforce an actual permutation
+    for (i, (variables, permuted)) in routed.into_iter().zip(permuted.iter()).enumerate() {
+        assert_eq!(variables.len(), permuted.len());
+
+        for (j, (variable, permuted)) in variables.into_iter().zip(permuted.iter()).enumerate() {
+            cs.enforce(
+                || format!("Enforce variable {} of record {} in permutation", j, i),
+                |lc| lc + variable.get_variable(),
+                |lc| lc + CS::one(),
+                |lc| lc + permuted.get_variable()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Routes records through the permutation network, so the record number `i`
+/// ends up at the place `permuted_order.get(i)`, and returns the routed records.
+/// All records must have the same number of variables.
+pub fn permute_records<E, CS>(
+    cs: CS,
+    original: &[Vec<AllocatedNum<E>>],
+    permuted_order: &IntegerPermutation,
+) -> Result<Vec<Vec<AllocatedNum<E>>>, SynthesisError>
+    where CS: ConstraintSystem<E>,
+          E: Engine
+{
+    assert_eq!(original.len(), permuted_order.size());
+
+    // calculate the witness for gate assignments
 
     let router = AsWaksmanRoute::new(permuted_order);
 
+    permute_records_with_route(cs, original, &router)
+}
+
+/// Same as `permute_records`, but switch settings are taken from the route
+/// that could be calculated in advance, e.g. from the `AsWaksmanSwitchWitness`
+pub fn permute_records_with_route<E, CS>(
+    mut cs: CS,
+    original: &[Vec<AllocatedNum<E>>],
+    router: &AsWaksmanRoute,
+) -> Result<Vec<Vec<AllocatedNum<E>>>, SynthesisError>
+    where CS: ConstraintSystem<E>,
+          E: Engine
+{
+    assert_eq!(original.len(), router.size);
+    let record_len = original.get(0).map(|r| r.len()).unwrap_or(0);
+    assert!(original.iter().all(|r| r.len() == record_len), "records must be of the same length");
+
+    // First make a topology
+
+    let topology = AsWaksmanTopology::new(original.len());
+
     // now route elements through the network. Deterministically do the bookkeeping of the variables in a plain array
 
     let num_columns = AsWaksmanTopology::num_colunms(topology.size);
 
-    let mut permutation: Vec<Option<AllocatedNum<E>>> = original.iter().map(|e| Some(e.clone())).collect();
-
-    // let mut permutation: Vec<Option<AllocatedNum<E>>> = permuted.iter().map(|e| Some(e.clone())).collect();
+    let mut permutation: Vec<Option<Vec<AllocatedNum<E>>>> = original.iter().map(|e| Some(e.clone())).collect();
 
     for column_idx in 0..num_columns {
         // this is just a bookkeeping variable and is deterministic
-        let mut result_of_this_column: Vec<Option<AllocatedNum<E>>> = vec![None; topology.size];
+        let mut result_of_this_column: Vec<Option<Vec<AllocatedNum<E>>>> = vec![None; topology.size];
         for packet_idx in 0..topology.size {
             if topology.topology[column_idx][packet_idx].0 == topology.topology[column_idx][packet_idx].1 {
-                // straight switch, there is no need to allocate witness
+                // straight switch, the same variables just go further
                 let routed_into_idx = topology.topology[column_idx][packet_idx].0;
-                let previous_level_variable = permutation.get(packet_idx);
-                let previous_level_variable = previous_level_variable.ok_or(SynthesisError::Unsatisfiable)?.as_ref();
-                let previous_level_variable = previous_level_variable.ok_or(SynthesisError::Unsatisfiable)?;
-                let new_variable_for_this_level = AllocatedNum::alloc(
-                    cs.namespace(|| format!("Straight line variable at column {} for packet {}", column_idx, packet_idx)),
-                    || {
-                        let value = *previous_level_variable.get_value().get()?;
-
-                        Ok(value)
-                    }
-                )?;
+                let previous_level_variables = permutation.get(packet_idx).ok_or(SynthesisError::Unsatisfiable)?.as_ref().ok_or(SynthesisError::Unsatisfiable)?;
 
-                result_of_this_column[routed_into_idx] = Some(new_variable_for_this_level);
+                result_of_this_column[routed_into_idx] = Some(previous_level_variables.clone());
             } else {
                 // validity check
                 let a = router.switches[column_idx].get(&packet_idx);
@@ -123,7 +182,7 @@ pub fn prove_shuffle<E, CS>(
                 assert!(another_idx.is_some());
                 let another_idx = another_idx.unwrap();
 
-                let previous_level_variable = permutation.get(packet_idx).ok_or(SynthesisError::Unsatisfiable)?.as_ref().ok_or(SynthesisError::Unsatisfiable)?;
+                let previous_level_variables = permutation.get(packet_idx).ok_or(SynthesisError::Unsatisfiable)?.as_ref().ok_or(SynthesisError::Unsatisfiable)?;
                 let previous_level_pair = permutation.get(another_idx).ok_or(SynthesisError::Unsatisfiable)?.as_ref().ok_or(SynthesisError::Unsatisfiable)?;
 
                 let boolean_switch = Boolean::from(AllocatedBit::alloc(
@@ -131,29 +190,20 @@ pub fn prove_shuffle<E, CS>(
                     switch_value
                 )?); 
 
-                let cross_value = AllocatedNum::alloc(
-                cs.namespace(|| format!("Cross variable at column {} for packet {} with pair {}", column_idx, packet_idx, another_idx)),
-                || {
-                    let value = *previous_level_pair.get_value().get()?;
-
-                    Ok(value)
-                })?;
-
-                let straight_value = AllocatedNum::alloc(
-                cs.namespace(|| format!("Straight variable at column {} for packet {} with pair {}", column_idx, packet_idx, another_idx)),
-                || {
-                    let value = *previous_level_variable.get_value().get()?;
-
-                    Ok(value)
-                })?;
-
-                // perform an actual switching 
-                let (next_level_straight, next_level_cross) = AllocatedNum::conditionally_reverse(
-                    cs.namespace(|| format!("Perform a switching at column {} for packets {} and {}", column_idx, packet_idx, another_idx)),
-                    &straight_value,
-                    &cross_value,
-                    &boolean_switch
-                )?;
+                // perform an actual switching, the same switch moves all the variables of a record
+                let mut next_level_straight = vec![];
+                let mut next_level_cross = vec![];
+                for (j, (straight_value, cross_value)) in previous_level_variables.iter().zip(previous_level_pair.iter()).enumerate() {
+                    let (straight, cross) = AllocatedNum::conditionally_reverse(
+                        cs.namespace(|| format!("Perform a switching at column {} for packets {} and {}, variable {}", column_idx, packet_idx, another_idx, j)),
+                        straight_value,
+                        cross_value,
+                        &boolean_switch
+                    )?;
+
+                    next_level_straight.push(straight);
+                    next_level_cross.push(cross);
+                }
 
                 result_of_this_column[routed_into_straght] = Some(next_level_straight);
                 result_of_this_column[routed_into_cross] = Some(next_level_cross);
@@ -163,21 +213,7 @@ pub fn prove_shuffle<E, CS>(
         permutation = result_of_this_column;
     }
 
-    // enforce an actual permutation
-    for (i, variable) in permutation.into_iter().enumerate() {
-        let variable = variable.ok_or(SynthesisError::Unsatisfiable)?;
-        let permuted = &permuted[i];
-
-        cs.enforce(
-            || format!("Enforce variable {} in permutation", i),
-            |lc| lc + variable.get_variable(),
-            |lc| lc + CS::one(),
-            |lc| lc + permuted.get_variable()
-        );
-    }
-
-
-    Ok(())
+    permutation.into_iter().map(|e| e.ok_or(SynthesisError::Unsatisfiable)).collect()
 }
 
 #[cfg(test)]
@@ -191,7 +227,8 @@ mod test {
         AllocatedNum,
     };
 
-    use super::{AsWaksmanRoute, AsWaksmanTopology, IntegerPermutation, prove_shuffle};
+    use super::{AsWaksmanRoute, AsWaksmanTopology, IntegerPermutation, prove_shuffle, permute_records_with_route};
+    use crate::as_waksman::{AsWaksmanSwitchWitness, all_permutations};
 
     #[test]
     fn test_as_waksman_gadget_positive() {
@@ -286,4 +323,43 @@ mod test {
             }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_as_waksman_records_exhaustive() {
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        for size in 1..=5 {
+            for elements in all_permutations(size).into_iter() {
+                let mut permutation = IntegerPermutation::new(size);
+                permutation.elements = elements;
+
+                // route is calculated "elsewhere" and transferred as bytes
+                let mut serialized = vec![];
+                AsWaksmanSwitchWitness::new(&permutation).write(&mut serialized).unwrap();
+                let route = AsWaksmanSwitchWitness::read(&serialized[..]).unwrap().to_route().unwrap();
+
+                let mut cs = TestConstraintSystem::<Bn256>::new();
+                let records: Vec<Vec<Fr>> = (0..size).map(|_| (0..3).map(|_| Fr::rand(rng)).collect()).collect();
+                let original: Vec<Vec<AllocatedNum<Bn256>>> = records.iter().enumerate().map(|(i, record)| {
+                    record.iter().enumerate().map(|(j, v)| {
+                        AllocatedNum::alloc(cs.namespace(|| format!("record {} variable {}", i, j)), || Ok(*v)).unwrap()
+                    }).collect()
+                }).collect();
+
+                let routed = permute_records_with_route(cs.namespace(|| "route"), &original, &route).unwrap();
+                assert!(cs.is_satisfied());
+                if size > 1 {
+                    assert_eq!(cs.find_unconstrained(), "");
+                }
+
+                let expected = route.route(&records);
+                for (record, expected) in routed.iter().zip(expected.iter()) {
+                    let values: Vec<Fr> = record.iter().map(|e| e.get_value().unwrap()).collect();
+                    assert_eq!(&values, expected);
+                }
+                for (i, record) in records.iter().enumerate() {
+                    assert_eq!(&expected[permutation.get(i)], record);
+                }
+            }
+        }
+    }
+}
@@ -2,19 +2,23 @@ use bellman::pairing::{
     Engine,
 };
 
-use bellman::pairing::ff::{Field};
+use bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
 
 use bellman::{
     SynthesisError,
-    ConstraintSystem
+    ConstraintSystem,
+    Variable
 };
 
+use num_bigint::BigUint;
+
 use super::{
     Assignment
 };
 
 use super::num::{
     AllocatedNum,
+    Num
 };
 
 use ::jubjub::{
@@ -30,13 +34,16 @@ use super::lookup::{
 
 use super::boolean::{
     Boolean, 
+    AllocatedBit,
     field_into_boolean_vec_le,
     le_bits_into_le_bytes
 };
 
 use super::sha256::sha256;
 
-use super::ecc::EdwardsPoint;
+use super::ecc::{EdwardsPoint, fixed_base_multiplication, multi_scalar_mul};
+
+use super::pedersen_hash::{pedersen_hash, Personalization};
 
 use super::blake2s::{blake2s};
 
@@ -330,6 +337,526 @@ impl <E: JubjubEngine>EddsaSignature<E> {
 } 
 
 
+/// Length of the random coefficients used by the batch verification.
+const BATCH_CHALLENGE_BITS: usize = 120;
+
+/// Maximum number of signatures in one batch, so that the random linear
+/// combination of the `s` values fits the integer arithmetic below.
+pub const MAX_BATCH_SIZE: usize = 128;
+
+/// Verifies a batch of signatures produced by `PrivateKey::sign_schnorr_blake2s`
+/// for a fixed generator `p_g`, which is cheaper than separate calls to
+/// `verify_schnorr_blake2s`.
+///
+/// Instead of `s_i * B = R_i + h_i * PK_i` for every signature the gadget checks
+///
+/// `(sum z_i * s_i) * B = sum z_i * R_i + sum (z_i * h_i) * PK_i`
+///
+/// for `BATCH_CHALLENGE_BITS` long coefficients `z_i` derived with the Pedersen
+/// hash from all `h_i` (which bind `R_i` and the messages) and public keys.
+/// The left hand side is a single fixed-base multiplication and the right hand
+/// side shares one doubling chain for all points. Scalars are reduced modulo
+/// the subgroup order inside the circuit, so every `R_i` is checked to be in
+/// the prime order subgroup. Public keys are expected to be in the prime order
+/// subgroup, exactly as in the single signature gadgets.
+pub fn verify_schnorr_blake2s_batch<E, CS>(
+    mut cs: CS,
+    params: &E::Params,
+    signatures: &[EddsaSignature<E>],
+    messages: &[Vec<Boolean>],
+    p_g: FixedGenerators
+) -> Result<(), SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>
+{
+    assert_eq!(signatures.len(), messages.len());
+    assert!(signatures.len() > 0);
+    assert!(signatures.len() <= MAX_BATCH_SIZE);
+
+    let mut hashes = vec![];
+    let mut scalars = vec![];
+    let mut transcript: Option<EdwardsPoint<E>> = None;
+
+    for (i, (signature, message)) in signatures.iter().zip(messages.iter()).enumerate() {
+        let mut cs = cs.namespace(|| format!("signature {}", i));
+
+        signature.assert_in_prime_order_subgroup(
+            cs.namespace(|| "R is in prime order subgroup"),
+            params
+        )?;
+
+        let r_x_bits = signature.r.get_x().into_bits_le_strict(
+            cs.namespace(|| "Serialize R_X")
+        )?;
+
+        let mut hash_bits: Vec<Boolean> = vec![];
+        hash_bits.extend(r_x_bits.into_iter());
+        hash_bits.resize(256, Boolean::Constant(false));
+
+        hash_bits.extend(message.iter().cloned());
+        hash_bits.resize(512, Boolean::Constant(false));
+
+        assert_eq!(hash_bits.len(), 512);
+
+        let h = blake2s(
+            cs.namespace(|| "Calculate EdDSA hash"),
+            &hash_bits,
+            MATTER_EDDSA_BLAKE2S_PERSONALIZATION
+        )?;
+
+        let s_bits = alloc_scalar_bits(
+            cs.namespace(|| "Get S bits"),
+            &signature.s
+        )?;
+
+        let pk_x_bits = signature.pk.get_x().into_bits_le_strict(
+            cs.namespace(|| "Serialize PK_X")
+        )?;
+
+        // transcript = H(transcript_X || h || PK_X), starting from zero
+        // bits so that every input has the same length
+        let mut transcript_bits = vec![];
+        match transcript {
+            Some(ref transcript) => {
+                transcript_bits.extend(transcript.get_x().into_bits_le_strict(
+                    cs.namespace(|| "Serialize transcript")
+                )?);
+            },
+            None => {
+                transcript_bits.resize(E::Fr::NUM_BITS as usize, Boolean::constant(false));
+            }
+        }
+        transcript_bits.extend(h.iter().cloned());
+        transcript_bits.extend(pk_x_bits.into_iter());
+
+        transcript = Some(pedersen_hash(
+            cs.namespace(|| "Update transcript"),
+            Personalization::BatchSignatureTranscript,
+            &transcript_bits,
+            params
+        )?);
+
+        hashes.push(h);
+        scalars.push(s_bits);
+    }
+
+    let seed_bits = transcript.unwrap().get_x().into_bits_le_strict(
+        cs.namespace(|| "Serialize transcript")
+    )?;
+
+    // every squeezed point gives two challenges
+    let mut challenges = vec![];
+    for j in 0..((signatures.len() + 1) / 2) {
+        let mut input = seed_bits.clone();
+        input.extend((0..8).map(|b| Boolean::constant((j >> b) & 1 == 1)));
+
+        let point = pedersen_hash(
+            cs.namespace(|| format!("Squeeze challenges {}", j)),
+            Personalization::BatchSignatureChallenge,
+            &input,
+            params
+        )?;
+
+        let bits = point.get_x().into_bits_le_strict(
+            cs.namespace(|| format!("Serialize challenges {}", j))
+        )?;
+
+        challenges.push(bits[0..BATCH_CHALLENGE_BITS].to_vec());
+        challenges.push(bits[BATCH_CHALLENGE_BITS..(2*BATCH_CHALLENGE_BITS)].to_vec());
+    }
+    challenges.truncate(signatures.len());
+
+    // sum z_i * s_i mod order
+    let s_combination = dot_product_mod_order(
+        cs.namespace(|| "Combine S"),
+        &challenges.iter().cloned().zip(scalars.into_iter()).collect::<Vec<_>>()
+    )?;
+
+    let lhs = fixed_base_multiplication(
+        cs.namespace(|| "S*B computation"),
+        p_g,
+        &s_combination,
+        params
+    )?;
+
+    let mut terms = vec![];
+    for (i, ((signature, h), z)) in signatures.iter().zip(hashes.into_iter()).zip(challenges.into_iter()).enumerate() {
+        // z_i * h_i mod order
+        let pk_scalar = dot_product_mod_order(
+            cs.namespace(|| format!("Combine hash {}", i)),
+            &[(z.clone(), h)]
+        )?;
+
+        terms.push((&signature.r, z));
+        terms.push((&signature.pk, pk_scalar));
+    }
+
+    let rhs = multi_scalar_mul(
+        cs.namespace(|| "Make signature RHS"),
+        &terms.iter().map(|&(p, ref by)| (p, &by[..])).collect::<Vec<_>>(),
+        params
+    )?;
+
+    let one = CS::one();
+    cs.enforce(
+        || "check x coordinate of signatures",
+        |lc| lc + rhs.get_x().get_variable(),
+        |lc| lc + one,
+        |lc| lc + lhs.get_x().get_variable()
+    );
+
+    cs.enforce(
+        || "check y coordinate of signatures",
+        |lc| lc + rhs.get_y().get_variable(),
+        |lc| lc + one,
+        |lc| lc + lhs.get_y().get_variable()
+    );
+
+    Ok(())
+}
+
+impl <E: JubjubEngine>EddsaSignature<E> {
+    /// Checks that R is a nonzero multiple of the cofactor, so it
+    /// can be safely combined with other points by a scalar modulo the
+    /// subgroup order.
+    fn assert_in_prime_order_subgroup<CS>(
+        &self,
+        mut cs: CS,
+        params: &E::Params
+    ) -> Result<(), SynthesisError>
+        where CS: ConstraintSystem<E>
+    {
+        // R' = R / 8
+        let r_prime = match (self.r.get_x().get_value(), self.r.get_y().get_value()) {
+            (Some(x), Some(y)) => {
+                let cofactor_inv = E::Fs::from_str("8").unwrap().inverse().unwrap();
+                edwards::Point::<E, _>::from_xy(x, y, params).map(|p| p.mul(cofactor_inv, params))
+            },
+            _ => None
+        };
+
+        let r_prime = EdwardsPoint::witness(
+            cs.namespace(|| "allocate R divided by cofactor"),
+            r_prime,
+            params
+        )?;
+
+        let tmp = r_prime.double(cs.namespace(|| "first doubling"), params)?;
+        let tmp = tmp.double(cs.namespace(|| "second doubling"), params)?;
+        let tmp = tmp.double(cs.namespace(|| "third doubling"), params)?;
+
+        let one = CS::one();
+        cs.enforce(
+            || "check x coordinate of R",
+            |lc| lc + tmp.get_x().get_variable(),
+            |lc| lc + one,
+            |lc| lc + self.r.get_x().get_variable()
+        );
+
+        cs.enforce(
+            || "check y coordinate of R",
+            |lc| lc + tmp.get_y().get_variable(),
+            |lc| lc + one,
+            |lc| lc + self.r.get_y().get_variable()
+        );
+
+        // the only multiple of the cofactor with x = 0 is the identity
+        self.r.get_x().assert_nonzero(cs.namespace(|| "check R != 0"))
+    }
+}
+
+/// Decomposes a scalar field element into `E::Fs::NUM_BITS` bits,
+/// so it can be used in integer arithmetic below.
+fn alloc_scalar_bits<E, CS>(
+    mut cs: CS,
+    s: &AllocatedNum<E>
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>
+{
+    let bits = alloc_integer_bits(
+        cs.namespace(|| "allocate bits"),
+        s.get_value().map(|s| repr_to_biguint(&s.into_repr())),
+        E::Fs::NUM_BITS as usize
+    )?;
+
+    let packed = pack_bits(CS::one(), &bits);
+    cs.enforce(
+        || "repack",
+        |lc| lc + s.get_variable(),
+        |lc| lc + CS::one(),
+        |_| packed.lc(E::Fr::one())
+    );
+
+    Ok(bits)
+}
+
+/// Computes `sum a_i * b_i` modulo the order of the prime order subgroup
+/// for little-endian bit vectors. The integer equality
+/// `sum a_i * b_i = q * order + t` is enforced modulo the field
+/// characteristic and modulo 2^128, which is enough as long as both sides
+/// are below their product.
+fn dot_product_mod_order<E, CS>(
+    mut cs: CS,
+    pairs: &[(Vec<Boolean>, Vec<Boolean>)]
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>
+{
+    const LIMB: usize = 64;
+
+    let order = repr_to_biguint(&E::Fs::char());
+    let order_bits = E::Fs::NUM_BITS as usize;
+
+    let log_n = (0..).find(|i| (1 << i) >= pairs.len()).unwrap();
+    let sum_bits = pairs.iter().map(|(a, b)| a.len() + b.len()).max().unwrap() + log_n;
+    // q < 2^sum_bits / order < 2^(sum_bits - order_bits + 1)
+    let quotient_bits = sum_bits - order_bits + 1;
+
+    let bound = E::Fr::CAPACITY as usize + 2 * LIMB;
+    assert!(sum_bits < bound);
+    assert!(quotient_bits + order_bits < bound);
+
+    let mut sum = Some(BigUint::from(0u32));
+    for (a, b) in pairs.iter() {
+        sum = match (sum, bits_to_biguint(a), bits_to_biguint(b)) {
+            (Some(sum), Some(a), Some(b)) => Some(sum + a * b),
+            _ => None
+        };
+    }
+
+    let quotient = alloc_integer_bits(
+        cs.namespace(|| "quotient"),
+        sum.as_ref().map(|sum| sum / &order),
+        quotient_bits
+    )?;
+
+    let remainder = alloc_integer_bits(
+        cs.namespace(|| "remainder"),
+        sum.as_ref().map(|sum| sum % &order),
+        order_bits
+    )?;
+
+    let order_fe = biguint_to_fe::<E::Fr>(&order);
+    let limb_mask = (BigUint::from(1u32) << LIMB) - BigUint::from(1u32);
+    let order_lo = biguint_to_fe::<E::Fr>(&(&order & &limb_mask));
+    let order_hi = biguint_to_fe::<E::Fr>(&((&order >> LIMB) & &limb_mask));
+
+    let mut shift = E::Fr::one();
+    for _ in 0..LIMB {
+        shift.double();
+    }
+
+    // sum a_i * b_i = q * order + t modulo the field characteristic
+    let mut full = Num::<E>::zero();
+    // low 128 bits of sum a_i * b_i - q * order - t, shifted by an offset
+    let mut low = Num::<E>::zero();
+    let mut low_value = Some(BigUint::from(0u32));
+
+    let offset_bits = 3 * LIMB + 3;
+    let mut offset = E::Fr::one();
+    for _ in 0..offset_bits {
+        offset.double();
+    }
+    low = low.add_bool_with_coeff(CS::one(), &Boolean::constant(true), offset);
+    low_value = low_value.map(|v| v + (BigUint::from(1u32) << offset_bits));
+
+    for (i, (a, b)) in pairs.iter().enumerate() {
+        let mut cs = cs.namespace(|| format!("pair {}", i));
+
+        let a_full = pack_bits(CS::one(), a);
+        let b_full = pack_bits(CS::one(), b);
+        let product = mul_nums(cs.namespace(|| "a * b"), &a_full, &b_full)?;
+        full = full.add_number_with_coeff(&product, E::Fr::one());
+
+        let (a_lo, a_hi) = split_limbs(CS::one(), a, LIMB);
+        let (b_lo, b_hi) = split_limbs(CS::one(), b, LIMB);
+
+        let lo_lo = mul_nums(cs.namespace(|| "a_lo * b_lo"), &a_lo, &b_lo)?;
+        let lo_hi = mul_nums(cs.namespace(|| "a_lo * b_hi"), &a_lo, &b_hi)?;
+        let hi_lo = mul_nums(cs.namespace(|| "a_hi * b_lo"), &a_hi, &b_lo)?;
+
+        low = low.add_number_with_coeff(&lo_lo, E::Fr::one())
+                 .add_number_with_coeff(&lo_hi, shift)
+                 .add_number_with_coeff(&hi_lo, shift);
+
+        low_value = match (low_value, bits_to_biguint(a), bits_to_biguint(b)) {
+            (Some(v), Some(a), Some(b)) => {
+                let (a_lo, a_hi) = (&a & &limb_mask, (&a >> LIMB) & &limb_mask);
+                let (b_lo, b_hi) = (&b & &limb_mask, (&b >> LIMB) & &limb_mask);
+                Some(v + &a_lo * &b_lo + ((&a_lo * &b_hi + &a_hi * &b_lo) << LIMB))
+            },
+            _ => None
+        };
+    }
+
+    let mut order_neg = order_fe;
+    order_neg.negate();
+    let mut minus_one = E::Fr::one();
+    minus_one.negate();
+
+    full = add_packed(CS::one(), full, &quotient, order_neg);
+    full = add_packed(CS::one(), full, &remainder, minus_one);
+
+    cs.enforce(
+        || "check sum modulo characteristic",
+        |lc| lc,
+        |lc| lc,
+        |_| full.lc(E::Fr::one())
+    );
+
+    let (q_lo, q_hi) = (&quotient[0..LIMB], &quotient[LIMB..(2*LIMB).min(quotient_bits)]);
+    let (t_lo, t_hi) = (&remainder[0..LIMB], &remainder[LIMB..(2*LIMB)]);
+
+    let mut minus_shift = shift;
+    minus_shift.negate();
+    let mut order_lo_neg = order_lo;
+    order_lo_neg.negate();
+    let mut order_lo_shifted_neg = order_lo;
+    order_lo_shifted_neg.mul_assign(&minus_shift);
+    let mut order_hi_shifted_neg = order_hi;
+    order_hi_shifted_neg.mul_assign(&minus_shift);
+
+    low = add_packed(CS::one(), low, q_lo, order_lo_neg);
+    low = add_packed(CS::one(), low, q_lo, order_hi_shifted_neg);
+    low = add_packed(CS::one(), low, q_hi, order_lo_shifted_neg);
+    low = add_packed(CS::one(), low, t_lo, minus_one);
+    low = add_packed(CS::one(), low, t_hi, minus_shift);
+
+    low_value = match (low_value, sum.as_ref()) {
+        (Some(v), Some(sum)) => {
+            let q = sum / &order;
+            let t = sum % &order;
+            let (q_lo, q_hi) = (&q & &limb_mask, (&q >> LIMB) & &limb_mask);
+            let (t_lo, t_hi) = (&t & &limb_mask, (&t >> LIMB) & &limb_mask);
+            let order_lo = &order & &limb_mask;
+            let order_hi = (&order >> LIMB) & &limb_mask;
+            let negative = &q_lo * &order_lo + ((&q_lo * &order_hi + &q_hi * &order_lo) << LIMB)
+                + &t_lo + (t_hi << LIMB);
+            Some(v - negative)
+        },
+        _ => None
+    };
+
+    // low = carry * 2^128
+    let carry_bits = offset_bits + log_n + 2 - 2 * LIMB;
+    let carry = alloc_integer_bits(
+        cs.namespace(|| "carry"),
+        low_value.map(|v| v >> (2 * LIMB)),
+        carry_bits
+    )?;
+
+    let mut minus_shift_squared = shift;
+    minus_shift_squared.square();
+    minus_shift_squared.negate();
+    low = add_packed(CS::one(), low, &carry, minus_shift_squared);
+
+    cs.enforce(
+        || "check sum modulo 2^128",
+        |lc| lc,
+        |lc| lc,
+        |_| low.lc(E::Fr::one())
+    );
+
+    Ok(remainder)
+}
+
+fn mul_nums<E, CS>(
+    mut cs: CS,
+    a: &Num<E>,
+    b: &Num<E>
+) -> Result<AllocatedNum<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>
+{
+    let product = AllocatedNum::alloc(cs.namespace(|| "product"), || {
+        let mut tmp = *a.get_value().get()?;
+        tmp.mul_assign(b.get_value().get()?);
+
+        Ok(tmp)
+    })?;
+
+    cs.enforce(
+        || "multiplication",
+        |_| a.lc(E::Fr::one()),
+        |_| b.lc(E::Fr::one()),
+        |lc| lc + product.get_variable()
+    );
+
+    Ok(product)
+}
+
+fn split_limbs<E: JubjubEngine>(one: Variable, bits: &[Boolean], limb: usize) -> (Num<E>, Num<E>)
+{
+    let lo = &bits[0..limb.min(bits.len())];
+    let hi = if bits.len() > limb { &bits[limb..(2*limb).min(bits.len())] } else { &[] };
+
+    (pack_bits(one, lo), pack_bits(one, hi))
+}
+
+fn pack_bits<E: JubjubEngine>(one: Variable, bits: &[Boolean]) -> Num<E>
+{
+    add_packed(one, Num::zero(), bits, E::Fr::one())
+}
+
+fn add_packed<E: JubjubEngine>(one: Variable, num: Num<E>, bits: &[Boolean], coeff: E::Fr) -> Num<E>
+{
+    let mut num = num;
+    let mut coeff = coeff;
+    for bit in bits.iter() {
+        num = num.add_bool_with_coeff(one, bit, coeff);
+        coeff.double();
+    }
+
+    num
+}
+
+fn alloc_integer_bits<E, CS>(
+    mut cs: CS,
+    value: Option<BigUint>,
+    num_bits: usize
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>
+{
+    if let Some(ref value) = value {
+        assert!(value.bits() <= num_bits);
+    }
+
+    (0..num_bits).map(|i| {
+        let bit = value.as_ref().map(|v| ((v >> i) & BigUint::from(1u32)) == BigUint::from(1u32));
+        AllocatedBit::alloc(cs.namespace(|| format!("bit {}", i)), bit).map(Boolean::from)
+    }).collect()
+}
+
+fn bits_to_biguint(bits: &[Boolean]) -> Option<BigUint> {
+    let mut bytes = vec![0u8; (bits.len() + 7) / 8];
+    for (i, bit) in bits.iter().enumerate() {
+        if bit.get_value()? {
+            bytes[i / 8] |= 1 << (i % 8);
+        }
+    }
+
+    Some(BigUint::from_bytes_le(&bytes))
+}
+
+fn repr_to_biguint<R: PrimeFieldRepr>(repr: &R) -> BigUint {
+    let mut bytes = vec![];
+    repr.write_le(&mut bytes).expect("writing to a vector can not fail");
+
+    BigUint::from_bytes_le(&bytes)
+}
+
+fn biguint_to_fe<F: PrimeField>(value: &BigUint) -> F {
+    let mut repr = F::Repr::default();
+    let bytes = value.to_bytes_le();
+    let mut padded = vec![0u8; repr.as_ref().len() * 8];
+    padded[..bytes.len()].copy_from_slice(&bytes);
+    repr.read_le(&padded[..]).expect("value fits into representation");
+
+    F::from_repr(repr).expect("value is smaller than the characteristic")
+}
+
 #[cfg(test)]
 mod test {
     use ::eddsa::{PrivateKey, PublicKey};
@@ -401,6 +928,182 @@ mod test {
         print!("Schnorr signature verification takes constraints: {}\n", cs.num_constraints());
     }
 
+    fn alloc_message_bits<CS: ConstraintSystem<Bn256>>(mut cs: CS, msg: &[u8]) -> Vec<Boolean> {
+        let mut input: Vec<bool> = vec![];
+        for b in msg.iter() {
+            for i in 0..8 {
+                input.push((b & (1 << i)) != 0);
+            }
+        }
+
+        input.iter().enumerate().map(|(i, b)| {
+            Boolean::from(
+                AllocatedBit::alloc(cs.namespace(|| format!("input {}", i)), Some(*b)).unwrap()
+            )
+        }).collect()
+    }
+
+    fn alloc_signature<CS: ConstraintSystem<Bn256>>(
+        mut cs: CS,
+        sig: ::eddsa::Signature<Bn256>,
+        vk: PublicKey<Bn256>,
+        params: &AltJubjubBn256
+    ) -> EddsaSignature<Bn256> {
+        let mut sigs_bytes = [0u8; 32];
+        sig.s.into_repr().write_le(& mut sigs_bytes[..]).expect("get LE bytes of signature S");
+        let mut sigs_repr = <Fr as PrimeField>::Repr::from(0);
+        sigs_repr.read_le(&sigs_bytes[..]).expect("interpret S as field element representation");
+        let sigs_converted = Fr::from_repr(sigs_repr).unwrap();
+
+        let s = AllocatedNum::alloc(cs.namespace(|| "allocate s"), || Ok(sigs_converted)).unwrap();
+        let r = EdwardsPoint::witness(cs.namespace(|| "allocate r"), Some(sig.r), params).unwrap();
+        let pk = EdwardsPoint::witness(cs.namespace(|| "allocate pk"), Some(vk.0), params).unwrap();
+
+        EddsaSignature{r, s, pk}
+    }
+
+    #[test]
+    fn test_batch_schnorr_signatures() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+        let params = &AltJubjubBn256::new();
+
+        for &n in [1, 2, 5].iter() {
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+
+            let mut signatures = vec![];
+            let mut messages = vec![];
+            let mut separate = 0;
+
+            for i in 0..n {
+                let sk = PrivateKey::<Bn256>(rng.gen());
+                let vk = PublicKey::from_private(&sk, p_g, params);
+                let msg: Vec<u8> = (0..16).map(|_| rng.gen()).collect();
+                let sig = sk.sign_schnorr_blake2s(&msg, &mut rng, p_g, params);
+                assert!(vk.verify_schnorr_blake2s(&msg, &sig, p_g, params));
+
+                let message = alloc_message_bits(cs.namespace(|| format!("message {}", i)), &msg);
+                signatures.push(alloc_signature(cs.namespace(|| format!("signature {}", i)), sig.clone(), vk.clone(), params));
+                messages.push(message);
+
+                // the same signature verified on its own
+                let mut single_cs = TestConstraintSystem::<Bn256>::new();
+                let generator = EdwardsPoint::witness(
+                    single_cs.namespace(|| "allocate public generator"),
+                    Some(params.generator(p_g).clone()),
+                    params
+                ).unwrap();
+                let message = alloc_message_bits(single_cs.namespace(|| "message"), &msg);
+                let signature = alloc_signature(single_cs.namespace(|| "signature"), sig, vk, params);
+
+                let allocation = single_cs.num_constraints();
+                signature.verify_schnorr_blake2s(single_cs.namespace(|| "verify"), params, &message, generator).unwrap();
+                assert!(single_cs.is_satisfied());
+                separate += single_cs.num_constraints() - allocation;
+            }
+
+            let allocation = cs.num_constraints();
+            verify_schnorr_blake2s_batch(cs.namespace(|| "verify batch"), params, &signatures, &messages, p_g).unwrap();
+            assert!(cs.is_satisfied());
+
+            let batch = (cs.num_constraints() - allocation) / n;
+            let single = separate / n;
+            print!("Batch of {} signatures takes {} constraints per signature, separate calls take {}\n", n, batch, single);
+            if n > 1 {
+                assert!(batch < single);
+            }
+        }
+    }
+
+    #[test]
+    fn test_batch_rejects_invalid_signature() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+        let params = &AltJubjubBn256::new();
+
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let mut signatures = vec![];
+        let mut messages = vec![];
+
+        for i in 0..3 {
+            let sk = PrivateKey::<Bn256>(rng.gen());
+            let vk = PublicKey::from_private(&sk, p_g, params);
+            let msg: Vec<u8> = (0..16).map(|_| rng.gen()).collect();
+            let sig = sk.sign_schnorr_blake2s(&msg, &mut rng, p_g, params);
+
+            // the last signature is for another message
+            let msg = if i == 2 { b"Foo bar pad to16".to_vec() } else { msg };
+
+            messages.push(alloc_message_bits(cs.namespace(|| format!("message {}", i)), &msg));
+            signatures.push(alloc_signature(cs.namespace(|| format!("signature {}", i)), sig, vk, params));
+        }
+
+        verify_schnorr_blake2s_batch(cs.namespace(|| "verify batch"), params, &signatures, &messages, p_g).unwrap();
+        assert!(!cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_dot_product_mod_order() {
+        use bellman::pairing::ff::Field;
+        use ::alt_babyjubjub::fs::Fs;
+
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for n in 1..4 {
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+            let mut expected = Fs::zero();
+            let mut pairs = vec![];
+
+            for i in 0..n {
+                let a: Vec<bool> = (0..120).map(|_| rng.gen()).collect();
+                let b: Vec<bool> = (0..256).map(|_| rng.gen()).collect();
+
+                let into_fs = |bits: &[bool]| {
+                    let mut tmp = Fs::zero();
+                    for bit in bits.iter().rev() {
+                        tmp.double();
+                        if *bit {
+                            tmp.add_assign(&Fs::one());
+                        }
+                    }
+                    tmp
+                };
+
+                let mut tmp = into_fs(&a);
+                tmp.mul_assign(&into_fs(&b));
+                expected.add_assign(&tmp);
+
+                let a = a.iter().enumerate().map(|(j, bit)| {
+                    Boolean::from(AllocatedBit::alloc(cs.namespace(|| format!("a {} {}", i, j)), Some(*bit)).unwrap())
+                }).collect();
+                let b = b.iter().enumerate().map(|(j, bit)| {
+                    Boolean::from(AllocatedBit::alloc(cs.namespace(|| format!("b {} {}", i, j)), Some(*bit)).unwrap())
+                }).collect();
+                pairs.push((a, b));
+            }
+
+            let result = dot_product_mod_order(cs.namespace(|| "dot product"), &pairs).unwrap();
+            assert!(cs.is_satisfied());
+
+            let mut result_fs = Fs::zero();
+            for bit in result.iter().rev() {
+                result_fs.double();
+                if bit.get_value().unwrap() {
+                    result_fs.add_assign(&Fs::one());
+                }
+            }
+            assert_eq!(result_fs, expected);
+
+            // remainder is not unique without the quotient
+            let path = "dot product/remainder/bit 0/boolean";
+            let bit = cs.get(path);
+            let mut flipped = Fr::one();
+            flipped.sub_assign(&bit);
+            cs.set(path, flipped);
+            assert!(!cs.is_satisfied());
+        }
+    }
+
     #[test]
     fn test_valid_musig_signatures() {
         
@@ -523,5 +1226,3 @@ mod test {
     }
 
 }
-
-
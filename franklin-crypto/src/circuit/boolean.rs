@@ -19,6 +19,8 @@ use super::{
     Assignment
 };
 
+use super::multieq::MultiEq;
+
 /// Represents a variable in the constraint system which is guaranteed
 /// to be either zero or one.
 #[derive(Clone)]
@@ -298,6 +300,42 @@ impl AllocatedBit {
     }
 }
 
+/// Enforces two bit vectors of the same length to be equal. The
+/// equalities of the bits are packed by a `MultiEq`, so every
+/// `CAPACITY - 1` bits take a single constraint, which is sound since
+/// both sides are sums of booleans and can not overflow the field.
+pub fn enforce_equal_bits<E, CS>(
+    cs: CS,
+    a: &[Boolean],
+    b: &[Boolean]
+) -> Result<(), SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    assert_eq!(a.len(), b.len());
+
+    let mut cs = MultiEq::new(cs);
+
+    for (a, b) in a.iter().zip(b.iter()) {
+        match (a, b) {
+            (&Boolean::Constant(a), &Boolean::Constant(b)) => {
+                if a != b {
+                    return Err(SynthesisError::Unsatisfiable);
+                }
+            },
+            (a, b) => {
+                cs.enforce_equal(
+                    1,
+                    &a.lc(CS::one(), E::Fr::one()),
+                    &b.lc(CS::one(), E::Fr::one())
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
 pub fn u64_into_boolean_vec_le<E: Engine, CS: ConstraintSystem<E>>(
     mut cs: CS,
     value: Option<u64>
@@ -858,10 +896,12 @@ mod test {
     use bellman::{ConstraintSystem};
     use bellman::pairing::bls12_381::{Bls12, Fr};
     use bellman::pairing::ff::{Field, PrimeField};
+    use rand::{XorShiftRng, SeedableRng, Rng};
     use ::circuit::test::*;
     use super::{
         AllocatedBit,
         Boolean,
+        enforce_equal_bits,
         field_into_allocated_bits_le,
         u64_into_boolean_vec_le
     };
@@ -1062,6 +1102,48 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_enforce_equal_bits() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for &len in [1, 64, 253, 254, 600].iter() {
+            let bits: Vec<bool> = (0..len).map(|_| rng.gen()).collect();
+
+            for flip in (0..len).filter(|i| i % 97 == 0).map(Some).chain(Some(None)) {
+                let mut cs = TestConstraintSystem::<Bls12>::new();
+
+                let a: Vec<Boolean> = bits.iter().enumerate().map(|(i, &b)| {
+                    let a = Boolean::from(AllocatedBit::alloc(cs.namespace(|| format!("a {}", i)), Some(b ^ (i % 5 == 0))).unwrap());
+
+                    if i % 5 == 0 { a.not() } else { a }
+                }).collect();
+                let b: Vec<Boolean> = bits.iter().enumerate().map(|(i, &b)| {
+                    let b = if flip == Some(i) { !b } else { b };
+
+                    if i % 3 == 0 {
+                        Boolean::constant(b)
+                    } else {
+                        AllocatedBit::alloc(cs.namespace(|| format!("b {}", i)), Some(b)).unwrap().into()
+                    }
+                }).collect();
+
+                let before = cs.num_constraints();
+
+                enforce_equal_bits(cs.namespace(|| "equality"), &a, &b).unwrap();
+
+                assert_eq!(cs.is_satisfied(), flip.is_none());
+                // 253 equalities of a single bit fit into one constraint
+                assert_eq!(cs.num_constraints() - before, (len + 252) / 253);
+            }
+        }
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        let constants = [Boolean::constant(true), Boolean::constant(false)];
+        enforce_equal_bits(&mut cs, &constants, &constants).unwrap();
+        assert_eq!(cs.num_constraints(), 0);
+        assert!(enforce_equal_bits(&mut cs, &constants[..1], &constants[1..]).is_err());
+    }
+
     #[test]
     fn test_boolean_negation() {
         let mut cs = TestConstraintSystem::<Bls12>::new();
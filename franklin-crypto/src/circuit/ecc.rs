@@ -19,10 +19,12 @@ use super::num::{
 };
 
 use ::jubjub::{
-    edwards,
     JubjubEngine,
     JubjubParams,
-    FixedGenerators
+    TwistedEdwardsParams,
+    EdwardsCoordinates,
+    FixedGenerators,
+    FixedBaseTable
 };
 
 use super::lookup::{
@@ -40,19 +42,48 @@ pub struct EdwardsPoint<E: Engine> {
 /// Perform a fixed-base scalar multiplication with
 /// `by` being in little-endian bit order.
 pub fn fixed_base_multiplication<E, CS>(
-    mut cs: CS,
+    cs: CS,
     base: FixedGenerators,
     by: &[Boolean],
     params: &E::Params
 ) -> Result<EdwardsPoint<E>, SynthesisError>
     where CS: ConstraintSystem<E>,
           E: JubjubEngine
+{
+    windowed_multiplication(cs, params.circuit_generators(base), by, params)
+}
+
+/// Perform a fixed-base scalar multiplication of an arbitrary base
+/// with precomputed window tables, `by` being in little-endian bit order.
+pub fn fixed_base_multiplication_with_table<E, CS>(
+    cs: CS,
+    table: &FixedBaseTable<E>,
+    by: &[Boolean],
+    params: &E::Params
+) -> Result<EdwardsPoint<E>, SynthesisError>
+    where CS: ConstraintSystem<E>,
+          E: JubjubEngine
+{
+    assert!(by.len() <= table.windows().len() * 3, "scalar does not fit the window tables");
+
+    windowed_multiplication(cs, table.windows(), by, params)
+}
+
+pub(crate) fn windowed_multiplication<E, CS, P>(
+    mut cs: CS,
+    windows: &[Vec<(E::Fr, E::Fr)>],
+    by: &[Boolean],
+    params: &P
+) -> Result<EdwardsPoint<E>, SynthesisError>
+    where CS: ConstraintSystem<E>,
+          E: Engine,
+          P: TwistedEdwardsParams<E>
 {
     // Represents the result of the multiplication
     let mut result = None;
 
     for (i, (chunk, window)) in by.chunks(3)
-                                  .zip(params.circuit_generators(base).iter())
+                                  .zip(windows.iter())
                                   .enumerate()
     {
         let chunk_a = chunk.get(0).map(|e| e.clone()).unwrap_or(Boolean::constant(false));
@@ -84,7 +115,129 @@ pub fn fixed_base_multiplication<E, CS>(
     Ok(result.get()?.clone())
 }
 
-impl<E: JubjubEngine> EdwardsPoint<E> {
+/// Computes `sum scalar_i * point_i` for scalars in little-endian
+/// bit order. All terms share one doubling chain and every term is
+/// processed in 2-bit windows over the table [0, P, 2P, 3P] (Straus'
+/// method), which is considerably cheaper than separate calls to
+/// `EdwardsPoint::mul` followed by additions.
+pub fn multi_scalar_mul<E, CS, P>(
+    mut cs: CS,
+    terms: &[(&EdwardsPoint<E>, &[Boolean])],
+    params: &P
+) -> Result<EdwardsPoint<E>, SynthesisError>
+    where CS: ConstraintSystem<E>,
+          E: Engine,
+          P: TwistedEdwardsParams<E>
+{
+    // Precompute 2P and 3P for every term which uses them
+    let mut tables = vec![];
+    for (j, &(point, by)) in terms.iter().enumerate() {
+        if by.len() > 1 {
+            let double = point.double(cs.namespace(|| format!("2P of term {}", j)), params)?;
+            let triple = double.add(cs.namespace(|| format!("3P of term {}", j)), point, params)?;
+            tables.push(Some((double, triple)));
+        } else {
+            tables.push(None);
+        }
+    }
+
+    let max_len = terms.iter().map(|&(_, by)| by.len()).max().unwrap_or(0);
+
+    // Represents the result of the multiplication
+    let mut result: Option<EdwardsPoint<E>> = None;
+
+    for i in (0..((max_len + 1) / 2)).rev() {
+        if let Some(tmp) = result {
+            let tmp = tmp.double(cs.namespace(|| format!("first doubling of window {}", i)), params)?;
+            let tmp = tmp.double(cs.namespace(|| format!("second doubling of window {}", i)), params)?;
+            result = Some(tmp);
+        }
+
+        for (j, (&(point, by), table)) in terms.iter().zip(tables.iter()).enumerate() {
+            if by.len() <= 2 * i {
+                continue;
+            }
+
+            let mut cs = cs.namespace(|| format!("window {} of term {}", i, j));
+
+            let low = point.conditionally_select(cs.namespace(|| "select low bit"), &by[2 * i])?;
+
+            let thisbase = match (by.get(2 * i + 1), table) {
+                (Some(bit), &Some((ref double, ref triple))) => {
+                    // 2P or 3P depending on the lower bit
+                    let high_x = AllocatedNum::conditionally_select(
+                        cs.namespace(|| "select high x"),
+                        &triple.x,
+                        &double.x,
+                        &by[2 * i]
+                    )?;
+                    let high_y = AllocatedNum::conditionally_select(
+                        cs.namespace(|| "select high y"),
+                        &triple.y,
+                        &double.y,
+                        &by[2 * i]
+                    )?;
+
+                    EdwardsPoint {
+                        x: AllocatedNum::conditionally_select(
+                            cs.namespace(|| "select x"),
+                            &high_x,
+                            &low.x,
+                            bit
+                        )?,
+                        y: AllocatedNum::conditionally_select(
+                            cs.namespace(|| "select y"),
+                            &high_y,
+                            &low.y,
+                            bit
+                        )?
+                    }
+                },
+                _ => low
+            };
+
+            result = Some(match result {
+                None => thisbase,
+                Some(tmp) => tmp.add(
+                    cs.namespace(|| "addition"),
+                    &thisbase,
+                    params
+                )?
+            });
+        }
+    }
+
+    // Without any bits the sum is empty
+    match result {
+        Some(result) => Ok(result),
+        None => EdwardsPoint::identity(cs.namespace(|| "identity"))
+    }
+}
+
+impl<E: Engine> EdwardsPoint<E> {
+    /// Allocates the identity (0, 1).
+    pub fn identity<CS>(
+        mut cs: CS
+    ) -> Result<Self, SynthesisError>
+        where CS: ConstraintSystem<E>
+    {
+        let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(E::Fr::zero()))?;
+        x.assert_zero(cs.namespace(|| "x is zero"))?;
+
+        let y = AllocatedNum::alloc(cs.namespace(|| "y"), || Ok(E::Fr::one()))?;
+        cs.enforce(
+            || "y is one",
+            |lc| lc + y.get_variable(),
+            |lc| lc + CS::one(),
+            |lc| lc + CS::one()
+        );
+
+        Ok(EdwardsPoint {
+            x: x,
+            y: y
+        })
+    }
+
     pub fn get_x(&self) -> &AllocatedNum<E> {
         &self.x
     }
@@ -93,12 +246,13 @@ impl<E: JubjubEngine> EdwardsPoint<E> {
         &self.y
     }
 
-    pub fn assert_not_small_order<CS>(
+    pub fn assert_not_small_order<CS, P>(
         &self,
         mut cs: CS,
-        params: &E::Params
+        params: &P
     ) -> Result<(), SynthesisError>
-        where CS: ConstraintSystem<E>
+        where CS: ConstraintSystem<E>,
+              P: TwistedEdwardsParams<E>
     {
         let tmp = self.double(
             cs.namespace(|| "first doubling"),
@@ -122,6 +276,33 @@ impl<E: JubjubEngine> EdwardsPoint<E> {
         Ok(())
     }
 
+    /// Returns the negation (-x, y) of this point.
+    pub fn negate<CS>(
+        &self,
+        mut cs: CS
+    ) -> Result<Self, SynthesisError>
+        where CS: ConstraintSystem<E>
+    {
+        let x = AllocatedNum::alloc(cs.namespace(|| "-x"), || {
+            let mut t0 = *self.x.get_value().get()?;
+            t0.negate();
+
+            Ok(t0)
+        })?;
+
+        cs.enforce(
+            || "negation",
+            |lc| lc + x.get_variable() + self.x.get_variable(),
+            |lc| lc + CS::one(),
+            |lc| lc
+        );
+
+        Ok(EdwardsPoint {
+            x: x,
+            y: self.y.clone()
+        })
+    }
+
     pub fn inputize<CS>(
         &self,
         mut cs: CS
@@ -159,14 +340,16 @@ impl<E: JubjubEngine> EdwardsPoint<E> {
 
     /// This 'witnesses' a point inside the constraint system.
     /// It guarantees the point is on the curve.
-    pub fn witness<Order, CS>(
+    pub fn witness<T, CS, P>(
         mut cs: CS,
-        p: Option<edwards::Point<E, Order>>,
-        params: &E::Params
+        p: Option<T>,
+        params: &P
     ) -> Result<Self, SynthesisError>
-        where CS: ConstraintSystem<E>
+        where CS: ConstraintSystem<E>,
+              T: EdwardsCoordinates<E>,
+              P: TwistedEdwardsParams<E>
     {
-        let p = p.map(|p| p.into_xy());
+        let p = p.map(|p| p.edwards_xy());
 
         // Allocate x
         let x = AllocatedNum::alloc(
@@ -250,13 +433,14 @@ impl<E: JubjubEngine> EdwardsPoint<E> {
     /// Performs a scalar multiplication of this twisted Edwards
     /// point by a scalar represented as a sequence of booleans
     /// in little-endian bit order.
-    pub fn mul<CS>(
+    pub fn mul<CS, P>(
         &self,
         mut cs: CS,
         by: &[Boolean],
-        params: &E::Params
+        params: &P
     ) -> Result<Self, SynthesisError>
-        where CS: ConstraintSystem<E>
+        where CS: ConstraintSystem<E>,
+              P: TwistedEdwardsParams<E>
     {
         // Represents the current "magnitude" of the base
         // that we're operating over. Starts at self,
@@ -302,15 +486,16 @@ impl<E: JubjubEngine> EdwardsPoint<E> {
         Ok(result.get()?.clone())
     }
 
-    pub fn interpret<CS>(
+    pub fn interpret<CS, P>(
         mut cs: CS,
         x: &AllocatedNum<E>,
         y: &AllocatedNum<E>,
-        params: &E::Params
+        params: &P
     ) -> Result<Self, SynthesisError>
-        where CS: ConstraintSystem<E>
+        where CS: ConstraintSystem<E>,
+              P: TwistedEdwardsParams<E>
     {
-        // -x^2 + y^2 = 1 + dx^2y^2
+        // a*x^2 + y^2 = 1 + dx^2y^2
 
         let x2 = x.square(cs.namespace(|| "x^2"))?;
         let y2 = y.square(cs.namespace(|| "y^2"))?;
@@ -319,7 +504,7 @@ impl<E: JubjubEngine> EdwardsPoint<E> {
         let one = CS::one();
         cs.enforce(
             || "on curve check",
-            |lc| lc - x2.get_variable()
+            |lc| lc + (*params.edwards_a(), x2.get_variable())
                     + y2.get_variable(),
             |lc| lc + one,
             |lc| lc + one
@@ -332,13 +517,18 @@ impl<E: JubjubEngine> EdwardsPoint<E> {
         })
     }
 
-    pub fn double<CS>(
+    pub fn double<CS, P>(
         &self,
         mut cs: CS,
-        params: &E::Params
+        params: &P
     ) -> Result<Self, SynthesisError>
-        where CS: ConstraintSystem<E>
+        where CS: ConstraintSystem<E>,
+              P: TwistedEdwardsParams<E>
     {
+        if !is_minus_one(params.edwards_a()) {
+            return self.double_for_any_a(cs, params);
+        }
+
         // Compute T = (x1 + y1) * (x1 + y1)
         let t = AllocatedNum::alloc(cs.namespace(|| "T"), || {
             let mut t0 = *self.x.get_value().get()?;
@@ -447,14 +637,19 @@ impl<E: JubjubEngine> EdwardsPoint<E> {
     }
 
     /// Perform addition between any two points
-    pub fn add<CS>(
+    pub fn add<CS, P>(
         &self,
         mut cs: CS,
         other: &Self,
-        params: &E::Params
+        params: &P
     ) -> Result<Self, SynthesisError>
-        where CS: ConstraintSystem<E>
+        where CS: ConstraintSystem<E>,
+              P: TwistedEdwardsParams<E>
     {
+        if !is_minus_one(params.edwards_a()) {
+            return self.add_for_any_a(cs, other, params);
+        }
+
         // Compute U = (x1 + y1) * (x2 + y2)
         let u = AllocatedNum::alloc(cs.namespace(|| "U"), || {
             let mut t0 = *self.x.get_value().get()?;
@@ -563,32 +758,52 @@ impl<E: JubjubEngine> EdwardsPoint<E> {
             y: y3
         })
     }
-}
-
-pub struct MontgomeryPoint<E: Engine> {
-    x: Num<E>,
-    y: Num<E>
-}
 
-impl<E: JubjubEngine> MontgomeryPoint<E> {
-    /// Converts an element in the prime order subgroup into
-    /// a point in the birationally equivalent twisted
-    /// Edwards curve.
-    pub fn into_edwards<CS>(
+    /// Doubling for curves with `a != -1`, which needs one more constraint
+    fn double_for_any_a<CS, P>(
         &self,
         mut cs: CS,
-        params: &E::Params
-    ) -> Result<EdwardsPoint<E>, SynthesisError>
-        where CS: ConstraintSystem<E>
+        params: &P
+    ) -> Result<Self, SynthesisError>
+        where CS: ConstraintSystem<E>,
+              P: TwistedEdwardsParams<E>
     {
-        // Compute u = (scale*x) / y
-        let u = AllocatedNum::alloc(cs.namespace(|| "u"), || {
-            let mut t0 = *self.x.get_value().get()?;
-            t0.mul_assign(params.scale());
+        // Compute A = x1 * y1
+        let a = self.x.mul(cs.namespace(|| "A computation"), &self.y)?;
 
-            match self.y.get_value().get()?.inverse() {
-                Some(invy) => {
-                    t0.mul_assign(&invy);
+        // Compute T = x1 * x1
+        let t = self.x.mul(cs.namespace(|| "T computation"), &self.x)?;
+
+        // Compute U = y1 * y1
+        let u = self.y.mul(cs.namespace(|| "U computation"), &self.y)?;
+
+        // Compute C = d*A*A
+        let c = AllocatedNum::alloc(cs.namespace(|| "C"), || {
+            let mut t0 = *a.get_value().get()?;
+            t0.square();
+            t0.mul_assign(params.edwards_d());
+
+            Ok(t0)
+        })?;
+
+        cs.enforce(
+            || "C computation",
+            |lc| lc + (*params.edwards_d(), a.get_variable()),
+            |lc| lc + a.get_variable(),
+            |lc| lc + c.get_variable()
+        );
+
+        // Compute x3 = (2.A) / (1 + C)
+        let x3 = AllocatedNum::alloc(cs.namespace(|| "x3"), || {
+            let mut t0 = *a.get_value().get()?;
+            t0.double();
+
+            let mut t1 = E::Fr::one();
+            t1.add_assign(c.get_value().get()?);
+
+            match t1.inverse() {
+                Some(t1) => {
+                    t0.mul_assign(&t1);
 
                     Ok(t0)
                 },
@@ -598,19 +813,26 @@ impl<E: JubjubEngine> MontgomeryPoint<E> {
             }
         })?;
 
+        let one = CS::one();
         cs.enforce(
-            || "u computation",
-            |lc| lc + &self.y.lc(E::Fr::one()),
-            |lc| lc + u.get_variable(),
-            |lc| lc + &self.x.lc(*params.scale())
+            || "x3 computation",
+            |lc| lc + one + c.get_variable(),
+            |lc| lc + x3.get_variable(),
+            |lc| lc + a.get_variable()
+                    + a.get_variable()
         );
 
-        // Compute v = (x - 1) / (x + 1)
-        let v = AllocatedNum::alloc(cs.namespace(|| "v"), || {
-            let mut t0 = *self.x.get_value().get()?;
-            let mut t1 = t0;
-            t0.sub_assign(&E::Fr::one());
-            t1.add_assign(&E::Fr::one());
+        // Compute y3 = (U - edwards_a.T) / (1 - C)
+        let y3 = AllocatedNum::alloc(cs.namespace(|| "y3"), || {
+            let mut t0 = *u.get_value().get()?;
+
+            let mut u0 = *t.get_value().get()?;
+            u0.mul_assign(params.edwards_a());
+
+            t0.sub_assign(&u0);
+
+            let mut t1 = E::Fr::one();
+            t1.sub_assign(c.get_value().get()?);
 
             match t1.inverse() {
                 Some(t1) => {
@@ -624,150 +846,825 @@ impl<E: JubjubEngine> MontgomeryPoint<E> {
             }
         })?;
 
-        let one = CS::one();
+        // y3 = (U - edwards_a.T) / (1 - C)
         cs.enforce(
-            || "v computation",
-            |lc| lc + &self.x.lc(E::Fr::one())
-                    + one,
-            |lc| lc + v.get_variable(),
-            |lc| lc + &self.x.lc(E::Fr::one())
-                    - one,
+            || "y3 computation",
+            |lc| lc + one - c.get_variable(),
+            |lc| lc + y3.get_variable(),
+            |lc| lc + u.get_variable()
+                    - (*params.edwards_a(), t.get_variable())
         );
 
         Ok(EdwardsPoint {
-            x: u,
-            y: v
+            x: x3,
+            y: y3
         })
     }
 
-    /// Interprets an (x, y) pair as a point
-    /// in Montgomery, does not check that it's
-    /// on the curve. Useful for constants and
-    /// window table lookups.
-    pub fn interpret_unchecked(
-        x: Num<E>,
-        y: Num<E>
-    ) -> Self
-    {
-        MontgomeryPoint {
-            x: x,
-            y: y
-        }
-    }
-
-    /// Performs an affine point addition, not defined for
-    /// coincident points.
-    pub fn add<CS>(
+    /// Addition for curves with `a != -1`, which needs one more constraint
+    fn add_for_any_a<CS, P>(
         &self,
         mut cs: CS,
         other: &Self,
-        params: &E::Params
+        params: &P
     ) -> Result<Self, SynthesisError>
-        where CS: ConstraintSystem<E>
+        where CS: ConstraintSystem<E>,
+              P: TwistedEdwardsParams<E>
     {
-        // Compute lambda = (y' - y) / (x' - x)
-        let lambda = AllocatedNum::alloc(cs.namespace(|| "lambda"), || {
-            let mut n = *other.y.get_value().get()?;
-            n.sub_assign(self.y.get_value().get()?);
+        // Compute A = y2 * x1
+        let a = other.y.mul(cs.namespace(|| "A computation"), &self.x)?;
 
-            let mut d = *other.x.get_value().get()?;
-            d.sub_assign(self.x.get_value().get()?);
+        // Compute B = x2 * y1
+        let b = other.x.mul(cs.namespace(|| "B computation"), &self.y)?;
 
-            match d.inverse() {
-                Some(d) => {
-                    n.mul_assign(&d);
-                    Ok(n)
-                },
-                None => {
-                    Err(SynthesisError::DivisionByZero)
-                }
-            }
-        })?;
+        // Compute T = (-a * x1 + y1) * (x2 + y2)
+        let t = AllocatedNum::alloc(cs.namespace(|| "t"), || {
+            let mut t0 = *self.x.get_value().get()?;
+            t0.mul_assign(params.edwards_a());
+            t0.negate();
+            t0.add_assign(self.y.get_value().get()?);
 
-        cs.enforce(
-            || "evaluate lambda",
-            |lc| lc + &other.x.lc(E::Fr::one())
-                    - &self.x.lc(E::Fr::one()),
+            let mut t1 = *other.x.get_value().get()?;
+            t1.add_assign(other.y.get_value().get()?);
 
-            |lc| lc + lambda.get_variable(),
+            t0.mul_assign(&t1);
 
-            |lc| lc + &other.y.lc(E::Fr::one())
-                    - &self.y.lc(E::Fr::one())
+            Ok(t0)
+        })?;
+
+        cs.enforce(
+            || "T computation",
+            |lc| lc - (*params.edwards_a(), self.x.get_variable()) + self.y.get_variable(),
+            |lc| lc + other.x.get_variable() + other.y.get_variable(),
+            |lc| lc + t.get_variable()
         );
 
-        // Compute x'' = lambda^2 - A - x - x'
-        let xprime = AllocatedNum::alloc(cs.namespace(|| "xprime"), || {
-            let mut t0 = *lambda.get_value().get()?;
-            t0.square();
-            t0.sub_assign(params.montgomery_a());
-            t0.sub_assign(self.x.get_value().get()?);
-            t0.sub_assign(other.x.get_value().get()?);
+        // Compute C = d*A*B
+        let c = AllocatedNum::alloc(cs.namespace(|| "C"), || {
+            let mut t0 = *a.get_value().get()?;
+            t0.mul_assign(b.get_value().get()?);
+            t0.mul_assign(params.edwards_d());
 
             Ok(t0)
         })?;
 
-        // (lambda) * (lambda) = (A + x + x' + x'')
-        let one = CS::one();
         cs.enforce(
-            || "evaluate xprime",
-            |lc| lc + lambda.get_variable(),
-            |lc| lc + lambda.get_variable(),
-            |lc| lc + (*params.montgomery_a(), one)
-                    + &self.x.lc(E::Fr::one())
-                    + &other.x.lc(E::Fr::one())
-                    + xprime.get_variable()
+            || "C computation",
+            |lc| lc + (*params.edwards_d(), a.get_variable()),
+            |lc| lc + b.get_variable(),
+            |lc| lc + c.get_variable()
         );
 
-        // Compute y' = -(y + lambda(x' - x))
-        let yprime = AllocatedNum::alloc(cs.namespace(|| "yprime"), || {
-            let mut t0 = *xprime.get_value().get()?;
-            t0.sub_assign(self.x.get_value().get()?);
-            t0.mul_assign(lambda.get_value().get()?);
-            t0.add_assign(self.y.get_value().get()?);
-            t0.negate();
+        // Compute x3 = (A + B) / (1 + C)
+        let x3 = AllocatedNum::alloc(cs.namespace(|| "x3"), || {
+            let mut t0 = *a.get_value().get()?;
+            t0.add_assign(b.get_value().get()?);
+
+            let mut t1 = E::Fr::one();
+            t1.add_assign(c.get_value().get()?);
+
+            match t1.inverse() {
+                Some(t1) => {
+                    t0.mul_assign(&t1);
+
+                    Ok(t0)
+                },
+                None => {
+                    Err(SynthesisError::DivisionByZero)
+                }
+            }
+        })?;
+
+        let one = CS::one();
+        cs.enforce(
+            || "x3 computation",
+            |lc| lc + one + c.get_variable(),
+            |lc| lc + x3.get_variable(),
+            |lc| lc + a.get_variable()
+                    + b.get_variable()
+        );
+
+        // Compute y3 = (T + edwards.a * A - B) / (1 - C)
+        let y3 = AllocatedNum::alloc(cs.namespace(|| "y3"), || {
+            let mut a0 = *a.get_value().get()?;
+            a0.mul_assign(params.edwards_a());
+
+            let mut t0 = *t.get_value().get()?;
+            t0.add_assign(&a0);
+            t0.sub_assign(b.get_value().get()?);
+
+            let mut t1 = E::Fr::one();
+            t1.sub_assign(c.get_value().get()?);
+
+            match t1.inverse() {
+                Some(t1) => {
+                    t0.mul_assign(&t1);
+
+                    Ok(t0)
+                },
+                None => {
+                    Err(SynthesisError::DivisionByZero)
+                }
+            }
+        })?;
+
+        cs.enforce(
+            || "y3 computation",
+            |lc| lc + one - c.get_variable(),
+            |lc| lc + y3.get_variable(),
+            |lc| lc + t.get_variable()
+                    + (*params.edwards_a(), a.get_variable()) - b.get_variable()
+        );
+
+        Ok(EdwardsPoint {
+            x: x3,
+            y: y3
+        })
+    }
+}
+
+pub struct MontgomeryPoint<E: Engine> {
+    x: Num<E>,
+    y: Num<E>
+}
+
+impl<E: Engine> MontgomeryPoint<E> {
+    /// Converts an element in the prime order subgroup into
+    /// a point in the birationally equivalent twisted
+    /// Edwards curve.
+    pub fn into_edwards<CS, P>(
+        &self,
+        mut cs: CS,
+        params: &P
+    ) -> Result<EdwardsPoint<E>, SynthesisError>
+        where CS: ConstraintSystem<E>,
+              P: TwistedEdwardsParams<E>
+    {
+        // Compute u = (scale*x) / y
+        let u = AllocatedNum::alloc(cs.namespace(|| "u"), || {
+            let mut t0 = *self.x.get_value().get()?;
+            t0.mul_assign(params.scale());
+
+            match self.y.get_value().get()?.inverse() {
+                Some(invy) => {
+                    t0.mul_assign(&invy);
+
+                    Ok(t0)
+                },
+                None => {
+                    Err(SynthesisError::DivisionByZero)
+                }
+            }
+        })?;
+
+        cs.enforce(
+            || "u computation",
+            |lc| lc + &self.y.lc(E::Fr::one()),
+            |lc| lc + u.get_variable(),
+            |lc| lc + &self.x.lc(*params.scale())
+        );
+
+        // Compute v = (x - 1) / (x + 1)
+        let v = AllocatedNum::alloc(cs.namespace(|| "v"), || {
+            let mut t0 = *self.x.get_value().get()?;
+            let mut t1 = t0;
+            t0.sub_assign(&E::Fr::one());
+            t1.add_assign(&E::Fr::one());
+
+            match t1.inverse() {
+                Some(t1) => {
+                    t0.mul_assign(&t1);
+
+                    Ok(t0)
+                },
+                None => {
+                    Err(SynthesisError::DivisionByZero)
+                }
+            }
+        })?;
+
+        let one = CS::one();
+        cs.enforce(
+            || "v computation",
+            |lc| lc + &self.x.lc(E::Fr::one())
+                    + one,
+            |lc| lc + v.get_variable(),
+            |lc| lc + &self.x.lc(E::Fr::one())
+                    - one,
+        );
+
+        Ok(EdwardsPoint {
+            x: u,
+            y: v
+        })
+    }
+
+    /// Interprets an (x, y) pair as a point
+    /// in Montgomery, does not check that it's
+    /// on the curve. Useful for constants and
+    /// window table lookups.
+    pub fn interpret_unchecked(
+        x: Num<E>,
+        y: Num<E>
+    ) -> Self
+    {
+        MontgomeryPoint {
+            x: x,
+            y: y
+        }
+    }
+
+    /// Performs an affine point addition, not defined for
+    /// coincident points.
+    pub fn add<CS, P>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+        params: &P
+    ) -> Result<Self, SynthesisError>
+        where CS: ConstraintSystem<E>,
+              P: TwistedEdwardsParams<E>
+    {
+        // Compute lambda = (y' - y) / (x' - x)
+        let lambda = AllocatedNum::alloc(cs.namespace(|| "lambda"), || {
+            let mut n = *other.y.get_value().get()?;
+            n.sub_assign(self.y.get_value().get()?);
+
+            let mut d = *other.x.get_value().get()?;
+            d.sub_assign(self.x.get_value().get()?);
+
+            match d.inverse() {
+                Some(d) => {
+                    n.mul_assign(&d);
+                    Ok(n)
+                },
+                None => {
+                    Err(SynthesisError::DivisionByZero)
+                }
+            }
+        })?;
+
+        cs.enforce(
+            || "evaluate lambda",
+            |lc| lc + &other.x.lc(E::Fr::one())
+                    - &self.x.lc(E::Fr::one()),
+
+            |lc| lc + lambda.get_variable(),
+
+            |lc| lc + &other.y.lc(E::Fr::one())
+                    - &self.y.lc(E::Fr::one())
+        );
+
+        // Compute x'' = lambda^2 - A - x - x'
+        let xprime = AllocatedNum::alloc(cs.namespace(|| "xprime"), || {
+            let mut t0 = *lambda.get_value().get()?;
+            t0.square();
+            t0.sub_assign(params.montgomery_a());
+            t0.sub_assign(self.x.get_value().get()?);
+            t0.sub_assign(other.x.get_value().get()?);
+
+            Ok(t0)
+        })?;
+
+        // (lambda) * (lambda) = (A + x + x' + x'')
+        let one = CS::one();
+        cs.enforce(
+            || "evaluate xprime",
+            |lc| lc + lambda.get_variable(),
+            |lc| lc + lambda.get_variable(),
+            |lc| lc + (*params.montgomery_a(), one)
+                    + &self.x.lc(E::Fr::one())
+                    + &other.x.lc(E::Fr::one())
+                    + xprime.get_variable()
+        );
+
+        // Compute y' = -(y + lambda(x' - x))
+        let yprime = AllocatedNum::alloc(cs.namespace(|| "yprime"), || {
+            let mut t0 = *xprime.get_value().get()?;
+            t0.sub_assign(self.x.get_value().get()?);
+            t0.mul_assign(lambda.get_value().get()?);
+            t0.add_assign(self.y.get_value().get()?);
+            t0.negate();
+
+            Ok(t0)
+        })?;
+
+        // y' + y = lambda(x - x')
+        cs.enforce(
+            || "evaluate yprime",
+            |lc| lc + &self.x.lc(E::Fr::one())
+                    - xprime.get_variable(),
+
+            |lc| lc + lambda.get_variable(),
+
+            |lc| lc + yprime.get_variable()
+                    + &self.y.lc(E::Fr::one())
+        );
+
+        Ok(MontgomeryPoint {
+            x: xprime.into(),
+            y: yprime.into()
+        })
+    }
+}
+
+fn is_minus_one<F: Field>(a: &F) -> bool {
+    let mut tmp = *a;
+    tmp.add_assign(&F::one());
+
+    tmp.is_zero()
+}
+
+#[cfg(test)]
+mod test {
+    use bellman::{ConstraintSystem};
+    use rand::{XorShiftRng, SeedableRng, Rand, Rng};
+    use bellman::pairing::bls12_381::{Bls12, Fr};
+    use bellman::pairing::ff::{BitIterator, Field, PrimeField};
+    use ::circuit::test::*;
+    use ::jubjub::{
+        montgomery,
+        edwards,
+        JubjubBls12,
+        JubjubParams,
+        FixedGenerators
+    };
+    use ::jubjub::fs::Fs;
+    use super::{
+        MontgomeryPoint,
+        EdwardsPoint,
+        AllocatedNum,
+        fixed_base_multiplication
+    };
+    use super::super::boolean::{
+        Boolean,
+        AllocatedBit
+    };
+
+    #[test]
+    fn test_into_edwards() {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for _ in 0..100 {
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+
+            let p = montgomery::Point::<Bls12, _>::rand(rng, params);
+            let (u, v) = edwards::Point::from_montgomery(&p, params).into_xy();
+            let (x, y) = p.into_xy().unwrap();
+
+            let numx = AllocatedNum::alloc(cs.namespace(|| "mont x"), || {
+                Ok(x)
+            }).unwrap();
+            let numy = AllocatedNum::alloc(cs.namespace(|| "mont y"), || {
+                Ok(y)
+            }).unwrap();
+
+            let p = MontgomeryPoint::interpret_unchecked(numx.into(), numy.into());
+
+            let q = p.into_edwards(&mut cs, params).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert!(q.x.get_value().unwrap() == u);
+            assert!(q.y.get_value().unwrap() == v);
+
+            cs.set("u/num", rng.gen());
+            assert_eq!(cs.which_is_unsatisfied().unwrap(), "u computation");
+            cs.set("u/num", u);
+            assert!(cs.is_satisfied());
+
+            cs.set("v/num", rng.gen());
+            assert_eq!(cs.which_is_unsatisfied().unwrap(), "v computation");
+            cs.set("v/num", v);
+            assert!(cs.is_satisfied());
+        }
+    }
+
+    #[test]
+    fn test_interpret() {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for _ in 0..100 {
+            let p = edwards::Point::<Bls12, _>::rand(rng, &params);
+
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+            let q = EdwardsPoint::witness(
+                &mut cs,
+                Some(p.clone()),
+                &params
+            ).unwrap();
+
+            let p = p.into_xy();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(q.x.get_value().unwrap(), p.0);
+            assert_eq!(q.y.get_value().unwrap(), p.1);
+        }
+
+        for _ in 0..100 {
+            let p = edwards::Point::<Bls12, _>::rand(rng, &params);
+            let (x, y) = p.into_xy();
+
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+            let numx = AllocatedNum::alloc(cs.namespace(|| "x"), || {
+                Ok(x)
+            }).unwrap();
+            let numy = AllocatedNum::alloc(cs.namespace(|| "y"), || {
+                Ok(y)
+            }).unwrap();
+
+            let p = EdwardsPoint::interpret(&mut cs, &numx, &numy, &params).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(p.x.get_value().unwrap(), x);
+            assert_eq!(p.y.get_value().unwrap(), y);
+        }
+
+        // Random (x, y) are unlikely to be on the curve.
+        for _ in 0..100 {
+            let x = rng.gen();
+            let y = rng.gen();
+
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+            let numx = AllocatedNum::alloc(cs.namespace(|| "x"), || {
+                Ok(x)
+            }).unwrap();
+            let numy = AllocatedNum::alloc(cs.namespace(|| "y"), || {
+                Ok(y)
+            }).unwrap();
+
+            EdwardsPoint::interpret(&mut cs, &numx, &numy, &params).unwrap();
+
+            assert_eq!(cs.which_is_unsatisfied().unwrap(), "on curve check");
+        }
+    }
+
+    #[test]
+    fn test_edwards_fixed_base_multiplication()  {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for _ in 0..100 {
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+
+            let p = params.generator(FixedGenerators::NoteCommitmentRandomness);
+            let s = Fs::rand(rng);
+            let q = p.mul(s, params);
+            let (x1, y1) = q.into_xy();
+
+            let mut s_bits = BitIterator::new(s.into_repr()).collect::<Vec<_>>();
+            s_bits.reverse();
+            s_bits.truncate(Fs::NUM_BITS as usize);
+
+            let s_bits = s_bits.into_iter()
+                               .enumerate()
+                               .map(|(i, b)| AllocatedBit::alloc(cs.namespace(|| format!("scalar bit {}", i)), Some(b)).unwrap())
+                               .map(|v| Boolean::from(v))
+                               .collect::<Vec<_>>();
+
+            let q = fixed_base_multiplication(
+                cs.namespace(|| "multiplication"),
+                FixedGenerators::NoteCommitmentRandomness,
+                &s_bits,
+                params
+            ).unwrap();
+
+            assert_eq!(q.x.get_value().unwrap(), x1);
+            assert_eq!(q.y.get_value().unwrap(), y1);
+        }
+    }
+
+    #[test]
+    fn test_edwards_multiplication() {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for _ in 0..100 {
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+
+            let p = edwards::Point::<Bls12, _>::rand(rng, params);
+            let s = Fs::rand(rng);
+            let q = p.mul(s, params);
+
+            let (x0, y0) = p.into_xy();
+            let (x1, y1) = q.into_xy();
+
+            let num_x0 = AllocatedNum::alloc(cs.namespace(|| "x0"), || {
+                Ok(x0)
+            }).unwrap();
+            let num_y0 = AllocatedNum::alloc(cs.namespace(|| "y0"), || {
+                Ok(y0)
+            }).unwrap();
+
+            let p = EdwardsPoint {
+                x: num_x0,
+                y: num_y0
+            };
+
+            let mut s_bits = BitIterator::new(s.into_repr()).collect::<Vec<_>>();
+            s_bits.reverse();
+            s_bits.truncate(Fs::NUM_BITS as usize);
+
+            let s_bits = s_bits.into_iter()
+                               .enumerate()
+                               .map(|(i, b)| AllocatedBit::alloc(cs.namespace(|| format!("scalar bit {}", i)), Some(b)).unwrap())
+                               .map(|v| Boolean::from(v))
+                               .collect::<Vec<_>>();
+
+            let q = p.mul(
+                cs.namespace(|| "scalar mul"),
+                &s_bits,
+                params
+            ).unwrap();
+
+            assert!(cs.is_satisfied());
+
+            assert_eq!(
+                q.x.get_value().unwrap(),
+                x1
+            );
+
+            assert_eq!(
+                q.y.get_value().unwrap(),
+                y1
+            );
+        }
+    }
+
+    #[test]
+    fn test_conditionally_select() {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for _ in 0..1000 {
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+
+            let p = edwards::Point::<Bls12, _>::rand(rng, params);
+
+            let (x0, y0) = p.into_xy();
+
+            let num_x0 = AllocatedNum::alloc(cs.namespace(|| "x0"), || {
+                Ok(x0)
+            }).unwrap();
+            let num_y0 = AllocatedNum::alloc(cs.namespace(|| "y0"), || {
+                Ok(y0)
+            }).unwrap();
+
+            let p = EdwardsPoint {
+                x: num_x0,
+                y: num_y0
+            };
+
+            let mut should_we_select = rng.gen();
+
+            // Conditionally allocate
+            let mut b = if rng.gen() {
+                Boolean::from(AllocatedBit::alloc(
+                    cs.namespace(|| "condition"),
+                    Some(should_we_select)
+                ).unwrap())
+            } else {
+                Boolean::constant(should_we_select)
+            };
+
+            // Conditionally negate
+            if rng.gen() {
+                b = b.not();
+                should_we_select = !should_we_select;
+            }
+
+            let q = p.conditionally_select(cs.namespace(|| "select"), &b).unwrap();
+
+            assert!(cs.is_satisfied());
+
+            if should_we_select {
+                assert_eq!(q.x.get_value().unwrap(), x0);
+                assert_eq!(q.y.get_value().unwrap(), y0);
+
+                cs.set("select/y'/num", Fr::one());
+                assert_eq!(cs.which_is_unsatisfied().unwrap(), "select/y' computation");
+                cs.set("select/x'/num", Fr::zero());
+                assert_eq!(cs.which_is_unsatisfied().unwrap(), "select/x' computation");
+            } else {
+                assert_eq!(q.x.get_value().unwrap(), Fr::zero());
+                assert_eq!(q.y.get_value().unwrap(), Fr::one());
+
+                cs.set("select/y'/num", x0);
+                assert_eq!(cs.which_is_unsatisfied().unwrap(), "select/y' computation");
+                cs.set("select/x'/num", y0);
+                assert_eq!(cs.which_is_unsatisfied().unwrap(), "select/x' computation");
+            }
+        }
+    }
+
+    #[test]
+    fn test_edwards_addition() {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for _ in 0..100 {
+            let p1 = edwards::Point::<Bls12, _>::rand(rng, params);
+            let p2 = edwards::Point::<Bls12, _>::rand(rng, params);
+
+            let p3 = p1.add(&p2, params);
+
+            let (x0, y0) = p1.into_xy();
+            let (x1, y1) = p2.into_xy();
+            let (x2, y2) = p3.into_xy();
+
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+
+            let num_x0 = AllocatedNum::alloc(cs.namespace(|| "x0"), || {
+                Ok(x0)
+            }).unwrap();
+            let num_y0 = AllocatedNum::alloc(cs.namespace(|| "y0"), || {
+                Ok(y0)
+            }).unwrap();
+
+            let num_x1 = AllocatedNum::alloc(cs.namespace(|| "x1"), || {
+                Ok(x1)
+            }).unwrap();
+            let num_y1 = AllocatedNum::alloc(cs.namespace(|| "y1"), || {
+                Ok(y1)
+            }).unwrap();
+
+            let p1 = EdwardsPoint {
+                x: num_x0,
+                y: num_y0
+            };
+
+            let p2 = EdwardsPoint {
+                x: num_x1,
+                y: num_y1
+            };
+
+            let p3 = p1.add(cs.namespace(|| "addition"), &p2, params).unwrap();
+
+            assert!(cs.is_satisfied());
+
+            assert!(p3.x.get_value().unwrap() == x2);
+            assert!(p3.y.get_value().unwrap() == y2);
+
+            let u = cs.get("addition/U/num");
+            cs.set("addition/U/num", rng.gen());
+            assert_eq!(cs.which_is_unsatisfied(), Some("addition/U computation"));
+            cs.set("addition/U/num", u);
+            assert!(cs.is_satisfied());
+
+            let x3 = cs.get("addition/x3/num");
+            cs.set("addition/x3/num", rng.gen());
+            assert_eq!(cs.which_is_unsatisfied(), Some("addition/x3 computation"));
+            cs.set("addition/x3/num", x3);
+            assert!(cs.is_satisfied());
+
+            let y3 = cs.get("addition/y3/num");
+            cs.set("addition/y3/num", rng.gen());
+            assert_eq!(cs.which_is_unsatisfied(), Some("addition/y3 computation"));
+            cs.set("addition/y3/num", y3);
+            assert!(cs.is_satisfied());
+        }
+    }
+
+    #[test]
+    fn test_edwards_doubling() {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for _ in 0..100 {
+            let p1 = edwards::Point::<Bls12, _>::rand(rng, params);
+            let p2 = p1.double(params);
+
+            let (x0, y0) = p1.into_xy();
+            let (x1, y1) = p2.into_xy();
+
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+
+            let num_x0 = AllocatedNum::alloc(cs.namespace(|| "x0"), || {
+                Ok(x0)
+            }).unwrap();
+            let num_y0 = AllocatedNum::alloc(cs.namespace(|| "y0"), || {
+                Ok(y0)
+            }).unwrap();
+
+            let p1 = EdwardsPoint {
+                x: num_x0,
+                y: num_y0
+            };
+
+            let p2 = p1.double(cs.namespace(|| "doubling"), params).unwrap();
+
+            assert!(cs.is_satisfied());
+
+            assert!(p2.x.get_value().unwrap() == x1);
+            assert!(p2.y.get_value().unwrap() == y1);
+        }
+    }
+
+    #[test]
+    fn test_montgomery_addition() {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for _ in 0..100 {
+            let p1 = loop {
+                let x: Fr = rng.gen();
+                let s: bool = rng.gen();
+
+                if let Some(p) = montgomery::Point::<Bls12, _>::get_for_x(x, s, params) {
+                    break p;
+                }
+            };
+
+            let p2 = loop {
+                let x: Fr = rng.gen();
+                let s: bool = rng.gen();
+
+                if let Some(p) = montgomery::Point::<Bls12, _>::get_for_x(x, s, params) {
+                    break p;
+                }
+            };
+
+            let p3 = p1.add(&p2, params);
+
+            let (x0, y0) = p1.into_xy().unwrap();
+            let (x1, y1) = p2.into_xy().unwrap();
+            let (x2, y2) = p3.into_xy().unwrap();
+
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+
+            let num_x0 = AllocatedNum::alloc(cs.namespace(|| "x0"), || {
+                Ok(x0)
+            }).unwrap();
+            let num_y0 = AllocatedNum::alloc(cs.namespace(|| "y0"), || {
+                Ok(y0)
+            }).unwrap();
+
+            let num_x1 = AllocatedNum::alloc(cs.namespace(|| "x1"), || {
+                Ok(x1)
+            }).unwrap();
+            let num_y1 = AllocatedNum::alloc(cs.namespace(|| "y1"), || {
+                Ok(y1)
+            }).unwrap();
+
+            let p1 = MontgomeryPoint {
+                x: num_x0.into(),
+                y: num_y0.into()
+            };
 
-            Ok(t0)
-        })?;
+            let p2 = MontgomeryPoint {
+                x: num_x1.into(),
+                y: num_y1.into()
+            };
 
-        // y' + y = lambda(x - x')
-        cs.enforce(
-            || "evaluate yprime",
-            |lc| lc + &self.x.lc(E::Fr::one())
-                    - xprime.get_variable(),
+            let p3 = p1.add(cs.namespace(|| "addition"), &p2, params).unwrap();
 
-            |lc| lc + lambda.get_variable(),
+            assert!(cs.is_satisfied());
 
-            |lc| lc + yprime.get_variable()
-                    + &self.y.lc(E::Fr::one())
-        );
+            assert!(p3.x.get_value().unwrap() == x2);
+            assert!(p3.y.get_value().unwrap() == y2);
 
-        Ok(MontgomeryPoint {
-            x: xprime.into(),
-            y: yprime.into()
-        })
+            cs.set("addition/yprime/num", rng.gen());
+            assert_eq!(cs.which_is_unsatisfied(), Some("addition/evaluate yprime"));
+            cs.set("addition/yprime/num", y2);
+            assert!(cs.is_satisfied());
+
+            cs.set("addition/xprime/num", rng.gen());
+            assert_eq!(cs.which_is_unsatisfied(), Some("addition/evaluate xprime"));
+            cs.set("addition/xprime/num", x2);
+            assert!(cs.is_satisfied());
+
+            cs.set("addition/lambda/num", rng.gen());
+            assert_eq!(cs.which_is_unsatisfied(), Some("addition/evaluate lambda"));
+        }
     }
 }
 
 #[cfg(test)]
-mod test {
+mod baby_test {
     use bellman::{ConstraintSystem};
     use rand::{XorShiftRng, SeedableRng, Rand, Rng};
-    use bellman::pairing::bls12_381::{Bls12, Fr};
+    use bellman::pairing::bn256::{Bn256, Fr};
     use bellman::pairing::ff::{BitIterator, Field, PrimeField};
     use ::circuit::test::*;
-    use ::jubjub::{
+    use ::alt_babyjubjub::{
         montgomery,
         edwards,
-        JubjubBls12,
+        AltJubjubBn256,
         JubjubParams,
-        FixedGenerators
+        FixedGenerators,
+        FixedBaseTable,
+        PrimeOrder
     };
-    use ::jubjub::fs::Fs;
-    use super::{
+    use ::alt_babyjubjub::fs::Fs;
+
+        use super::{
         MontgomeryPoint,
         EdwardsPoint,
         AllocatedNum,
-        fixed_base_multiplication
+        fixed_base_multiplication,
+        fixed_base_multiplication_with_table,
+        multi_scalar_mul
     };
     use super::super::boolean::{
         Boolean,
@@ -776,13 +1673,13 @@ mod test {
 
     #[test]
     fn test_into_edwards() {
-        let params = &JubjubBls12::new();
+        let params = &AltJubjubBn256::new();
         let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
 
         for _ in 0..100 {
-            let mut cs = TestConstraintSystem::<Bls12>::new();
+            let mut cs = TestConstraintSystem::<Bn256>::new();
 
-            let p = montgomery::Point::<Bls12, _>::rand(rng, params);
+            let p = montgomery::Point::<Bn256, _>::rand(rng, params);
             let (u, v) = edwards::Point::from_montgomery(&p, params).into_xy();
             let (x, y) = p.into_xy().unwrap();
 
@@ -815,13 +1712,13 @@ mod test {
 
     #[test]
     fn test_interpret() {
-        let params = &JubjubBls12::new();
+        let params = &AltJubjubBn256::new();
         let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
 
         for _ in 0..100 {
-            let p = edwards::Point::<Bls12, _>::rand(rng, &params);
+            let p = edwards::Point::<Bn256, _>::rand(rng, &params);
 
-            let mut cs = TestConstraintSystem::<Bls12>::new();
+            let mut cs = TestConstraintSystem::<Bn256>::new();
             let q = EdwardsPoint::witness(
                 &mut cs,
                 Some(p.clone()),
@@ -836,10 +1733,10 @@ mod test {
         }
 
         for _ in 0..100 {
-            let p = edwards::Point::<Bls12, _>::rand(rng, &params);
+            let p = edwards::Point::<Bn256, _>::rand(rng, &params);
             let (x, y) = p.into_xy();
 
-            let mut cs = TestConstraintSystem::<Bls12>::new();
+            let mut cs = TestConstraintSystem::<Bn256>::new();
             let numx = AllocatedNum::alloc(cs.namespace(|| "x"), || {
                 Ok(x)
             }).unwrap();
@@ -859,7 +1756,7 @@ mod test {
             let x = rng.gen();
             let y = rng.gen();
 
-            let mut cs = TestConstraintSystem::<Bls12>::new();
+            let mut cs = TestConstraintSystem::<Bn256>::new();
             let numx = AllocatedNum::alloc(cs.namespace(|| "x"), || {
                 Ok(x)
             }).unwrap();
@@ -875,11 +1772,11 @@ mod test {
 
     #[test]
     fn test_edwards_fixed_base_multiplication()  {
-        let params = &JubjubBls12::new();
+        let params = &AltJubjubBn256::new();
         let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
 
         for _ in 0..100 {
-            let mut cs = TestConstraintSystem::<Bls12>::new();
+            let mut cs = TestConstraintSystem::<Bn256>::new();
 
             let p = params.generator(FixedGenerators::NoteCommitmentRandomness);
             let s = Fs::rand(rng);
@@ -908,15 +1805,78 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_edwards_fixed_base_multiplication_with_table()  {
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let bases = (0..2).map(|_| edwards::Point::<Bn256, _>::rand(rng, &AltJubjubBn256::new()).mul_by_cofactor(&AltJubjubBn256::new()))
+                          .collect::<Vec<_>>();
+        let params = &AltJubjubBn256::new().with_fixed_bases(&bases);
+        assert_eq!(params.fixed_base_tables().len(), 2);
+
+        // tables of the protocol generators are the same as precomputed ones
+        let table = FixedBaseTable::new(params.generator(FixedGenerators::NoteCommitmentRandomness), params);
+        assert_eq!(table.windows(), params.circuit_generators(FixedGenerators::NoteCommitmentRandomness));
+
+        for i in 0..20 {
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+
+            let (p, table) = if i % 2 == 0 {
+                (&bases[i % 4 / 2], params.fixed_base_tables()[i % 4 / 2].clone())
+            } else {
+                (&bases[0], FixedBaseTable::new(&bases[0], params))
+            };
+            assert!(table.base() == p);
+
+            let s = Fs::rand(rng);
+            let q = p.mul(s, params);
+            let (x1, y1) = q.into_xy();
+
+            let mut s_bits = BitIterator::new(s.into_repr()).collect::<Vec<_>>();
+            s_bits.reverse();
+            s_bits.truncate(Fs::NUM_BITS as usize);
+
+            let s_bits = s_bits.into_iter()
+                               .enumerate()
+                               .map(|(i, b)| AllocatedBit::alloc(cs.namespace(|| format!("scalar bit {}", i)), Some(b)).unwrap())
+                               .map(|v| Boolean::from(v))
+                               .collect::<Vec<_>>();
+
+            let q = fixed_base_multiplication_with_table(
+                cs.namespace(|| "multiplication"),
+                &table,
+                &s_bits,
+                params
+            ).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(q.x.get_value().unwrap(), x1);
+            assert_eq!(q.y.get_value().unwrap(), y1);
+
+            // exactly the cost of a multiplication by a protocol generator
+            let mut generator_cs = TestConstraintSystem::<Bn256>::new();
+            let bits = s_bits.iter().enumerate()
+                             .map(|(i, b)| Boolean::from(AllocatedBit::alloc(generator_cs.namespace(|| format!("scalar bit {}", i)), b.get_value()).unwrap()))
+                             .collect::<Vec<_>>();
+            fixed_base_multiplication(
+                generator_cs.namespace(|| "multiplication"),
+                FixedGenerators::NoteCommitmentRandomness,
+                &bits,
+                params
+            ).unwrap();
+            assert_eq!(cs.num_constraints(), generator_cs.num_constraints());
+        }
+    }
+
     #[test]
     fn test_edwards_multiplication() {
-        let params = &JubjubBls12::new();
+        let params = &AltJubjubBn256::new();
         let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
 
         for _ in 0..100 {
-            let mut cs = TestConstraintSystem::<Bls12>::new();
+            let mut cs = TestConstraintSystem::<Bn256>::new();
 
-            let p = edwards::Point::<Bls12, _>::rand(rng, params);
+            let p = edwards::Point::<Bn256, _>::rand(rng, params);
             let s = Fs::rand(rng);
             let q = p.mul(s, params);
 
@@ -965,15 +1925,156 @@ mod test {
         }
     }
 
+    fn alloc_terms<CS: ConstraintSystem<Bn256>, R: Rng>(
+        mut cs: CS,
+        rng: &mut R,
+        lengths: &[usize],
+        params: &AltJubjubBn256
+    ) -> (Vec<(EdwardsPoint<Bn256>, Vec<Boolean>)>, edwards::Point<Bn256, PrimeOrder>)
+    {
+        let mut terms = vec![];
+        let mut expected = edwards::Point::zero();
+
+        for (j, &length) in lengths.iter().enumerate() {
+            // scalars may exceed the group order, so stay in the prime order subgroup
+            let p = edwards::Point::<Bn256, _>::rand(rng, params).mul_by_cofactor(params);
+            let bits: Vec<bool> = (0..length).map(|_| rng.gen()).collect();
+
+            let mut s = Fs::zero();
+            for &b in bits.iter().rev() {
+                s.double();
+                if b {
+                    s.add_assign(&Fs::one());
+                }
+            }
+            expected = expected.add(&p.mul(s, params), params);
+
+            let point = EdwardsPoint::witness(
+                cs.namespace(|| format!("point {}", j)),
+                Some(p),
+                params
+            ).unwrap();
+            let bits = bits.into_iter()
+                           .enumerate()
+                           .map(|(i, b)| AllocatedBit::alloc(cs.namespace(|| format!("scalar {} bit {}", j, i)), Some(b)).unwrap())
+                           .map(|v| Boolean::from(v))
+                           .collect::<Vec<_>>();
+
+            terms.push((point, bits));
+        }
+
+        (terms, expected)
+    }
+
+    #[test]
+    fn test_multi_scalar_mul() {
+        let params = &AltJubjubBn256::new();
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let cases: Vec<Vec<usize>> = vec![
+            vec![1],
+            vec![2],
+            vec![Fs::NUM_BITS as usize],
+            vec![Fs::NUM_BITS as usize, Fs::NUM_BITS as usize],
+            vec![1, 7, 120, Fs::NUM_BITS as usize],
+            vec![0, 3, 0],
+        ];
+
+        for lengths in cases.iter() {
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+
+            let (terms, expected) = alloc_terms(cs.namespace(|| "terms"), rng, lengths, params);
+            let terms = terms.iter().map(|&(ref p, ref by)| (p, &by[..])).collect::<Vec<_>>();
+
+            let q = multi_scalar_mul(
+                cs.namespace(|| "multi scalar mul"),
+                &terms,
+                params
+            ).unwrap();
+
+            assert!(cs.is_satisfied());
+
+            let (x1, y1) = expected.into_xy();
+            assert_eq!(q.x.get_value().unwrap(), x1);
+            assert_eq!(q.y.get_value().unwrap(), y1);
+
+            // flipping a bit of the largest scalar changes the result
+            let last = (0..lengths.len()).max_by_key(|&j| lengths[j]).unwrap();
+            let bit = terms[last].1[0].get_value().unwrap();
+            let path = format!("terms/scalar {} bit 0/boolean", last);
+            cs.set(&path, if bit { Fr::zero() } else { Fr::one() });
+            assert!(!cs.is_satisfied());
+        }
+    }
+
+    #[test]
+    fn test_multi_scalar_mul_empty() {
+        let params = &AltJubjubBn256::new();
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for lengths in [vec![], vec![0], vec![0, 0, 0]].iter() {
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+
+            let (terms, _) = alloc_terms(cs.namespace(|| "terms"), rng, lengths, params);
+            let terms = terms.iter().map(|&(ref p, ref by)| (p, &by[..])).collect::<Vec<_>>();
+
+            let q = multi_scalar_mul(
+                cs.namespace(|| "multi scalar mul"),
+                &terms,
+                params
+            ).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(q.x.get_value().unwrap(), Fr::zero());
+            assert_eq!(q.y.get_value().unwrap(), Fr::one());
+
+            // the identity is constrained
+            cs.set("multi scalar mul/identity/y/num", Fr::zero());
+            assert!(!cs.is_satisfied());
+        }
+    }
+
+    #[test]
+    fn test_multi_scalar_mul_constraints() {
+        let params = &AltJubjubBn256::new();
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for &n in [1, 2, 4, 8].iter() {
+            let lengths = vec![Fs::NUM_BITS as usize; n];
+
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+            let (terms, _) = alloc_terms(cs.namespace(|| "terms"), rng, &lengths, params);
+            let allocation = cs.num_constraints();
+
+            let refs = terms.iter().map(|&(ref p, ref by)| (p, &by[..])).collect::<Vec<_>>();
+            multi_scalar_mul(cs.namespace(|| "multi scalar mul"), &refs, params).unwrap();
+            let straus = cs.num_constraints() - allocation;
+
+            let mut result: Option<EdwardsPoint<Bn256>> = None;
+            for (j, &(ref p, ref by)) in terms.iter().enumerate() {
+                let tmp = p.mul(cs.namespace(|| format!("mul {}", j)), by, params).unwrap();
+                result = Some(match result {
+                    None => tmp,
+                    Some(acc) => acc.add(cs.namespace(|| format!("add {}", j)), &tmp, params).unwrap()
+                });
+            }
+            let separate = cs.num_constraints() - allocation - straus;
+
+            assert!(cs.is_satisfied());
+            print!("{} terms: multi_scalar_mul takes {} constraints, mul + add take {}\n", n, straus, separate);
+            assert!(straus < separate);
+        }
+    }
+
     #[test]
     fn test_conditionally_select() {
-        let params = &JubjubBls12::new();
+        let params = &AltJubjubBn256::new();
         let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
 
         for _ in 0..1000 {
-            let mut cs = TestConstraintSystem::<Bls12>::new();
+            let mut cs = TestConstraintSystem::<Bn256>::new();
 
-            let p = edwards::Point::<Bls12, _>::rand(rng, params);
+            let p = edwards::Point::<Bn256, _>::rand(rng, params);
 
             let (x0, y0) = p.into_xy();
 
@@ -1033,12 +2134,12 @@ mod test {
 
     #[test]
     fn test_edwards_addition() {
-        let params = &JubjubBls12::new();
+        let params = &AltJubjubBn256::new();
         let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
 
         for _ in 0..100 {
-            let p1 = edwards::Point::<Bls12, _>::rand(rng, params);
-            let p2 = edwards::Point::<Bls12, _>::rand(rng, params);
+            let p1 = edwards::Point::<Bn256, _>::rand(rng, params);
+            let p2 = edwards::Point::<Bn256, _>::rand(rng, params);
 
             let p3 = p1.add(&p2, params);
 
@@ -1046,7 +2147,7 @@ mod test {
             let (x1, y1) = p2.into_xy();
             let (x2, y2) = p3.into_xy();
 
-            let mut cs = TestConstraintSystem::<Bls12>::new();
+            let mut cs = TestConstraintSystem::<Bn256>::new();
 
             let num_x0 = AllocatedNum::alloc(cs.namespace(|| "x0"), || {
                 Ok(x0)
@@ -1101,17 +2202,17 @@ mod test {
 
     #[test]
     fn test_edwards_doubling() {
-        let params = &JubjubBls12::new();
+        let params = &AltJubjubBn256::new();
         let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
 
         for _ in 0..100 {
-            let p1 = edwards::Point::<Bls12, _>::rand(rng, params);
+            let p1 = edwards::Point::<Bn256, _>::rand(rng, params);
             let p2 = p1.double(params);
 
             let (x0, y0) = p1.into_xy();
             let (x1, y1) = p2.into_xy();
 
-            let mut cs = TestConstraintSystem::<Bls12>::new();
+            let mut cs = TestConstraintSystem::<Bn256>::new();
 
             let num_x0 = AllocatedNum::alloc(cs.namespace(|| "x0"), || {
                 Ok(x0)
@@ -1136,7 +2237,7 @@ mod test {
 
     #[test]
     fn test_montgomery_addition() {
-        let params = &JubjubBls12::new();
+        let params = &AltJubjubBn256::new();
         let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
 
         for _ in 0..100 {
@@ -1144,7 +2245,7 @@ mod test {
                 let x: Fr = rng.gen();
                 let s: bool = rng.gen();
 
-                if let Some(p) = montgomery::Point::<Bls12, _>::get_for_x(x, s, params) {
+                if let Some(p) = montgomery::Point::<Bn256, _>::get_for_x(x, s, params) {
                     break p;
                 }
             };
@@ -1153,7 +2254,7 @@ mod test {
                 let x: Fr = rng.gen();
                 let s: bool = rng.gen();
 
-                if let Some(p) = montgomery::Point::<Bls12, _>::get_for_x(x, s, params) {
+                if let Some(p) = montgomery::Point::<Bn256, _>::get_for_x(x, s, params) {
                     break p;
                 }
             };
@@ -1164,7 +2265,7 @@ mod test {
             let (x1, y1) = p2.into_xy().unwrap();
             let (x2, y2) = p3.into_xy().unwrap();
 
-            let mut cs = TestConstraintSystem::<Bls12>::new();
+            let mut cs = TestConstraintSystem::<Bn256>::new();
 
             let num_x0 = AllocatedNum::alloc(cs.namespace(|| "x0"), || {
                 Ok(x0)
@@ -1214,35 +2315,79 @@ mod test {
 }
 
 #[cfg(test)]
-mod baby_test {
+mod babyjubjub_test {
     use bellman::{ConstraintSystem};
     use rand::{XorShiftRng, SeedableRng, Rand, Rng};
     use bellman::pairing::bn256::{Bn256, Fr};
     use bellman::pairing::ff::{BitIterator, Field, PrimeField};
     use ::circuit::test::*;
-    use ::alt_babyjubjub::{
+    use ::babyjubjub::{
         montgomery,
         edwards,
-        AltJubjubBn256,
+        JubjubBn256,
         JubjubParams,
         FixedGenerators
     };
-    use ::alt_babyjubjub::fs::Fs;
-
-        use super::{
+    use ::babyjubjub::fs::Fs;
+    use super::{
         MontgomeryPoint,
         EdwardsPoint,
         AllocatedNum,
-        fixed_base_multiplication
+        multi_scalar_mul
     };
+    use super::super::baby_ecc::fixed_base_multiplication;
     use super::super::boolean::{
         Boolean,
         AllocatedBit
     };
 
+    #[test]
+    fn test_multi_scalar_mul() {
+        let params = &JubjubBn256::new();
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for _ in 0..10 {
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+
+            let mut expected = edwards::Point::zero();
+            let mut terms = vec![];
+
+            for j in 0..3 {
+                let p = edwards::Point::<Bn256, _>::rand(rng, params).mul_by_cofactor(params);
+                let s = Fs::rand(rng);
+                expected = expected.add(&p.mul(s, params), params);
+
+                let mut s_bits = BitIterator::new(s.into_repr()).collect::<Vec<_>>();
+                s_bits.reverse();
+                s_bits.truncate(Fs::NUM_BITS as usize);
+
+                let s_bits = s_bits.into_iter()
+                                   .enumerate()
+                                   .map(|(i, b)| AllocatedBit::alloc(cs.namespace(|| format!("scalar {} bit {}", j, i)), Some(b)).unwrap())
+                                   .map(|v| Boolean::from(v))
+                                   .collect::<Vec<_>>();
+                let p = EdwardsPoint::witness(cs.namespace(|| format!("point {}", j)), Some(p), params).unwrap();
+
+                terms.push((p, s_bits));
+            }
+
+            let q = multi_scalar_mul(
+                cs.namespace(|| "multi scalar mul"),
+                &terms.iter().map(|&(ref p, ref by)| (p, &by[..])).collect::<Vec<_>>(),
+                params
+            ).unwrap();
+
+            assert!(cs.is_satisfied());
+
+            let (x, y) = expected.into_xy();
+            assert_eq!(q.x.get_value().unwrap(), x);
+            assert_eq!(q.y.get_value().unwrap(), y);
+        }
+    }
+
     #[test]
     fn test_into_edwards() {
-        let params = &AltJubjubBn256::new();
+        let params = &JubjubBn256::new();
         let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
 
         for _ in 0..100 {
@@ -1281,7 +2426,7 @@ mod baby_test {
 
     #[test]
     fn test_interpret() {
-        let params = &AltJubjubBn256::new();
+        let params = &JubjubBn256::new();
         let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
 
         for _ in 0..100 {
@@ -1341,7 +2486,7 @@ mod baby_test {
 
     #[test]
     fn test_edwards_fixed_base_multiplication()  {
-        let params = &AltJubjubBn256::new();
+        let params = &JubjubBn256::new();
         let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
 
         for _ in 0..100 {
@@ -1376,7 +2521,7 @@ mod baby_test {
 
     #[test]
     fn test_edwards_multiplication() {
-        let params = &AltJubjubBn256::new();
+        let params = &JubjubBn256::new();
         let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
 
         for _ in 0..100 {
@@ -1433,7 +2578,7 @@ mod baby_test {
 
     #[test]
     fn test_conditionally_select() {
-        let params = &AltJubjubBn256::new();
+        let params = &JubjubBn256::new();
         let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
 
         for _ in 0..1000 {
@@ -1499,7 +2644,7 @@ mod baby_test {
 
     #[test]
     fn test_edwards_addition() {
-        let params = &AltJubjubBn256::new();
+        let params = &JubjubBn256::new();
         let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
 
         for _ in 0..100 {
@@ -1545,11 +2690,11 @@ mod baby_test {
             assert!(p3.x.get_value().unwrap() == x2);
             assert!(p3.y.get_value().unwrap() == y2);
 
-            let u = cs.get("addition/U/num");
-            cs.set("addition/U/num", rng.gen());
-            assert_eq!(cs.which_is_unsatisfied(), Some("addition/U computation"));
-            cs.set("addition/U/num", u);
-            assert!(cs.is_satisfied());
+            // let u = cs.get("addition/U/num");
+            // cs.set("addition/U/num", rng.gen());
+            // assert_eq!(cs.which_is_unsatisfied(), Some("addition/U computation"));
+            // cs.set("addition/U/num", u);
+            // assert!(cs.is_satisfied());
 
             let x3 = cs.get("addition/x3/num");
             cs.set("addition/x3/num", rng.gen());
@@ -1567,7 +2712,7 @@ mod baby_test {
 
     #[test]
     fn test_edwards_doubling() {
-        let params = &AltJubjubBn256::new();
+        let params = &JubjubBn256::new();
         let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
 
         for _ in 0..100 {
@@ -1602,7 +2747,7 @@ mod baby_test {
 
     #[test]
     fn test_montgomery_addition() {
-        let params = &AltJubjubBn256::new();
+        let params = &JubjubBn256::new();
         let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
 
         for _ in 0..100 {
@@ -1677,4 +2822,4 @@ mod baby_test {
             assert_eq!(cs.which_is_unsatisfied(), Some("addition/evaluate lambda"));
         }
     }
-}
\ No newline at end of file
+}
@@ -1,65 +1,306 @@
 use bellman::pairing::{Engine};
-use bellman::pairing::ff::{Field, PrimeField};
+use bellman::pairing::ff::{Field, PrimeField, BitIterator};
 use bellman::{ConstraintSystem, SynthesisError};
-use super::boolean::{Boolean};
+use super::boolean::{AllocatedBit, Boolean};
 use super::num::{AllocatedNum, Num};
 use super::Assignment;
 
-/// Takes a bit decomposition, parses and packs into an AllocatedNum
-/// If exponent is equal to zero, then exponent multiplier is equal to 1
-pub fn parse_with_exponent_le<E: Engine, CS: ConstraintSystem<E>>(
-    mut cs: CS,
-    bits: &[Boolean],
-    exponent_length: usize,
-    mantissa_length: usize,
-    exponent_base: u64
-) -> Result<AllocatedNum<E>, SynthesisError>
-{
-    assert!(bits.len() == exponent_length + mantissa_length);
+/// Rounding mode of the encoding
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    /// Fails if the value is not representable
+    Exact,
+    /// Takes the largest representable value that is not larger
+    Down
+}
 
-    let one_allocated = AllocatedNum::alloc(
-        cs.namespace(|| "allocate one"),
-        || Ok(E::Fr::one())
-    )?;
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The format has no mantissa bits
+    EmptyMantissa,
+    /// The base of the exponent is less than 2
+    BaseTooSmall,
+    /// The largest encoded value doesn't fit into u128
+    RangeTooLarge,
+    /// The value has no exact encoding
+    NotRepresentable,
+    /// The encoding is not the canonical one of its value
+    NonCanonical,
+    /// The encoding has a wrong number of bits
+    InvalidLength
+}
 
-    let mut exponent_result = AllocatedNum::alloc(
-        cs.namespace(|| "allocate exponent result"),
-        || Ok(E::Fr::one())
-    )?;
+/// Floating point format of non-negative integers: value = mantissa * base^exponent.
+/// Encoding is exponent bits followed by mantissa bits, both in LE order.
+/// Encoding is canonical if exponent is the smallest possible for the value,
+/// so every value has exactly one canonical encoding
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FloatCodec {
+    pub exponent_bits: usize,
+    pub mantissa_bits: usize,
+    pub base: u32
+}
+
+impl FloatCodec {
+    /// Makes the format, the largest encoded value must fit into u128
+    pub fn new(exponent_bits: usize, mantissa_bits: usize, base: u32) -> Result<Self, Error> {
+        if mantissa_bits == 0 {
+            return Err(Error::EmptyMantissa);
+        }
+        if base < 2 {
+            return Err(Error::BaseTooSmall);
+        }
 
-    let exponent_base_string = exponent_base.to_string();
-    let exponent_base_value = E::Fr::from_str(&exponent_base_string.clone()).unwrap();
+        // with base >= 2 the exponent is at most 127, so this also bounds the exponent bits
+        let max_exponent = 1u32.checked_shl(exponent_bits as u32).filter(|_| exponent_bits < 32).map(|e| e - 1);
+        let max_value = max_exponent
+            .and_then(|e| u128::from(base).checked_pow(e))
+            .filter(|_| mantissa_bits <= 128)
+            .and_then(|m| (u128::max_value() >> (128 - mantissa_bits)).checked_mul(m));
+        if max_value.is_none() {
+            return Err(Error::RangeTooLarge);
+        }
 
-    let mut exponent_base = AllocatedNum::alloc(
-        cs.namespace(|| "allocate exponent base"), 
-        || Ok(exponent_base_value)
-    )?;
+        Ok(FloatCodec {
+            exponent_bits,
+            mantissa_bits,
+            base
+        })
+    }
 
-    let exponent_value = exponent_base_value;
+    pub fn encoding_length(&self) -> usize {
+        self.exponent_bits + self.mantissa_bits
+    }
 
-    for i in 0..exponent_length {
-        let thisbit = &bits[i];
+    pub fn max_mantissa(&self) -> u128 {
+        u128::max_value() >> (128 - self.mantissa_bits)
+    }
 
-        let multiplier = AllocatedNum::conditionally_select(
-            cs.namespace(|| format!("select exponent multiplier {}", i)),
-            &exponent_base, 
-            &one_allocated, 
-            &thisbit
-        )?;
+    pub fn max_exponent(&self) -> u32 {
+        (1u32 << self.exponent_bits) - 1
+    }
+
+    pub fn max_value(&self) -> u128 {
+        self.max_mantissa() * self.exponent_multiplier(self.max_exponent())
+    }
+
+    /// Number of bits of the largest encoded value
+    pub fn max_value_bits(&self) -> u32 {
+        128 - self.max_value().leading_zeros()
+    }
+
+    fn exponent_multiplier(&self, exponent: u32) -> u128 {
+        u128::from(self.base).pow(exponent)
+    }
 
-        exponent_result = exponent_result.mul(
-            cs.namespace(|| format!("make exponent result {}", i)),
-            &multiplier
+    /// Smallest mantissa that can be used with a non-zero exponent in a canonical encoding
+    fn min_canonical_mantissa(&self) -> u128 {
+        // ceil(2^mantissa_bits / base) without overflowing for 128 bits
+        self.max_mantissa() / u128::from(self.base) + 1
+    }
+
+    pub fn encode(&self, value: u128, rounding: Rounding) -> Result<Vec<bool>, Error> {
+        // find the largest representable value, smallest exponent wins on ties
+        let mut best: Option<(u32, u128, u128)> = None;
+        for exponent in 0..=self.max_exponent() {
+            let multiplier = self.exponent_multiplier(exponent);
+            let mantissa = std::cmp::min(value / multiplier, self.max_mantissa());
+            let candidate = mantissa * multiplier;
+
+            if best.map(|(_, _, best_value)| candidate > best_value).unwrap_or(true) {
+                best = Some((exponent, mantissa, candidate));
+            }
+        }
+
+        let (exponent, mantissa, encoded_value) = best.expect("there is at least one exponent");
+        if rounding == Rounding::Exact && encoded_value != value {
+            return Err(Error::NotRepresentable);
+        }
+
+        let mut encoding = Vec::with_capacity(self.encoding_length());
+        encoding.extend((0..self.exponent_bits).map(|i| exponent & (1 << i) != 0));
+        encoding.extend((0..self.mantissa_bits).map(|i| mantissa & (1 << i) != 0));
+
+        Ok(encoding)
+    }
+
+    pub fn is_canonical(&self, encoding: &[bool]) -> bool {
+        if encoding.len() != self.encoding_length() {
+            return false;
+        }
+        let (exponent, mantissa) = self.split(encoding);
+
+        exponent == 0 || mantissa >= self.min_canonical_mantissa()
+    }
+
+    /// Decodes the value, fails on non-canonical encodings
+    pub fn decode(&self, encoding: &[bool]) -> Result<u128, Error> {
+        if encoding.len() != self.encoding_length() {
+            return Err(Error::InvalidLength);
+        }
+        if !self.is_canonical(encoding) {
+            return Err(Error::NonCanonical);
+        }
+
+        let (exponent, mantissa) = self.split(encoding);
+
+        Ok(mantissa * self.exponent_multiplier(exponent))
+    }
+
+    fn split(&self, encoding: &[bool]) -> (u32, u128) {
+        assert_eq!(encoding.len(), self.encoding_length());
+
+        let exponent = encoding[..self.exponent_bits].iter().rev().fold(0u32, |acc, b| (acc << 1) | (*b as u32));
+        let mantissa = encoding[self.exponent_bits..].iter().rev().fold(0u128, |acc, b| (acc << 1) | (*b as u128));
+
+        (exponent, mantissa)
+    }
+
+    /// Decodes the value in the circuit. The constraint system is unsatisfied
+    /// if the encoding is not canonical. Bits are expected to be constrained
+    /// to be boolean already. Fails if the encoded values don't fit into the field
+    pub fn decode_in_circuit<E: Engine, CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        bits: &[Boolean]
+    ) -> Result<AllocatedNum<E>, SynthesisError>
+    {
+        assert_eq!(bits.len(), self.encoding_length());
+        if self.max_value_bits() > E::Fr::CAPACITY {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+
+        let base = E::Fr::from_str(&self.base.to_string()).unwrap();
+        let (result, mantissa) = pack_float(
+            cs.namespace(|| "pack"),
+            bits,
+            self.exponent_bits,
+            self.mantissa_bits,
+            base
         )?;
 
-        exponent_base = exponent_base.clone().square(
-            cs.namespace(|| format!("make exponent base {}", i))
+        // exponent is zero if all the bits are zero
+        let mut exponent_is_zero = Boolean::constant(true);
+        for (i, bit) in bits[..self.exponent_bits].iter().enumerate() {
+            exponent_is_zero = Boolean::and(
+                cs.namespace(|| format!("exponent is zero up to bit {}", i)),
+                &exponent_is_zero,
+                &bit.not()
+            )?;
+        }
+        let exponent_is_non_zero = exponent_is_zero.not();
+
+        // if exponent is non-zero then mantissa - min_canonical_mantissa should fit into mantissa bits,
+        // so we check that (mantissa - min_canonical_mantissa) * exponent_is_non_zero fits
+        let min_mantissa = E::Fr::from_str(&self.min_canonical_mantissa().to_string()).unwrap();
+        let difference_value = match (mantissa.get_value(), exponent_is_non_zero.get_value()) {
+            (Some(mantissa), Some(is_non_zero)) => {
+                if is_non_zero {
+                    let mut tmp = mantissa;
+                    tmp.sub_assign(&min_mantissa);
+                    Some(tmp)
+                } else {
+                    Some(E::Fr::zero())
+                }
+            },
+            _ => None
+        };
+
+        let difference = AllocatedNum::alloc(
+            cs.namespace(|| "canonical mantissa difference"),
+            || Ok(*difference_value.get()?)
         )?;
 
-        // exponent_base = exponent_base.mul(
-        //     cs.namespace(|| format!("make exponent base {}", i)), 
-        //     &exponent_base.clone()
-        // )?;
+        cs.enforce(
+            || "enforce canonical mantissa difference",
+            |lc| lc + &mantissa.lc(E::Fr::one()) - (min_mantissa, CS::one()),
+            |lc| lc + &exponent_is_non_zero.lc(CS::one(), E::Fr::one()),
+            |lc| lc + difference.get_variable()
+        );
+
+        let difference_bits: Vec<Option<bool>> = match difference_value {
+            Some(value) => {
+                let mut bits: Vec<bool> = BitIterator::new(value.into_repr()).collect();
+                bits.reverse();
+                bits.truncate(self.mantissa_bits);
+
+                bits.into_iter().map(|b| Some(b)).collect()
+            },
+            None => vec![None; self.mantissa_bits]
+        };
+
+        let mut packed = Num::<E>::zero();
+        let mut coeff = E::Fr::one();
+        for (i, value) in difference_bits.into_iter().enumerate() {
+            let bit = AllocatedBit::alloc(
+                cs.namespace(|| format!("canonical mantissa difference bit {}", i)),
+                value
+            )?;
+            packed = packed.add_bool_with_coeff(CS::one(), &Boolean::from(bit), coeff);
+            coeff.double();
+        }
+
+        cs.enforce(
+            || "enforce canonical mantissa difference fits",
+            |lc| lc + &packed.lc(E::Fr::one()),
+            |lc| lc + CS::one(),
+            |lc| lc + difference.get_variable()
+        );
+
+        Ok(result)
+    }
+}
+
+/// Packs exponent and mantissa bits into mantissa * base^exponent,
+/// returns the result and the mantissa
+fn pack_float<E: Engine, CS: ConstraintSystem<E>>(
+    mut cs: CS,
+    bits: &[Boolean],
+    exponent_length: usize,
+    mantissa_length: usize,
+    exponent_base: E::Fr
+) -> Result<(AllocatedNum<E>, Num<E>), SynthesisError>
+{
+    assert!(bits.len() == exponent_length + mantissa_length);
+
+    // multiplier for the exponent bit i is 1 + bit * (base^(2^i) - 1), that is linear in the bit,
+    // and the exponent is a product of such multipliers
+    let mut exponent_result = Num::<E>::zero().add_bool_with_coeff(CS::one(), &Boolean::constant(true), E::Fr::one());
+    let mut exponent_base = exponent_base;
+
+    for i in 0..exponent_length {
+        let thisbit = &bits[i];
+
+        let mut coeff = exponent_base;
+        coeff.sub_assign(&E::Fr::one());
+        let multiplier = Num::<E>::zero()
+            .add_bool_with_coeff(CS::one(), &Boolean::constant(true), E::Fr::one())
+            .add_bool_with_coeff(CS::one(), thisbit, coeff);
+
+        exponent_result = if i == 0 {
+            multiplier
+        } else {
+            let product = AllocatedNum::alloc(
+                cs.namespace(|| format!("make exponent result {}", i)),
+                || {
+                    let mut tmp = *exponent_result.get_value().get()?;
+                    tmp.mul_assign(multiplier.get_value().get()?);
+
+                    Ok(tmp)
+                }
+            )?;
+
+            cs.enforce(
+                || format!("enforce exponent result {}", i),
+                |lc| lc + &exponent_result.lc(E::Fr::one()),
+                |lc| lc + &multiplier.lc(E::Fr::one()),
+                |lc| lc + product.get_variable()
+            );
+
+            Num::from(product)
+        };
+
+        exponent_base.square();
     }
 
     let mut mantissa_result = Num::<E>::zero();
@@ -72,38 +313,40 @@ pub fn parse_with_exponent_le<E: Engine, CS: ConstraintSystem<E>>(
         mantissa_base.double();
     }
 
-    let mantissa = AllocatedNum::alloc(
-        cs.namespace(|| "allocating mantissa"),
-        || Ok(*mantissa_result.get_value().get()?)
-    )?;
-
-    mantissa.mul(
-        cs.namespace(|| "calculate floating point result"),
-        &exponent_result
-    )
-
-    // return 
+    let result = AllocatedNum::alloc(
+        cs.namespace(|| "float point parsing result"),
+        || {
+            let mut tmp = *mantissa_result.get_value().get()?;
+            tmp.mul_assign(exponent_result.get_value().get()?);
 
-    // let mut result = mantissa_result.get_value().get()?.clone();
-
-    // let exponent_value = exponent_result.get_value().get()?.clone();
+            Ok(tmp)
+        }
+    )?;
 
-    // result.mul_assign(&exponent_value);
+    cs.enforce(
+        || "float point result constraint",
+        |lc| lc + &exponent_result.lc(E::Fr::one()),
+        |lc| lc + &mantissa_result.lc(E::Fr::one()),
+        |lc| lc + result.get_variable()
+    );
 
-    // let result_allocated = AllocatedNum::alloc(
-    //     cs.namespace(|| "float point parsing result"),
-    //     || Ok(result)
-    // )?;
+    Ok((result, mantissa_result))
+}
 
-    // // num * 1 = input
-    // cs.enforce(
-    //     || "float point result constraint",
-    //     |lc| lc + exponent_result.get_variable(),
-    //     |_| mantissa_result.lc(E::Fr::one()),
-    //     |lc| lc + result_allocated.get_variable()
-    // );
+/// Takes a bit decomposition, parses and packs into an AllocatedNum
+/// If exponent is equal to zero, then exponent multiplier is equal to 1.
+/// Doesn't check that the encoding is canonical, see `FloatCodec::decode_in_circuit`
+pub fn parse_with_exponent_le<E: Engine, CS: ConstraintSystem<E>>(
+    cs: CS,
+    bits: &[Boolean],
+    exponent_length: usize,
+    mantissa_length: usize,
+    exponent_base: u64
+) -> Result<AllocatedNum<E>, SynthesisError>
+{
+    let exponent_base = E::Fr::from_str(&exponent_base.to_string()).unwrap();
 
-    // Ok(result_allocated)
+    pack_float(cs, bits, exponent_length, mantissa_length, exponent_base).map(|(result, _)| result)
 }
 
 pub fn convert_to_float(
@@ -346,3 +589,129 @@ fn test_encoding_small_numbers() {
         print!("\n");
     }
 }
+
+#[test]
+fn test_parsing_is_constrained() {
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use ::circuit::test::*;
+
+    let mut cs = TestConstraintSystem::<Bn256>::new();
+    let bits: Vec<Boolean> = [true, false, true, true, false, true].iter().enumerate().map(|(i, &b)| {
+        Boolean::from(AllocatedBit::alloc(cs.namespace(|| format!("bit {}", i)), Some(b)).unwrap())
+    }).collect();
+
+    // 10^5 * 5
+    let result = parse_with_exponent_le(cs.namespace(|| "parse"), &bits, 3, 3, 10).unwrap();
+    assert_eq!(result.get_value().unwrap(), Fr::from_str("500000").unwrap());
+    assert!(cs.is_satisfied());
+    assert_eq!(cs.find_unconstrained(), "");
+
+    cs.set("parse/float point parsing result/num", Fr::from_str("50000").unwrap());
+    assert!(!cs.is_satisfied());
+}
+
+#[test]
+fn test_float_codec_native() {
+    for &(exponent_bits, mantissa_bits, base) in [(0, 5, 10), (1, 4, 7), (2, 3, 10), (2, 5, 3), (3, 3, 2), (3, 4, 10)].iter() {
+        let codec = FloatCodec::new(exponent_bits, mantissa_bits, base).unwrap();
+        let length = codec.encoding_length();
+
+        // every representable value has exactly one canonical encoding
+        let mut representable = std::collections::BTreeMap::new();
+        for encoding in 0..(1u32 << length) {
+            let encoding: Vec<bool> = (0..length).map(|i| encoding & (1 << i) != 0).collect();
+            if let Ok(value) = codec.decode(&encoding) {
+                assert!(representable.insert(value, encoding).is_none());
+            } else {
+                assert!(!codec.is_canonical(&encoding));
+            }
+        }
+        assert_eq!(*representable.keys().last().unwrap(), codec.max_value());
+
+        for value in 0..=(codec.max_value() + 10) {
+            match representable.get(&value) {
+                Some(encoding) => {
+                    assert_eq!(&codec.encode(value, Rounding::Exact).unwrap(), encoding);
+                    assert_eq!(&codec.encode(value, Rounding::Down).unwrap(), encoding);
+                },
+                None => {
+                    assert!(codec.encode(value, Rounding::Exact).is_err());
+
+                    let (expected, encoding) = representable.range(..value).last().unwrap();
+                    let rounded = codec.encode(value, Rounding::Down).unwrap();
+                    assert_eq!(&rounded, encoding);
+                    assert_eq!(codec.decode(&rounded).unwrap(), *expected);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_float_codec_circuit() {
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use ::circuit::test::*;
+
+    for &(exponent_bits, mantissa_bits, base) in [(0, 5, 10), (1, 4, 7), (2, 3, 10), (2, 5, 3), (3, 3, 2), (3, 4, 10), (2, 2, 5)].iter() {
+        let codec = FloatCodec::new(exponent_bits, mantissa_bits, base).unwrap();
+        let length = codec.encoding_length();
+
+        for encoding in 0..(1u32 << length) {
+            let encoding: Vec<bool> = (0..length).map(|i| encoding & (1 << i) != 0).collect();
+
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+            let bits: Vec<Boolean> = encoding.iter().enumerate().map(|(i, &b)| {
+                Boolean::from(AllocatedBit::alloc(cs.namespace(|| format!("bit {}", i)), Some(b)).unwrap())
+            }).collect();
+
+            let result = codec.decode_in_circuit(cs.namespace(|| "decode"), &bits).unwrap();
+
+            match codec.decode(&encoding) {
+                Ok(value) => {
+                    assert!(cs.is_satisfied());
+                    assert_eq!(result.get_value().unwrap(), Fr::from_str(&value.to_string()).unwrap());
+                },
+                Err(_) => {
+                    assert!(!cs.is_satisfied());
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_float_codec_limits() {
+    assert_eq!(FloatCodec::new(3, 0, 10), Err(Error::EmptyMantissa));
+    assert_eq!(FloatCodec::new(3, 5, 1), Err(Error::BaseTooSmall));
+
+    // largest formats that still fit into u128
+    assert_eq!(FloatCodec::new(0, 128, 10).unwrap().max_value(), u128::max_value());
+    assert_eq!(FloatCodec::new(7, 1, 2).unwrap().max_value_bits(), 128);
+    assert_eq!(FloatCodec::new(5, 11, 10).unwrap().max_value(), 2047 * 10u128.pow(31));
+
+    for &(exponent_bits, mantissa_bits, base) in [(0, 129, 2), (7, 2, 2), (8, 1, 2), (32, 1, 2), (64, 1, 2), (usize::max_value(), 1, 2), (5, 11, 20), (1, usize::max_value(), 2)].iter() {
+        assert_eq!(FloatCodec::new(exponent_bits, mantissa_bits, base), Err(Error::RangeTooLarge));
+    }
+
+    let codec = FloatCodec::new(5, 11, 10).unwrap();
+    assert_eq!(codec.encode(12345, Rounding::Exact), Err(Error::NotRepresentable));
+    assert_eq!(codec.decode(&[false; 15]), Err(Error::InvalidLength));
+    assert!(!codec.is_canonical(&[false; 17]));
+
+    // 1 * 10^1 instead of 10 * 10^0
+    let mut encoding = vec![false; 16];
+    encoding[0] = true;
+    encoding[5] = true;
+    assert_eq!(codec.decode(&encoding), Err(Error::NonCanonical));
+
+    // u128 values always fit into the fields in use
+    let codec = FloatCodec::new(0, 128, 2).unwrap();
+    let encoding = codec.encode(u128::max_value(), Rounding::Exact).unwrap();
+    let mut cs = ::circuit::test::TestConstraintSystem::<::bellman::pairing::bn256::Bn256>::new();
+    let bits: Vec<Boolean> = encoding.iter().enumerate().map(|(i, &b)| {
+        Boolean::from(AllocatedBit::alloc(cs.namespace(|| format!("bit {}", i)), Some(b)).unwrap())
+    }).collect();
+    let result = codec.decode_in_circuit(cs.namespace(|| "decode"), &bits).unwrap();
+    assert_eq!(result.get_value().unwrap().into_repr(), ::bellman::pairing::bn256::Fr::from_str(&u128::max_value().to_string()).unwrap().into_repr());
+    assert!(cs.is_satisfied());
+}
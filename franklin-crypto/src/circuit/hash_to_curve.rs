@@ -0,0 +1,410 @@
+use bellman::pairing::ff::{
+    Field,
+    PrimeField,
+    PrimeFieldRepr,
+    SqrtField,
+    LegendreSymbol
+};
+
+use bellman::{
+    SynthesisError,
+    ConstraintSystem
+};
+
+use super::Assignment;
+
+use super::num::{
+    AllocatedNum,
+    Num
+};
+
+use super::boolean::{
+    AllocatedBit,
+    Boolean
+};
+
+use super::ecc::{
+    MontgomeryPoint,
+    EdwardsPoint
+};
+
+use super::blake2s::blake2s;
+
+use ::jubjub::{
+    JubjubEngine,
+    JubjubParams,
+    TwistedEdwardsParams
+};
+
+use ::hash_to_curve::{
+    elligator_z,
+    BLOCKS_PER_FIELD_ELEMENT
+};
+
+/// Hashes the message into `count` field elements, matching
+/// `hash_to_curve::hash_to_field`. The message bits are in the
+/// order expected by `blake2s`, so its length must be a multiple of 8.
+pub fn hash_to_field<E, CS>(
+    mut cs: CS,
+    personalization: &[u8],
+    message: &[Boolean],
+    count: usize
+) -> Result<Vec<Num<E>>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>
+{
+    assert_eq!(message.len() % 8, 0);
+    assert!(count * BLOCKS_PER_FIELD_ELEMENT <= 256);
+
+    let mut res = vec![];
+    for i in 0..count {
+        let mut bits = vec![];
+        for j in 0..BLOCKS_PER_FIELD_ELEMENT {
+            let counter = (i * BLOCKS_PER_FIELD_ELEMENT + j) as u8;
+
+            let mut input = message.to_vec();
+            input.extend((0..8).map(|b| Boolean::constant((counter >> b) & 1 == 1)));
+
+            bits.extend(blake2s(
+                cs.namespace(|| format!("element {} block {}", i, j)),
+                &input,
+                personalization
+            )?);
+        }
+
+        // The reduction modulo the field characteristic is free
+        let mut num = Num::zero();
+        let mut coeff = E::Fr::one();
+        for bit in bits.iter() {
+            num = num.add_bool_with_coeff(CS::one(), bit, coeff);
+            coeff.double();
+        }
+
+        res.push(num);
+    }
+
+    Ok(res)
+}
+
+/// Maps a field element to a point of the Montgomery curve with
+/// Elligator 2, matching `hash_to_curve::map_to_montgomery`.
+/// The result is not in the prime order subgroup. The map is not
+/// defined in the circuit for `u = 0` if it hits the point of order 2.
+pub fn map_to_montgomery<E, CS>(
+    mut cs: CS,
+    u: &Num<E>,
+    params: &E::Params
+) -> Result<MontgomeryPoint<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>
+{
+    let z = elligator_z::<E>();
+    let a = *params.montgomery_a();
+    let one = CS::one();
+
+    // u2 = u^2
+    let u2 = AllocatedNum::alloc(cs.namespace(|| "u^2"), || {
+        let mut t = *u.get_value().get()?;
+        t.square();
+
+        Ok(t)
+    })?;
+
+    cs.enforce(
+        || "u^2 computation",
+        |lc| lc + &u.lc(E::Fr::one()),
+        |lc| lc + &u.lc(E::Fr::one()),
+        |lc| lc + u2.get_variable()
+    );
+
+    // x1 = -A / (1 + Z.u^2)
+    let x1 = AllocatedNum::alloc(cs.namespace(|| "x1"), || {
+        let mut den = *u2.get_value().get()?;
+        den.mul_assign(&z);
+        den.add_assign(&E::Fr::one());
+
+        let mut t = a;
+        t.negate();
+        t.mul_assign(den.inverse().get()?);
+
+        Ok(t)
+    })?;
+
+    let mut minus_a = a;
+    minus_a.negate();
+
+    cs.enforce(
+        || "x1 computation",
+        |lc| lc + one + (z, u2.get_variable()),
+        |lc| lc + x1.get_variable(),
+        |lc| lc + (minus_a, one)
+    );
+
+    // gx1 = x1.(x1^2 + A.x1 + 1)
+    let x1_squared = x1.square(cs.namespace(|| "x1^2"))?;
+
+    let gx1 = AllocatedNum::alloc(cs.namespace(|| "gx1"), || {
+        let x1 = *x1.get_value().get()?;
+
+        let mut t = x1;
+        t.add_assign(&a);
+        t.mul_assign(&x1);
+        t.add_assign(&E::Fr::one());
+        t.mul_assign(&x1);
+
+        Ok(t)
+    })?;
+
+    cs.enforce(
+        || "gx1 computation",
+        |lc| lc + x1.get_variable(),
+        |lc| lc + x1_squared.get_variable() + (a, x1.get_variable()) + one,
+        |lc| lc + gx1.get_variable()
+    );
+
+    // gx2 = Z.u^2.gx1 is the curve equation at x2 = -x1 - A
+    let gx2 = AllocatedNum::alloc(cs.namespace(|| "gx2"), || {
+        let mut t = *u2.get_value().get()?;
+        t.mul_assign(&z);
+        t.mul_assign(gx1.get_value().get()?);
+
+        Ok(t)
+    })?;
+
+    cs.enforce(
+        || "gx2 computation",
+        |lc| lc + (z, u2.get_variable()),
+        |lc| lc + gx1.get_variable(),
+        |lc| lc + gx2.get_variable()
+    );
+
+    // Exactly one of gx1 and gx2 is a square, so the prover can't
+    // choose the other candidate without failing the y^2 check
+    let is_square = AllocatedBit::alloc(
+        cs.namespace(|| "is square"),
+        gx1.get_value().map(|g| g.legendre() != LegendreSymbol::QuadraticNonResidue)
+    )?;
+
+    // x = x2 + is_square.(x1 - x2)
+    let x = AllocatedNum::alloc(cs.namespace(|| "x"), || {
+        if *is_square.get_value().get()? {
+            Ok(*x1.get_value().get()?)
+        } else {
+            let mut t = *x1.get_value().get()?;
+            t.add_assign(&a);
+            t.negate();
+
+            Ok(t)
+        }
+    })?;
+
+    let mut two = E::Fr::one();
+    two.double();
+
+    cs.enforce(
+        || "x selection",
+        |lc| lc + is_square.get_variable(),
+        |lc| lc + (two, x1.get_variable()) + (a, one),
+        |lc| lc + x.get_variable() + x1.get_variable() + (a, one)
+    );
+
+    // g = gx2 + is_square.(gx1 - gx2)
+    let g = AllocatedNum::alloc(cs.namespace(|| "g"), || {
+        if *is_square.get_value().get()? {
+            Ok(*gx1.get_value().get()?)
+        } else {
+            Ok(*gx2.get_value().get()?)
+        }
+    })?;
+
+    cs.enforce(
+        || "g selection",
+        |lc| lc + is_square.get_variable(),
+        |lc| lc + gx1.get_variable() - gx2.get_variable(),
+        |lc| lc + g.get_variable() - gx2.get_variable()
+    );
+
+    // y = sqrt(g), odd for x1 and even for x2
+    let y = AllocatedNum::alloc(cs.namespace(|| "y"), || {
+        let mut y = *g.get_value().get()?.sqrt().get()?;
+        if y.into_repr().is_odd() != *is_square.get_value().get()? {
+            y.negate();
+        }
+
+        Ok(y)
+    })?;
+
+    cs.enforce(
+        || "y^2 = g",
+        |lc| lc + y.get_variable(),
+        |lc| lc + y.get_variable(),
+        |lc| lc + g.get_variable()
+    );
+
+    let y_bits = y.into_bits_le_strict(cs.namespace(|| "y into bits"))?;
+
+    Boolean::enforce_equal(
+        cs.namespace(|| "y parity"),
+        &y_bits[0],
+        &Boolean::from(is_square)
+    )?;
+
+    Ok(MontgomeryPoint::interpret_unchecked(x.into(), y.into()))
+}
+
+fn clear_cofactor<E, CS>(
+    mut cs: CS,
+    p: &EdwardsPoint<E>,
+    params: &E::Params
+) -> Result<EdwardsPoint<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>
+{
+    let p = p.double(cs.namespace(|| "first doubling"), params)?;
+    let p = p.double(cs.namespace(|| "second doubling"), params)?;
+    let p = p.double(cs.namespace(|| "third doubling"), params)?;
+
+    Ok(p)
+}
+
+/// Maps a single hashed field element to the prime order subgroup,
+/// matching `hash_to_curve::encode_to_curve`.
+pub fn encode_to_curve<E, CS>(
+    mut cs: CS,
+    personalization: &[u8],
+    message: &[Boolean],
+    params: &E::Params
+) -> Result<EdwardsPoint<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>
+{
+    let u = hash_to_field(cs.namespace(|| "hash to field"), personalization, message, 1)?;
+
+    let p = map_to_montgomery(cs.namespace(|| "map"), &u[0], params)?
+        .into_edwards(cs.namespace(|| "into edwards"), params)?;
+
+    clear_cofactor(cs.namespace(|| "clear cofactor"), &p, params)
+}
+
+/// Hashes the message to the prime order subgroup,
+/// matching `hash_to_curve::hash_to_curve`.
+pub fn hash_to_curve<E, CS>(
+    mut cs: CS,
+    personalization: &[u8],
+    message: &[Boolean],
+    params: &E::Params
+) -> Result<EdwardsPoint<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>
+{
+    let u = hash_to_field(cs.namespace(|| "hash to field"), personalization, message, 2)?;
+
+    let p0 = map_to_montgomery(cs.namespace(|| "first map"), &u[0], params)?
+        .into_edwards(cs.namespace(|| "first into edwards"), params)?;
+
+    let p1 = map_to_montgomery(cs.namespace(|| "second map"), &u[1], params)?
+        .into_edwards(cs.namespace(|| "second into edwards"), params)?;
+
+    let p = p0.add(cs.namespace(|| "addition"), &p1, params)?;
+
+    clear_cofactor(cs.namespace(|| "clear cofactor"), &p, params)
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{SeedableRng, Rand, Rng, XorShiftRng};
+    use bellman::ConstraintSystem;
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use bellman::pairing::ff::{Field, PrimeField};
+    use ::circuit::test::*;
+    use ::circuit::num::{AllocatedNum, Num};
+    use ::circuit::boolean::{AllocatedBit, Boolean};
+    use ::alt_babyjubjub::AltJubjubBn256;
+    use ::jubjub::edwards;
+    use ::hash_to_curve;
+    use super::*;
+
+    fn alloc_message<CS: ConstraintSystem<Bn256>>(mut cs: CS, message: &[u8]) -> Vec<Boolean> {
+        message.iter().enumerate().flat_map(|(i, &byte)| {
+            (0..8).map(|b| {
+                AllocatedBit::alloc(cs.namespace(|| format!("message bit {}", i * 8 + b)), Some((byte >> b) & 1 == 1)).unwrap().into()
+            }).collect::<Vec<_>>()
+        }).collect()
+    }
+
+    #[test]
+    fn test_map_to_montgomery() {
+        let params = &AltJubjubBn256::new();
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for i in 0..20 {
+            let u_value = if i == 0 { Fr::one() } else { Fr::rand(rng) };
+
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+
+            let u = AllocatedNum::alloc(cs.namespace(|| "u"), || Ok(u_value)).unwrap();
+            let p = map_to_montgomery(cs.namespace(|| "map"), &Num::from(u), params).unwrap()
+                .into_edwards(cs.namespace(|| "into edwards"), params).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(cs.num_constraints(), 366);
+
+            let expected = edwards::Point::from_montgomery(&hash_to_curve::map_to_montgomery::<Bn256>(u_value, params), params);
+            let (x, y) = expected.into_xy();
+            assert_eq!(p.get_x().get_value().unwrap(), x);
+            assert_eq!(p.get_y().get_value().unwrap(), y);
+
+            // The other candidate x2 = -x1 - A is never on the curve: with
+            // a consistent selection its curve equation is not a square
+            let x1 = cs.get("map/x1/num");
+            let mut x2 = x1;
+            x2.add_assign(params.montgomery_a());
+            x2.negate();
+            let is_square = cs.get("map/is square/boolean") == Fr::one();
+            let (other_x, other_g, other_bit) = if is_square {
+                (x2, cs.get("map/gx2/num"), Fr::zero())
+            } else {
+                (x1, cs.get("map/gx1/num"), Fr::one())
+            };
+            assert_eq!(cs.get("map/x/num"), if is_square { x1 } else { x2 });
+            assert!(other_g.legendre() == LegendreSymbol::QuadraticNonResidue);
+
+            cs.set("map/is square/boolean", other_bit);
+            cs.set("map/x/num", other_x);
+            cs.set("map/g/num", other_g);
+            assert_eq!(cs.which_is_unsatisfied(), Some("map/y^2 = g"));
+        }
+    }
+
+    #[test]
+    fn test_hash_to_curve() {
+        let params = &AltJubjubBn256::new();
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for length in &[0, 7, 40] {
+            let message: Vec<u8> = (0..*length).map(|_| rng.gen()).collect();
+
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+
+            let bits = alloc_message(cs.namespace(|| "message"), &message);
+            let p = hash_to_curve(cs.namespace(|| "hash to curve"), b"Zcash_H_", &bits, params).unwrap();
+
+            assert!(cs.is_satisfied());
+
+            let (x, y) = hash_to_curve::hash_to_curve::<Bn256>(b"Zcash_H_", &message, params).into_xy();
+            assert_eq!(p.get_x().get_value().unwrap(), x);
+            assert_eq!(p.get_y().get_value().unwrap(), y);
+
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+
+            let bits = alloc_message(cs.namespace(|| "message"), &message);
+            let p = encode_to_curve(cs.namespace(|| "encode to curve"), b"Zcash_H_", &bits, params).unwrap();
+
+            assert!(cs.is_satisfied());
+
+            let (x, y) = hash_to_curve::encode_to_curve::<Bn256>(b"Zcash_H_", &message, params).into_xy();
+            assert_eq!(p.get_x().get_value().unwrap(), x);
+            assert_eq!(p.get_y().get_value().unwrap(), y);
+        }
+    }
+}
@@ -0,0 +1,573 @@
+//! Input commitment mode: every public input allocated by the wrapped
+//! synthesis is turned into an auxiliary variable, and only a single
+//! hash of all of them is exposed to the verifier. This keeps the cost
+//! of verification constant no matter how many values the circuit
+//! calls `inputize` on.
+
+use bellman::pairing::{
+    Engine,
+};
+
+use bellman::pairing::ff::{
+    PrimeField,
+    BitIterator
+};
+
+use bellman::{
+    SynthesisError,
+    ConstraintSystem,
+    Circuit,
+    LinearCombination,
+    Variable
+};
+
+use super::num::{
+    AllocatedNum
+};
+
+use super::public_inputs::{
+    PublicInputsLayout,
+    PackingMode
+};
+
+use super::pedersen_hash;
+
+use ::jubjub::{
+    JubjubEngine
+};
+
+/// Constraint system wrapper that collects the public inputs of the
+/// wrapped synthesis as auxiliary variables. The inner constraint system
+/// gets a single public input once the collected values are committed to
+/// with `commit_sha256` or `commit_pedersen`.
+pub struct InputCommitmentCS<E: Engine, CS: ConstraintSystem<E>> {
+    cs: CS,
+    inputs: Vec<AllocatedNum<E>>
+}
+
+impl<E: Engine, CS: ConstraintSystem<E>> InputCommitmentCS<E, CS> {
+    pub fn new(cs: CS) -> Self {
+        InputCommitmentCS {
+            cs: cs,
+            inputs: vec![]
+        }
+    }
+
+    /// Values that would have been public inputs, in the order of allocation
+    pub fn inputs(&self) -> &[AllocatedNum<E>] {
+        &self.inputs
+    }
+
+    /// Exposes the lowest `CAPACITY` bits of SHA-256 over the collected
+    /// inputs, each serialized as 256 big endian bits
+    pub fn commit_sha256(mut self) -> Result<AllocatedNum<E>, SynthesisError> {
+        assert!(self.inputs.len() > 0, "there must be at least one input to commit to");
+
+        let layout = sha256_layout(self.inputs.len());
+        let mut public_inputs = layout.circuit_inputs::<E>();
+        for (i, input) in self.inputs.iter().enumerate() {
+            public_inputs.element(&format!("input {}", i), input);
+        }
+
+        // The commitment is packed into a single input by the layout,
+        // but the caller may want to keep using it, so allocate it
+        // separately and tie both together.
+        let mut cs = self.cs.namespace(|| "input commitment");
+
+        let commitment = {
+            let native: Option<Vec<E::Fr>> = self.inputs.iter().map(|i| i.get_value()).collect();
+
+            AllocatedNum::alloc(
+                cs.namespace(|| "commitment"),
+                || {
+                    let native = native.ok_or(SynthesisError::AssignmentMissing)?;
+
+                    Ok(compute_sha256_input_commitment::<E>(&native))
+                }
+            )?
+        };
+
+        let mut cs = InputCommitmentRedirect {
+            cs: &mut cs,
+            commitment: commitment.clone(),
+            allocated: false
+        };
+        public_inputs.inputize(cs.namespace(|| "pack"))?;
+        assert!(cs.allocated, "layout must allocate exactly one input");
+
+        Ok(commitment)
+    }
+
+    /// Exposes the x coordinate of a chained Pedersen hash over the
+    /// collected inputs, see `compute_pedersen_input_commitment`
+    pub fn commit_pedersen(mut self, params: &E::Params) -> Result<AllocatedNum<E>, SynthesisError>
+        where E: JubjubEngine
+    {
+        assert!(self.inputs.len() > 0, "there must be at least one input to commit to");
+
+        let mut cs = self.cs.namespace(|| "input commitment");
+
+        let mut acc: Option<AllocatedNum<E>> = None;
+        for (i, input) in self.inputs.iter().enumerate() {
+            let mut cs = cs.namespace(|| format!("hash input {}", i));
+
+            let mut bits = vec![];
+            if let Some(ref acc) = acc {
+                bits.extend(acc.into_bits_le_strict(cs.namespace(|| "accumulator into bits"))?);
+            }
+            bits.extend(input.into_bits_le_strict(cs.namespace(|| "input into bits"))?);
+
+            let hash = pedersen_hash::pedersen_hash(
+                cs.namespace(|| "hash"),
+                pedersen_hash::Personalization::InputCommitment,
+                &bits,
+                params
+            )?;
+
+            acc = Some(hash.get_x().clone());
+        }
+
+        let commitment = acc.expect("there is at least one input");
+        commitment.inputize(cs.namespace(|| "commitment"))?;
+
+        Ok(commitment)
+    }
+}
+
+fn sha256_layout(num_inputs: usize) -> PublicInputsLayout {
+    (0..num_inputs).fold(
+        PublicInputsLayout::new(PackingMode::Sha256),
+        |layout, i| layout.element(&format!("input {}", i))
+    )
+}
+
+/// Computes the single public input exposed by `commit_sha256`
+/// from the public inputs of the wrapped circuit (without ONE)
+pub fn compute_sha256_input_commitment<E: Engine>(inputs: &[E::Fr]) -> E::Fr {
+    assert!(inputs.len() > 0, "there must be at least one input to commit to");
+
+    let layout = sha256_layout(inputs.len());
+    let mut native = layout.native_inputs::<E>();
+    for (i, input) in inputs.iter().enumerate() {
+        native.element(&format!("input {}", i), *input);
+    }
+
+    let result = native.to_inputs();
+    assert_eq!(result.len(), 1);
+
+    result[0]
+}
+
+/// Computes the single public input exposed by `commit_pedersen`
+/// from the public inputs of the wrapped circuit (without ONE).
+///
+/// The commitment is `acc_n`, where `acc_1 = H(input_1)` and
+/// `acc_i = H(acc_{i-1} || input_i)`, every element is taken as
+/// `NUM_BITS` little endian bits and `H` is the x coordinate of the
+/// Pedersen hash with the `InputCommitment` personalization.
+pub fn compute_pedersen_input_commitment<E: JubjubEngine>(
+    inputs: &[E::Fr],
+    params: &E::Params
+) -> E::Fr
+{
+    assert!(inputs.len() > 0, "there must be at least one input to commit to");
+
+    fn element_into_bits_le<E: JubjubEngine>(element: &E::Fr) -> Vec<bool> {
+        let mut bits: Vec<bool> = BitIterator::new(element.into_repr()).collect();
+        bits.reverse();
+        bits.truncate(E::Fr::NUM_BITS as usize);
+
+        bits
+    }
+
+    let mut acc: Option<E::Fr> = None;
+    for input in inputs.iter() {
+        let mut bits = vec![];
+        if let Some(ref acc) = acc {
+            bits.extend(element_into_bits_le::<E>(acc));
+        }
+        bits.extend(element_into_bits_le::<E>(input));
+
+        let hash = ::pedersen_hash::pedersen_hash::<E, _>(
+            ::pedersen_hash::Personalization::InputCommitment,
+            bits,
+            params
+        );
+
+        acc = Some(hash.into_xy().0);
+    }
+
+    acc.expect("there is at least one input")
+}
+
+/// Synthesizes the wrapped circuit with its public inputs
+/// replaced by a single SHA-256 commitment
+pub struct Sha256InputCommitment<C> {
+    pub circuit: C
+}
+
+impl<E: Engine, C: Circuit<E>> Circuit<E> for Sha256InputCommitment<C> {
+    fn synthesize<CS: ConstraintSystem<E>>(
+        self,
+        cs: &mut CS
+    ) -> Result<(), SynthesisError>
+    {
+        let mut cs = InputCommitmentCS::new(cs.namespace(|| "committed circuit"));
+        self.circuit.synthesize(&mut cs)?;
+        cs.commit_sha256()?;
+
+        Ok(())
+    }
+}
+
+/// Synthesizes the wrapped circuit with its public inputs
+/// replaced by a single Pedersen commitment
+pub struct PedersenInputCommitment<'a, E: JubjubEngine + 'a, C> {
+    pub params: &'a E::Params,
+    pub circuit: C
+}
+
+impl<'a, E: JubjubEngine, C: Circuit<E>> Circuit<E> for PedersenInputCommitment<'a, E, C> {
+    fn synthesize<CS: ConstraintSystem<E>>(
+        self,
+        cs: &mut CS
+    ) -> Result<(), SynthesisError>
+    {
+        let mut cs = InputCommitmentCS::new(cs.namespace(|| "committed circuit"));
+        self.circuit.synthesize(&mut cs)?;
+        cs.commit_pedersen(self.params)?;
+
+        Ok(())
+    }
+}
+
+impl<E: Engine, CS: ConstraintSystem<E>> ConstraintSystem<E> for InputCommitmentCS<E, CS>
+{
+    type Root = Self;
+
+    fn one() -> Variable {
+        CS::one()
+    }
+
+    fn alloc<F, A, AR>(
+        &mut self,
+        annotation: A,
+        f: F
+    ) -> Result<Variable, SynthesisError>
+        where F: FnOnce() -> Result<E::Fr, SynthesisError>, A: FnOnce() -> AR, AR: Into<String>
+    {
+        self.cs.alloc(annotation, f)
+    }
+
+    fn alloc_input<F, A, AR>(
+        &mut self,
+        annotation: A,
+        f: F
+    ) -> Result<Variable, SynthesisError>
+        where F: FnOnce() -> Result<E::Fr, SynthesisError>, A: FnOnce() -> AR, AR: Into<String>
+    {
+        let input = AllocatedNum::alloc(self.cs.namespace(annotation), f)?;
+        let variable = input.get_variable();
+        self.inputs.push(input);
+
+        Ok(variable)
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(
+        &mut self,
+        annotation: A,
+        a: LA,
+        b: LB,
+        c: LC
+    )
+        where A: FnOnce() -> AR, AR: Into<String>,
+              LA: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+              LB: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+              LC: FnOnce(LinearCombination<E>) -> LinearCombination<E>
+    {
+        self.cs.enforce(annotation, a, b, c)
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+        where NR: Into<String>, N: FnOnce() -> NR
+    {
+        self.cs.get_root().push_namespace(name_fn)
+    }
+
+    fn pop_namespace(&mut self)
+    {
+        self.cs.get_root().pop_namespace()
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root
+    {
+        self
+    }
+}
+
+/// Lets the public inputs layout pack the SHA-256 commitment as usual,
+/// but enforces the packed input to be equal to an already allocated
+/// commitment, so the caller gets an `AllocatedNum` for it.
+struct InputCommitmentRedirect<'a, E: Engine, CS: ConstraintSystem<E> + 'a> {
+    cs: &'a mut CS,
+    commitment: AllocatedNum<E>,
+    allocated: bool
+}
+
+impl<'a, E: Engine, CS: ConstraintSystem<E>> ConstraintSystem<E> for InputCommitmentRedirect<'a, E, CS>
+{
+    type Root = Self;
+
+    fn one() -> Variable {
+        CS::one()
+    }
+
+    fn alloc<F, A, AR>(
+        &mut self,
+        annotation: A,
+        f: F
+    ) -> Result<Variable, SynthesisError>
+        where F: FnOnce() -> Result<E::Fr, SynthesisError>, A: FnOnce() -> AR, AR: Into<String>
+    {
+        self.cs.alloc(annotation, f)
+    }
+
+    fn alloc_input<F, A, AR>(
+        &mut self,
+        annotation: A,
+        f: F
+    ) -> Result<Variable, SynthesisError>
+        where F: FnOnce() -> Result<E::Fr, SynthesisError>, A: FnOnce() -> AR, AR: Into<String>
+    {
+        assert!(!self.allocated, "layout must allocate exactly one input");
+        self.allocated = true;
+
+        let input = self.cs.alloc_input(annotation, f)?;
+        let commitment = self.commitment.get_variable();
+
+        self.cs.enforce(
+            || "commitment is the packed hash",
+            |lc| lc + input,
+            |lc| lc + CS::one(),
+            |lc| lc + commitment
+        );
+
+        Ok(input)
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(
+        &mut self,
+        annotation: A,
+        a: LA,
+        b: LB,
+        c: LC
+    )
+        where A: FnOnce() -> AR, AR: Into<String>,
+              LA: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+              LB: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+              LC: FnOnce(LinearCombination<E>) -> LinearCombination<E>
+    {
+        self.cs.enforce(annotation, a, b, c)
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+        where NR: Into<String>, N: FnOnce() -> NR
+    {
+        self.cs.get_root().push_namespace(name_fn)
+    }
+
+    fn pop_namespace(&mut self)
+    {
+        self.cs.get_root().pop_namespace()
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root
+    {
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bellman::pairing::bls12_381::{Bls12, Fr};
+    use bellman::pairing::ff::{Field};
+    use bellman::{Circuit, ConstraintSystem, SynthesisError};
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use ::circuit::test::*;
+    use ::circuit::num::AllocatedNum;
+    use ::jubjub::JubjubBls12;
+    use super::*;
+
+    /// Exposes `a`, `b` and `a * b`
+    struct Product {
+        a: Option<Fr>,
+        b: Option<Fr>
+    }
+
+    impl Circuit<Bls12> for Product {
+        fn synthesize<CS: ConstraintSystem<Bls12>>(
+            self,
+            cs: &mut CS
+        ) -> Result<(), SynthesisError>
+        {
+            let a = AllocatedNum::alloc(cs.namespace(|| "a"), || self.a.ok_or(SynthesisError::AssignmentMissing))?;
+            let b = AllocatedNum::alloc(cs.namespace(|| "b"), || self.b.ok_or(SynthesisError::AssignmentMissing))?;
+            let c = a.mul(cs.namespace(|| "c"), &b)?;
+
+            a.inputize(cs.namespace(|| "a input"))?;
+            b.inputize(cs.namespace(|| "b input"))?;
+            c.inputize(cs.namespace(|| "c input"))?;
+
+            Ok(())
+        }
+    }
+
+    fn product_inputs(a: Fr, b: Fr) -> Vec<Fr> {
+        let mut c = a;
+        c.mul_assign(&b);
+
+        vec![a, b, c]
+    }
+
+    #[test]
+    fn test_sha256_input_commitment() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for _ in 0..5 {
+            let a: Fr = rng.gen();
+            let b: Fr = rng.gen();
+
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+            Sha256InputCommitment { circuit: Product { a: Some(a), b: Some(b) } }.synthesize(&mut cs).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(cs.num_inputs(), 2);
+
+            let expected = compute_sha256_input_commitment::<Bls12>(&product_inputs(a, b));
+            assert!(cs.verify(&[expected]));
+
+            let mut wrong = product_inputs(a, b);
+            wrong[2].add_assign(&Fr::one());
+            assert!(!cs.verify(&[compute_sha256_input_commitment::<Bls12>(&wrong)]));
+        }
+    }
+
+    #[test]
+    fn test_pedersen_input_commitment() {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for _ in 0..5 {
+            let a: Fr = rng.gen();
+            let b: Fr = rng.gen();
+
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+            PedersenInputCommitment { params: params, circuit: Product { a: Some(a), b: Some(b) } }.synthesize(&mut cs).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(cs.num_inputs(), 2);
+
+            let expected = compute_pedersen_input_commitment::<Bls12>(&product_inputs(a, b), params);
+            assert!(cs.verify(&[expected]));
+
+            let mut wrong = product_inputs(a, b);
+            wrong[0].add_assign(&Fr::one());
+            assert!(!cs.verify(&[compute_pedersen_input_commitment::<Bls12>(&wrong, params)]));
+        }
+    }
+
+    #[test]
+    fn test_commitment_is_usable_in_circuit() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let a: Fr = rng.gen();
+        let b: Fr = rng.gen();
+        let expected = compute_sha256_input_commitment::<Bls12>(&product_inputs(a, b));
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        let commitment = {
+            let mut committed = InputCommitmentCS::new(cs.namespace(|| "committed"));
+            Product { a: Some(a), b: Some(b) }.synthesize(&mut committed).unwrap();
+            assert_eq!(committed.inputs().len(), 3);
+            committed.commit_sha256().unwrap()
+        };
+
+        assert_eq!(commitment.get_value(), Some(expected));
+        assert!(cs.is_satisfied());
+
+        // The returned value is bound to the public input
+        cs.set("committed/input commitment/commitment/num", Fr::one());
+        assert!(!cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_sapling_output_input_commitment() {
+        use ::circuit::sapling::Output;
+        use ::jubjub::{fs, edwards};
+        use ::primitives::{Diversifier, ProofGenerationKey, ValueCommitment};
+
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6258, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let value_commitment = ValueCommitment {
+            value: rng.gen(),
+            randomness: rng.gen()
+        };
+
+        let proof_generation_key = ProofGenerationKey {
+            ak: edwards::Point::rand(rng, params).mul_by_cofactor(params),
+            nsk: rng.gen()
+        };
+        let viewing_key = proof_generation_key.into_viewing_key(params);
+
+        let payment_address = loop {
+            if let Some(p) = viewing_key.into_payment_address(Diversifier(rng.gen()), params) {
+                break p;
+            }
+        };
+
+        let commitment_randomness: fs::Fs = rng.gen();
+        let esk: fs::Fs = rng.gen();
+
+        let expected_cm = payment_address.create_note(
+            value_commitment.value,
+            commitment_randomness,
+            params
+        ).expect("should be valid").cm(params);
+        let expected_value_cm = value_commitment.cm(params).into_xy();
+        let expected_epk = payment_address.g_d(params).expect("should be valid").mul(esk, params).into_xy();
+
+        let inputs = vec![
+            expected_value_cm.0,
+            expected_value_cm.1,
+            expected_epk.0,
+            expected_epk.1,
+            expected_cm
+        ];
+
+        let output = || Output {
+            params: params,
+            value_commitment: Some(value_commitment.clone()),
+            payment_address: Some(payment_address.clone()),
+            commitment_randomness: Some(commitment_randomness),
+            esk: Some(esk.clone())
+        };
+
+        {
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+            Sha256InputCommitment { circuit: output() }.synthesize(&mut cs).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(cs.num_inputs(), 2);
+            assert!(cs.verify(&[compute_sha256_input_commitment::<Bls12>(&inputs)]));
+        }
+
+        {
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+            PedersenInputCommitment { params: params, circuit: output() }.synthesize(&mut cs).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(cs.num_inputs(), 2);
+            assert!(cs.verify(&[compute_pedersen_input_commitment::<Bls12>(&inputs, params)]));
+        }
+    }
+}
@@ -0,0 +1,529 @@
+use bellman::pairing::{
+    Engine,
+};
+
+use bellman::pairing::ff::{
+    Field,
+    PrimeField,
+    BitIterator
+};
+
+use bellman::{
+    SynthesisError,
+    ConstraintSystem
+};
+
+use super::{
+    Assignment
+};
+
+use super::num::{
+    AllocatedNum,
+    Num
+};
+
+use super::boolean::{
+    AllocatedBit,
+    Boolean
+};
+
+use super::polynomial_lookup::{
+    generate_powers,
+    do_the_lookup
+};
+
+use ::polynomial::SubproductTree;
+
+/// The way the table is turned into constraints
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LookupStrategy {
+    /// Index is decomposed into bits that select the entry through a tree
+    /// of multiplexers. Only possible for keys 0, 1, ..., n - 1
+    Multiplexer,
+    /// Every column is interpolated as a polynomial of the key, and the index
+    /// is enforced to be a root of the polynomial that vanishes on all the keys
+    Polynomial
+}
+
+/// Lookup table of constants that maps a key to one or more values
+pub struct LookupTable<E: Engine> {
+    keys: Vec<E::Fr>,
+    values: Vec<Vec<E::Fr>>,
+    width: usize,
+    strategy: LookupStrategy,
+    // coefficients of the polynomial for every column
+    column_polynomials: Vec<Vec<E::Fr>>,
+    // coefficients of the polynomial that vanishes on all the keys
+    vanishing_polynomial: Vec<E::Fr>
+}
+
+impl<E: Engine> LookupTable<E> {
+    /// Makes a table with keys 0, 1, ..., n - 1
+    pub fn from_values(values: Vec<Vec<E::Fr>>) -> Self {
+        let entries = values.into_iter().enumerate().map(|(i, v)| {
+            (E::Fr::from_str(&i.to_string()).expect("must fit into the field"), v)
+        }).collect();
+
+        Self::new(entries)
+    }
+
+    /// Makes a table from (key, values) entries. Keys must be distinct
+    /// and all the entries must have the same number of values.
+    /// The cheapest available strategy is picked
+    pub fn new(mut entries: Vec<(E::Fr, Vec<E::Fr>)>) -> Self {
+        assert!(entries.len() > 0, "table can not be empty");
+        let width = entries[0].1.len();
+        assert!(width > 0, "entries must have values");
+        assert!(entries.iter().all(|e| e.1.len() == width), "entries must have the same number of values");
+
+        entries.sort_by(|a, b| a.0.into_repr().cmp(&b.0.into_repr()));
+        for pair in entries.windows(2) {
+            assert!(pair[0].0 != pair[1].0, "keys must be distinct");
+        }
+
+        let (keys, values): (Vec<E::Fr>, Vec<Vec<E::Fr>>) = entries.into_iter().unzip();
+
+        // interpolation and the vanishing polynomial share the tree of the keys
+        let tree = SubproductTree::<E>::new(&keys).expect("keys are distinct");
+
+        let column_polynomials = (0..width).map(|column| {
+            let column: Vec<E::Fr> = values.iter().map(|v| v[column]).collect();
+
+            tree.interpolate(&column)
+        }).collect();
+
+        let vanishing_polynomial = tree.vanishing_polynomial().to_vec();
+
+        let mut table = LookupTable {
+            keys,
+            values,
+            width,
+            strategy: LookupStrategy::Polynomial,
+            column_polynomials,
+            vanishing_polynomial
+        };
+
+        if let Some(cost) = table.cost(LookupStrategy::Multiplexer) {
+            if cost < table.cost(LookupStrategy::Polynomial).expect("always available") {
+                table.strategy = LookupStrategy::Multiplexer;
+            }
+        }
+
+        table
+    }
+
+    /// Forces the strategy, returns `None` if it's not available for this table
+    pub fn with_strategy(mut self, strategy: LookupStrategy) -> Option<Self> {
+        self.cost(strategy)?;
+        self.strategy = strategy;
+
+        Some(self)
+    }
+
+    pub fn strategy(&self) -> LookupStrategy {
+        self.strategy
+    }
+
+    pub fn size(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Native lookup
+    pub fn get(&self, key: &E::Fr) -> Option<&[E::Fr]> {
+        self.keys.iter().position(|k| k == key).map(|i| &self.values[i][..])
+    }
+
+    /// Number of constraints that `lookup` takes with the given strategy,
+    /// or `None` if the strategy is not available
+    pub fn cost(&self, strategy: LookupStrategy) -> Option<usize> {
+        let n = self.size();
+        match strategy {
+            LookupStrategy::Polynomial => {
+                // allocated one, powers from x^2 to x^n, membership and a constraint per column
+                Some(1 + (n - 1) + 1 + self.width)
+            },
+            LookupStrategy::Multiplexer => {
+                if !self.has_dense_keys() {
+                    return None;
+                }
+
+                let num_bits = Self::num_bits(n);
+                let range_check = if n.is_power_of_two() {
+                    0
+                } else {
+                    Self::range_check_cost(n as u64 - 1, num_bits)
+                };
+                let per_column = if num_bits <= 1 {
+                    1
+                } else {
+                    (1 << (num_bits - 1)) - 1
+                };
+
+                // booleanity of the bits and the packing
+                Some(num_bits + 1 + range_check + per_column * self.width)
+            }
+        }
+    }
+
+    /// Looks up the values for the key. The constraint system is unsatisfied
+    /// if there is no such key in the table
+    pub fn lookup<CS>(
+        &self,
+        mut cs: CS,
+        index: &AllocatedNum<E>
+    ) -> Result<Vec<AllocatedNum<E>>, SynthesisError>
+        where CS: ConstraintSystem<E>
+    {
+        match self.strategy {
+            LookupStrategy::Polynomial => self.polynomial_lookup(cs.namespace(|| "polynomial lookup"), index),
+            LookupStrategy::Multiplexer => self.multiplexer_lookup(cs.namespace(|| "multiplexer lookup"), index)
+        }
+    }
+
+    fn has_dense_keys(&self) -> bool {
+        self.keys.iter().enumerate().all(|(i, k)| {
+            *k == E::Fr::from_str(&i.to_string()).expect("must fit into the field")
+        })
+    }
+
+    fn num_bits(size: usize) -> usize {
+        let mut num_bits = 0;
+        while (1 << num_bits) < size {
+            num_bits += 1;
+        }
+
+        num_bits
+    }
+
+    fn range_check_cost(max: u64, num_bits: usize) -> usize {
+        // mirrors `enforce_less_or_equal`
+        let mut cost = 0;
+        let mut is_constant = true;
+        for i in (0..num_bits).rev() {
+            // AND with the constant flag is free
+            if (max >> i) & 1 == 0 || !is_constant {
+                cost += 1;
+            }
+            is_constant = false;
+        }
+
+        cost
+    }
+
+    fn polynomial_lookup<CS>(
+        &self,
+        mut cs: CS,
+        index: &AllocatedNum<E>
+    ) -> Result<Vec<AllocatedNum<E>>, SynthesisError>
+        where CS: ConstraintSystem<E>
+    {
+        let n = self.size();
+
+        let powers = generate_powers(
+            cs.namespace(|| "powers"),
+            index,
+            n + 1
+        )?;
+
+        // membership
+        let mut vanishing = Num::<E>::zero();
+        for (c, p) in self.vanishing_polynomial.iter().zip(powers.iter()) {
+            vanishing = vanishing.add_number_with_coeff(p, *c);
+        }
+        cs.enforce(
+            || "enforce the key is in the table",
+            |lc| lc + &vanishing.lc(E::Fr::one()),
+            |lc| lc + CS::one(),
+            |lc| lc
+        );
+
+        self.column_polynomials.iter().enumerate().map(|(column, coeffs)| {
+            do_the_lookup(
+                cs.namespace(|| format!("evaluate column {}", column)),
+                coeffs,
+                &powers[..coeffs.len()]
+            )
+        }).collect()
+    }
+
+    fn multiplexer_lookup<CS>(
+        &self,
+        mut cs: CS,
+        index: &AllocatedNum<E>
+    ) -> Result<Vec<AllocatedNum<E>>, SynthesisError>
+        where CS: ConstraintSystem<E>
+    {
+        let n = self.size();
+        let num_bits = Self::num_bits(n);
+
+        // decompose the index into exactly `num_bits` bits
+        let bit_values: Vec<Option<bool>> = match index.get_value() {
+            Some(value) => {
+                let mut bits: Vec<bool> = BitIterator::new(value.into_repr()).collect();
+                bits.reverse();
+                bits.truncate(num_bits);
+
+                bits.into_iter().map(|b| Some(b)).collect()
+            },
+            None => vec![None; num_bits]
+        };
+
+        let mut bits = vec![];
+        let mut packed = Num::<E>::zero();
+        let mut coeff = E::Fr::one();
+        for (i, value) in bit_values.into_iter().enumerate() {
+            let bit = Boolean::from(AllocatedBit::alloc(
+                cs.namespace(|| format!("index bit {}", i)),
+                value
+            )?);
+            packed = packed.add_bool_with_coeff(CS::one(), &bit, coeff);
+            coeff.double();
+            bits.push(bit);
+        }
+
+        cs.enforce(
+            || "pack index bits",
+            |lc| lc + &packed.lc(E::Fr::one()),
+            |lc| lc + CS::one(),
+            |lc| lc + index.get_variable()
+        );
+
+        if !n.is_power_of_two() {
+            enforce_less_or_equal(
+                cs.namespace(|| "enforce index is in range"),
+                &bits,
+                n as u64 - 1
+            )?;
+        }
+
+        (0..self.width).map(|column| {
+            let mut cs = cs.namespace(|| format!("select column {}", column));
+
+            // pad the table to the power of two with the last entry that is never selected
+            let mut column_values: Vec<E::Fr> = self.values.iter().map(|v| v[column]).collect();
+            column_values.resize(1 << num_bits, self.values[n - 1][column]);
+
+            // the first level of the tree selects between constants, so it's linear
+            let mut level: Vec<Num<E>> = if num_bits == 0 {
+                vec![
+                    Num::zero().add_bool_with_coeff(CS::one(), &Boolean::constant(true), column_values[0])
+                ]
+            } else {
+                column_values.chunks(2).map(|pair| {
+                    let mut difference = pair[1];
+                    difference.sub_assign(&pair[0]);
+
+                    Num::zero()
+                        .add_bool_with_coeff(CS::one(), &Boolean::constant(true), pair[0])
+                        .add_bool_with_coeff(CS::one(), &bits[0], difference)
+                }).collect()
+            };
+
+            let mut selected = None;
+            for (depth, bit) in bits.iter().enumerate().skip(1) {
+                let mut next_level = vec![];
+                for (i, pair) in level.chunks(2).enumerate() {
+                    let result = select(
+                        cs.namespace(|| format!("select {} at depth {}", i, depth)),
+                        bit,
+                        &pair[0],
+                        &pair[1]
+                    )?;
+                    selected = Some(result.clone());
+                    next_level.push(Num::from(result));
+                }
+                level = next_level;
+            }
+
+            if let Some(selected) = selected {
+                return Ok(selected);
+            }
+
+            // for one or two entries there is no allocated select
+            let result = level.pop().expect("is not empty");
+            let allocated = AllocatedNum::alloc(
+                cs.namespace(|| "result"),
+                || Ok(*result.get_value().get()?)
+            )?;
+
+            cs.enforce(
+                || "enforce result",
+                |lc| lc + allocated.get_variable(),
+                |lc| lc + CS::one(),
+                |lc| lc + &result.lc(E::Fr::one())
+            );
+
+            Ok(allocated)
+        }).collect()
+    }
+}
+
+/// Selects `a` if the bit is false and `b` otherwise
+fn select<E, CS>(
+    mut cs: CS,
+    bit: &Boolean,
+    a: &Num<E>,
+    b: &Num<E>
+) -> Result<AllocatedNum<E>, SynthesisError>
+    where CS: ConstraintSystem<E>,
+          E: Engine
+{
+    let result = AllocatedNum::alloc(
+        cs.namespace(|| "selected"),
+        || {
+            if *bit.get_value().get()? {
+                Ok(*b.get_value().get()?)
+            } else {
+                Ok(*a.get_value().get()?)
+            }
+        }
+    )?;
+
+    // (b - a) * bit = result - a
+    cs.enforce(
+        || "select",
+        |lc| lc + &b.lc(E::Fr::one()) - &a.lc(E::Fr::one()),
+        |lc| lc + &bit.lc(CS::one(), E::Fr::one()),
+        |lc| lc + result.get_variable() - &a.lc(E::Fr::one())
+    );
+
+    Ok(result)
+}
+
+/// Enforces that the number given by little endian bits is at most `max`.
+/// Going from the top bit, keeps a flag that all the higher bits are equal
+/// to the ones of `max`, and then a bit can't be set where `max` has zero
+fn enforce_less_or_equal<E, CS>(
+    mut cs: CS,
+    bits: &[Boolean],
+    max: u64
+) -> Result<(), SynthesisError>
+    where CS: ConstraintSystem<E>,
+          E: Engine
+{
+    let mut is_equal = Boolean::constant(true);
+    for (i, bit) in bits.iter().enumerate().rev() {
+        if (max >> i) & 1 == 1 {
+            is_equal = Boolean::and(
+                cs.namespace(|| format!("equal up to bit {}", i)),
+                &is_equal,
+                bit
+            )?;
+        } else {
+            // if it's set then the number is larger.
+            // otherwise the flag doesn't change
+            cs.enforce(
+                || format!("bit {} is not larger", i),
+                |lc| lc + &is_equal.lc(CS::one(), E::Fr::one()),
+                |lc| lc + &bit.lc(CS::one(), E::Fr::one()),
+                |lc| lc
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use bellman::{ConstraintSystem};
+    use rand::{XorShiftRng, SeedableRng, Rand, Rng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use bellman::pairing::ff::{Field, PrimeField};
+    use ::circuit::test::*;
+    use super::super::num::AllocatedNum;
+    use super::*;
+
+    fn check_lookups(table: &LookupTable<Bn256>, keys: &[Fr], rng: &mut XorShiftRng) {
+        for key in keys.iter() {
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+            let index = AllocatedNum::alloc(cs.namespace(|| "index"), || Ok(*key)).unwrap();
+
+            let result = table.lookup(cs.namespace(|| "lookup"), &index).unwrap();
+            assert!(cs.is_satisfied());
+            assert_eq!(cs.num_constraints(), table.cost(table.strategy()).unwrap());
+
+            let values: Vec<Fr> = result.iter().map(|r| r.get_value().unwrap()).collect();
+            assert_eq!(&values[..], table.get(key).unwrap());
+
+            // tamper with the result
+            let column = rng.gen_range(0, table.width());
+            let path = match table.strategy() {
+                LookupStrategy::Polynomial => format!("lookup/polynomial lookup/evaluate column {}/do the lookup/num", column),
+                LookupStrategy::Multiplexer if table.size() <= 2 => format!("lookup/multiplexer lookup/select column {}/result/num", column),
+                LookupStrategy::Multiplexer => format!("lookup/multiplexer lookup/select column {}/select 0 at depth {}/selected/num",
+                    column, LookupTable::<Bn256>::num_bits(table.size()) - 1)
+            };
+            cs.set(&path, Fr::rand(rng));
+            assert!(!cs.is_satisfied());
+        }
+
+        // keys that are not in the table
+        for _ in 0..5 {
+            let key = match rng.gen::<bool>() {
+                true => Fr::rand(rng),
+                false => Fr::from_str(&(table.size() + rng.gen_range(0, 8)).to_string()).unwrap()
+            };
+            if table.get(&key).is_some() {
+                continue;
+            }
+
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+            let index = AllocatedNum::alloc(cs.namespace(|| "index"), || Ok(key)).unwrap();
+            table.lookup(cs.namespace(|| "lookup"), &index).unwrap();
+            assert!(!cs.is_satisfied());
+        }
+    }
+
+    #[test]
+    fn test_lookup_dense_keys() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        for size in 1..20 {
+            for width in 1..4 {
+                let values: Vec<Vec<Fr>> = (0..size).map(|_| (0..width).map(|_| Fr::rand(rng)).collect()).collect();
+                let keys: Vec<Fr> = (0..size).map(|i| Fr::from_str(&i.to_string()).unwrap()).collect();
+
+                let table = LookupTable::<Bn256>::from_values(values.clone());
+                let multiplexer_cost = table.cost(LookupStrategy::Multiplexer).unwrap();
+                let polynomial_cost = table.cost(LookupStrategy::Polynomial).unwrap();
+                assert_eq!(table.strategy() == LookupStrategy::Multiplexer, multiplexer_cost < polynomial_cost);
+
+                for &strategy in [LookupStrategy::Multiplexer, LookupStrategy::Polynomial].iter() {
+                    let table = LookupTable::<Bn256>::from_values(values.clone()).with_strategy(strategy).unwrap();
+                    check_lookups(&table, &keys, rng);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_lookup_sparse_keys() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        for size in 1..20 {
+            let entries: Vec<(Fr, Vec<Fr>)> = (0..size).map(|_| (Fr::rand(rng), vec![Fr::rand(rng), Fr::rand(rng)])).collect();
+            let keys: Vec<Fr> = entries.iter().map(|e| e.0).collect();
+
+            let table = LookupTable::<Bn256>::new(entries);
+            assert_eq!(table.strategy(), LookupStrategy::Polynomial);
+            assert!(table.cost(LookupStrategy::Multiplexer).is_none());
+
+            check_lookups(&table, &keys, rng);
+        }
+    }
+
+    #[test]
+    fn test_lookup_strategy_choice() {
+        // a single wide column favors the multiplexer, many columns favor the polynomial
+        let narrow = LookupTable::<Bn256>::from_values((0..256).map(|i| vec![Fr::from_str(&i.to_string()).unwrap()]).collect());
+        assert_eq!(narrow.strategy(), LookupStrategy::Multiplexer);
+        assert_eq!(narrow.cost(LookupStrategy::Multiplexer), Some(8 + 1 + 127));
+        assert_eq!(narrow.cost(LookupStrategy::Polynomial), Some(256 + 2));
+
+        let wide = LookupTable::<Bn256>::from_values((0..256).map(|i| vec![Fr::from_str(&i.to_string()).unwrap(); 4]).collect());
+        assert_eq!(wide.strategy(), LookupStrategy::Polynomial);
+    }
+}
@@ -12,11 +12,17 @@ pub mod ecc;
 pub mod pedersen_hash;
 pub mod baby_pedersen_hash;
 pub mod multipack;
+pub mod public_inputs;
+pub mod input_commitment;
+pub mod hash_to_curve;
+pub mod vrf;
 pub mod sha256;
 pub mod baby_eddsa;
 pub mod float_point;
 pub mod polynomial_lookup;
+pub mod lookup_table;
 pub mod as_waksman;
+pub mod permutation;
 // pub mod shark_mimc;
 
 pub mod sapling;
@@ -50,6 +50,14 @@ impl<E: Engine, CS: ConstraintSystem<E>> MultiEq<E, CS> {
         self.ops += 1;
     }
 
+    /// Enforces the pending equalities now rather than when dropped
+    pub fn flush(&mut self)
+    {
+        if self.bits_used > 0 {
+           self.accumulate();
+        }
+    }
+
     pub fn enforce_equal(
         &mut self,
         num_bits: usize,
@@ -73,9 +81,7 @@ impl<E: Engine, CS: ConstraintSystem<E>> MultiEq<E, CS> {
 
 impl<E: Engine, CS: ConstraintSystem<E>> Drop for MultiEq<E, CS> {
     fn drop(&mut self) {
-        if self.bits_used > 0 {
-           self.accumulate();
-        }
+        self.flush();
     }
 }
 
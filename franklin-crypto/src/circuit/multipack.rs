@@ -1,7 +1,7 @@
 use bellman::pairing::{Engine,};
 use bellman::pairing::ff::{Field, PrimeField};
 use bellman::{ConstraintSystem, SynthesisError};
-use super::boolean::{Boolean};
+use super::boolean::{AllocatedBit, Boolean};
 use super::num::Num;
 use super::Assignment;
 
@@ -39,6 +39,41 @@ pub fn pack_into_inputs<E, CS>(
     Ok(())
 }
 
+/// Allocates `length` bits that are exposed as the compact public inputs
+/// of `pack_into_inputs`, with the witness taken from the values of these
+/// inputs, so the circuit gets back the bits the verifier has packed
+pub fn unpack_inputs<E, CS>(
+    mut cs: CS,
+    length: usize,
+    inputs: Option<&[E::Fr]>
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: Engine, CS: ConstraintSystem<E>
+{
+    let capacity = E::Fr::CAPACITY as usize;
+    if let Some(inputs) = inputs {
+        assert_eq!(inputs.len(), (length + capacity - 1) / capacity, "wrong number of inputs");
+    }
+
+    let mut bits = Vec::with_capacity(length);
+    for i in 0..length {
+        let value = inputs.map(|inputs| {
+            let repr = inputs[i / capacity].into_repr();
+            let position = i % capacity;
+
+            (repr.as_ref()[position / 64] >> (position % 64)) & 1 == 1
+        });
+
+        bits.push(Boolean::from(AllocatedBit::alloc(
+            cs.namespace(|| format!("bit {}", i)),
+            value
+        )?));
+    }
+
+    pack_into_inputs(cs.namespace(|| "pack"), &bits)?;
+
+    Ok(bits)
+}
+
 pub fn bytes_to_bits(bytes: &[u8]) -> Vec<bool>
 {
     bytes.iter()
@@ -112,3 +147,40 @@ fn test_multipacking() {
         assert!(cs.verify(&expected_inputs));
     }
 }
+
+#[test]
+fn test_unpacking() {
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::{ConstraintSystem};
+    use bellman::pairing::bls12_381::{Bls12, Fr};
+    use ::circuit::test::*;
+
+    let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+    for &num_bits in [0, 1, 254, 255, 256, 700].iter() {
+        let bits: Vec<bool> = (0..num_bits).map(|_| rng.gen()).collect();
+        let inputs = compute_multipacking::<Bls12>(&bits);
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        let unpacked = unpack_inputs(cs.namespace(|| "unpack"), num_bits, Some(&inputs)).unwrap();
+
+        assert!(cs.is_satisfied());
+        assert!(cs.verify(&inputs));
+        assert_eq!(cs.num_inputs(), inputs.len() + 1);
+        assert_eq!(unpacked.iter().map(|b| b.get_value().unwrap()).collect::<Vec<_>>(), bits);
+
+        // inputs with bits above the length can't be unpacked
+        if num_bits > 0 {
+            let mut too_long = inputs.clone();
+            let mut top = Fr::one();
+            for _ in 0..((num_bits - 1) % Fr::CAPACITY as usize + 1) {
+                top.double();
+            }
+            too_long.last_mut().unwrap().add_assign(&top);
+
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+            unpack_inputs(cs.namespace(|| "unpack"), num_bits, Some(&too_long)).unwrap();
+            assert!(!cs.verify(&too_long));
+        }
+    }
+}
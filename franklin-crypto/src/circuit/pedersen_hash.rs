@@ -4,6 +4,7 @@ use super::ecc::{
     EdwardsPoint
 };
 use super::boolean::Boolean;
+use super::num::Num;
 use ::jubjub::*;
 use bellman::{
     ConstraintSystem
@@ -28,16 +29,22 @@ pub fn pedersen_hash<E: JubjubEngine, CS>(
 ) -> Result<EdwardsPoint<E>, SynthesisError>
     where CS: ConstraintSystem<E>
 {
+    let domain_generator = personalization.domain_generator::<E>(params);
     let personalization = personalization.get_constant_bools();
     assert_eq!(personalization.len(), 6);
 
     let mut edwards_result = None;
-    let mut bits = personalization.iter().chain(bits.iter());
+    let mut bits = personalization.iter().chain(bits.iter()).peekable();
     let mut segment_generators = params.pedersen_circuit_generators().iter();
     let boolean_false = Boolean::constant(false);
 
     let mut segment_i = 0;
     loop {
+        // The input may fill the last segment exactly
+        if bits.peek().is_none() {
+            break;
+        }
+
         let mut segment_result = None;
         let mut segment_windows = &segment_generators.next()
                                                      .expect("enough segments")[..];
@@ -107,7 +114,29 @@ pub fn pedersen_hash<E: JubjubEngine, CS>(
         segment_i += 1;
     }
 
-    Ok(edwards_result.unwrap())
+    let mut edwards_result = edwards_result.unwrap();
+
+    if let Some(generator) = domain_generator {
+        // The domain generator is added as a constant segment
+        let (x, y) = montgomery::Point::from_edwards(&generator, params)
+                                       .into_xy()
+                                       .expect("domain generator is not the identity");
+        let generator = MontgomeryPoint::interpret_unchecked(
+            Num::zero().add_bool_with_coeff(CS::one(), &Boolean::constant(true), x),
+            Num::zero().add_bool_with_coeff(CS::one(), &Boolean::constant(true), y)
+        ).into_edwards(
+            cs.namespace(|| "conversion of domain generator into edwards"),
+            params
+        )?;
+
+        edwards_result = generator.add(
+            cs.namespace(|| "addition of domain generator to accumulator"),
+            &edwards_result,
+            params
+        )?;
+    }
+
+    Ok(edwards_result)
 }
 
 #[cfg(test)]
@@ -191,6 +220,39 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_pedersen_hash_domain_generator() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = &JubjubBls12::new();
+
+        let input: Vec<bool> = (0..(Fr::NUM_BITS * 2)).map(|_| rng.gen()).collect();
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        let input_bools: Vec<Boolean> = input.iter().enumerate().map(|(i, b)| {
+            Boolean::from(
+                AllocatedBit::alloc(cs.namespace(|| format!("input {}", i)), Some(*b)).unwrap()
+            )
+        }).collect();
+
+        let res = pedersen_hash(
+            cs.namespace(|| "pedersen hash"),
+            Personalization::BatchSignatureTranscript,
+            &input_bools,
+            params
+        ).unwrap();
+
+        assert!(cs.is_satisfied());
+
+        let expected = ::pedersen_hash::pedersen_hash::<Bls12, _>(
+            Personalization::BatchSignatureTranscript,
+            input.into_iter(),
+            params
+        ).into_xy();
+
+        assert_eq!(res.get_x().get_value().unwrap(), expected.0);
+        assert_eq!(res.get_y().get_value().unwrap(), expected.1);
+    }
 }
 
 #[cfg(test)]
@@ -275,4 +337,43 @@ mod baby_test {
             }
         }
     }
+
+    #[test]
+    fn test_baby_pedersen_hash_all_segments() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = &AltJubjubBn256::new();
+
+        // inputs that leave a few bits of the last segment free or fill it exactly
+        let capacity = params.pedersen_circuit_generators().len() * params.pedersen_hash_chunks_per_generator() * 3 - 6;
+
+        for length in (capacity - 6)..(capacity + 1) {
+            let input: Vec<bool> = (0..length).map(|_| rng.gen()).collect();
+
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+
+            let input_bools: Vec<Boolean> = input.iter().enumerate().map(|(i, b)| {
+                Boolean::from(
+                    AllocatedBit::alloc(cs.namespace(|| format!("input {}", i)), Some(*b)).unwrap()
+                )
+            }).collect();
+
+            let res = pedersen_hash(
+                cs.namespace(|| "pedersen hash"),
+                Personalization::NoteCommitment,
+                &input_bools,
+                params
+            ).unwrap();
+
+            assert!(cs.is_satisfied());
+
+            let expected = ::pedersen_hash::pedersen_hash::<Bn256, _>(
+                Personalization::NoteCommitment,
+                input.into_iter(),
+                params
+            ).into_xy();
+
+            assert_eq!(res.get_x().get_value().unwrap(), expected.0);
+            assert_eq!(res.get_y().get_value().unwrap(), expected.1);
+        }
+    }
 }
@@ -0,0 +1,629 @@
+use bellman::pairing::{
+    Engine,
+};
+
+use bellman::pairing::ff::{
+    Field,
+    PrimeField,
+    PrimeFieldRepr,
+    BitIterator
+};
+
+use bellman::{
+    SynthesisError,
+    ConstraintSystem
+};
+
+use super::{
+    Assignment
+};
+
+use super::num::{
+    AllocatedNum,
+    Num
+};
+
+use super::boolean::{
+    AllocatedBit
+};
+
+use super::as_waksman::{
+    prove_shuffle,
+    prove_shuffle_of_records,
+    permute_records
+};
+
+use crate::as_waksman::{
+    IntegerPermutation
+};
+
+/// Calculates a permutation that puts the values into the ascending order,
+/// so the value number `i` goes into the place `permutation.get(i)`.
+/// Equal values keep their relative order. If some value is unknown
+/// an identity permutation is returned
+pub fn sorting_permutation<F: PrimeField>(
+    values: &[Option<F>]
+) -> IntegerPermutation
+{
+    let mut permutation = IntegerPermutation::new(values.len());
+
+    let values: Option<Vec<F::Repr>> = values.iter().map(|v| v.map(|v| v.into_repr())).collect();
+    if let Some(values) = values {
+        let mut order: Vec<usize> = (0..values.len()).collect();
+        order.sort_by(|a, b| values[*a].cmp(&values[*b]));
+
+        for (place, idx) in order.into_iter().enumerate() {
+            permutation.set(idx, place);
+        }
+    }
+
+    permutation
+}
+
+/// Calculates a permutation that transforms one list of records into another,
+/// so the record `original[i]` is equal to `permuted[permutation.get(i)]`.
+/// Returns `None` if some value is unknown or lists are not equal as multisets
+pub fn multiset_permutation<F: PrimeField>(
+    original: &[Vec<Option<F>>],
+    permuted: &[Vec<Option<F>>]
+) -> Option<IntegerPermutation>
+{
+    if original.len() != permuted.len() {
+        return None;
+    }
+
+    let mut permutation = IntegerPermutation::new(original.len());
+    let mut used = vec![false; permuted.len()];
+
+    for (i, record) in original.iter().enumerate() {
+        if record.iter().any(|v| v.is_none()) {
+            return None;
+        }
+
+        let place = permuted.iter().enumerate().position(|(j, candidate)| {
+            !used[j] && candidate == record
+        })?;
+
+        used[place] = true;
+        permutation.set(i, place);
+    }
+
+    Some(permutation)
+}
+
+/// Enforces that the number is in the range [0, 2^number_of_bits)
+/// by decomposing it into exactly `number_of_bits` bits
+fn enforce_fits_into_bits<E, CS>(
+    mut cs: CS,
+    number: &Num<E>,
+    number_of_bits: usize
+) -> Result<(), SynthesisError>
+    where CS: ConstraintSystem<E>,
+          E: Engine
+{
+    assert!(number_of_bits < E::Fr::CAPACITY as usize);
+
+    let values: Vec<Option<bool>> = match number.get_value() {
+        Some(value) => {
+            let mut bits: Vec<bool> = BitIterator::new(value.into_repr()).collect();
+            bits.reverse();
+            bits.truncate(number_of_bits);
+
+            bits.into_iter().map(|b| Some(b)).collect()
+        },
+        None => vec![None; number_of_bits]
+    };
+
+    let mut packed = Num::<E>::zero();
+    let mut coeff = E::Fr::one();
+    for (i, value) in values.into_iter().enumerate() {
+        let bit = AllocatedBit::alloc(
+            cs.namespace(|| format!("bit {}", i)),
+            value
+        )?;
+        packed = packed.add_bool_with_coeff(CS::one(), &bit.into(), coeff);
+        coeff.double();
+    }
+
+    cs.enforce(
+        || "repack bits",
+        |lc| lc + &packed.lc(E::Fr::one()),
+        |lc| lc + CS::one(),
+        |lc| lc + &number.lc(E::Fr::one())
+    );
+
+    Ok(())
+}
+
+/// Enforces that the list is sorted in the ascending order. Every element
+/// of the list is expected to fit into `number_of_bits` bits, and it's a
+/// caller's responsibility to enforce it. In this case the list is sorted
+/// if and only if the difference of any two consecutive elements also fits
+pub fn enforce_sorted<E, CS>(
+    mut cs: CS,
+    list: &[AllocatedNum<E>],
+    number_of_bits: usize
+) -> Result<(), SynthesisError>
+    where CS: ConstraintSystem<E>,
+          E: Engine
+{
+    for (i, pair) in list.windows(2).enumerate() {
+        let difference = Num::from(pair[1].clone())
+            .add_number_with_coeff(&pair[0], {
+                let mut minus_one = E::Fr::one();
+                minus_one.negate();
+
+                minus_one
+            });
+
+        enforce_fits_into_bits(
+            cs.namespace(|| format!("range check difference {}", i)),
+            &difference,
+            number_of_bits
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Proves that `sorted` is a permutation of `original` given by
+/// `permutation` and is sorted in the ascending order.
+/// Elements should fit into `number_of_bits` bits, see `enforce_sorted`
+pub fn prove_sorted<E, CS>(
+    mut cs: CS,
+    original: &[AllocatedNum<E>],
+    sorted: &[AllocatedNum<E>],
+    permutation: &IntegerPermutation,
+    number_of_bits: usize
+) -> Result<(), SynthesisError>
+    where CS: ConstraintSystem<E>,
+          E: Engine
+{
+    prove_shuffle(
+        cs.namespace(|| "prove shuffle"),
+        original,
+        sorted,
+        permutation
+    )?;
+
+    enforce_sorted(
+        cs.namespace(|| "enforce sorted"),
+        sorted,
+        number_of_bits
+    )
+}
+
+/// Sorts records by the variable number `key` in the ascending order.
+/// Routing witness is calculated from the values of keys, and the
+/// result is enforced to be a permutation of the input that is sorted.
+/// Keys should fit into `number_of_bits` bits, see `enforce_sorted`
+pub fn sort_records<E, CS>(
+    mut cs: CS,
+    records: &[Vec<AllocatedNum<E>>],
+    key: usize,
+    number_of_bits: usize
+) -> Result<Vec<Vec<AllocatedNum<E>>>, SynthesisError>
+    where CS: ConstraintSystem<E>,
+          E: Engine
+{
+    let keys: Vec<Option<E::Fr>> = records.iter().map(|r| r[key].get_value()).collect();
+    let permutation = sorting_permutation(&keys);
+
+    let sorted = permute_records(
+        cs.namespace(|| "route records"),
+        records,
+        &permutation
+    )?;
+
+    let sorted_keys: Vec<AllocatedNum<E>> = sorted.iter().map(|r| r[key].clone()).collect();
+
+    enforce_sorted(
+        cs.namespace(|| "enforce sorted"),
+        &sorted_keys,
+        number_of_bits
+    )?;
+
+    Ok(sorted)
+}
+
+/// Sorts the list in the ascending order, see `sort_records`
+pub fn sort<E, CS>(
+    cs: CS,
+    list: &[AllocatedNum<E>],
+    number_of_bits: usize
+) -> Result<Vec<AllocatedNum<E>>, SynthesisError>
+    where CS: ConstraintSystem<E>,
+          E: Engine
+{
+    let records: Vec<Vec<AllocatedNum<E>>> = list.iter().map(|e| vec![e.clone()]).collect();
+    let sorted = sort_records(cs, &records, 0, number_of_bits)?;
+
+    Ok(sorted.into_iter().map(|mut r| r.pop().unwrap()).collect())
+}
+
+/// Proves that two lists of records are equal as multisets by routing
+/// whole records through the permutation network. Routing witness is
+/// calculated from the values, and the constraint system is unsatisfied
+/// if there is no such permutation
+pub fn prove_multiset_equality<E, CS>(
+    cs: CS,
+    original: &[Vec<AllocatedNum<E>>],
+    permuted: &[Vec<AllocatedNum<E>>]
+) -> Result<(), SynthesisError>
+    where CS: ConstraintSystem<E>,
+          E: Engine
+{
+    assert_eq!(original.len(), permuted.len());
+
+    let values = |records: &[Vec<AllocatedNum<E>>]| -> Vec<Vec<Option<E::Fr>>> {
+        records.iter().map(|r| r.iter().map(|e| e.get_value()).collect()).collect()
+    };
+
+    let permutation = multiset_permutation(&values(original), &values(permuted))
+        .unwrap_or(IntegerPermutation::new(original.len()));
+
+    prove_shuffle_of_records(cs, original, permuted, &permutation)
+}
+
+/// Calculates a product of (r_0 + beta * r_1 + ... + beta^(k-1) * r_(k-1) + gamma)
+/// over all the records
+fn grand_product<E, CS>(
+    mut cs: CS,
+    records: &[Vec<AllocatedNum<E>>],
+    beta: &AllocatedNum<E>,
+    gamma: &AllocatedNum<E>
+) -> Result<Num<E>, SynthesisError>
+    where CS: ConstraintSystem<E>,
+          E: Engine
+{
+    let mut product: Option<Num<E>> = None;
+
+    for (i, record) in records.iter().enumerate() {
+        assert!(record.len() > 0);
+
+        // compress the record using the Horner's rule
+        let mut compressed = Num::from(record[record.len() - 1].clone());
+        for j in (0..(record.len() - 1)).rev() {
+            let shifted = mul_by_lc(
+                cs.namespace(|| format!("compress variable {} of record {}", j, i)),
+                &Num::from(beta.clone()),
+                &compressed
+            )?;
+
+            compressed = Num::from(shifted).add_number_with_coeff(&record[j], E::Fr::one());
+        }
+
+        let term = compressed.add_number_with_coeff(gamma, E::Fr::one());
+
+        product = Some(match product {
+            None => term,
+            Some(product) => {
+                let product = mul_by_lc(
+                    cs.namespace(|| format!("accumulate record {}", i)),
+                    &product,
+                    &term
+                )?;
+
+                Num::from(product)
+            }
+        });
+    }
+
+    Ok(product.unwrap_or(Num::zero()))
+}
+
+/// Allocates a product of two linear combinations
+fn mul_by_lc<E, CS>(
+    mut cs: CS,
+    a: &Num<E>,
+    b: &Num<E>
+) -> Result<AllocatedNum<E>, SynthesisError>
+    where CS: ConstraintSystem<E>,
+          E: Engine
+{
+    let product = AllocatedNum::alloc(
+        cs.namespace(|| "product"),
+        || {
+            let mut value = *a.get_value().get()?;
+            value.mul_assign(b.get_value().get()?);
+
+            Ok(value)
+        }
+    )?;
+
+    cs.enforce(
+        || "multiplication constraint",
+        |lc| lc + &a.lc(E::Fr::one()),
+        |lc| lc + &b.lc(E::Fr::one()),
+        |lc| lc + product.get_variable()
+    );
+
+    Ok(product)
+}
+
+/// Proves that two lists of records are equal as multisets with a grand
+/// product argument: records are compressed into single elements with
+/// powers of `beta` and then products of (compressed + `gamma`) are enforced
+/// to be equal. It costs (k - 1) * n + (n - 1) constraints per list of n records
+/// of k variables, and doesn't require any routing witness.
+///
+/// Soundness relies on challenges being random and independent of the
+/// records, so caller is responsible to derive `beta` and `gamma`
+/// from a commitment to both lists (or take them as public inputs after
+/// the lists are fixed)
+pub fn prove_permutation_by_grand_product<E, CS>(
+    mut cs: CS,
+    original: &[Vec<AllocatedNum<E>>],
+    permuted: &[Vec<AllocatedNum<E>>],
+    beta: &AllocatedNum<E>,
+    gamma: &AllocatedNum<E>
+) -> Result<(), SynthesisError>
+    where CS: ConstraintSystem<E>,
+          E: Engine
+{
+    assert_eq!(original.len(), permuted.len());
+
+    let lhs = grand_product(
+        cs.namespace(|| "grand product of original"),
+        original,
+        beta,
+        gamma
+    )?;
+
+    let rhs = grand_product(
+        cs.namespace(|| "grand product of permuted"),
+        permuted,
+        beta,
+        gamma
+    )?;
+
+    cs.enforce(
+        || "enforce equal grand products",
+        |lc| lc + &lhs.lc(E::Fr::one()),
+        |lc| lc + CS::one(),
+        |lc| lc + &rhs.lc(E::Fr::one())
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use bellman::{ConstraintSystem};
+    use rand::{XorShiftRng, SeedableRng, Rand, Rng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use bellman::pairing::ff::{Field, PrimeField};
+    use ::circuit::test::*;
+    use super::super::num::{
+        AllocatedNum,
+    };
+    use super::*;
+
+    fn alloc_records<CS: ConstraintSystem<Bn256>>(
+        mut cs: CS,
+        values: &[Vec<Fr>]
+    ) -> Vec<Vec<AllocatedNum<Bn256>>>
+    {
+        values.iter().enumerate().map(|(i, record)| {
+            record.iter().enumerate().map(|(j, v)| {
+                AllocatedNum::alloc(
+                    cs.namespace(|| format!("record {} variable {}", i, j)),
+                    || Ok(*v)
+                ).unwrap()
+            }).collect()
+        }).collect()
+    }
+
+    fn random_records<R: Rng>(rng: &mut R, size: usize, width: usize) -> Vec<Vec<Fr>> {
+        (0..size).map(|_| {
+            // keys are small and repeat, so sorting has to deal with duplicates
+            let mut record = vec![Fr::from_str(&rng.gen_range(0u32, 16).to_string()).unwrap()];
+            record.extend((1..width).map(|_| Fr::rand(rng)));
+
+            record
+        }).collect()
+    }
+
+    fn shuffle_records<R: Rng>(rng: &mut R, records: &[Vec<Fr>]) -> Vec<Vec<Fr>> {
+        let mut shuffled = records.to_vec();
+        rng.shuffle(&mut shuffled);
+
+        shuffled
+    }
+
+    #[test]
+    fn test_sort() {
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        for size in 1..40 {
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+
+            let records = random_records(rng, size, 3);
+            let allocated = alloc_records(cs.namespace(|| "records"), &records);
+
+            let sorted = sort_records(cs.namespace(|| "sort"), &allocated, 0, 8).unwrap();
+            assert!(cs.is_satisfied());
+
+            let mut expected = records.clone();
+            expected.sort_by(|a, b| a[0].into_repr().cmp(&b[0].into_repr()));
+
+            for (record, expected) in sorted.iter().zip(expected.iter()) {
+                let values: Vec<Fr> = record.iter().map(|e| e.get_value().unwrap()).collect();
+                assert_eq!(&values, expected);
+            }
+
+            let list: Vec<AllocatedNum<Bn256>> = allocated.iter().map(|r| r[1].clone()).collect();
+            sort(cs.namespace(|| "sort random field elements"), &list, 8).unwrap();
+            if size > 1 {
+                // elements are not in the range
+                assert!(!cs.is_satisfied());
+            }
+        }
+    }
+
+    #[test]
+    fn test_prove_sorted() {
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        for size in 2..40 {
+            let values: Vec<Fr> = (0..size).map(|_| {
+                Fr::from_str(&rng.gen::<u32>().to_string()).unwrap()
+            }).collect();
+            let permutation = sorting_permutation(&values.iter().map(|v| Some(*v)).collect::<Vec<_>>());
+
+            let mut sorted_values = values.clone();
+            for i in 0..size {
+                sorted_values[permutation.get(i)] = values[i];
+            }
+
+            {
+                let mut cs = TestConstraintSystem::<Bn256>::new();
+                let original = alloc_records(cs.namespace(|| "original"), &[values.clone()]).pop().unwrap();
+                let sorted = alloc_records(cs.namespace(|| "sorted"), &[sorted_values.clone()]).pop().unwrap();
+
+                prove_sorted(cs.namespace(|| "prove sorted"), &original, &sorted, &permutation, 32).unwrap();
+                assert!(cs.is_satisfied());
+            }
+
+            {
+                // a permutation that is not sorted
+                let mut cs = TestConstraintSystem::<Bn256>::new();
+                let original = alloc_records(cs.namespace(|| "original"), &[values.clone()]).pop().unwrap();
+                let mut unsorted_values = sorted_values.clone();
+                unsorted_values.reverse();
+                let unsorted = alloc_records(cs.namespace(|| "unsorted"), &[unsorted_values]).pop().unwrap();
+
+                let inverse_order: Vec<usize> = permutation.elements.iter().map(|p| size - 1 - p).collect();
+                let mut reversed = IntegerPermutation::new(size);
+                reversed.elements = inverse_order;
+
+                prove_sorted(cs.namespace(|| "prove sorted"), &original, &unsorted, &reversed, 32).unwrap();
+                if sorted_values[0] != sorted_values[size - 1] {
+                    assert!(!cs.is_satisfied());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sort_tampered_switch() {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+
+        let records = vec![
+            vec![Fr::from_str("7").unwrap()],
+            vec![Fr::from_str("3").unwrap()]
+        ];
+        let allocated = alloc_records(cs.namespace(|| "records"), &records);
+        let sorted = sort_records(cs.namespace(|| "sort"), &allocated, 0, 8).unwrap();
+        assert_eq!(sorted[0][0].get_value().unwrap(), records[1][0]);
+        assert!(cs.is_satisfied());
+        assert_eq!(cs.find_unconstrained(), "");
+
+        // the only switch of the network decides the order
+        let switch = "sort/route records/Allocate boolean witness for switch in column 0 for packet 0 and it's pair 1/boolean";
+        let value = cs.get(switch);
+        let mut flipped = Fr::one();
+        flipped.sub_assign(&value);
+        cs.set(switch, flipped);
+        assert!(!cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_multiset_equality() {
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        for size in 1..40 {
+            let records = random_records(rng, size, 3);
+            let shuffled = shuffle_records(rng, &records);
+
+            {
+                let mut cs = TestConstraintSystem::<Bn256>::new();
+                let original = alloc_records(cs.namespace(|| "original"), &records);
+                let permuted = alloc_records(cs.namespace(|| "permuted"), &shuffled);
+
+                prove_multiset_equality(cs.namespace(|| "multiset equality"), &original, &permuted).unwrap();
+                assert!(cs.is_satisfied());
+            }
+
+            {
+                // the same columns taken from different records
+                let mut tampered = shuffled.clone();
+                let idx = rng.gen_range(0, size);
+                tampered[idx][2] = Fr::rand(rng);
+
+                let mut cs = TestConstraintSystem::<Bn256>::new();
+                let original = alloc_records(cs.namespace(|| "original"), &records);
+                let permuted = alloc_records(cs.namespace(|| "permuted"), &tampered);
+
+                prove_multiset_equality(cs.namespace(|| "multiset equality"), &original, &permuted).unwrap();
+                assert!(!cs.is_satisfied());
+            }
+        }
+    }
+
+    #[test]
+    fn test_grand_product() {
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        for size in 1..40 {
+            let records = random_records(rng, size, 3);
+            let shuffled = shuffle_records(rng, &records);
+            let mut tampered = shuffled.clone();
+            let idx = rng.gen_range(0, size);
+            tampered[idx][0].add_assign(&Fr::one());
+
+            for (permuted, expected) in vec![(shuffled, true), (tampered, false)] {
+                let mut cs = TestConstraintSystem::<Bn256>::new();
+                let original = alloc_records(cs.namespace(|| "original"), &records);
+                let permuted = alloc_records(cs.namespace(|| "permuted"), &permuted);
+                let beta = AllocatedNum::alloc(cs.namespace(|| "beta"), || Ok(Fr::rand(rng))).unwrap();
+                let gamma = AllocatedNum::alloc(cs.namespace(|| "gamma"), || Ok(Fr::rand(rng))).unwrap();
+
+                prove_permutation_by_grand_product(
+                    cs.namespace(|| "grand product"),
+                    &original,
+                    &permuted,
+                    &beta,
+                    &gamma
+                ).unwrap();
+
+                assert_eq!(cs.is_satisfied(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_permutation_constraints() {
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let expected = [
+            (8, 1, 59), (8, 3, 143),
+            (32, 1, 419), (32, 3, 999),
+            (128, 1, 2435), (128, 3, 5767)
+        ];
+        for &(size, width, expected_as_waksman) in expected.iter() {
+            let records = random_records(rng, size, width);
+            let shuffled = shuffle_records(rng, &records);
+
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+            let original = alloc_records(cs.namespace(|| "original"), &records);
+            let permuted = alloc_records(cs.namespace(|| "permuted"), &shuffled);
+            let beta = AllocatedNum::alloc(cs.namespace(|| "beta"), || Ok(Fr::rand(rng))).unwrap();
+            let gamma = AllocatedNum::alloc(cs.namespace(|| "gamma"), || Ok(Fr::rand(rng))).unwrap();
+
+            let start = cs.num_constraints();
+            prove_multiset_equality(cs.namespace(|| "multiset equality"), &original, &permuted).unwrap();
+            let as_waksman = cs.num_constraints() - start;
+
+            let start = cs.num_constraints();
+            prove_permutation_by_grand_product(
+                cs.namespace(|| "grand product"),
+                &original,
+                &permuted,
+                &beta,
+                &gamma
+            ).unwrap();
+            let grand_product = cs.num_constraints() - start;
+
+            assert!(cs.is_satisfied());
+            assert_eq!(grand_product, 2 * ((width - 1) * size + size - 1) + 1);
+            assert_eq!(as_waksman, expected_as_waksman);
+            assert!(grand_product < as_waksman);
+        }
+    }
+}
@@ -3,6 +3,9 @@ use bellman::{ConstraintSystem, SynthesisError};
 use bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr, BitIterator};
 use super::*;
 
+/// Generates powers 1, base, base^2, ..., base^(max_power - 1).
+/// Every power is enforced to be the previous one times the base,
+/// so it costs max_power - 1 constraints
 pub fn generate_powers<E: Engine, CS>(
     mut cs: CS,
     base: &num::AllocatedNum<E>, 
@@ -12,28 +15,33 @@ pub fn generate_powers<E: Engine, CS>(
     {
         let mut result = vec![];
 
-        let mut power = num::AllocatedNum::alloc(
+        let power = num::AllocatedNum::alloc(
             cs.namespace(|| format!("0-th power")), 
             || {
                 return Ok(E::Fr::one());
             }
         )?;
 
-        result.push(power.clone());
-
-        for i in 1..max_power {
-            power = num::AllocatedNum::alloc(
-                cs.namespace(|| format!("{}-th power", i)), 
-                || {
-                    let mut power = power.get_value().get()?.clone();
-                    let value = base.get_value().get()?.clone();
-                    power.mul_assign(&value);
-                    
-                    Ok(power)
-                }
+        cs.enforce(
+            || "enforce 0-th power",
+            |lc| lc + power.get_variable(),
+            |lc| lc + CS::one(),
+            |lc| lc + CS::one()
+        );
+
+        result.push(power);
+
+        if max_power > 1 {
+            result.push(base.clone());
+        }
+
+        for i in 2..max_power {
+            let power = result[i - 1].mul(
+                cs.namespace(|| format!("{}-th power", i)),
+                base
             )?;
 
-            result.push(power.clone());
+            result.push(power);
         }
 
     Ok(result)
@@ -68,4 +76,51 @@ pub fn do_the_lookup<E: Engine, CS>(
         );
 
         Ok(result)
-    }
\ No newline at end of file
+    }
+
+#[cfg(test)]
+mod test {
+    use bellman::{ConstraintSystem};
+    use rand::{XorShiftRng, SeedableRng, Rand};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use bellman::pairing::ff::{Field};
+    use ::circuit::test::*;
+    use super::*;
+
+    #[test]
+    fn test_generate_powers() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+
+        let base_value = Fr::rand(rng);
+        let base = num::AllocatedNum::alloc(cs.namespace(|| "base"), || Ok(base_value)).unwrap();
+        let powers = generate_powers(cs.namespace(|| "powers"), &base, 10).unwrap();
+
+        assert!(cs.is_satisfied());
+        assert_eq!(cs.num_constraints(), 9);
+
+        let mut expected = Fr::one();
+        for power in powers.iter() {
+            assert_eq!(power.get_value().unwrap(), expected);
+            expected.mul_assign(&base_value);
+        }
+
+        // substitute one of the powers
+        cs.set("powers/5-th power/product num", Fr::rand(rng));
+        assert!(!cs.is_satisfied());
+        assert_eq!(cs.which_is_unsatisfied(), Some("powers/5-th power/multiplication constraint"));
+    }
+
+    #[test]
+    fn test_generate_powers_tampered_one() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+
+        let base = num::AllocatedNum::alloc(cs.namespace(|| "base"), || Ok(Fr::rand(rng))).unwrap();
+        generate_powers(cs.namespace(|| "powers"), &base, 4).unwrap();
+        assert!(cs.is_satisfied());
+
+        cs.set("powers/0-th power/num", Fr::rand(rng));
+        assert!(!cs.is_satisfied());
+    }
+}
@@ -0,0 +1,558 @@
+use bellman::pairing::{
+    Engine,
+};
+
+use bellman::pairing::ff::{
+    Field,
+    PrimeField,
+    BitIterator
+};
+
+use bellman::{
+    SynthesisError,
+    ConstraintSystem
+};
+
+use crypto::sha2::Sha256;
+use crypto::digest::Digest;
+
+use super::boolean::{
+    Boolean
+};
+
+use super::Assignment;
+
+use super::num::{
+    AllocatedNum
+};
+
+use super::ecc::{
+    EdwardsPoint
+};
+
+use super::multipack::{
+    pack_into_inputs,
+    unpack_inputs,
+    compute_multipacking
+};
+
+use super::sha256::sha256;
+
+use ::jubjub::EdwardsCoordinates;
+
+/// Kind of the value exposed as a public input
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PublicInputKind {
+    /// Sequence of bits of the fixed length
+    Bits(usize),
+    /// Field element
+    Element,
+    /// Point given by x and y coordinates
+    Point
+}
+
+/// How the values are turned into public inputs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PackingMode {
+    /// Every bits field is multipacked into as few inputs as possible,
+    /// elements take one input and points take two
+    Multipacking,
+    /// All the fields are serialized and hashed with SHA-256, and the hash
+    /// truncated to the field capacity is the only public input.
+    /// Bits fields are padded with zeros to full bytes, and elements
+    /// are serialized as 256 bit big endian numbers
+    Sha256
+}
+
+/// Ordered list of named public input fields. The same layout is used
+/// to expose inputs in the circuit and to calculate them for the verifier
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublicInputsLayout {
+    mode: PackingMode,
+    fields: Vec<(String, PublicInputKind)>
+}
+
+impl PublicInputsLayout {
+    pub fn new(mode: PackingMode) -> Self {
+        PublicInputsLayout {
+            mode,
+            fields: vec![]
+        }
+    }
+
+    pub fn bits(self, name: &str, length: usize) -> Self {
+        self.field(name, PublicInputKind::Bits(length))
+    }
+
+    pub fn element(self, name: &str) -> Self {
+        self.field(name, PublicInputKind::Element)
+    }
+
+    pub fn point(self, name: &str) -> Self {
+        self.field(name, PublicInputKind::Point)
+    }
+
+    fn field(mut self, name: &str, kind: PublicInputKind) -> Self {
+        assert!(self.fields.iter().all(|(n, _)| n != name), "field names must be unique");
+        self.fields.push((name.to_string(), kind));
+
+        self
+    }
+
+    pub fn mode(&self) -> PackingMode {
+        self.mode
+    }
+
+    pub fn fields(&self) -> &[(String, PublicInputKind)] {
+        &self.fields
+    }
+
+    /// Number of public inputs the verifier expects
+    pub fn num_inputs<E: Engine>(&self) -> usize {
+        match self.mode {
+            PackingMode::Sha256 => 1,
+            PackingMode::Multipacking => {
+                let capacity = E::Fr::CAPACITY as usize;
+                self.fields.iter().map(|(_, kind)| match kind {
+                    PublicInputKind::Bits(length) => (length + capacity - 1) / capacity,
+                    PublicInputKind::Element => 1,
+                    PublicInputKind::Point => 2
+                }).sum()
+            }
+        }
+    }
+
+    /// Starts collecting the values in the circuit
+    pub fn circuit_inputs<E: Engine>(&self) -> PublicInputs<E> {
+        PublicInputs {
+            layout: self.clone(),
+            values: vec![]
+        }
+    }
+
+    /// Starts collecting the values for the verifier
+    pub fn native_inputs<E: Engine>(&self) -> NativePublicInputs<E> {
+        NativePublicInputs {
+            layout: self.clone(),
+            values: vec![]
+        }
+    }
+
+    /// Allocates the public inputs of a multipacking layout and gets the values
+    /// of all the fields back in the layout order, so a circuit can take them
+    /// from the inputs the verifier has calculated by `NativePublicInputs::to_inputs`.
+    /// Points are given by the coordinates, that are not checked to be on the curve
+    pub fn unpack<E, CS>(
+        &self,
+        mut cs: CS,
+        inputs: Option<&[E::Fr]>
+    ) -> Result<Vec<CircuitValue<E>>, SynthesisError>
+        where E: Engine,
+              CS: ConstraintSystem<E>
+    {
+        assert_eq!(self.mode, PackingMode::Multipacking, "hashed inputs can not be unpacked");
+        if let Some(inputs) = inputs {
+            assert_eq!(inputs.len(), self.num_inputs::<E>(), "wrong number of inputs");
+        }
+
+        let capacity = E::Fr::CAPACITY as usize;
+        let mut offset = 0;
+        let mut take = |count: usize| {
+            let taken = inputs.map(|inputs| &inputs[offset..(offset + count)]);
+            offset += count;
+
+            taken
+        };
+
+        let mut result = vec![];
+        for (name, kind) in self.fields.iter() {
+            let mut cs = cs.namespace(|| format!("field {}", name));
+            let value = match *kind {
+                PublicInputKind::Bits(length) => {
+                    let inputs = take((length + capacity - 1) / capacity);
+
+                    CircuitValue::Bits(unpack_inputs(cs.namespace(|| "unpack bits"), length, inputs)?)
+                },
+                PublicInputKind::Element => {
+                    CircuitValue::Element(alloc_input(cs.namespace(|| "element"), take(1))?)
+                },
+                PublicInputKind::Point => {
+                    let x = alloc_input(cs.namespace(|| "x"), take(1))?;
+                    let y = alloc_input(cs.namespace(|| "y"), take(1))?;
+
+                    CircuitValue::Point(x, y)
+                }
+            };
+            result.push(value);
+        }
+
+        Ok(result)
+    }
+
+    fn check_next(&self, filled: usize, name: &str, kind: PublicInputKind) {
+        let (expected_name, expected_kind) = self.fields.get(filled).expect("all the fields are already filled");
+        assert_eq!(expected_name, name, "fields must be filled in the layout order");
+        assert_eq!(*expected_kind, kind, "field {} has a different kind", name);
+    }
+}
+
+/// Value of a public input field in the circuit
+pub enum CircuitValue<E: Engine> {
+    Bits(Vec<Boolean>),
+    Element(AllocatedNum<E>),
+    /// x and y coordinates
+    Point(AllocatedNum<E>, AllocatedNum<E>)
+}
+
+/// Allocates the element that is exposed as a single public input
+fn alloc_input<E, CS>(
+    mut cs: CS,
+    value: Option<&[E::Fr]>
+) -> Result<AllocatedNum<E>, SynthesisError>
+    where CS: ConstraintSystem<E>,
+          E: Engine
+{
+    let num = AllocatedNum::alloc(cs.namespace(|| "value"), || Ok(value.get()?[0]))?;
+    num.inputize(cs.namespace(|| "input"))?;
+
+    Ok(num)
+}
+
+/// Values of the public inputs in the circuit, that are exposed by `inputize`
+pub struct PublicInputs<E: Engine> {
+    layout: PublicInputsLayout,
+    values: Vec<CircuitValue<E>>
+}
+
+impl<E: Engine> PublicInputs<E> {
+    pub fn bits(&mut self, name: &str, bits: &[Boolean]) -> &mut Self {
+        self.layout.check_next(self.values.len(), name, PublicInputKind::Bits(bits.len()));
+        self.values.push(CircuitValue::Bits(bits.to_vec()));
+
+        self
+    }
+
+    pub fn element(&mut self, name: &str, element: &AllocatedNum<E>) -> &mut Self {
+        self.layout.check_next(self.values.len(), name, PublicInputKind::Element);
+        self.values.push(CircuitValue::Element(element.clone()));
+
+        self
+    }
+
+    pub fn point(&mut self, name: &str, point: &EdwardsPoint<E>) -> &mut Self {
+        self.layout.check_next(self.values.len(), name, PublicInputKind::Point);
+        self.values.push(CircuitValue::Point(point.get_x().clone(), point.get_y().clone()));
+
+        self
+    }
+
+    /// Allocates public inputs for all the fields of the layout
+    pub fn inputize<CS>(
+        &self,
+        mut cs: CS
+    ) -> Result<(), SynthesisError>
+        where CS: ConstraintSystem<E>
+    {
+        assert_eq!(self.values.len(), self.layout.fields.len(), "all the fields must be filled");
+
+        match self.layout.mode {
+            PackingMode::Multipacking => {
+                for ((name, _), value) in self.layout.fields.iter().zip(self.values.iter()) {
+                    let mut cs = cs.namespace(|| format!("field {}", name));
+                    match value {
+                        CircuitValue::Bits(bits) => {
+                            pack_into_inputs(cs.namespace(|| "pack bits"), bits)?;
+                        },
+                        CircuitValue::Element(element) => {
+                            element.inputize(cs.namespace(|| "element"))?;
+                        },
+                        CircuitValue::Point(x, y) => {
+                            x.inputize(cs.namespace(|| "x"))?;
+                            y.inputize(cs.namespace(|| "y"))?;
+                        }
+                    }
+                }
+            },
+            PackingMode::Sha256 => {
+                let mut serialized = vec![];
+                for ((name, _), value) in self.layout.fields.iter().zip(self.values.iter()) {
+                    let mut cs = cs.namespace(|| format!("serialize field {}", name));
+                    match value {
+                        CircuitValue::Bits(bits) => {
+                            serialized.extend(bits.iter().cloned());
+                            while serialized.len() % 8 != 0 {
+                                serialized.push(Boolean::constant(false));
+                            }
+                        },
+                        CircuitValue::Element(element) => {
+                            serialized.extend(element_into_bits_be(cs.namespace(|| "element"), element)?);
+                        },
+                        CircuitValue::Point(x, y) => {
+                            serialized.extend(element_into_bits_be(cs.namespace(|| "x"), x)?);
+                            serialized.extend(element_into_bits_be(cs.namespace(|| "y"), y)?);
+                        }
+                    }
+                }
+
+                let hash = sha256(cs.namespace(|| "hash public inputs"), &serialized)?;
+
+                pack_into_inputs(cs.namespace(|| "pack hash"), &truncate_hash(hash, E::Fr::CAPACITY as usize))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Serializes the element as 256 bits in big endian order
+fn element_into_bits_be<E, CS>(
+    cs: CS,
+    element: &AllocatedNum<E>
+) -> Result<Vec<Boolean>, SynthesisError>
+    where CS: ConstraintSystem<E>,
+          E: Engine
+{
+    let mut bits = element.into_bits_le_strict(cs)?;
+    assert!(bits.len() <= 256);
+    bits.resize(256, Boolean::constant(false));
+    bits.reverse();
+
+    Ok(bits)
+}
+
+/// Takes the lowest bits of the big endian hash that fit into the field,
+/// in little endian order
+fn truncate_hash<T>(mut hash: Vec<T>, capacity: usize) -> Vec<T> {
+    hash.reverse();
+    hash.truncate(capacity);
+
+    hash
+}
+
+/// Values of the public inputs for the verifier
+pub struct NativePublicInputs<E: Engine> {
+    layout: PublicInputsLayout,
+    values: Vec<NativeValue<E>>
+}
+
+enum NativeValue<E: Engine> {
+    Bits(Vec<bool>),
+    Element(E::Fr),
+    Point(E::Fr, E::Fr)
+}
+
+impl<E: Engine> NativePublicInputs<E> {
+    pub fn bits(&mut self, name: &str, bits: &[bool]) -> &mut Self {
+        self.layout.check_next(self.values.len(), name, PublicInputKind::Bits(bits.len()));
+        self.values.push(NativeValue::Bits(bits.to_vec()));
+
+        self
+    }
+
+    pub fn element(&mut self, name: &str, element: E::Fr) -> &mut Self {
+        self.layout.check_next(self.values.len(), name, PublicInputKind::Element);
+        self.values.push(NativeValue::Element(element));
+
+        self
+    }
+
+    pub fn point<P: EdwardsCoordinates<E>>(&mut self, name: &str, point: &P) -> &mut Self {
+        self.layout.check_next(self.values.len(), name, PublicInputKind::Point);
+        let (x, y) = point.edwards_xy();
+        self.values.push(NativeValue::Point(x, y));
+
+        self
+    }
+
+    /// Public inputs in the order the circuit allocates them
+    pub fn to_inputs(&self) -> Vec<E::Fr> {
+        assert_eq!(self.values.len(), self.layout.fields.len(), "all the fields must be filled");
+
+        match self.layout.mode {
+            PackingMode::Multipacking => {
+                let mut result = vec![];
+                for value in self.values.iter() {
+                    match value {
+                        NativeValue::Bits(bits) => result.extend(compute_multipacking::<E>(bits)),
+                        NativeValue::Element(element) => result.push(*element),
+                        NativeValue::Point(x, y) => {
+                            result.push(*x);
+                            result.push(*y);
+                        }
+                    }
+                }
+
+                result
+            },
+            PackingMode::Sha256 => {
+                let mut serialized = vec![];
+                for value in self.values.iter() {
+                    match value {
+                        NativeValue::Bits(bits) => {
+                            serialized.extend(bits.iter().cloned());
+                            while serialized.len() % 8 != 0 {
+                                serialized.push(false);
+                            }
+                        },
+                        NativeValue::Element(element) => serialized.extend(BitIterator::new(element.into_repr())),
+                        NativeValue::Point(x, y) => {
+                            serialized.extend(BitIterator::new(x.into_repr()));
+                            serialized.extend(BitIterator::new(y.into_repr()));
+                        }
+                    }
+                }
+
+                let bytes: Vec<u8> = serialized.chunks(8).map(|byte| {
+                    byte.iter().fold(0u8, |acc, b| (acc << 1) | (*b as u8))
+                }).collect();
+
+                let mut hasher = Sha256::new();
+                hasher.input(&bytes);
+                let mut hash = [0u8; 32];
+                hasher.result(&mut hash);
+
+                let hash_bits: Vec<bool> = hash.iter().flat_map(|&byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1)).collect();
+
+                compute_multipacking::<E>(&truncate_hash(hash_bits, E::Fr::CAPACITY as usize))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bellman::{ConstraintSystem};
+    use rand::{XorShiftRng, SeedableRng, Rand, Rng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use bellman::pairing::ff::Field;
+    use ::circuit::test::*;
+    use ::circuit::boolean::{AllocatedBit, Boolean};
+    use ::circuit::num::AllocatedNum;
+    use ::circuit::ecc::EdwardsPoint;
+    use ::alt_babyjubjub::AltJubjubBn256;
+    use ::jubjub::edwards;
+    use super::*;
+
+    fn layout(mode: PackingMode) -> PublicInputsLayout {
+        PublicInputsLayout::new(mode)
+            .bits("nullifier", 300)
+            .element("root")
+            .point("public key")
+            .bits("flags", 3)
+    }
+
+    #[test]
+    fn test_public_inputs() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = &AltJubjubBn256::new();
+
+        for &mode in [PackingMode::Multipacking, PackingMode::Sha256].iter() {
+            let layout = layout(mode);
+
+            let nullifier: Vec<bool> = (0..300).map(|_| rng.gen()).collect();
+            let flags: Vec<bool> = (0..3).map(|_| rng.gen()).collect();
+            let root = Fr::rand(rng);
+            let public_key = edwards::Point::<Bn256, _>::rand(rng, params);
+
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+            let alloc_bits = |cs: &mut TestConstraintSystem<Bn256>, name: &str, bits: &[bool]| -> Vec<Boolean> {
+                bits.iter().enumerate().map(|(i, b)| {
+                    Boolean::from(AllocatedBit::alloc(cs.namespace(|| format!("{} bit {}", name, i)), Some(*b)).unwrap())
+                }).collect()
+            };
+            let nullifier_bits = alloc_bits(&mut cs, "nullifier", &nullifier);
+            let flags_bits = alloc_bits(&mut cs, "flags", &flags);
+            let root_num = AllocatedNum::alloc(cs.namespace(|| "root"), || Ok(root)).unwrap();
+            let public_key_point = EdwardsPoint::witness(cs.namespace(|| "public key"), Some(public_key.clone()), params).unwrap();
+
+            let mut inputs = layout.circuit_inputs::<Bn256>();
+            inputs.bits("nullifier", &nullifier_bits)
+                .element("root", &root_num)
+                .point("public key", &public_key_point)
+                .bits("flags", &flags_bits);
+            inputs.inputize(cs.namespace(|| "public inputs")).unwrap();
+
+            assert!(cs.is_satisfied());
+
+            let mut native = layout.native_inputs::<Bn256>();
+            native.bits("nullifier", &nullifier)
+                .element("root", root)
+                .point("public key", &public_key)
+                .bits("flags", &flags);
+            let expected = native.to_inputs();
+
+            assert_eq!(expected.len(), layout.num_inputs::<Bn256>());
+            assert_eq!(cs.num_inputs(), expected.len() + 1);
+            assert!(cs.verify(&expected));
+
+            // any other value gives other inputs
+            let mut other = layout.native_inputs::<Bn256>();
+            let mut other_flags = flags.clone();
+            other_flags[2] = !other_flags[2];
+            other.bits("nullifier", &nullifier)
+                .element("root", root)
+                .point("public key", &public_key)
+                .bits("flags", &other_flags);
+            assert!(!cs.verify(&other.to_inputs()));
+        }
+
+        assert_eq!(layout(PackingMode::Multipacking).num_inputs::<Bn256>(), 2 + 1 + 2 + 1);
+    }
+
+    #[test]
+    fn test_unpack_public_inputs() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = &AltJubjubBn256::new();
+        let layout = layout(PackingMode::Multipacking);
+
+        let nullifier: Vec<bool> = (0..300).map(|_| rng.gen()).collect();
+        let flags: Vec<bool> = (0..3).map(|_| rng.gen()).collect();
+        let root = Fr::rand(rng);
+        let public_key = edwards::Point::<Bn256, _>::rand(rng, params);
+
+        let mut native = layout.native_inputs::<Bn256>();
+        native.bits("nullifier", &nullifier)
+            .element("root", root)
+            .point("public key", &public_key)
+            .bits("flags", &flags);
+        let inputs = native.to_inputs();
+
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let values = layout.unpack(cs.namespace(|| "unpack"), Some(&inputs)).unwrap();
+        assert!(cs.is_satisfied());
+        assert_eq!(cs.find_unconstrained(), "");
+        assert!(cs.verify(&inputs));
+
+        let bits_value = |bits: &[Boolean]| -> Vec<bool> { bits.iter().map(|b| b.get_value().unwrap()).collect() };
+        let (x, y) = public_key.into_xy();
+        match (&values[0], &values[1], &values[2], &values[3]) {
+            (CircuitValue::Bits(n), CircuitValue::Element(r), CircuitValue::Point(px, py), CircuitValue::Bits(f)) => {
+                assert_eq!(bits_value(n), nullifier);
+                assert_eq!(r.get_value().unwrap(), root);
+                assert_eq!((px.get_value().unwrap(), py.get_value().unwrap()), (x, y));
+                assert_eq!(bits_value(f), flags);
+            },
+            _ => panic!("values must follow the layout")
+        }
+
+        // other inputs give other values
+        let mut tampered = inputs.clone();
+        tampered[2].add_assign(&Fr::one());
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let values = layout.unpack(cs.namespace(|| "unpack"), Some(&tampered)).unwrap();
+        assert!(cs.verify(&tampered));
+        assert!(!cs.verify(&inputs));
+        match values[1] {
+            CircuitValue::Element(ref r) => assert!(r.get_value().unwrap() != root),
+            _ => panic!("values must follow the layout")
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "fields must be filled in the layout order")]
+    fn test_public_inputs_order() {
+        let layout = layout(PackingMode::Multipacking);
+        let mut native = layout.native_inputs::<Bn256>();
+        native.element("root", Fr::one());
+    }
+}
@@ -27,6 +27,8 @@ use primitives::{
     PaymentAddress
 };
 
+use primitives::asset::AssetType;
+
 use super::Assignment;
 use super::boolean;
 use super::ecc;
@@ -34,6 +36,7 @@ use super::pedersen_hash;
 use super::blake2s;
 use super::num;
 use super::multipack;
+use super::vrf::point_encoding;
 
 /// This is an instance of the `Spend` circuit.
 pub struct Spend<'a, E: JubjubEngine> {
@@ -80,11 +83,109 @@ pub struct Output<'a, E: JubjubEngine> {
     pub esk: Option<E::Fs>
 }
 
+/// This is a `Spend` of a note of some asset type, whose
+/// value commitment uses the value generator of the asset.
+pub struct AssetSpend<'a, E: JubjubEngine> {
+    pub spend: Spend<'a, E>,
+
+    /// The asset type of the note being spent
+    pub asset_type: Option<AssetType>
+}
+
+/// This is an `Output` of a note of some asset type, whose
+/// value commitment uses the value generator of the asset.
+pub struct AssetOutput<'a, E: JubjubEngine> {
+    pub output: Output<'a, E>,
+
+    /// The asset type of the new note
+    pub asset_type: Option<AssetType>
+}
+
+/// The value generator of an asset type with its
+/// representation, as placed in the note.
+struct AssetGenerator<E: JubjubEngine> {
+    generator: ecc::EdwardsPoint<E>,
+    repr: Vec<boolean::Boolean>
+}
+
+/// Derives the value generator of a witnessed asset type,
+/// matching `AssetType::value_commitment_generator`.
+fn derive_asset_generator<E, CS>(
+    mut cs: CS,
+    asset_type: Option<AssetType>,
+    params: &E::Params
+) -> Result<AssetGenerator<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>
+{
+    // The group hash preimage is GH_FIRST_BLOCK followed by the
+    // identifier, in the bit order of the BLAKE2s gadget
+    let mut preimage: Vec<boolean::Boolean> = constants::GH_FIRST_BLOCK.iter()
+        .flat_map(|&byte| (0..8).map(move |i| boolean::Boolean::constant((byte >> i) & 1 == 1)))
+        .collect();
+
+    for i in 0..32 {
+        for j in 0..8 {
+            preimage.push(boolean::Boolean::from(boolean::AllocatedBit::alloc(
+                cs.namespace(|| format!("identifier bit {}", i * 8 + j)),
+                asset_type.as_ref().map(|a| (a.get_identifier()[i] >> j) & 1 == 1)
+            )?));
+        }
+    }
+
+    let hash = blake2s::blake2s(
+        cs.namespace(|| "group hash"),
+        &preimage,
+        constants::ASSET_GENERATOR_PERSONALIZATION
+    )?;
+
+    // Witness the point hashed to, ensuring it's on the curve
+    let unscaled = ecc::EdwardsPoint::witness(
+        cs.namespace(|| "witness unscaled generator"),
+        asset_type.as_ref().map(|a| a.unscaled_value_commitment_generator::<E>(params)),
+        params
+    )?;
+
+    // The encoding of the point is the hash. As the encoding is
+    // computed strictly, the hash is a canonical encoding.
+    let encoding = point_encoding(
+        cs.namespace(|| "encoding of unscaled generator"),
+        &unscaled
+    )?;
+
+    boolean::enforce_equal_bits(
+        cs.namespace(|| "encoding equals hash"),
+        &encoding,
+        &hash
+    )?;
+
+    // Clearing the cofactor of a point which is not small
+    // order gives a point of prime order, which is not the
+    // identity, as in the group hash.
+    unscaled.assert_not_small_order(
+        cs.namespace(|| "unscaled generator not small order"),
+        params
+    )?;
+
+    let generator = unscaled.double(cs.namespace(|| "first doubling"), params)?;
+    let generator = generator.double(cs.namespace(|| "second doubling"), params)?;
+    let generator = generator.double(cs.namespace(|| "third doubling"), params)?;
+
+    let repr = generator.repr(cs.namespace(|| "representation of generator"))?;
+
+    Ok(AssetGenerator {
+        generator: generator,
+        repr: repr
+    })
+}
+
 /// Exposes a Pedersen commitment to the value as an
-/// input to the circuit
+/// input to the circuit, using the value generator of
+/// the asset if there is one
 fn expose_value_commitment<E, CS>(
     mut cs: CS,
     value_commitment: Option<ValueCommitment<E>>,
+    asset: Option<&AssetGenerator<E>>,
     params: &E::Params
 ) -> Result<Vec<boolean::Boolean>, SynthesisError>
     where E: JubjubEngine,
@@ -97,12 +198,19 @@ fn expose_value_commitment<E, CS>(
     )?;
 
     // Compute the note value in the exponent
-    let value = ecc::fixed_base_multiplication(
-        cs.namespace(|| "compute the value in the exponent"),
-        FixedGenerators::ValueCommitmentValue,
-        &value_bits,
-        params
-    )?;
+    let value = match asset {
+        Some(asset) => asset.generator.mul(
+            cs.namespace(|| "compute the value in the exponent"),
+            &value_bits,
+            params
+        )?,
+        None => ecc::fixed_base_multiplication(
+            cs.namespace(|| "compute the value in the exponent"),
+            FixedGenerators::ValueCommitmentValue,
+            &value_bits,
+            params
+        )?
+    };
 
     // Booleanize the randomness. This does not ensure
     // the bit representation is "in the field" because
@@ -133,8 +241,29 @@ fn expose_value_commitment<E, CS>(
     Ok(value_bits)
 }
 
-impl<'a, E: JubjubEngine> Circuit<E> for Spend<'a, E> {
-    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError>
+impl<'a, E: JubjubEngine> Spend<'a, E> {
+    /// A `Spend` instance without a witness, for a tree of
+    /// depth `tree_depth`, as used for parameter generation.
+    pub fn blank(params: &'a E::Params, tree_depth: usize) -> Self {
+        Spend {
+            params: params,
+            value_commitment: None,
+            proof_generation_key: None,
+            payment_address: None,
+            commitment_randomness: None,
+            ar: None,
+            auth_path: vec![None; tree_depth],
+            anchor: None
+        }
+    }
+
+    /// Synthesizes the spend, returning the bits of the
+    /// value of the note in little-endian order.
+    fn synthesize_spend<CS: ConstraintSystem<E>>(
+        self,
+        cs: &mut CS,
+        asset: Option<&AssetGenerator<E>>
+    ) -> Result<Vec<boolean::Boolean>, SynthesisError>
     {
         // Prover witnesses ak (ensures that it's on the curve)
         let ak = ecc::EdwardsPoint::witness(
@@ -266,17 +395,24 @@ impl<'a, E: JubjubEngine> Circuit<E> for Spend<'a, E> {
         )?;
 
         // Compute note contents:
-        // value (in big endian) followed by g_d and pk_d
+        // value (in big endian) followed by g_d and pk_d,
+        // preceded by the asset generator for asset notes
         let mut note_contents = vec![];
 
+        if let Some(asset) = asset {
+            note_contents.extend(asset.repr.iter().cloned());
+        }
+
         // Handle the value; we'll need it later for the
         // dummy input check.
         let mut value_num = num::Num::zero();
+        let spent_value_bits;
         {
             // Get the value in little-endian bit order
             let value_bits = expose_value_commitment(
                 cs.namespace(|| "value commitment"),
                 self.value_commitment,
+                asset,
                 self.params
             )?;
 
@@ -293,7 +429,8 @@ impl<'a, E: JubjubEngine> Circuit<E> for Spend<'a, E> {
             }
 
             // Place the value in the note
-            note_contents.extend(value_bits);
+            note_contents.extend(value_bits.iter().cloned());
+            spent_value_bits = value_bits;
         }
 
         // Place g_d in the note
@@ -308,15 +445,21 @@ impl<'a, E: JubjubEngine> Circuit<E> for Spend<'a, E> {
 
         assert_eq!(
             note_contents.len(),
+            asset.map(|_| 256).unwrap_or(0) + // asset generator
             64 + // value
             256 + // g_d
             256 // p_d
         );
 
-        // Compute the hash of the note contents
+        // Compute the hash of the note contents, asset notes
+        // are committed to in their own domain
+        let personalization = match asset {
+            Some(_) => pedersen_hash::Personalization::AssetNoteCommitment,
+            None => pedersen_hash::Personalization::NoteCommitment
+        };
         let mut cm = pedersen_hash::pedersen_hash(
             cs.namespace(|| "note content hash"),
-            pedersen_hash::Personalization::NoteCommitment,
+            personalization,
             &note_contents,
             self.params
         )?;
@@ -461,24 +604,58 @@ impl<'a, E: JubjubEngine> Circuit<E> for Spend<'a, E> {
             constants::PRF_NF_PERSONALIZATION
         )?;
 
-        multipack::pack_into_inputs(cs.namespace(|| "pack nullifier"), &nf)
+        multipack::pack_into_inputs(cs.namespace(|| "pack nullifier"), &nf)?;
+
+        Ok(spent_value_bits)
     }
 }
 
-impl<'a, E: JubjubEngine> Circuit<E> for Output<'a, E> {
+impl<'a, E: JubjubEngine> Circuit<E> for Spend<'a, E> {
     fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError>
+    {
+        self.synthesize_spend(cs, None).map(|_| ())
+    }
+}
+
+impl<'a, E: JubjubEngine> Output<'a, E> {
+    /// An `Output` instance without a witness, as used
+    /// for parameter generation.
+    pub fn blank(params: &'a E::Params) -> Self {
+        Output {
+            params: params,
+            value_commitment: None,
+            payment_address: None,
+            commitment_randomness: None,
+            esk: None
+        }
+    }
+
+    /// Synthesizes the output, returning the bits of the
+    /// value of the note in little-endian order.
+    fn synthesize_output<CS: ConstraintSystem<E>>(
+        self,
+        cs: &mut CS,
+        asset: Option<&AssetGenerator<E>>
+    ) -> Result<Vec<boolean::Boolean>, SynthesisError>
     {
         // Let's start to construct our note, which contains
-        // value (big endian)
+        // value (big endian), preceded by the asset generator
+        // for asset notes
         let mut note_contents = vec![];
 
+        if let Some(asset) = asset {
+            note_contents.extend(asset.repr.iter().cloned());
+        }
+
         // Expose the value commitment and place the value
         // in the note.
-        note_contents.extend(expose_value_commitment(
+        let value_bits = expose_value_commitment(
             cs.namespace(|| "value commitment"),
             self.value_commitment,
+            asset,
             self.params
-        )?);
+        )?;
+        note_contents.extend(value_bits.iter().cloned());
 
         // Let's deal with g_d
         {
@@ -556,15 +733,21 @@ impl<'a, E: JubjubEngine> Circuit<E> for Output<'a, E> {
 
         assert_eq!(
             note_contents.len(),
+            asset.map(|_| 256).unwrap_or(0) + // asset generator
             64 + // value
             256 + // g_d
             256 // pk_d
         );
 
-        // Compute the hash of the note contents
+        // Compute the hash of the note contents, asset notes
+        // are committed to in their own domain
+        let personalization = match asset {
+            Some(_) => pedersen_hash::Personalization::AssetNoteCommitment,
+            None => pedersen_hash::Personalization::NoteCommitment
+        };
         let mut cm = pedersen_hash::pedersen_hash(
             cs.namespace(|| "note content hash"),
-            pedersen_hash::Personalization::NoteCommitment,
+            personalization,
             &note_contents,
             self.params
         )?;
@@ -598,7 +781,161 @@ impl<'a, E: JubjubEngine> Circuit<E> for Output<'a, E> {
         // prime-order elements.
         cm.get_x().inputize(cs.namespace(|| "commitment"))?;
 
-        Ok(())
+        Ok(value_bits)
+    }
+}
+
+impl<'a, E: JubjubEngine> Circuit<E> for Output<'a, E> {
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError>
+    {
+        self.synthesize_output(cs, None).map(|_| ())
+    }
+}
+
+impl<'a, E: JubjubEngine> AssetSpend<'a, E> {
+    pub fn blank(params: &'a E::Params, tree_depth: usize) -> Self {
+        AssetSpend {
+            spend: Spend::blank(params, tree_depth),
+            asset_type: None
+        }
+    }
+}
+
+impl<'a, E: JubjubEngine> AssetOutput<'a, E> {
+    pub fn blank(params: &'a E::Params) -> Self {
+        AssetOutput {
+            output: Output::blank(params),
+            asset_type: None
+        }
+    }
+}
+
+impl<'a, E: JubjubEngine> Circuit<E> for AssetSpend<'a, E> {
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError>
+    {
+        let asset = derive_asset_generator(
+            cs.namespace(|| "asset generator"),
+            self.asset_type,
+            self.spend.params
+        )?;
+
+        self.spend.synthesize_spend(cs, Some(&asset)).map(|_| ())
+    }
+}
+
+impl<'a, E: JubjubEngine> Circuit<E> for AssetOutput<'a, E> {
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError>
+    {
+        let asset = derive_asset_generator(
+            cs.namespace(|| "asset generator"),
+            self.asset_type,
+            self.output.params
+        )?;
+
+        self.output.synthesize_output(cs, Some(&asset)).map(|_| ())
+    }
+}
+
+/// A transfer of any number of notes to any number of
+/// new notes, in a single circuit. The values of the
+/// spent notes and `vpub_old` must balance the values of
+/// the outputs and `vpub_new`.
+pub struct Transfer<'a, E: JubjubEngine> {
+    pub params: &'a E::Params,
+
+    /// The value entering the transfer from the
+    /// transparent value pool
+    pub vpub_old: Option<u64>,
+
+    /// The value leaving the transfer into the
+    /// transparent value pool
+    pub vpub_new: Option<u64>,
+
+    pub spends: Vec<Spend<'a, E>>,
+    pub outputs: Vec<Output<'a, E>>
+}
+
+impl<'a, E: JubjubEngine> Transfer<'a, E> {
+    /// A `Transfer` instance without a witness, with
+    /// `num_spends` spends from a tree of depth `tree_depth`
+    /// and `num_outputs` outputs.
+    pub fn blank(
+        params: &'a E::Params,
+        num_spends: usize,
+        num_outputs: usize,
+        tree_depth: usize
+    ) -> Self
+    {
+        Transfer {
+            params: params,
+            vpub_old: None,
+            vpub_new: None,
+            spends: (0..num_spends).map(|_| Spend::blank(params, tree_depth)).collect(),
+            outputs: (0..num_outputs).map(|_| Output::blank(params)).collect()
+        }
+    }
+}
+
+/// Adds the value given by its little-endian bits
+/// to `num`.
+fn add_value<E: JubjubEngine, CS: ConstraintSystem<E>>(
+    mut num: num::Num<E>,
+    value_bits: &[boolean::Boolean]
+) -> num::Num<E>
+{
+    let mut coeff = E::Fr::one();
+    for bit in value_bits {
+        num = num.add_bool_with_coeff(CS::one(), bit, coeff);
+        coeff.double();
+    }
+
+    num
+}
+
+impl<'a, E: JubjubEngine> Circuit<E> for Transfer<'a, E> {
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError>
+    {
+        let vpub_old = boolean::u64_into_boolean_vec_le(
+            cs.namespace(|| "vpub_old"),
+            self.vpub_old
+        )?;
+
+        let vpub_new = boolean::u64_into_boolean_vec_le(
+            cs.namespace(|| "vpub_new"),
+            self.vpub_new
+        )?;
+
+        // The left hand side of the balance equation,
+        // vpub_old + sum of the spent values
+        let mut lhs = add_value::<E, CS>(num::Num::zero(), &vpub_old);
+
+        // The right hand side of the balance equation,
+        // vpub_new + sum of the output values
+        let mut rhs = add_value::<E, CS>(num::Num::zero(), &vpub_new);
+
+        for (i, spend) in self.spends.into_iter().enumerate() {
+            let value_bits = spend.synthesize_spend(&mut cs.namespace(|| format!("spend {}", i)), None)?;
+            lhs = add_value::<E, CS>(lhs, &value_bits);
+        }
+
+        for (i, output) in self.outputs.into_iter().enumerate() {
+            let value_bits = output.synthesize_output(&mut cs.namespace(|| format!("output {}", i)), None)?;
+            rhs = add_value::<E, CS>(rhs, &value_bits);
+        }
+
+        // Each value is at most 64 bits, so the sums can't
+        // overflow the field for any sensible number of notes.
+        cs.enforce(
+            || "balance equation",
+            |lc| lc + &lhs.lc(E::Fr::one()),
+            |lc| lc + CS::one(),
+            |lc| lc + &rhs.lc(E::Fr::one())
+        );
+
+        let mut public_values = vpub_old;
+        public_values.extend(vpub_new);
+
+        multipack::pack_into_inputs(cs.namespace(|| "pack public values"), &public_values)
     }
 }
 
@@ -817,3 +1154,271 @@ fn test_output_circuit_with_bls12_381() {
         }
     }
 }
+
+#[test]
+fn test_transfer_circuit_shapes() {
+    use bellman::pairing::ff::{Field, BitIterator};
+    use bellman::pairing::bls12_381::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use ::circuit::test::*;
+    use jubjub::{JubjubBls12, fs, edwards};
+
+    let params = &JubjubBls12::new();
+    let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+    let tree_depth = 4;
+
+    let merkle_hash = |depth: usize, lhs: &Fr, rhs: &Fr| -> Fr {
+        let mut lhs: Vec<bool> = BitIterator::new(lhs.into_repr()).collect();
+        let mut rhs: Vec<bool> = BitIterator::new(rhs.into_repr()).collect();
+
+        lhs.reverse();
+        rhs.reverse();
+
+        ::pedersen_hash::pedersen_hash::<Bls12, _>(
+            ::pedersen_hash::Personalization::MerkleTree(depth),
+            lhs.into_iter()
+               .take(Fr::NUM_BITS as usize)
+               .chain(rhs.into_iter().take(Fr::NUM_BITS as usize)),
+            params
+        ).into_xy().0
+    };
+
+    for &(num_spends, num_outputs) in &[(1, 1), (1, 3), (4, 1), (2, 2)] {
+        for &balanced in &[true, false] {
+            let spend_values: Vec<u64> = (0..num_spends).map(|_| rng.gen::<u32>() as u64).collect();
+            let output_values: Vec<u64> = (0..num_outputs).map(|_| rng.gen::<u32>() as u64).collect();
+
+            let nsk: fs::Fs = rng.gen();
+            let ak = edwards::Point::rand(rng, params).mul_by_cofactor(params);
+
+            let proof_generation_key = ::primitives::ProofGenerationKey {
+                ak: ak.clone(),
+                nsk: nsk.clone()
+            };
+
+            let viewing_key = proof_generation_key.into_viewing_key(params);
+
+            let dk = ::primitives::keys::DiversifierKey(rng.gen());
+            let (_, payment_address) = dk.find_address(&viewing_key, ::primitives::keys::DiversifierIndex::new(), params).unwrap();
+
+            // The notes are placed at the leftmost positions
+            // of a tree filled with random leaves
+            let mut spends = vec![];
+            let mut layer = vec![];
+            for &value in &spend_values {
+                let commitment_randomness: fs::Fs = rng.gen();
+                let note = payment_address.create_note(value, commitment_randomness, params).unwrap();
+                layer.push(note.cm(params));
+
+                spends.push(Spend {
+                    params: params,
+                    value_commitment: Some(ValueCommitment {
+                        value: value,
+                        randomness: rng.gen()
+                    }),
+                    proof_generation_key: Some(proof_generation_key.clone()),
+                    payment_address: Some(payment_address.clone()),
+                    commitment_randomness: Some(commitment_randomness),
+                    ar: Some(rng.gen()),
+                    auth_path: vec![],
+                    anchor: None
+                });
+            }
+
+            while layer.len() < (1 << tree_depth) {
+                layer.push(rng.gen());
+            }
+
+            for depth in 0..tree_depth {
+                for (position, spend) in spends.iter_mut().enumerate() {
+                    let position = position >> depth;
+                    spend.auth_path.push(Some((layer[position ^ 1], position & 1 == 1)));
+                }
+
+                layer = layer.chunks(2).map(|pair| merkle_hash(depth, &pair[0], &pair[1])).collect();
+            }
+
+            for spend in spends.iter_mut() {
+                spend.anchor = Some(layer[0]);
+            }
+
+            let total_in = spend_values.iter().fold(0, |acc, v| acc + v);
+            let total_out = output_values.iter().fold(0, |acc, v| acc + v);
+            let (vpub_old, vpub_new) = if total_in >= total_out {
+                (0, total_in - total_out)
+            } else {
+                (total_out - total_in, 0)
+            };
+            let vpub_new = if balanced { vpub_new } else { vpub_new + 1 };
+
+            let instance = Transfer {
+                params: params,
+                vpub_old: Some(vpub_old),
+                vpub_new: Some(vpub_new),
+                spends: spends,
+                outputs: output_values.iter().map(|&value| Output {
+                    params: params,
+                    value_commitment: Some(ValueCommitment {
+                        value: value,
+                        randomness: rng.gen()
+                    }),
+                    payment_address: Some(payment_address.clone()),
+                    commitment_randomness: Some(rng.gen()),
+                    esk: Some(rng.gen())
+                }).collect()
+            };
+
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+            instance.synthesize(&mut cs).unwrap();
+
+            assert_eq!(cs.is_satisfied(), balanced);
+            if !balanced {
+                assert_eq!(cs.which_is_unsatisfied(), Some("balance equation"));
+            }
+
+            // Each spend has 7 inputs, each output 5, and the
+            // public values are packed in a single input
+            assert_eq!(cs.num_inputs(), 1 + 7 * num_spends + 5 * num_outputs + 1);
+            assert_eq!(cs.get_input(5, "spend 0/anchor/input variable"), layer[0]);
+        }
+    }
+}
+
+#[test]
+fn test_asset_circuits_with_bls12_381() {
+    use bellman::pairing::ff::{Field, BitIterator};
+    use bellman::pairing::bls12_381::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use ::circuit::test::*;
+    use jubjub::{JubjubBls12, fs, edwards};
+    use primitives::asset::{AssetNote, AssetType};
+
+    let params = &JubjubBls12::new();
+    let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+    let tree_depth = 4;
+
+    for name in &[&b"token a"[..], &b"token b"[..]] {
+        let asset_type = AssetType::new::<Bls12>(name, params).unwrap();
+
+        let value_commitment = ValueCommitment::<Bls12> {
+            value: rng.gen(),
+            randomness: rng.gen()
+        };
+        let expected_value_cm = asset_type.value_commitment::<Bls12>(
+            value_commitment.value,
+            value_commitment.randomness
+        ).cm(params).into_xy();
+
+        let nsk: fs::Fs = rng.gen();
+        let ak = edwards::Point::rand(rng, params).mul_by_cofactor(params);
+
+        let proof_generation_key = ::primitives::ProofGenerationKey {
+            ak: ak.clone(),
+            nsk: nsk.clone()
+        };
+
+        let viewing_key = proof_generation_key.into_viewing_key(params);
+
+        let dk = ::primitives::keys::DiversifierKey(rng.gen());
+        let (_, payment_address) = dk.find_address(&viewing_key, ::primitives::keys::DiversifierIndex::new(), params).unwrap();
+
+        let commitment_randomness: fs::Fs = rng.gen();
+        let note = AssetNote {
+            asset_type: asset_type,
+            value: value_commitment.value,
+            g_d: payment_address.g_d(params).unwrap(),
+            pk_d: payment_address.pk_d.clone(),
+            r: commitment_randomness
+        };
+
+        // The output creates the note
+        {
+            let esk: fs::Fs = rng.gen();
+
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+
+            let instance = AssetOutput {
+                output: Output {
+                    params: params,
+                    value_commitment: Some(value_commitment.clone()),
+                    payment_address: Some(payment_address.clone()),
+                    commitment_randomness: Some(commitment_randomness),
+                    esk: Some(esk)
+                },
+                asset_type: Some(asset_type)
+            };
+
+            instance.synthesize(&mut cs).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(cs.num_constraints(), 31750);
+            assert_eq!(cs.num_inputs(), 6);
+            assert_eq!(cs.get_input(1, "value commitment/commitment point/x/input variable"), expected_value_cm.0);
+            assert_eq!(cs.get_input(2, "value commitment/commitment point/y/input variable"), expected_value_cm.1);
+            assert_eq!(cs.get_input(5, "commitment/input variable"), note.cm(params));
+
+            // The witnessed identifier must hash to the generator
+            let bit = "asset generator/identifier bit 0/boolean";
+            let flipped = if cs.get(bit) == Fr::zero() { Fr::one() } else { Fr::zero() };
+            cs.set(bit, flipped);
+            assert!(!cs.is_satisfied());
+        }
+
+        // The spend of the note at position 0 of a random tree
+        {
+            let mut cur = note.cm(params);
+            let mut auth_path = vec![];
+            for i in 0..tree_depth {
+                let sibling: Fr = rng.gen();
+                auth_path.push(Some((sibling, false)));
+
+                let mut lhs: Vec<bool> = BitIterator::new(cur.into_repr()).collect();
+                let mut rhs: Vec<bool> = BitIterator::new(sibling.into_repr()).collect();
+
+                lhs.reverse();
+                rhs.reverse();
+
+                cur = ::pedersen_hash::pedersen_hash::<Bls12, _>(
+                    ::pedersen_hash::Personalization::MerkleTree(i),
+                    lhs.into_iter()
+                       .take(Fr::NUM_BITS as usize)
+                       .chain(rhs.into_iter().take(Fr::NUM_BITS as usize)),
+                    params
+                ).into_xy().0;
+            }
+
+            let expected_nf = note.nf(&viewing_key, 0, params);
+            let expected_nf = multipack::bytes_to_bits_le(&expected_nf);
+            let expected_nf = multipack::compute_multipacking::<Bls12>(&expected_nf);
+
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+
+            let instance = AssetSpend {
+                spend: Spend {
+                    params: params,
+                    value_commitment: Some(value_commitment.clone()),
+                    proof_generation_key: Some(proof_generation_key.clone()),
+                    payment_address: Some(payment_address.clone()),
+                    commitment_randomness: Some(commitment_randomness),
+                    ar: Some(rng.gen()),
+                    auth_path: auth_path,
+                    anchor: Some(cur)
+                },
+                asset_type: Some(asset_type)
+            };
+
+            instance.synthesize(&mut cs).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(cs.num_constraints(), 83923);
+            assert_eq!(cs.num_inputs(), 8);
+            assert_eq!(cs.get_input(3, "value commitment/commitment point/x/input variable"), expected_value_cm.0);
+            assert_eq!(cs.get_input(4, "value commitment/commitment point/y/input variable"), expected_value_cm.1);
+            assert_eq!(cs.get_input(5, "anchor/input variable"), cur);
+            assert_eq!(cs.get_input(6, "pack nullifier/input 0"), expected_nf[0]);
+            assert_eq!(cs.get_input(7, "pack nullifier/input 1"), expected_nf[1]);
+        }
+    }
+}
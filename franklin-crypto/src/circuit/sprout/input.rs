@@ -25,8 +25,8 @@ impl InputNote {
         r: Option<CommitmentRandomness>,
         value: &NoteValue,
         h_sig: &[Boolean],
-        nonce: bool,
-        auth_path: [Option<([u8; 32], bool)>; TREE_DEPTH],
+        index: usize,
+        auth_path: Vec<Option<([u8; 32], bool)>>,
         rt: &[Boolean]
     ) -> Result<InputNote, SynthesisError>
         where E: Engine, CS: ConstraintSystem<E>
@@ -61,7 +61,7 @@ impl InputNote {
             cs.namespace(|| "mac computation"),
             &a_sk,
             h_sig,
-            nonce
+            index
         )?;
 
         let cm = note_comm(
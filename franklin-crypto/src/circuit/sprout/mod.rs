@@ -17,6 +17,10 @@ use self::output::*;
 
 pub const TREE_DEPTH: usize = 29;
 
+/// The largest number of inputs, and of outputs, of a
+/// `JoinSplit`, limited by the PRF domain separation.
+pub const MAX_NOTES: usize = 4;
+
 pub struct SpendingKey(pub [u8; 32]);
 pub struct PayingKey(pub [u8; 32]);
 pub struct UniqueRandomness(pub [u8; 32]);
@@ -37,7 +41,7 @@ pub struct JSInput {
     pub a_sk: Option<SpendingKey>,
     pub rho: Option<UniqueRandomness>,
     pub r: Option<CommitmentRandomness>,
-    pub auth_path: [Option<([u8; 32], bool)>; TREE_DEPTH]
+    pub auth_path: Vec<Option<([u8; 32], bool)>>
 }
 
 pub struct JSOutput {
@@ -46,14 +50,57 @@ pub struct JSOutput {
     pub r: Option<CommitmentRandomness>
 }
 
+impl JoinSplit {
+    /// A `JoinSplit` instance without a witness, with
+    /// `num_inputs` inputs from a tree of depth `tree_depth`
+    /// and `num_outputs` outputs.
+    pub fn blank(num_inputs: usize, num_outputs: usize, tree_depth: usize) -> Self {
+        JoinSplit {
+            vpub_old: None,
+            vpub_new: None,
+            h_sig: None,
+            phi: None,
+            inputs: (0..num_inputs).map(|_| JSInput::blank(tree_depth)).collect(),
+            outputs: (0..num_outputs).map(|_| JSOutput::blank()).collect(),
+            rt: None
+        }
+    }
+}
+
+impl JSInput {
+    pub fn blank(tree_depth: usize) -> Self {
+        JSInput {
+            value: None,
+            a_sk: None,
+            rho: None,
+            r: None,
+            auth_path: vec![None; tree_depth]
+        }
+    }
+}
+
+impl JSOutput {
+    pub fn blank() -> Self {
+        JSOutput {
+            value: None,
+            a_pk: None,
+            r: None
+        }
+    }
+}
+
 impl<E: Engine> Circuit<E> for JoinSplit {
     fn synthesize<CS: ConstraintSystem<E>>(
         self,
         cs: &mut CS
     ) -> Result<(), SynthesisError>
     {
-        assert_eq!(self.inputs.len(), 2);
-        assert_eq!(self.outputs.len(), 2);
+        assert!(self.inputs.len() >= 1 && self.inputs.len() <= MAX_NOTES);
+        assert!(self.outputs.len() >= 1 && self.outputs.len() <= MAX_NOTES);
+
+        // All of the inputs are checked against the same root
+        let tree_depth = self.inputs[0].auth_path.len();
+        assert!(self.inputs.iter().all(|input| input.auth_path.len() == tree_depth));
 
         // vpub_old is the value entering the
         // JoinSplit from the "outside" value
@@ -72,11 +119,11 @@ impl<E: Engine> Circuit<E> for JoinSplit {
         )?;
 
         // The left hand side of the balance equation
-        // vpub_old + inputs[0].value + inputs[1].value
+        // vpub_old + inputs[0].value + inputs[1].value + ...
         let mut lhs = vpub_old.lc();
 
         // The right hand side of the balance equation
-        // vpub_old + inputs[0].value + inputs[1].value
+        // vpub_new + outputs[0].value + outputs[1].value + ...
         let mut rhs = vpub_new.lc();
 
         // Witness rt (merkle tree root)
@@ -115,15 +162,8 @@ impl<E: Engine> Circuit<E> for JoinSplit {
                 input.value
             )?;
 
-            // Compute the nonce (for PRF inputs) which is false
-            // for the first input, and true for the second input.
-            let nonce = match i {
-                0 => false,
-                1 => true,
-                _ => unreachable!()
-            };
-
-            // Perform input note computations
+            // Perform input note computations, with the index
+            // of the input separating the PRF domains
             input_notes.push(InputNote::compute(
                 cs.namespace(|| "note"),
                 input.a_sk,
@@ -131,7 +171,7 @@ impl<E: Engine> Circuit<E> for JoinSplit {
                 input.r,
                 &value,
                 &h_sig,
-                nonce,
+                i,
                 input.auth_path,
                 &rt
             )?);
@@ -174,15 +214,8 @@ impl<E: Engine> Circuit<E> for JoinSplit {
                 output.value
             )?;
 
-            // Compute the nonce (for PRF inputs) which is false
-            // for the first output, and true for the second output.
-            let nonce = match i {
-                0 => false,
-                1 => true,
-                _ => unreachable!()
-            };
-
-            // Perform output note computations
+            // Perform output note computations, with the index
+            // of the output separating the PRF domains
             output_notes.push(OutputNote::compute(
                 cs.namespace(|| "note"),
                 output.a_pk,
@@ -190,7 +223,7 @@ impl<E: Engine> Circuit<E> for JoinSplit {
                 output.r,
                 &phi,
                 &h_sig,
-                nonce
+                i
             )?);
 
             // Add the note value to the right hand side of
@@ -383,7 +416,7 @@ fn test_sprout_constraints() {
         for _ in 0..2 {
             test_vector.read_u8().unwrap();
 
-            let mut auth_path = [None; TREE_DEPTH];
+            let mut auth_path = vec![None; TREE_DEPTH];
             for i in (0..TREE_DEPTH).rev() {
                 test_vector.read_u8().unwrap();
 
@@ -487,3 +520,140 @@ fn test_sprout_constraints() {
         assert!(cs.verify(&expected_inputs));
     }
 }
+
+#[test]
+fn test_sprout_shapes() {
+    use bellman::pairing::bls12_381::{Bls12};
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use crypto::sha2::Sha256;
+    use crypto::digest::Digest;
+    use byteorder::WriteBytesExt;
+    use ::circuit::test::*;
+    use ::circuit::multipack;
+    use ::circuit::sha256::sha256_block_no_padding;
+
+    // SHA-256 compression of a single block with the initial state,
+    // evaluated by the gadget on constant bits
+    fn compress(block: &[u8]) -> [u8; 32] {
+        let bits: Vec<Boolean> = multipack::bytes_to_bits(block).into_iter().map(Boolean::constant).collect();
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        let hash = sha256_block_no_padding(&mut cs, &bits).unwrap();
+
+        let mut out = [0u8; 32];
+        for (i, bit) in hash.iter().enumerate() {
+            if bit.get_value().unwrap() {
+                out[i / 8] |= 1 << (7 - i % 8);
+            }
+        }
+
+        out
+    }
+
+    fn a_pk(a_sk: &[u8; 32]) -> [u8; 32] {
+        let mut block = [0u8; 64];
+        block[0..32].copy_from_slice(a_sk);
+        block[0] = 0xc0 | (a_sk[0] & 0x0f);
+
+        compress(&block)
+    }
+
+    fn note_comm(a_pk: &[u8; 32], value: u64, rho: &[u8; 32], r: &[u8; 32]) -> [u8; 32] {
+        let mut h = Sha256::new();
+        h.input(&[0xb0]);
+        h.input(a_pk);
+        let mut value_bytes = vec![];
+        value_bytes.write_u64::<::byteorder::LittleEndian>(value).unwrap();
+        h.input(&value_bytes);
+        h.input(rho);
+        h.input(r);
+
+        let mut out = [0u8; 32];
+        h.result(&mut out);
+
+        out
+    }
+
+    let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+    let tree_depth = 4;
+
+    for &(num_inputs, num_outputs) in &[(1, 1), (1, 3), (4, 1), (2, 2)] {
+        for &balanced in &[true, false] {
+            let input_values: Vec<u64> = (0..num_inputs).map(|_| rng.gen::<u32>() as u64).collect();
+            let output_values: Vec<u64> = (0..num_outputs).map(|_| rng.gen::<u32>() as u64).collect();
+            let vpub_old = rng.gen::<u32>() as u64;
+
+            // The notes are placed at the leftmost positions
+            // of a tree filled with random leaves
+            let mut cms = vec![];
+            let mut inputs = vec![];
+            for &value in &input_values {
+                let a_sk: [u8; 32] = rng.gen();
+                let rho: [u8; 32] = rng.gen();
+                let r: [u8; 32] = rng.gen();
+
+                cms.push(note_comm(&a_pk(&a_sk), value, &rho, &r));
+
+                inputs.push(JSInput {
+                    value: Some(value),
+                    a_sk: Some(SpendingKey(a_sk)),
+                    rho: Some(UniqueRandomness(rho)),
+                    r: Some(CommitmentRandomness(r)),
+                    auth_path: vec![]
+                });
+            }
+
+            let mut layer = cms;
+            while layer.len() < (1 << tree_depth) {
+                layer.push(rng.gen());
+            }
+
+            for depth in 0..tree_depth {
+                for (position, input) in inputs.iter_mut().enumerate() {
+                    let position = position >> depth;
+                    input.auth_path.push(Some((layer[position ^ 1], position & 1 == 1)));
+                }
+
+                layer = layer.chunks(2).map(|pair| {
+                    let mut block = pair[0].to_vec();
+                    block.extend_from_slice(&pair[1]);
+                    compress(&block)
+                }).collect();
+            }
+            let rt = layer[0];
+
+            let total_in = input_values.iter().fold(vpub_old, |acc, v| acc + v);
+            let total_out = output_values.iter().fold(0, |acc, v| acc + v);
+            let vpub_new = if total_in >= total_out { total_in - total_out } else { 0 };
+            let vpub_old = if total_in >= total_out { vpub_old } else { vpub_old + total_out - total_in };
+            let vpub_new = if balanced { vpub_new } else { vpub_new + 1 };
+
+            let js = JoinSplit {
+                vpub_old: Some(vpub_old),
+                vpub_new: Some(vpub_new),
+                h_sig: Some(rng.gen()),
+                phi: Some(rng.gen()),
+                inputs: inputs,
+                outputs: output_values.iter().map(|&value| JSOutput {
+                    value: Some(value),
+                    a_pk: Some(PayingKey(rng.gen())),
+                    r: Some(CommitmentRandomness(rng.gen()))
+                }).collect(),
+                rt: Some(rt)
+            };
+
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+            js.synthesize(&mut cs).unwrap();
+
+            assert_eq!(cs.is_satisfied(), balanced, "{:?} {:?}", (num_inputs, num_outputs), cs.which_is_unsatisfied());
+            if !balanced {
+                assert_eq!(cs.which_is_unsatisfied(), Some("balance equation"));
+            }
+
+            // rt, h_sig, (nf, mac) per input, cm per output and
+            // the public values, packed in 253 bit chunks
+            let public_bits = 512 + 512 * num_inputs + 256 * num_outputs + 128;
+            assert_eq!(cs.num_inputs(), 1 + (public_bits + 252) / 253);
+        }
+    }
+}
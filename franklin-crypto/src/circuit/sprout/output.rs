@@ -18,7 +18,7 @@ impl OutputNote {
         r: Option<CommitmentRandomness>,
         phi: &[Boolean],
         h_sig: &[Boolean],
-        nonce: bool
+        index: usize
     ) -> Result<Self, SynthesisError>
         where E: Engine, CS: ConstraintSystem<E>,
     {
@@ -26,7 +26,7 @@ impl OutputNote {
             cs.namespace(|| "rho"),
             phi,
             h_sig,
-            nonce
+            index
         )?;
 
         let a_pk = witness_u256(
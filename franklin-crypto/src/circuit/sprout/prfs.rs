@@ -56,24 +56,37 @@ pub fn prf_nf<E, CS>(
     prf(cs, true, true, true, false, a_sk, rho)
 }
 
+/// The index of the note is encoded in the second and
+/// fourth bits, so the first two notes use the prefixes
+/// of the Zcash protocol specification.
+fn index_bits(index: usize) -> (bool, bool) {
+    assert!(index < 4);
+
+    (index & 1 == 1, index & 2 == 2)
+}
+
 pub fn prf_pk<E, CS>(
     cs: CS,
     a_sk: &[Boolean],
     h_sig: &[Boolean],
-    nonce: bool
+    index: usize
 ) -> Result<Vec<Boolean>, SynthesisError>
     where E: Engine, CS: ConstraintSystem<E>
 {
-    prf(cs, false, nonce, false, false, a_sk, h_sig)
+    let (b, d) = index_bits(index);
+
+    prf(cs, false, b, false, d, a_sk, h_sig)
 }
 
 pub fn prf_rho<E, CS>(
     cs: CS,
     phi: &[Boolean],
     h_sig: &[Boolean],
-    nonce: bool
+    index: usize
 ) -> Result<Vec<Boolean>, SynthesisError>
     where E: Engine, CS: ConstraintSystem<E>
 {
-    prf(cs, false, nonce, true, false, phi, h_sig)
+    let (b, d) = index_bits(index);
+
+    prf(cs, false, b, true, d, phi, h_sig)
 }
@@ -0,0 +1,275 @@
+use bellman::pairing::Engine;
+
+use bellman::pairing::ff::{
+    Field,
+    PrimeField,
+    PrimeFieldRepr
+};
+
+use bellman::{
+    SynthesisError,
+    ConstraintSystem
+};
+
+use super::boolean::{
+    Boolean,
+    enforce_equal_bits,
+    field_into_boolean_vec_le,
+    u64_into_boolean_vec_le
+};
+
+use super::ecc::{
+    EdwardsPoint,
+    fixed_base_multiplication
+};
+
+use super::blake2s::blake2s;
+use super::hash_to_curve::hash_to_curve;
+
+use ::jubjub::{
+    JubjubEngine,
+    FixedGenerators
+};
+
+use ::vrf::{
+    Proof,
+    CHALLENGE_BITS
+};
+
+use ::constants::{
+    VRF_HASH_TO_CURVE_PERSONALIZATION,
+    VRF_CHALLENGE_PERSONALIZATION,
+    VRF_OUTPUT_PERSONALIZATION
+};
+
+/// VRF proof witnessed in the circuit.
+pub struct VrfProof<E: Engine> {
+    gamma: EdwardsPoint<E>,
+    c: Vec<Boolean>,
+    s: Vec<Boolean>
+}
+
+impl<E: JubjubEngine> VrfProof<E> {
+    /// Witnesses `Gamma` as a point on the curve, the challenge
+    /// as `CHALLENGE_BITS` bits and `s` as `Fs::NUM_BITS` bits.
+    pub fn witness<CS>(
+        mut cs: CS,
+        proof: Option<&Proof<E>>,
+        params: &E::Params
+    ) -> Result<Self, SynthesisError>
+        where CS: ConstraintSystem<E>
+    {
+        let gamma = EdwardsPoint::witness(
+            cs.namespace(|| "gamma"),
+            proof.map(|p| p.gamma.clone()),
+            params
+        )?;
+
+        let c_repr = proof.map(|p| p.c.into_repr());
+        let mut c = vec![];
+        for i in 0..(CHALLENGE_BITS / 64) {
+            c.extend(u64_into_boolean_vec_le(
+                cs.namespace(|| format!("c limb {}", i)),
+                c_repr.map(|c| c.as_ref()[i])
+            )?);
+        }
+
+        let s = field_into_boolean_vec_le(
+            cs.namespace(|| "s"),
+            proof.map(|p| p.s)
+        )?;
+
+        Ok(VrfProof {
+            gamma: gamma,
+            c: c,
+            s: s
+        })
+    }
+
+    pub fn get_gamma(&self) -> &EdwardsPoint<E> {
+        &self.gamma
+    }
+}
+
+/// Returns the 256 bit encoding of the point used by
+/// `edwards::Point::write`: `y` in little-endian order
+/// padded to 255 bits, followed by the sign of `x`.
+pub fn point_encoding<E, CS>(
+    cs: CS,
+    p: &EdwardsPoint<E>
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    let mut bits = p.repr(cs)?;
+    let sign = bits.pop().expect("is not empty");
+
+    while bits.len() < 255 {
+        bits.push(Boolean::constant(false));
+    }
+    bits.push(sign);
+
+    Ok(bits)
+}
+
+/// Verifies a VRF proof for the public key `pk` and the input `alpha`,
+/// given in the bit order expected by `blake2s`, and returns the bits
+/// of the VRF output, matching `vrf::verify`. The public key is expected
+/// to be checked by the caller.
+pub fn vrf_verify<E, CS>(
+    mut cs: CS,
+    pk: &EdwardsPoint<E>,
+    alpha: &[Boolean],
+    proof: &VrfProof<E>,
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>
+{
+    let pk_bits = point_encoding(cs.namespace(|| "pk encoding"), pk)?;
+
+    // H = hash_to_curve(Y || alpha)
+    let mut input = pk_bits.clone();
+    input.extend(alpha.iter().cloned());
+
+    let h = hash_to_curve(
+        cs.namespace(|| "hash to curve"),
+        VRF_HASH_TO_CURVE_PERSONALIZATION,
+        &input,
+        params
+    )?;
+
+    // U = s . G - c . Y
+    let s_g = fixed_base_multiplication(
+        cs.namespace(|| "s . G"),
+        p_g,
+        &proof.s,
+        params
+    )?;
+
+    let c_y = pk.mul(cs.namespace(|| "c . Y"), &proof.c, params)?
+                .negate(cs.namespace(|| "-c . Y"))?;
+
+    let u = s_g.add(cs.namespace(|| "U"), &c_y, params)?;
+
+    // V = s . H - c . Gamma
+    let s_h = h.mul(cs.namespace(|| "s . H"), &proof.s, params)?;
+
+    let c_gamma = proof.gamma.mul(cs.namespace(|| "c . Gamma"), &proof.c, params)?
+                             .negate(cs.namespace(|| "-c . Gamma"))?;
+
+    let v = s_h.add(cs.namespace(|| "V"), &c_gamma, params)?;
+
+    // c = BLAKE2s(Y || H || Gamma || U || V)
+    let mut challenge_input = pk_bits;
+    challenge_input.extend(point_encoding(cs.namespace(|| "H encoding"), &h)?);
+    challenge_input.extend(point_encoding(cs.namespace(|| "gamma encoding"), &proof.gamma)?);
+    challenge_input.extend(point_encoding(cs.namespace(|| "U encoding"), &u)?);
+    challenge_input.extend(point_encoding(cs.namespace(|| "V encoding"), &v)?);
+
+    let challenge = blake2s(
+        cs.namespace(|| "challenge"),
+        &challenge_input,
+        VRF_CHALLENGE_PERSONALIZATION
+    )?;
+
+    // The challenge is the first `CHALLENGE_BITS` bits of the hash
+    enforce_equal_bits(
+        cs.namespace(|| "challenge equality"),
+        &challenge[..proof.c.len()],
+        &proof.c
+    )?;
+
+    // output = BLAKE2s(cofactor . Gamma)
+    let gamma = proof.gamma.double(cs.namespace(|| "gamma first doubling"), params)?;
+    let gamma = gamma.double(cs.namespace(|| "gamma second doubling"), params)?;
+    let gamma = gamma.double(cs.namespace(|| "gamma third doubling"), params)?;
+
+    let gamma_bits = point_encoding(cs.namespace(|| "cleared gamma encoding"), &gamma)?;
+
+    blake2s(
+        cs.namespace(|| "output"),
+        &gamma_bits,
+        VRF_OUTPUT_PERSONALIZATION
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::ConstraintSystem;
+    use bellman::pairing::bn256::Bn256;
+    use bellman::pairing::ff::Field;
+    use ::circuit::test::*;
+    use ::circuit::boolean::{AllocatedBit, Boolean};
+    use ::circuit::ecc::EdwardsPoint;
+    use ::alt_babyjubjub::{AltJubjubBn256, fs::Fs};
+    use ::jubjub::FixedGenerators;
+    use ::eddsa::{PrivateKey, PublicKey};
+    use ::vrf;
+    use super::*;
+
+    fn alloc_bytes<CS: ConstraintSystem<Bn256>>(mut cs: CS, bytes: &[u8]) -> Vec<Boolean> {
+        bytes.iter().enumerate().flat_map(|(i, &byte)| {
+            (0..8).map(|b| {
+                AllocatedBit::alloc(cs.namespace(|| format!("bit {}", i * 8 + b)), Some((byte >> b) & 1 == 1)).unwrap().into()
+            }).collect::<Vec<_>>()
+        }).collect()
+    }
+
+    fn synthesize(
+        pk: &PublicKey<Bn256>,
+        alpha: &[u8],
+        proof: &vrf::Proof<Bn256>,
+        params: &AltJubjubBn256
+    ) -> (TestConstraintSystem<Bn256>, Vec<Boolean>)
+    {
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+
+        let pk = EdwardsPoint::witness(cs.namespace(|| "pk"), Some(pk.0.clone()), params).unwrap();
+        let alpha = alloc_bytes(cs.namespace(|| "alpha"), alpha);
+        let proof = VrfProof::witness(cs.namespace(|| "proof"), Some(proof), params).unwrap();
+
+        let output = vrf_verify(cs.namespace(|| "vrf"), &pk, &alpha, &proof, p_g, params).unwrap();
+
+        (cs, output)
+    }
+
+    #[test]
+    fn test_vrf_verify() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = &AltJubjubBn256::new();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+
+        for i in 0..2 {
+            let sk = if i == 0 { PrivateKey::<Bn256>(Fs::from_str("42").unwrap()) } else { PrivateKey::<Bn256>(rng.gen()) };
+            let pk = PublicKey::from_private(&sk, p_g, params);
+            let alpha: &[u8] = if i == 0 { b"lottery round 1" } else { b"" };
+
+            let (proof, expected) = vrf::prove(&sk, alpha, p_g, params);
+
+            let (cs, output) = synthesize(&pk, alpha, &proof, params);
+            assert!(cs.is_satisfied());
+
+            let output: Vec<bool> = output.iter().map(|b| b.get_value().unwrap()).collect();
+            let expected: Vec<bool> = expected.iter().flat_map(|&byte| (0..8).map(move |b| (byte >> b) & 1 == 1)).collect();
+            assert_eq!(output, expected);
+
+            // Tampered proofs are rejected
+            let mut tampered = proof.clone();
+            tampered.s.add_assign(&Fs::one());
+            let (cs, _) = synthesize(&pk, alpha, &tampered, params);
+            assert!(!cs.is_satisfied());
+
+            let mut tampered = proof.clone();
+            tampered.c.add_assign(&Fs::one());
+            let (cs, _) = synthesize(&pk, alpha, &tampered, params);
+            assert!(!cs.is_satisfied());
+
+            let (cs, _) = synthesize(&pk, b"lottery round 2", &proof, params);
+            assert!(!cs.is_satisfied());
+        }
+    }
+}
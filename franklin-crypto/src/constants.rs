@@ -19,6 +19,11 @@ pub const PRF_NF_PERSONALIZATION: &'static [u8; 8]
 pub const PEDERSEN_HASH_GENERATORS_PERSONALIZATION: &'static [u8; 8]
           = b"Zcash_PH";
 
+/// BLAKE2s Personalization for the generators of the Pedersen hash domains
+/// that share the note commitment code
+pub const PEDERSEN_HASH_DOMAINS_PERSONALIZATION: &'static [u8; 8]
+          = b"MatterPD";
+
 /// BLAKE2s Personalization for the group hash for key diversification
 pub const KEY_DIVERSIFICATION_PERSONALIZATION: &'static [u8; 8]
           = b"Zcash_gd";
@@ -35,10 +40,31 @@ pub const PROOF_GENERATION_KEY_BASE_GENERATOR_PERSONALIZATION: &'static [u8; 8]
 pub const VALUE_COMMITMENT_GENERATOR_PERSONALIZATION: &'static [u8; 8]
           = b"Zcash_cv";
 
+/// BLAKE2s Personalization for the value commitment generators of asset types
+pub const ASSET_GENERATOR_PERSONALIZATION: &'static [u8; 8]
+          = b"Matter_v";
+
+/// BLAKE2s Personalization for deriving asset identifiers from asset names
+pub const ASSET_IDENTIFIER_PERSONALIZATION: &'static [u8; 8]
+          = b"Matter_t";
+
 /// BLAKE2s Personalization for the nullifier position generator (for computing rho)
 pub const NULLIFIER_POSITION_IN_TREE_GENERATOR_PERSONALIZATION: &'static [u8; 8]
           = b"Zcash_J_";
 
 /// BLAKE2s Personalization hash of (R_x || message) in EdDSA variant with 256 bit hash
 pub const MATTER_EDDSA_BLAKE2S_PERSONALIZATION: &'static [u8; 8] 
-            = b"Matter_H";
\ No newline at end of file
+            = b"Matter_H";
+
+// VRF personalizations
+/// BLAKE2s Personalization for hashing (public key || input) to the curve in the VRF
+pub const VRF_HASH_TO_CURVE_PERSONALIZATION: &'static [u8; 8]
+          = b"MatterVh";
+
+/// BLAKE2s Personalization for the VRF challenge
+pub const VRF_CHALLENGE_PERSONALIZATION: &'static [u8; 8]
+          = b"MatterVc";
+
+/// BLAKE2s Personalization for the VRF output hash
+pub const VRF_OUTPUT_PERSONALIZATION: &'static [u8; 8]
+          = b"MatterVo";
@@ -1,10 +1,30 @@
 //! This is an implementation of EdDSA as refered in literature
 //! Generation of randomness is not specified
+//!
+//! Canonical encoding (shared with `redbabyjubjub`):
+//!
+//! * a public key is 32 bytes: the little-endian `y` coordinate with the
+//!   parity of `x` stored in the most significant bit of the last byte;
+//! * a signature is 64 bytes: the encoding of `R` as above, followed by
+//!   the little-endian `s`.
+//!
+//! Decoding through `Signature::read`, `Signature::from_serialized` and
+//! `PublicKey::from_bytes` is strict: `y` and `s` must be fully reduced,
+//! the point must be on the curve, must not have a redundant sign bit, must
+//! not be of small order and must lie in the prime order subgroup.
+//!
+//! Signatures of `sign` in this encoding are checked with
+//! `PublicKey::verify_canonical`. The deprecated
+//! `PublicKey::verify_serialized` uses another challenge, `H*(Rbar || M)`,
+//! and lenient decoding, so it does not accept signatures of `sign`.
 
 use bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
 use rand::{Rng};
 use std::io::{self, Read, Write};
 
+use hex;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+
 use jubjub::{
     FixedGenerators, 
     JubjubEngine, 
@@ -13,7 +33,7 @@ use jubjub::{
     edwards::Point,
     ToUniform};
 
-use util::{hash_to_scalar, hash_to_scalar_s, sha256_hash_to_scalar};
+use util::{self, hash_to_scalar, hash_to_scalar_s, sha256_hash_to_scalar, decode_hex, deserialize_hex};
 
 use ::constants::{MATTER_EDDSA_BLAKE2S_PERSONALIZATION};
 
@@ -34,6 +54,18 @@ fn write_scalar<E: JubjubEngine, W: Write>(s: &E::Fs, writer: W) -> io::Result<(
     s.into_repr().write_le(writer)
 }
 
+fn read_point_strict<E: JubjubEngine, R: Read>(
+    reader: R,
+    params: &E::Params
+) -> io::Result<Point<E, Unknown>> {
+    util::read_point_strict(
+        reader,
+        |bytes| Point::read(bytes, params),
+        |p, bytes| p.write(bytes),
+        |p| p.mul_by_cofactor(params) != Point::zero() && p.as_prime_order(params).is_some()
+    )
+}
+
 fn h_star<E: JubjubEngine>(a: &[u8], b: &[u8]) -> E::Fs {
     hash_to_scalar::<E>(b"Zcash_RedJubjubH", a, b)
 }
@@ -46,7 +78,15 @@ fn sha256_h_star<E: JubjubEngine>(a: &[u8], b: &[u8]) -> E::Fs {
     sha256_hash_to_scalar::<E>(&[], a, b)
 }
 
-#[derive(Copy, Clone)]
+/// Length of the canonical signature encoding `R || s`.
+pub const SIGNATURE_BYTES: usize = 64;
+
+/// Length of the canonical compressed public key encoding.
+pub const PUBLIC_KEY_BYTES: usize = 32;
+
+/// Signature in the canonical 64 byte encoding. No checks are performed
+/// until it is decoded with `Signature::from_serialized`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub struct SerializedSignature {
     rbar: [u8; 32],
     sbar: [u8; 32],
@@ -76,6 +116,98 @@ impl SerializedSignature {
         writer.write_all(&self.rbar)?;
         writer.write_all(&self.sbar)
     }
+
+    pub fn from_bytes(bytes: &[u8; SIGNATURE_BYTES]) -> Self {
+        let mut rbar = [0u8; 32];
+        let mut sbar = [0u8; 32];
+        rbar.copy_from_slice(&bytes[..32]);
+        sbar.copy_from_slice(&bytes[32..]);
+        SerializedSignature { rbar, sbar }
+    }
+
+    pub fn to_bytes(&self) -> [u8; SIGNATURE_BYTES] {
+        let mut bytes = [0u8; SIGNATURE_BYTES];
+        bytes[..32].copy_from_slice(&self.rbar);
+        bytes[32..].copy_from_slice(&self.sbar);
+        bytes
+    }
+
+    pub fn from_hex(s: &str) -> io::Result<Self> {
+        let bytes = decode_hex(s, SIGNATURE_BYTES)?;
+        Self::read(&bytes[..])
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(&self.to_bytes()[..])
+    }
+}
+
+impl Serialize for SerializedSignature {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for SerializedSignature {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_hex(deserializer, "a hex encoded 64 byte signature", SerializedSignature::from_hex)
+    }
+}
+
+/// Public key in the canonical 32 byte encoding. No checks are performed
+/// until it is decoded with `PublicKey::from_bytes`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SerializedPublicKey(pub [u8; PUBLIC_KEY_BYTES]);
+
+impl SerializedPublicKey {
+    pub fn from_hex(s: &str) -> io::Result<Self> {
+        let bytes = decode_hex(s, PUBLIC_KEY_BYTES)?;
+        let mut pk = [0u8; PUBLIC_KEY_BYTES];
+        pk.copy_from_slice(&bytes);
+        Ok(SerializedPublicKey(pk))
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(&self.0[..])
+    }
+}
+
+impl Serialize for SerializedPublicKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for SerializedPublicKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_hex(deserializer, "a hex encoded 32 byte public key", SerializedPublicKey::from_hex)
+    }
+}
+
+impl<E: JubjubEngine> Signature<E> {
+    /// Strictly decodes a signature, see the module documentation.
+    pub fn read<R: Read>(mut reader: R, params: &E::Params) -> io::Result<Self> {
+        let r = read_point_strict::<E, _>(&mut reader, params)?;
+        let s = read_scalar::<E, _>(reader)?;
+        Ok(Signature { r, s })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        self.r.write(&mut writer)?;
+        write_scalar::<E, W>(&self.s, writer)
+    }
+
+    pub fn serialize(&self) -> SerializedSignature {
+        let mut rbar = [0u8; 32];
+        let mut sbar = [0u8; 32];
+        self.r.write(&mut rbar[..]).expect("points should serialize to 32 bytes");
+        write_scalar::<E, &mut [u8]>(&self.s, &mut sbar[..]).expect("scalars should serialize to 32 bytes");
+        SerializedSignature { rbar, sbar }
+    }
+
+    pub fn from_serialized(sig: &SerializedSignature, params: &E::Params) -> io::Result<Self> {
+        Self::read(&sig.to_bytes()[..], params)
+    }
 }
 
 impl<E: JubjubEngine> PrivateKey<E> {
@@ -325,6 +457,26 @@ impl<E: JubjubEngine> PublicKey<E> {
         self.0.write(writer)
     }
 
+    /// Strictly decodes a public key, see the module documentation.
+    pub fn from_bytes(bytes: &[u8; PUBLIC_KEY_BYTES], params: &E::Params) -> io::Result<Self> {
+        let p = read_point_strict::<E, _>(&bytes[..], params)?;
+        Ok(PublicKey(p))
+    }
+
+    pub fn to_bytes(&self) -> [u8; PUBLIC_KEY_BYTES] {
+        let mut bytes = [0u8; PUBLIC_KEY_BYTES];
+        self.0.write(&mut bytes[..]).expect("points should serialize to 32 bytes");
+        bytes
+    }
+
+    pub fn from_serialized(pk: &SerializedPublicKey, params: &E::Params) -> io::Result<Self> {
+        Self::from_bytes(&pk.0, params)
+    }
+
+    pub fn serialize(&self) -> SerializedPublicKey {
+        SerializedPublicKey(self.to_bytes())
+    }
+
     pub fn verify(
         &self,
         msg: &[u8],
@@ -521,6 +673,11 @@ impl<E: JubjubEngine> PublicKey<E> {
         ).eq(&Point::zero())
     }
 
+    /// Verifies a signature with the RedJubjub challenge `H*(Rbar || M)`,
+    /// which is not the challenge of `sign` and `verify`. The encoding of
+    /// `R` and `s` is decoded leniently, as it always was, so signatures
+    /// that verified before keep verifying.
+    #[deprecated(note = "signatures of `sign` are checked with `verify_canonical`")]
     pub fn verify_serialized(
         &self,
         msg: &[u8],
@@ -549,11 +706,27 @@ impl<E: JubjubEngine> PublicKey<E> {
             params
         ).mul_by_cofactor(params).eq(&Point::zero())
     }
+
+    /// Same as `verify`, for a signature of `sign` in the canonical
+    /// encoding. Fails if the signature can not be strictly decoded.
+    pub fn verify_canonical(
+        &self,
+        msg: &[u8],
+        sig: &SerializedSignature,
+        p_g: FixedGenerators,
+        params: &E::Params,
+    ) -> bool {
+        match Signature::from_serialized(sig, params) {
+            Ok(sig) => self.verify(msg, &sig, p_g, params),
+            Err(_) => false,
+        }
+    }
 }
 
 #[cfg(test)]
 mod baby_tests {
-    use bellman::pairing::bn256::Bn256;
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use bellman::pairing::ff::SqrtField;
     use rand::thread_rng;
 
     use alt_babyjubjub::{AltJubjubBn256, fs::Fs, edwards, FixedGenerators};
@@ -625,6 +798,229 @@ mod baby_tests {
     //     }
     // }
 
+    #[test]
+    fn canonical_serialization_round_trip() {
+        use serde::de::IntoDeserializer;
+        use serde::de::value::{StrDeserializer, Error as ValueError};
+
+        let rng = &mut thread_rng();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+        let params = &AltJubjubBn256::new();
+
+        for _ in 0..100 {
+            let sk = PrivateKey::<Bn256>(rng.gen());
+            let vk = PublicKey::from_private(&sk, p_g, params);
+            let msg = b"Foo bar";
+            let sig = sk.sign(msg, rng, p_g, params);
+
+            let serialized = sig.serialize();
+            let mut sig_bytes = [0u8; SIGNATURE_BYTES];
+            sig.write(&mut sig_bytes[..]).unwrap();
+            assert_eq!(serialized.to_bytes()[..], sig_bytes[..]);
+            assert_eq!(SerializedSignature::from_bytes(&sig_bytes), serialized);
+
+            let hex = serialized.to_hex();
+            assert_eq!(hex.len(), 2 * SIGNATURE_BYTES);
+            assert_eq!(SerializedSignature::from_hex(&hex).unwrap(), serialized);
+
+            let deserializer: StrDeserializer<ValueError> = hex.as_str().into_deserializer();
+            assert_eq!(SerializedSignature::deserialize(deserializer).unwrap(), serialized);
+
+            let sig_2 = Signature::<Bn256>::from_serialized(&serialized, params).unwrap();
+            assert!(sig_2.r == sig.r);
+            assert!(sig_2.s == sig.s);
+            assert!(vk.verify(msg, &sig_2, p_g, params));
+            assert!(vk.verify_canonical(msg, &serialized, p_g, params));
+            assert!(!vk.verify_canonical(b"Spam eggs", &serialized, p_g, params));
+
+            let vk_bytes = vk.to_bytes();
+            let vk_2 = PublicKey::<Bn256>::from_bytes(&vk_bytes, params).unwrap();
+            assert!(vk_2.0 == vk.0);
+
+            let vk_hex = vk.serialize().to_hex();
+            let deserializer: StrDeserializer<ValueError> = vk_hex.as_str().into_deserializer();
+            let vk_3 = SerializedPublicKey::deserialize(deserializer).unwrap();
+            assert_eq!(vk_3.0, vk_bytes);
+            assert!(PublicKey::<Bn256>::from_serialized(&vk_3, params).unwrap().0 == vk.0);
+        }
+    }
+
+    #[test]
+    fn strict_decoding() {
+        let rng = &mut thread_rng();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+        let params = &AltJubjubBn256::new();
+
+        let sk = PrivateKey::<Bn256>(rng.gen());
+        let vk = PublicKey::from_private(&sk, p_g, params);
+        let msg = b"Foo bar";
+        let sig = sk.sign(msg, rng, p_g, params).serialize().to_bytes();
+        assert!(vk.verify_canonical(msg, &SerializedSignature::from_bytes(&sig), p_g, params));
+
+        // s + order(G) still fits into 256 bits, but is not canonical
+        {
+            let mut s_repr = <Fs as PrimeField>::Repr::default();
+            s_repr.read_le(&sig[32..]).unwrap();
+            s_repr.add_nocarry(&Fs::char());
+            let mut malleated = sig;
+            s_repr.write_le(&mut malleated[32..]).unwrap();
+            let malleated = SerializedSignature::from_bytes(&malleated);
+            assert!(Signature::<Bn256>::from_serialized(&malleated, params).is_err());
+            assert!(!vk.verify_canonical(msg, &malleated, p_g, params));
+        }
+
+        // y >= modulus
+        {
+            let mut encoding = [0xffu8; PUBLIC_KEY_BYTES];
+            encoding[31] = 0x7f;
+            assert!(PublicKey::<Bn256>::from_bytes(&encoding, params).is_err());
+        }
+
+        // point off the curve
+        {
+            let mut y = 2u64;
+            let encoding = loop {
+                let repr = <Fr as PrimeField>::Repr::from(y);
+                let mut encoding = [0u8; PUBLIC_KEY_BYTES];
+                repr.write_le(&mut encoding[..]).unwrap();
+                if edwards::Point::<Bn256, _>::read(&encoding[..], params).is_err() {
+                    break encoding;
+                }
+                y += 1;
+            };
+            assert!(PublicKey::<Bn256>::from_bytes(&encoding, params).is_err());
+
+            let mut malformed = sig;
+            malformed[..32].copy_from_slice(&encoding);
+            let malformed = SerializedSignature::from_bytes(&malformed);
+            assert!(!vk.verify_canonical(msg, &malformed, p_g, params));
+        }
+
+        // identity, and the identity with a redundant sign bit
+        {
+            let mut encoding = [0u8; PUBLIC_KEY_BYTES];
+            encoding[0] = 1;
+            assert!(PublicKey::<Bn256>::from_bytes(&encoding, params).is_err());
+            encoding[31] |= 0x80;
+            assert!(edwards::Point::<Bn256, _>::read(&encoding[..], params).is_ok());
+            assert!(PublicKey::<Bn256>::from_bytes(&encoding, params).is_err());
+        }
+
+        // small and mixed order points
+        {
+            let zero = edwards::Point::zero();
+            let p8 = loop {
+                let r = edwards::Point::<Bn256, _>::rand(rng, params).mul(Fs::char(), params);
+                if r.double(params).double(params) != zero && r.double(params).double(params).double(params) == zero {
+                    break r;
+                }
+            };
+
+            let small = PublicKey::<Bn256>(p8.clone());
+            assert!(PublicKey::<Bn256>::from_bytes(&small.to_bytes(), params).is_err());
+
+            let mixed = PublicKey::<Bn256>(vk.0.add(&p8, params));
+            assert!(PublicKey::<Bn256>::from_bytes(&mixed.to_bytes(), params).is_err());
+
+            let mut malformed = sig;
+            malformed[..32].copy_from_slice(&small.to_bytes());
+            let malformed = SerializedSignature::from_bytes(&malformed);
+            assert!(!vk.verify_canonical(msg, &malformed, p_g, params));
+        }
+
+        assert!(SerializedSignature::from_hex("00").is_err());
+        assert!(SerializedSignature::from_hex(&"zz".repeat(SIGNATURE_BYTES)).is_err());
+    }
+
+    #[test]
+    fn circomlib_vectors() {
+        // Points of circomlib's Baby Jubjub tests (test/babyjub.js in
+        // https://github.com/iden3/circomlib), moved from the `a = 168700`
+        // form to the `a = -1` form used here by x' = sqrt(-168700) * x
+        let params = &AltJubjubBn256::new();
+        let mut scale = Fr::from_str("168700").unwrap();
+        scale.negate();
+        let scale = scale.sqrt().unwrap();
+
+        let point = |x: &str, y: &str| {
+            let mut x = Fr::from_str(x).unwrap();
+            x.mul_assign(&scale);
+            edwards::Point::<Bn256, _>::from_xy(x, Fr::from_str(y).unwrap(), params).unwrap()
+        };
+
+        let p1 = point(
+            "17777552123799933955779906779655732241715742912184938656739573121738514868268",
+            "2626589144620713026669568689430873010625803728049924121243784502389097019475"
+        );
+        let p2 = point(
+            "16540640123574156134436876038791482806971768689494387082833631921987005038935",
+            "20819045374670962167435360035096875258406992893633759881276124905556507972311"
+        );
+        let p1_double = point(
+            "6890855772600357754907169075114257697580319025794532037257385534741338397365",
+            "4338620300185947561074059802482547481416142213883829469920100239455078257889"
+        );
+        let sum = point(
+            "7916061937171219682591368294088513039687205273691143098332585753343424131937",
+            "14035240266687799601661095864649209771790948434046947201833777492504781204499"
+        );
+
+        // "Should add 2 same numbers", "Should add 2 different numbers"
+        assert!(p1.double(params) == p1_double);
+        assert!(p1.add(&p2, params) == sum);
+
+        // "should packPoint - unpackPoint 1" and "2". circomlib sets the
+        // top bit for x > (p - 1) / 2, this encoding for odd x, so only
+        // the bytes of y are shared.
+        for &(ref p, packed) in [
+            (p1, "53b81ed5bffe9545b54016234682e7b2f699bd42a5e9eae27ff4051bc698ce85"),
+            (p1_double, "e114eb17eddf794f063a68fecac515e3620e131976108555735c8b0773929709")
+        ].iter() {
+            let packed = hex::decode(packed).unwrap();
+            let encoding = PublicKey::<Bn256>(p.clone()).to_bytes();
+            assert_eq!(encoding[..31], packed[..31]);
+            assert_eq!(encoding[31] & 0x7f, packed[31] & 0x7f);
+
+            let vk = PublicKey::<Bn256>::from_bytes(&encoding, params).unwrap();
+            assert!(vk.0 == *p);
+        }
+    }
+
+    #[test]
+    fn iden3_base_point_encoding() {
+        use babyjubjub::{JubjubBn256, edwards as baby_edwards};
+
+        // Base8 of the circomlib / iden3 Baby Jubjub implementation
+        let x = Fr::from_str("5299619240641551281634865583518297030282874472190772894086521144482721001553").unwrap();
+        let y = Fr::from_str("16950150798460657717958625567821834550301663161624707787222815936182638968203").unwrap();
+
+        // same point in the `a = 168700` form used by `redbabyjubjub`
+        let baby_params = &JubjubBn256::new();
+        let p = baby_edwards::Point::<Bn256, _>::get_for_y(y, x.into_repr().is_odd(), baby_params).unwrap();
+        assert_eq!(p.into_xy(), (x, y));
+        assert!(p.as_prime_order(baby_params).is_some());
+
+        let mut encoding = [0u8; PUBLIC_KEY_BYTES];
+        p.write(&mut encoding[..]).unwrap();
+        let vk = ::redbabyjubjub::PublicKey::<Bn256>::from_bytes(&encoding, baby_params).unwrap();
+        assert_eq!(vk.to_bytes(), encoding);
+
+        // and in the `a = -1` form used here: x' = sqrt(-168700) * x, y' = y
+        let params = &AltJubjubBn256::new();
+        let mut scale = Fr::from_str("168700").unwrap();
+        scale.negate();
+        let mut alt_x = scale.sqrt().unwrap();
+        alt_x.mul_assign(&x);
+        let p = edwards::Point::<Bn256, _>::from_xy(alt_x, y, params).unwrap();
+        assert!(p.as_prime_order(params).is_some());
+
+        let vk = PublicKey::<Bn256>::from_bytes(&PublicKey(p.clone()).to_bytes(), params).unwrap();
+        assert!(vk.0 == p);
+        // both encodings share y, only the sign bit may differ
+        assert_eq!(vk.to_bytes()[..31], encoding[..31]);
+        assert_eq!(vk.to_bytes()[31] & 0x7f, encoding[31] & 0x7f);
+    }
+
     #[test]
     fn random_signatures() {
         let rng = &mut thread_rng();
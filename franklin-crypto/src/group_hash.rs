@@ -1,9 +1,16 @@
 use jubjub::{
     JubjubEngine,
+    EdwardsPoint,
+    PrimeOrderPoint,
     PrimeOrder,
+    FixedGenerators,
     edwards
 };
 
+use bellman::pairing::Engine;
+
+use std::marker::PhantomData;
+
 use bellman::pairing::ff::{
     PrimeField
 };
@@ -146,14 +153,14 @@ pub fn baby_group_hash<E: JubjubEngine>(
     }
 }
 
-/// Produces a random point in the Jubjub curve.
-/// The point is guaranteed to be prime order
-/// and not the identity.
-pub fn generic_group_hash<E: JubjubEngine, H: GroupHasher>(
+/// Produces a random point in the curve of `P`, which can be a
+/// Jubjub or a Baby Jubjub point. The point is guaranteed to be
+/// prime order and not the identity.
+pub fn generic_group_hash<E: Engine, H: GroupHasher, P: PrimeOrderPoint<E>>(
     tag: &[u8],
     personalization: &[u8],
-    params: &E::Params
-) -> Option<edwards::Point<E, PrimeOrder>>
+    params: &P::Params
+) -> Option<P>
 {
     assert_eq!(personalization.len(), 8);
 
@@ -166,11 +173,9 @@ pub fn generic_group_hash<E: JubjubEngine, H: GroupHasher>(
     let h = h.finalize();
     assert!(h.len() == 32);
 
-    match edwards::Point::<E, _>::read(&h[..], params) {
+    match P::read_mul_by_cofactor(&h[..], params) {
         Ok(p) => {
-            let p = p.mul_by_cofactor(params);
-
-            if p != edwards::Point::zero() {
+            if p != P::zero() {
                 Some(p)
             } else {
                 None
@@ -180,6 +185,173 @@ pub fn generic_group_hash<E: JubjubEngine, H: GroupHasher>(
     }
 }
 
+/// Finds the first tag `m || i` for a one byte counter `i`, such that
+/// `generic_group_hash` gives a point.
+pub fn find_group_hash<E: Engine, H: GroupHasher, P: PrimeOrderPoint<E>>(
+    m: &[u8],
+    personalization: &[u8; 8],
+    params: &P::Params
+) -> P
+{
+    let mut tag = m.to_vec();
+    let i = tag.len();
+    tag.push(0u8);
+
+    loop {
+        let gh = generic_group_hash::<E, H, P>(
+            &tag,
+            personalization,
+            params
+        );
+
+        // We don't want to overflow and start reusing generators
+        assert!(tag[i] != u8::max_value());
+        tag[i] += 1;
+
+        if let Some(gh) = gh {
+            break gh;
+        }
+    }
+}
+
+/// Describes how the generators of the curve parameters are derived:
+/// the group hash function, the personalizations and tags of every
+/// generator and the number of Pedersen hash segments. `new` gives the
+/// generators of the Sapling protocol, so applications that want their
+/// own domain separation should override all of them.
+pub struct GeneratorSet<H: GroupHasher> {
+    pedersen_hash_personalization: [u8; 8],
+    pedersen_hash_segments: usize,
+    fixed_bases: Vec<([u8; 8], Vec<u8>)>,
+    _marker: PhantomData<H>
+}
+
+impl<H: GroupHasher> Clone for GeneratorSet<H> {
+    fn clone(&self) -> Self {
+        GeneratorSet {
+            pedersen_hash_personalization: self.pedersen_hash_personalization,
+            pedersen_hash_segments: self.pedersen_hash_segments,
+            fixed_bases: self.fixed_bases.clone(),
+            _marker: PhantomData
+        }
+    }
+}
+
+impl<H: GroupHasher> Default for GeneratorSet<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H: GroupHasher> GeneratorSet<H> {
+    pub fn new() -> Self {
+        let mut fixed_bases = vec![([0u8; 8], vec![]); FixedGenerators::Max as usize];
+
+        fixed_bases[FixedGenerators::ProofGenerationKey as usize] =
+            (*constants::PROOF_GENERATION_KEY_BASE_GENERATOR_PERSONALIZATION, vec![]);
+        fixed_bases[FixedGenerators::NoteCommitmentRandomness as usize] =
+            (*constants::PEDERSEN_HASH_GENERATORS_PERSONALIZATION, b"r".to_vec());
+        fixed_bases[FixedGenerators::NullifierPosition as usize] =
+            (*constants::NULLIFIER_POSITION_IN_TREE_GENERATOR_PERSONALIZATION, vec![]);
+        fixed_bases[FixedGenerators::ValueCommitmentValue as usize] =
+            (*constants::VALUE_COMMITMENT_GENERATOR_PERSONALIZATION, b"v".to_vec());
+        fixed_bases[FixedGenerators::ValueCommitmentRandomness as usize] =
+            (*constants::VALUE_COMMITMENT_GENERATOR_PERSONALIZATION, b"r".to_vec());
+        fixed_bases[FixedGenerators::SpendingKeyGenerator as usize] =
+            (*constants::SPENDING_KEY_GENERATOR_PERSONALIZATION, vec![]);
+
+        GeneratorSet {
+            pedersen_hash_personalization: *constants::PEDERSEN_HASH_GENERATORS_PERSONALIZATION,
+            pedersen_hash_segments: 5,
+            fixed_bases: fixed_bases,
+            _marker: PhantomData
+        }
+    }
+
+    /// Segment `i` of the Pedersen hash uses the generator found with
+    /// the personalization and the tag `i` as 4 little endian bytes.
+    pub fn pedersen_hash(mut self, personalization: &[u8; 8], segments: usize) -> Self {
+        assert!(segments > 0);
+
+        self.pedersen_hash_personalization = *personalization;
+        self.pedersen_hash_segments = segments;
+
+        self
+    }
+
+    pub fn fixed_base(mut self, base: FixedGenerators, personalization: &[u8; 8], tag: &[u8]) -> Self {
+        assert!((base as usize) < (FixedGenerators::Max as usize));
+
+        self.fixed_bases[base as usize] = (*personalization, tag.to_vec());
+
+        self
+    }
+
+    pub fn pedersen_hash_segments(&self) -> usize {
+        self.pedersen_hash_segments
+    }
+
+    pub fn pedersen_hash_personalization(&self) -> &[u8; 8] {
+        &self.pedersen_hash_personalization
+    }
+
+    /// Personalization and tag of every fixed generator, indexed by `FixedGenerators`
+    pub fn fixed_bases(&self) -> &[([u8; 8], Vec<u8>)] {
+        &self.fixed_bases
+    }
+
+    /// Derives the Pedersen hash generators. Only the curve constants
+    /// of `params` are used, so they may be not fully constructed yet.
+    pub fn pedersen_hash_generators<E: Engine, P: PrimeOrderPoint<E>>(&self, params: &P::Params) -> Vec<P> {
+        use byteorder::{WriteBytesExt, LittleEndian};
+
+        let mut pedersen_hash_generators = vec![];
+
+        for m in 0..(self.pedersen_hash_segments as u32) {
+            let mut segment_number = [0u8; 4];
+            (&mut segment_number[0..4]).write_u32::<LittleEndian>(m).unwrap();
+
+            pedersen_hash_generators.push(
+                find_group_hash::<E, H, P>(
+                    &segment_number,
+                    &self.pedersen_hash_personalization,
+                    params
+                )
+            );
+        }
+
+        check_generators(&pedersen_hash_generators);
+
+        pedersen_hash_generators
+    }
+
+    /// Derives the fixed generators, indexed by `FixedGenerators`
+    pub fn fixed_base_generators<E: Engine, P: PrimeOrderPoint<E>>(&self, params: &P::Params) -> Vec<P> {
+        let fixed_base_generators: Vec<_> = self.fixed_bases.iter().map(|&(ref personalization, ref tag)| {
+            find_group_hash::<E, H, P>(tag, personalization, params)
+        }).collect();
+
+        check_generators(&fixed_base_generators);
+
+        fixed_base_generators
+    }
+}
+
+fn check_generators<E: Engine, P: EdwardsPoint<E>>(generators: &[P]) {
+    // Check for duplicates, far worse than spec inconsistencies!
+    for (i, p1) in generators.iter().enumerate() {
+        if p1 == &P::zero() {
+            panic!("Neutral element!");
+        }
+
+        for p2 in generators.iter().skip(i+1) {
+            if p1 == p2 {
+                panic!("Duplicate generator!");
+            }
+        }
+    }
+}
+
 #[test]
 fn test_generic_hash() {
     use bellman::pairing::bn256::Bn256;
@@ -191,7 +363,7 @@ fn test_generic_hash() {
     for t in 0u8..=255u8 {
         let tag = [t];
         let blake_point = baby_group_hash::<Bn256>(&tag, &personalization[..], &params);
-        let generic_point = generic_group_hash::<Bn256, BlakeHasher>(&tag, &personalization[..], &params);
+        let generic_point = generic_group_hash::<Bn256, BlakeHasher, _>(&tag, &personalization[..], &params);
         assert!(blake_point == generic_point);
     }
 }
@@ -207,7 +379,7 @@ fn test_export_blake_generators() {
     for t in 0u8..=255u8 {
         let tag = [t];
         let blake_point = baby_group_hash::<Bn256>(&tag, &personalization[..], &params);
-        let generic_point = generic_group_hash::<Bn256, BlakeHasher>(&tag, &personalization[..], &params);
+        let generic_point = generic_group_hash::<Bn256, BlakeHasher, _>(&tag, &personalization[..], &params);
         assert!(blake_point == generic_point);
     }
 }
@@ -223,4 +395,91 @@ fn blake2s_consistency_test() {
     let reference = hex!("989e1d96f8d977db95b7fcb59d26fe7f66b4e21e84cdb9387b67aa78ebd07ecf");
 
     assert_eq!(reference[..], h[..]);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_generator_set_defaults() {
+    use bellman::pairing::bn256::Bn256;
+    use alt_babyjubjub::AltJubjubBn256;
+    use jubjub::JubjubParams;
+
+    let params = AltJubjubBn256::new();
+
+    // Same derivation as before the generator set was introduced
+    let mut tag = vec![0u8, 0, 0, 0, 0];
+    let first_segment = loop {
+        if let Some(p) = baby_group_hash::<Bn256>(&tag, constants::PEDERSEN_HASH_GENERATORS_PERSONALIZATION, &params) {
+            break p;
+        }
+        tag[4] += 1;
+    };
+    assert!(params.pedersen_hash_generators()[0] == first_segment);
+
+    let mut tag = vec![b'v', 0];
+    let value_generator = loop {
+        if let Some(p) = baby_group_hash::<Bn256>(&tag, constants::VALUE_COMMITMENT_GENERATOR_PERSONALIZATION, &params) {
+            break p;
+        }
+        tag[1] += 1;
+    };
+    assert!(params.generator(FixedGenerators::ValueCommitmentValue) == &value_generator);
+}
+
+#[test]
+fn test_custom_generator_set() {
+    use rand::{XorShiftRng, SeedableRng, Rng};
+    use bellman::pairing::bn256::Bn256;
+    use alt_babyjubjub::AltJubjubBn256;
+    use jubjub::JubjubParams;
+    use circuit::boolean::{Boolean, AllocatedBit};
+    use circuit::test::TestConstraintSystem;
+    use bellman::ConstraintSystem;
+    use pedersen_hash::{pedersen_hash, Personalization};
+
+    let set = GeneratorSet::<Keccak256Hasher>::new()
+        .pedersen_hash(b"MyApp_PH", 3)
+        .fixed_base(FixedGenerators::SpendingKeyGenerator, b"MyApp_G_", &[])
+        .fixed_base(FixedGenerators::ProofGenerationKey, b"MyApp_H_", &[])
+        .fixed_base(FixedGenerators::NoteCommitmentRandomness, b"MyApp_PH", b"r")
+        .fixed_base(FixedGenerators::NullifierPosition, b"MyApp_J_", &[])
+        .fixed_base(FixedGenerators::ValueCommitmentValue, b"MyApp_cv", b"v")
+        .fixed_base(FixedGenerators::ValueCommitmentRandomness, b"MyApp_cv", b"r");
+
+    let params = AltJubjubBn256::from_generator_set(&set);
+    let sapling = AltJubjubBn256::new();
+    let keccak_sapling = AltJubjubBn256::new_with_hasher::<Keccak256Hasher>();
+
+    assert_eq!(params.pedersen_hash_generators().len(), 3);
+    assert_eq!(params.pedersen_circuit_generators().len(), 3);
+    assert_eq!(params.pedersen_hash_exp_table().len(), 3);
+
+    for (custom, default) in params.pedersen_hash_generators().iter().zip(sapling.pedersen_hash_generators().iter()) {
+        assert!(custom != default);
+    }
+    assert!(params.generator(FixedGenerators::SpendingKeyGenerator) != sapling.generator(FixedGenerators::SpendingKeyGenerator));
+    assert!(keccak_sapling.generator(FixedGenerators::SpendingKeyGenerator) != sapling.generator(FixedGenerators::SpendingKeyGenerator));
+
+    // The parameters can be used as any other ones, e.g. for Pedersen hashes
+    let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+    let input: Vec<bool> = (0..300).map(|_| rng.gen()).collect();
+
+    let mut cs = TestConstraintSystem::<Bn256>::new();
+    let input_bools: Vec<Boolean> = input.iter().enumerate().map(|(i, b)| {
+        Boolean::from(AllocatedBit::alloc(cs.namespace(|| format!("input {}", i)), Some(*b)).unwrap())
+    }).collect();
+
+    let res = ::circuit::pedersen_hash::pedersen_hash(
+        cs.namespace(|| "pedersen hash"),
+        Personalization::NoteCommitment,
+        &input_bools,
+        &params
+    ).unwrap();
+    assert!(cs.is_satisfied());
+
+    let expected = pedersen_hash::<Bn256, _>(Personalization::NoteCommitment, input.clone(), &params).into_xy();
+    assert_eq!(res.get_x().get_value().unwrap(), expected.0);
+    assert_eq!(res.get_y().get_value().unwrap(), expected.1);
+
+    let default_hash = pedersen_hash::<Bn256, _>(Personalization::NoteCommitment, input, &sapling).into_xy();
+    assert!(default_hash != expected);
+}
@@ -0,0 +1,213 @@
+//! Hashing of arbitrary messages to the prime order subgroup of
+//! a Jubjub-like curve. Field elements are mapped to the Montgomery
+//! form of the curve with Elligator 2, so, unlike the try-and-increment
+//! approach of `group_hash`, the number of operations doesn't depend
+//! on the message.
+
+use jubjub::{
+    JubjubEngine,
+    JubjubParams,
+    TwistedEdwardsParams,
+    PrimeOrder,
+    Unknown,
+    edwards,
+    montgomery
+};
+
+use bellman::pairing::ff::{
+    Field,
+    PrimeField,
+    PrimeFieldRepr,
+    SqrtField,
+    LegendreSymbol
+};
+
+use blake2_rfc::blake2s::Blake2s;
+
+/// Number of Blake2s outputs reduced into a single field
+/// element, so that the result is close to uniform.
+pub const BLOCKS_PER_FIELD_ELEMENT: usize = 2;
+
+/// Returns the nonsquare `Z` used by the Elligator 2 map,
+/// which is the smallest nonsquare integer starting from 2.
+pub fn elligator_z<E: JubjubEngine>() -> E::Fr {
+    // -1 has to be a square so that 1 + Z.u^2 never vanishes
+    let mut minus_one = E::Fr::one();
+    minus_one.negate();
+    assert!(minus_one.legendre() == LegendreSymbol::QuadraticResidue);
+
+    let mut z = E::Fr::one();
+    loop {
+        z.add_assign(&E::Fr::one());
+        if z.legendre() == LegendreSymbol::QuadraticNonResidue {
+            return z;
+        }
+    }
+}
+
+/// Hashes the message into `count` field elements. Every element is
+/// the little-endian reduction of `BLOCKS_PER_FIELD_ELEMENT` outputs
+/// of Blake2s(personalization, message || i) for a one byte counter `i`.
+pub fn hash_to_field<E: JubjubEngine>(
+    personalization: &[u8],
+    message: &[u8],
+    count: usize
+) -> Vec<E::Fr>
+{
+    assert_eq!(personalization.len(), 8);
+    assert!(count * BLOCKS_PER_FIELD_ELEMENT <= 256);
+
+    let mut byte_coeff = E::Fr::one();
+    for _ in 0..8 {
+        byte_coeff.double();
+    }
+
+    (0..count).map(|i| {
+        let mut bytes = vec![];
+        for j in 0..BLOCKS_PER_FIELD_ELEMENT {
+            let mut h = Blake2s::with_params(32, &[], &[], personalization);
+            h.update(message);
+            h.update(&[(i * BLOCKS_PER_FIELD_ELEMENT + j) as u8]);
+            bytes.extend_from_slice(h.finalize().as_ref());
+        }
+
+        // Horner's rule from the most significant byte
+        let mut res = E::Fr::zero();
+        for &byte in bytes.iter().rev() {
+            res.mul_assign(&byte_coeff);
+            res.add_assign(&E::Fr::from_repr(<E::Fr as PrimeField>::Repr::from(byte as u64)).unwrap());
+        }
+
+        res
+    }).collect()
+}
+
+/// Maps a field element to a point of the Montgomery curve with
+/// Elligator 2. The result is not in the prime order subgroup.
+pub fn map_to_montgomery<E: JubjubEngine>(
+    u: E::Fr,
+    params: &E::Params
+) -> montgomery::Point<E, Unknown>
+{
+    let z = elligator_z::<E>();
+
+    // x1 = -A / (1 + Z.u^2), never divides by zero as -1/Z is nonsquare
+    let mut zu2 = u;
+    zu2.square();
+    zu2.mul_assign(&z);
+
+    let mut den = zu2;
+    den.add_assign(&E::Fr::one());
+
+    let mut x1 = *params.montgomery_a();
+    x1.negate();
+    x1.mul_assign(&den.inverse().expect("1 + Z.u^2 is never zero"));
+
+    // gx1 = x1^3 + A.x1^2 + x1
+    let mut gx1 = x1;
+    gx1.add_assign(params.montgomery_a());
+    gx1.mul_assign(&x1);
+    gx1.add_assign(&E::Fr::one());
+    gx1.mul_assign(&x1);
+
+    // Exactly one of gx1 and gx2 = Z.u^2.gx1 is a square, the point
+    // is taken with an odd y for x1 and an even y for x2 = -x1 - A
+    let is_square = gx1.legendre() != LegendreSymbol::QuadraticNonResidue;
+
+    let x = if is_square {
+        x1
+    } else {
+        let mut x2 = x1;
+        x2.add_assign(params.montgomery_a());
+        x2.negate();
+
+        x2
+    };
+
+    montgomery::Point::get_for_x(x, is_square, params).expect("exactly one candidate is on the curve")
+}
+
+/// Maps a single hashed field element to the prime order subgroup.
+/// Cheaper than `hash_to_curve`, but the output distribution is not uniform.
+pub fn encode_to_curve<E: JubjubEngine>(
+    personalization: &[u8],
+    message: &[u8],
+    params: &E::Params
+) -> edwards::Point<E, PrimeOrder>
+{
+    let u = hash_to_field::<E>(personalization, message, 1);
+
+    let p = map_to_montgomery::<E>(u[0], params);
+
+    edwards::Point::from_montgomery(&p, params).mul_by_cofactor(params)
+}
+
+/// Hashes the message to the prime order subgroup by adding the
+/// images of two hashed field elements and clearing the cofactor.
+pub fn hash_to_curve<E: JubjubEngine>(
+    personalization: &[u8],
+    message: &[u8],
+    params: &E::Params
+) -> edwards::Point<E, PrimeOrder>
+{
+    let u = hash_to_field::<E>(personalization, message, 2);
+
+    let p0 = edwards::Point::from_montgomery(&map_to_montgomery::<E>(u[0], params), params);
+    let p1 = edwards::Point::from_montgomery(&map_to_montgomery::<E>(u[1], params), params);
+
+    p0.add(&p1, params).mul_by_cofactor(params)
+}
+
+#[test]
+fn test_map_to_montgomery() {
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use alt_babyjubjub::AltJubjubBn256;
+    use rand::{XorShiftRng, SeedableRng, Rand};
+
+    let params = AltJubjubBn256::new();
+    let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+    let mut inputs = vec![Fr::zero(), Fr::one()];
+    for _ in 0..100 {
+        inputs.push(Rand::rand(rng));
+    }
+
+    for u in inputs {
+        let p = map_to_montgomery::<Bn256>(u, &params);
+
+        // The map is even in u
+        let mut neg_u = u;
+        neg_u.negate();
+        assert!(p == map_to_montgomery::<Bn256>(neg_u, &params));
+
+        // get_for_x only returns points on the curve
+        let (x, y) = p.into_xy().unwrap();
+        assert!(montgomery::Point::<Bn256, Unknown>::get_for_x(x, y.into_repr().is_odd(), &params).unwrap() == p);
+    }
+}
+
+#[test]
+fn test_hash_to_curve() {
+    use bellman::pairing::bls12_381::Bls12;
+    use bellman::pairing::bn256::Bn256;
+    use alt_babyjubjub::AltJubjubBn256;
+    use jubjub::JubjubBls12;
+
+    fn check<E: JubjubEngine>(params: &E::Params) {
+        let p = hash_to_curve::<E>(b"Zcash_H_", b"message", params);
+        assert!(p != edwards::Point::zero());
+        assert!(p.mul(E::Fs::char(), params) == edwards::Point::zero());
+
+        // Deterministic, but depends on both the message and the personalization
+        assert!(p == hash_to_curve::<E>(b"Zcash_H_", b"message", params));
+        assert!(p != hash_to_curve::<E>(b"Zcash_H_", b"massage", params));
+        assert!(p != hash_to_curve::<E>(b"Zcash_G_", b"message", params));
+        assert!(p != encode_to_curve::<E>(b"Zcash_H_", b"message", params));
+
+        let q = encode_to_curve::<E>(b"Zcash_H_", b"", params);
+        assert!(q.mul(E::Fs::char(), params) == edwards::Point::zero());
+    }
+
+    check::<Bn256>(&AltJubjubBn256::new());
+    check::<Bls12>(&JubjubBls12::new());
+}
@@ -7,6 +7,8 @@ use bellman::pairing::ff::{
 
 /// Perform a Lagrange interpolation for a set of points
 /// It's O(n^2) operations, so use with caution
+/// For points that form a domain of roots of unity use `polynomial::Domain::ifft`,
+/// for many arbitrary points use `polynomial::SubproductTree::interpolate`
 pub fn interpolate<E: Engine>(
     points: &[(E::Fr, E::Fr)]
 ) -> Option<Vec<E::Fr>> {
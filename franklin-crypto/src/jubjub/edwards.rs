@@ -1,3 +1,5 @@
+use bellman::pairing::Engine;
+
 use bellman::pairing::ff::{
     Field,
     SqrtField,
@@ -9,6 +11,10 @@ use bellman::pairing::ff::{
 use super::{
     JubjubEngine,
     JubjubParams,
+    TwistedEdwardsParams,
+    EdwardsCoordinates,
+    EdwardsPoint,
+    PrimeOrderPoint,
     Unknown,
     PrimeOrder,
     montgomery
@@ -43,6 +49,42 @@ pub struct Point<E: JubjubEngine, Subgroup> {
     _marker: PhantomData<Subgroup>
 }
 
+impl<E: JubjubEngine, Subgroup> EdwardsCoordinates<E> for Point<E, Subgroup> {
+    fn edwards_xy(&self) -> (E::Fr, E::Fr) {
+        self.into_xy()
+    }
+}
+
+impl<E: JubjubEngine, Subgroup> EdwardsPoint<E> for Point<E, Subgroup> {
+    type Params = E::Params;
+
+    fn zero() -> Self {
+        Point::zero()
+    }
+
+    fn into_projective(&self) -> (E::Fr, E::Fr, E::Fr) {
+        (self.x, self.y, self.z)
+    }
+
+    fn double(&self, params: &E::Params) -> Self {
+        Point::double(self, params)
+    }
+
+    fn add(&self, other: &Self, params: &E::Params) -> Self {
+        Point::add(self, other, params)
+    }
+
+    fn negate(&self) -> Self {
+        Point::negate(self)
+    }
+}
+
+impl<E: JubjubEngine> PrimeOrderPoint<E> for Point<E, PrimeOrder> {
+    fn read_mul_by_cofactor<R: Read>(reader: R, params: &E::Params) -> io::Result<Self> {
+        Ok(Point::<E, Unknown>::read(reader, params)?.mul_by_cofactor(params))
+    }
+}
+
 fn convert_subgroup<E: JubjubEngine, S1, S2>(from: &Point<E, S1>) -> Point<E, S2>
 {
     Point {
@@ -240,6 +282,22 @@ impl<E: JubjubEngine> Point<E, Unknown> {
 }
 
 impl<E: JubjubEngine, Subgroup> Point<E, Subgroup> {
+    /// Constructs the point from its affine coordinates without
+    /// checking that it is on the curve or in the subgroup. Only for
+    /// points that are known to be valid, e.g. from a checksummed file.
+    pub(crate) fn from_xy_unchecked(x: E::Fr, y: E::Fr) -> Self {
+        let mut t = x;
+        t.mul_assign(&y);
+
+        Point {
+            x: x,
+            y: y,
+            t: t,
+            z: E::Fr::one(),
+            _marker: PhantomData
+        }
+    }
+
     pub fn write<W: Write>(
         &self,
         writer: W
@@ -560,18 +618,136 @@ impl<E: JubjubEngine, Subgroup> Point<E, Subgroup> {
         params: &E::Params
     ) -> Self
     {
-        // Standard double-and-add scalar multiplication
+        wnaf_mul(self, scalar.into(), params)
+    }
 
-        let mut res = Self::zero();
+    /// Converts all the points into affine coordinates
+    /// at the cost of a single inversion
+    pub fn batch_normalize(points: &[Self]) -> Vec<(E::Fr, E::Fr)> {
+        batch_normalize(points)
+    }
+}
 
-        for b in BitIterator::new(scalar.into()) {
+/// Windowed NAF scalar multiplication of a point of any curve family
+pub(crate) fn wnaf_mul<E: Engine, P: EdwardsPoint<E>, R: PrimeFieldRepr>(
+    point: &P,
+    scalar: R,
+    params: &P::Params
+) -> P
+{
+    // The table holds the odd multiples P, 3P, ..., (2^(w - 1) - 1)P
+    const WINDOW: usize = 5;
+
+    let mut table = Vec::with_capacity(1 << (WINDOW - 2));
+    table.push(point.clone());
+    let double = point.double(params);
+    for i in 1..(1 << (WINDOW - 2)) {
+        let next = table[i - 1].add(&double, params);
+        table.push(next);
+    }
+
+    let mut res = P::zero();
+    let mut found_one = false;
+
+    for &digit in wnaf_form(scalar, WINDOW).iter().rev() {
+        if found_one {
             res = res.double(params);
+        }
 
-            if b {
-                res = res.add(self, params);
-            }
+        if digit > 0 {
+            res = res.add(&table[(digit / 2) as usize], params);
+            found_one = true;
+        } else if digit < 0 {
+            res = res.add(&table[(-digit / 2) as usize].negate(), params);
+            found_one = true;
         }
+    }
+
+    res
+}
+
+/// Converts points of any curve family into affine coordinates
+/// at the cost of a single inversion
+pub(crate) fn batch_normalize<E: Engine, P: EdwardsPoint<E>>(points: &[P]) -> Vec<(E::Fr, E::Fr)> {
+    // Montgomery's trick: invert the product of all the z
+    // coordinates, then peel off the inverses one by one
+    let points: Vec<_> = points.iter().map(|p| p.into_projective()).collect();
+
+    let mut products = Vec::with_capacity(points.len());
+    let mut acc = E::Fr::one();
+    for &(_, _, z) in points.iter() {
+        products.push(acc);
+        acc.mul_assign(&z);
+    }
+
+    let mut acc_inv = acc.inverse().expect("z is never zero");
+
+    let mut result = vec![(E::Fr::zero(), E::Fr::zero()); points.len()];
+    for ((&(x, y, z), product), result) in points.iter().zip(products.into_iter()).zip(result.iter_mut()).rev() {
+        let mut zinv = acc_inv;
+        zinv.mul_assign(&product);
+        acc_inv.mul_assign(&z);
+
+        let mut x = x;
+        x.mul_assign(&zinv);
+
+        let mut y = y;
+        y.mul_assign(&zinv);
+
+        *result = (x, y);
+    }
+
+    result
+}
+
+/// Returns the width-`window` NAF of the scalar, least significant
+/// digit first. Nonzero digits are odd, less than `2^(window - 1)` in
+/// absolute value and followed by at least `window - 1` zeros.
+pub(crate) fn wnaf_form<R: PrimeFieldRepr>(scalar: R, window: usize) -> Vec<i64> {
+    assert!(window >= 2 && window < 64);
+
+    let limbs = scalar.as_ref();
+    let bit_len = limbs.len() * 64;
 
-        res
+    let width = 1u64 << window;
+    let window_mask = width - 1;
+
+    let mut wnaf = Vec::with_capacity(bit_len + 1);
+    let mut pos = 0;
+    let mut carry = 0;
+
+    while pos < bit_len || carry != 0 {
+        let u64_idx = pos / 64;
+        let bit_idx = pos % 64;
+
+        let bit_buf = if u64_idx >= limbs.len() {
+            0
+        } else if bit_idx + window < 64 || u64_idx + 1 == limbs.len() {
+            limbs[u64_idx] >> bit_idx
+        } else {
+            (limbs[u64_idx] >> bit_idx) | (limbs[u64_idx + 1] << (64 - bit_idx))
+        };
+
+        let window_val = carry + (bit_buf & window_mask);
+
+        if window_val & 1 == 0 {
+            wnaf.push(0);
+            pos += 1;
+        } else {
+            if window_val < width / 2 {
+                carry = 0;
+                wnaf.push(window_val as i64);
+            } else {
+                carry = 1;
+                wnaf.push((window_val as i64) - (width as i64));
+            }
+
+            for _ in 1..window {
+                wnaf.push(0);
+            }
+            pos += window;
+        }
     }
+
+    wnaf
 }
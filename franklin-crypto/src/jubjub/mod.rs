@@ -27,10 +27,17 @@ use bellman::pairing::ff::{
     SqrtField
 };
 
-use group_hash::group_hash;
+use group_hash::{GeneratorSet, GroupHasher, BlakeHasher};
 
 use constants;
 
+use std::borrow::Cow;
+use std::io::{
+    self,
+    Read,
+    Write
+};
+
 use bellman::pairing::bls12_381::{
     Bls12,
     Fr
@@ -46,6 +53,9 @@ pub mod montgomery;
 /// This is an implementation of the scalar field for Jubjub.
 pub mod fs;
 
+/// Binary format of the precomputed parameters.
+pub(crate) mod params_file;
+
 #[cfg(test)]
 pub mod tests;
 
@@ -103,9 +113,13 @@ pub trait JubjubEngine: Engine {
     type Params: JubjubParams<Self>;
 }
 
-/// The pre-computed parameters for Jubjub, including curve
-/// constants and various limits and window tables.
-pub trait JubjubParams<E: JubjubEngine>: Sized {
+/// The constants of a twisted Edwards curve `a * x^2 + y^2 = 1 + d * x^2 * y^2`
+/// defined over the scalar field of `E` and of its birationally equivalent
+/// Montgomery form. Jubjub, Baby Jubjub and alt Baby Jubjub parameters all
+/// implement it, so the same point gadgets work with any of them.
+pub trait TwistedEdwardsParams<E: Engine> {
+    /// The `a` constant of the twisted Edwards curve.
+    fn edwards_a(&self) -> &E::Fr;
     /// The `d` constant of the twisted Edwards curve.
     fn edwards_d(&self) -> &E::Fr;
     /// The `A` constant of the birationally equivalent Montgomery curve.
@@ -114,6 +128,52 @@ pub trait JubjubParams<E: JubjubEngine>: Sized {
     fn montgomery_2a(&self) -> &E::Fr;
     /// The scaling factor used for conversion from the Montgomery form.
     fn scale(&self) -> &E::Fr;
+}
+
+impl<'a, E: Engine, P: TwistedEdwardsParams<E>> TwistedEdwardsParams<E> for &'a P {
+    fn edwards_a(&self) -> &E::Fr { (**self).edwards_a() }
+    fn edwards_d(&self) -> &E::Fr { (**self).edwards_d() }
+    fn montgomery_a(&self) -> &E::Fr { (**self).montgomery_a() }
+    fn montgomery_2a(&self) -> &E::Fr { (**self).montgomery_2a() }
+    fn scale(&self) -> &E::Fr { (**self).scale() }
+}
+
+/// A point of a twisted Edwards curve outside the circuit, which
+/// can be witnessed by the point gadgets.
+pub trait EdwardsCoordinates<E: Engine> {
+    /// Returns the affine coordinates `(x, y)`.
+    fn edwards_xy(&self) -> (E::Fr, E::Fr);
+}
+
+/// The group law of a twisted Edwards curve outside the circuit. The
+/// points of Jubjub and of Baby Jubjub implement it, so the algorithms
+/// built on it, like the wNAF multiplication, are written once.
+pub trait EdwardsPoint<E: Engine>: EdwardsCoordinates<E> + Clone + PartialEq {
+    /// The parameters of the curve the point is on.
+    type Params: TwistedEdwardsParams<E>;
+
+    /// Returns the identity.
+    fn zero() -> Self;
+    /// Returns the projective coordinates `(X, Y, Z)`, the affine
+    /// coordinates being `(X/Z, Y/Z)`.
+    fn into_projective(&self) -> (E::Fr, E::Fr, E::Fr);
+    fn double(&self, params: &Self::Params) -> Self;
+    fn add(&self, other: &Self, params: &Self::Params) -> Self;
+    fn negate(&self) -> Self;
+}
+
+/// A point of the prime order subgroup of a twisted Edwards curve,
+/// implemented by the points of Jubjub and of Baby Jubjub so that the
+/// group hash derives the generators of both.
+pub trait PrimeOrderPoint<E: Engine>: EdwardsPoint<E> {
+    /// Reads a compressed point of the curve and multiplies it by the
+    /// cofactor, which gives a point of the prime order subgroup.
+    fn read_mul_by_cofactor<R: Read>(reader: R, params: &Self::Params) -> io::Result<Self>;
+}
+
+/// The pre-computed parameters for Jubjub, including curve
+/// constants and various limits and window tables.
+pub trait JubjubParams<E: JubjubEngine>: TwistedEdwardsParams<E> + Sized {
     /// Returns the generators (for each segment) used in all Pedersen commitments.
     fn pedersen_hash_generators(&self) -> &[edwards::Point<E, PrimeOrder>];
     /// Returns the exp table for Pedersen hashes.
@@ -135,6 +195,89 @@ pub trait JubjubParams<E: JubjubEngine>: Sized {
     /// Returns the window size for exponentiation of Pedersen hash generators
     /// outside the circuit
     fn pedersen_hash_exp_window_size(&self) -> u32;
+    /// Returns the window tables of application specific bases the
+    /// parameters were constructed with, in the order they were given.
+    /// Parameters without such tables return none, and the tables are
+    /// computed when needed, see `FixedBaseTable::for_base`.
+    fn fixed_base_tables(&self) -> &[FixedBaseTable<E>] {
+        &[]
+    }
+}
+
+/// Window tables [0, 1, ..., 7] for different magnitudes of an arbitrary
+/// base, so it can be used for fixed-base exponentiation in the circuit
+/// the same way as the `FixedGenerators`.
+pub struct FixedBaseTable<E: JubjubEngine> {
+    base: edwards::Point<E, PrimeOrder>,
+    windows: Vec<Vec<(E::Fr, E::Fr)>>
+}
+
+impl<E: JubjubEngine> Clone for FixedBaseTable<E> {
+    fn clone(&self) -> Self {
+        FixedBaseTable {
+            base: self.base.clone(),
+            windows: self.windows.clone()
+        }
+    }
+}
+
+impl<E: JubjubEngine> FixedBaseTable<E> {
+    /// Precomputes the tables for a base, enough to multiply it by
+    /// a full scalar.
+    pub fn new(base: &edwards::Point<E, PrimeOrder>, params: &E::Params) -> Self {
+        FixedBaseTable {
+            base: base.clone(),
+            windows: fixed_base_windows(base, params.fixed_base_chunks_per_generator(), params)
+        }
+    }
+
+    /// Returns the table of `base` from the parameters, or computes it
+    /// if the parameters were not constructed with it.
+    pub fn for_base<'a>(base: &edwards::Point<E, PrimeOrder>, params: &'a E::Params) -> Cow<'a, Self> {
+        match params.fixed_base_tables().iter().find(|table| &table.base == base) {
+            Some(table) => Cow::Borrowed(table),
+            None => Cow::Owned(Self::new(base, params))
+        }
+    }
+
+    pub fn base(&self) -> &edwards::Point<E, PrimeOrder> {
+        &self.base
+    }
+
+    pub fn windows(&self) -> &[Vec<(E::Fr, E::Fr)>] {
+        &self.windows[..]
+    }
+}
+
+/// Creates `num_windows` 3-bit window tables for fixed-base exponentiation
+/// of `base` in the circuit, each window being 8 times the previous one.
+pub fn fixed_base_windows<E: JubjubEngine>(
+    base: &edwards::Point<E, PrimeOrder>,
+    num_windows: usize,
+    params: &E::Params
+) -> Vec<Vec<(E::Fr, E::Fr)>>
+{
+    let mut gen = base.clone();
+    let mut points = Vec::with_capacity(num_windows * 7);
+    for _ in 0..num_windows {
+        let mut g = gen.clone();
+        for _ in 0..7 {
+            points.push(g.clone());
+            g = g.add(&gen, params);
+        }
+
+        // gen = gen * 8
+        gen = g;
+    }
+
+    // Normalize the whole table with a single inversion
+    let affine = edwards::Point::batch_normalize(&points);
+
+    affine.chunks(7).map(|chunk| {
+        let mut coeffs = vec![(E::Fr::zero(), E::Fr::one())];
+        coeffs.extend_from_slice(chunk);
+        coeffs
+    }).collect()
 }
 
 impl JubjubEngine for Bls12 {
@@ -143,6 +286,7 @@ impl JubjubEngine for Bls12 {
 }
 
 pub struct JubjubBls12 {
+    edwards_a: Fr,
     edwards_d: Fr,
     montgomery_a: Fr,
     montgomery_2a: Fr,
@@ -154,13 +298,19 @@ pub struct JubjubBls12 {
 
     fixed_base_generators: Vec<edwards::Point<Bls12, PrimeOrder>>,
     fixed_base_circuit_generators: Vec<Vec<Vec<(Fr, Fr)>>>,
+
+    fixed_base_tables: Vec<FixedBaseTable<Bls12>>,
 }
 
-impl JubjubParams<Bls12> for JubjubBls12 {
+impl TwistedEdwardsParams<Bls12> for JubjubBls12 {
+    fn edwards_a(&self) -> &Fr { &self.edwards_a }
     fn edwards_d(&self) -> &Fr { &self.edwards_d }
     fn montgomery_a(&self) -> &Fr { &self.montgomery_a }
     fn montgomery_2a(&self) -> &Fr { &self.montgomery_2a }
     fn scale(&self) -> &Fr { &self.scale }
+}
+
+impl JubjubParams<Bls12> for JubjubBls12 {
     fn pedersen_hash_generators(&self) -> &[edwards::Point<Bls12, PrimeOrder>] {
         &self.pedersen_hash_generators
     }
@@ -187,15 +337,58 @@ impl JubjubParams<Bls12> for JubjubBls12 {
     fn pedersen_hash_exp_window_size(&self) -> u32 {
         8
     }
+    fn fixed_base_tables(&self) -> &[FixedBaseTable<Bls12>] {
+        &self.fixed_base_tables
+    }
 }
 
 impl JubjubBls12 {
-    pub fn new() -> Self {
+    /// Adds window tables for application specific bases, so they can be
+    /// used for fixed-base exponentiation in the circuit. Tables are
+    /// available through `fixed_base_tables` in the same order as `bases`.
+    pub fn with_fixed_bases(mut self, bases: &[edwards::Point<Bls12, PrimeOrder>]) -> Self {
+        for base in bases {
+            let table = FixedBaseTable::new(base, &self);
+            self.fixed_base_tables.push(table);
+        }
+
+        self
+    }
+
+    /// Writes the generators and all the precomputed tables, so that
+    /// the parameters can be loaded with `read` instead of recomputed
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        params_file::write::<Bls12, edwards::Point<Bls12, PrimeOrder>, _>(self, writer)
+    }
+
+    /// Reads the parameters written by `write`, checking their integrity
+    /// and that they were computed for this curve
+    pub fn read<R: Read>(reader: R) -> io::Result<Self> {
+        let mut params = Self::curve();
+        let data = params_file::read::<Bls12, edwards::Point<Bls12, PrimeOrder>, _>(reader, &params)?;
+
+        params.pedersen_hash_generators = data.pedersen_hash_generators;
+        params.pedersen_hash_exp = data.pedersen_hash_exp;
+        params.pedersen_circuit_generators = data.pedersen_circuit_generators;
+        params.fixed_base_generators = data.fixed_base_generators;
+        params.fixed_base_circuit_generators = data.fixed_base_circuit_generators;
+        params.fixed_base_tables = params_file::fixed_base_tables(data.fixed_base_tables);
+
+        Ok(params)
+    }
+
+    /// Curve constants, without any generators or tables
+    fn curve() -> Self {
         let montgomery_a = Fr::from_str("40962").unwrap();
         let mut montgomery_2a = montgomery_a;
         montgomery_2a.double();
 
-        let mut tmp_params = JubjubBls12 {
+        let mut edwards_a = Fr::one();
+        edwards_a.negate();
+
+        JubjubBls12 {
+            // a = -1
+            edwards_a: edwards_a,
             // d = -(10240/10241)
             edwards_d: Fr::from_str("19257038036680949359750312669786877991949435402254120286184196891950884077233").unwrap(),
             // A = 40962
@@ -205,75 +398,27 @@ impl JubjubBls12 {
             // scaling factor = sqrt(4 / (a - d))
             scale: Fr::from_str("17814886934372412843466061268024708274627479829237077604635722030778476050649").unwrap(),
 
-            // We'll initialize these below
+            // Filled in by `from_generator_set` or `read`
             pedersen_hash_generators: vec![],
             pedersen_hash_exp: vec![],
             pedersen_circuit_generators: vec![],
             fixed_base_generators: vec![],
             fixed_base_circuit_generators: vec![],
-        };
-
-        fn find_group_hash<E: JubjubEngine>(
-            m: &[u8],
-            personalization: &[u8; 8],
-            params: &E::Params
-        ) -> edwards::Point<E, PrimeOrder>
-        {
-            let mut tag = m.to_vec();
-            let i = tag.len();
-            tag.push(0u8);
-
-            loop {
-                let gh = group_hash(
-                    &tag,
-                    personalization,
-                    params
-                );
-
-                // We don't want to overflow and start reusing generators
-                assert!(tag[i] != u8::max_value());
-                tag[i] += 1;
-
-                if let Some(gh) = gh {
-                    break gh;
-                }
-            }
+            fixed_base_tables: vec![],
         }
+    }
 
-        // Create the bases for the Pedersen hashes
-        {
-            let mut pedersen_hash_generators = vec![];
-
-            for m in 0..5 {
-                use byteorder::{WriteBytesExt, LittleEndian};
-
-                let mut segment_number = [0u8; 4];
-                (&mut segment_number[0..4]).write_u32::<LittleEndian>(m).unwrap();
-
-                pedersen_hash_generators.push(
-                    find_group_hash(
-                        &segment_number,
-                        constants::PEDERSEN_HASH_GENERATORS_PERSONALIZATION,
-                        &tmp_params
-                    )
-                );
-            }
-
-            // Check for duplicates, far worse than spec inconsistencies!
-            for (i, p1) in pedersen_hash_generators.iter().enumerate() {
-                if p1 == &edwards::Point::zero() {
-                    panic!("Neutral element!");
-                }
+    pub fn new() -> Self {
+        Self::from_generator_set(&GeneratorSet::<BlakeHasher>::new())
+    }
 
-                for p2 in pedersen_hash_generators.iter().skip(i+1) {
-                    if p1 == p2 {
-                        panic!("Duplicate generator!");
-                    }
-                }
-            }
+    /// Constructs the parameters with the generators derived as
+    /// described by `generators`, instead of the Sapling ones.
+    pub fn from_generator_set<H: GroupHasher>(generators: &GeneratorSet<H>) -> Self {
+        let mut tmp_params = Self::curve();
 
-            tmp_params.pedersen_hash_generators = pedersen_hash_generators;
-        }
+        // Create the bases for the Pedersen hashes
+        tmp_params.pedersen_hash_generators = generators.pedersen_hash_generators::<Bls12, _>(&tmp_params);
 
         // Create the exp table for the Pedersen hash generators
         {
@@ -312,42 +457,7 @@ impl JubjubBls12 {
         }
 
         // Create the bases for other parts of the protocol
-        {
-            let mut fixed_base_generators = vec![edwards::Point::zero(); FixedGenerators::Max as usize];
-
-            fixed_base_generators[FixedGenerators::ProofGenerationKey as usize] =
-                find_group_hash(&[], constants::PROOF_GENERATION_KEY_BASE_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::NoteCommitmentRandomness as usize] =
-                find_group_hash(b"r", constants::PEDERSEN_HASH_GENERATORS_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::NullifierPosition as usize] =
-                find_group_hash(&[], constants::NULLIFIER_POSITION_IN_TREE_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::ValueCommitmentValue as usize] =
-                find_group_hash(b"v", constants::VALUE_COMMITMENT_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::ValueCommitmentRandomness as usize] =
-                find_group_hash(b"r", constants::VALUE_COMMITMENT_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            fixed_base_generators[FixedGenerators::SpendingKeyGenerator as usize] =
-                find_group_hash(&[], constants::SPENDING_KEY_GENERATOR_PERSONALIZATION, &tmp_params);
-
-            // Check for duplicates, far worse than spec inconsistencies!
-            for (i, p1) in fixed_base_generators.iter().enumerate() {
-                if p1 == &edwards::Point::zero() {
-                    panic!("Neutral element!");
-                }
-
-                for p2 in fixed_base_generators.iter().skip(i+1) {
-                    if p1 == p2 {
-                        panic!("Duplicate generator!");
-                    }
-                }
-            }
-
-            tmp_params.fixed_base_generators = fixed_base_generators;
-        }
+        tmp_params.fixed_base_generators = generators.fixed_base_generators::<Bls12, _>(&tmp_params);
 
         // Create the 2-bit window table lookups for each 4-bit
         // "chunk" in each segment of the Pedersen hash
@@ -386,21 +496,10 @@ impl JubjubBls12 {
         {
             let mut fixed_base_circuit_generators = vec![];
 
-            for mut gen in tmp_params.fixed_base_generators.iter().cloned() {
-                let mut windows = vec![];
-                for _ in 0..tmp_params.fixed_base_chunks_per_generator() {
-                    let mut coeffs = vec![(Fr::zero(), Fr::one())];
-                    let mut g = gen.clone();
-                    for _ in 0..7 {
-                        coeffs.push(g.into_xy());
-                        g = g.add(&gen, &tmp_params);
-                    }
-                    windows.push(coeffs);
-
-                    // gen = gen * 8
-                    gen = g;
-                }
-                fixed_base_circuit_generators.push(windows);
+            for gen in tmp_params.fixed_base_generators.iter() {
+                fixed_base_circuit_generators.push(
+                    fixed_base_windows(gen, tmp_params.fixed_base_chunks_per_generator(), &tmp_params)
+                );
             }
 
             tmp_params.fixed_base_circuit_generators = fixed_base_circuit_generators;
@@ -444,3 +543,86 @@ fn test_jubjub_bls12_num_generators() {
 
     assert_eq!(params.pedersen_circuit_generators.len(), 5);
 }
+
+#[test]
+fn test_fixed_base_table_for_base() {
+    use std::borrow::Cow;
+
+    let params = JubjubBls12::new();
+    let base = params.generator(FixedGenerators::SpendingKeyGenerator).double(&params);
+    let other = base.double(&params);
+    let params = params.with_fixed_bases(&[base.clone()]);
+
+    match FixedBaseTable::for_base(&base, &params) {
+        Cow::Borrowed(table) => assert!(table.base() == &base),
+        Cow::Owned(_) => panic!("the table should come from the parameters")
+    }
+
+    match FixedBaseTable::for_base(&other, &params) {
+        Cow::Owned(table) => assert_eq!(table.windows(), FixedBaseTable::new(&other, &params).windows()),
+        Cow::Borrowed(_) => panic!("the parameters have no table for this base")
+    }
+}
+
+#[test]
+fn test_jubjub_bls12_params_file() {
+    use blake2_rfc::blake2s::blake2s;
+    use group_hash::Keccak256Hasher;
+    use alt_babyjubjub::AltJubjubBn256;
+
+    let params = JubjubBls12::from_generator_set(&GeneratorSet::<Keccak256Hasher>::new().pedersen_hash(b"Custom__", 3));
+    let extra_base = params.generator(FixedGenerators::SpendingKeyGenerator).double(&params);
+    let params = params.with_fixed_bases(&[extra_base]);
+
+    let mut file = vec![];
+    params.write(&mut file).unwrap();
+
+    let loaded = JubjubBls12::read(&file[..]).unwrap();
+
+    assert!(loaded.pedersen_hash_generators == params.pedersen_hash_generators);
+    assert!(loaded.pedersen_hash_exp == params.pedersen_hash_exp);
+    assert_eq!(loaded.pedersen_circuit_generators, params.pedersen_circuit_generators);
+    assert!(loaded.fixed_base_generators == params.fixed_base_generators);
+    assert_eq!(loaded.fixed_base_circuit_generators, params.fixed_base_circuit_generators);
+    assert_eq!(loaded.fixed_base_tables.len(), 1);
+    assert!(loaded.fixed_base_tables[0].base() == params.fixed_base_tables[0].base());
+    assert_eq!(loaded.fixed_base_tables[0].windows(), params.fixed_base_tables[0].windows());
+
+    // Writing the loaded parameters gives the same file
+    let mut rewritten = vec![];
+    loaded.write(&mut rewritten).unwrap();
+    assert!(rewritten == file);
+
+    // Any corruption is detected
+    for &position in [0, 20, 200, file.len() / 2, file.len() - 1].iter() {
+        let mut corrupted = file.clone();
+        corrupted[position] ^= 0x01;
+        assert!(JubjubBls12::read(&corrupted[..]).is_err());
+    }
+    assert!(JubjubBls12::read(&file[..file.len() - 1]).is_err());
+
+    // Tables are spot-checked even when the checksum matches: entry 1 of
+    // the second exp table of the first segment is replaced by entry 2
+    let entry = 180 + (4 + 256 * 64) + 4 + 64;
+    let mut tampered = file.clone();
+    let next = tampered[entry + 64..entry + 128].to_vec();
+    tampered[entry..entry + 64].copy_from_slice(&next);
+    let body = tampered.len() - 32;
+    let hash = blake2s(32, &[], &tampered[..body]);
+    tampered[body..].copy_from_slice(hash.as_bytes());
+    assert!(JubjubBls12::read(&tampered[..]).is_err());
+
+    // Lengths are checked before anything is allocated for them: the
+    // number of segments, then the number of exp tables of the first one
+    for &position in [108, 176].iter() {
+        let mut corrupted = file.clone();
+        corrupted[position..position + 4].copy_from_slice(&[0xff; 4]);
+        assert!(JubjubBls12::read(&corrupted[..]).is_err());
+    }
+
+    // Parameters of a different curve are rejected
+    let mut other = vec![];
+    AltJubjubBn256::new().write(&mut other).unwrap();
+    assert!(JubjubBls12::read(&other[..]).is_err());
+    assert!(AltJubjubBn256::read(&other[..]).is_ok());
+}
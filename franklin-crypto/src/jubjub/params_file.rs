@@ -0,0 +1,503 @@
+//! Binary format of the precomputed curve parameters, so that the
+//! generators and window tables can be computed once, stored and then
+//! loaded instead of being recomputed by `new` on every start.
+//!
+//! The file consists of a header (magic, version and the curve constants),
+//! the generators, all the tables and finally the BLAKE2s hash of
+//! everything before it. The parameters of Jubjub, alt Baby Jubjub and
+//! Baby Jubjub all use this format, they are told apart by the constants.
+//!
+//! Loading checks the hash, the curve constants and that every generator
+//! is a point of the prime order subgroup. All the table sizes are checked
+//! before anything is allocated for them. Every table and window is then
+//! spot-checked: its first entries and its last one are compared with the
+//! multiples of the generator they should hold, the other entries aren't
+//! recomputed. The hash only detects a corrupted file, it doesn't
+//! authenticate it: a file with a matching hash is trusted to contain the
+//! right tables, so a file from an untrusted source should be replaced by
+//! the output of `new` instead of being loaded.
+
+use bellman::pairing::Engine;
+
+use bellman::pairing::ff::{
+    Field,
+    PrimeField,
+    PrimeFieldRepr
+};
+
+use blake2_rfc::blake2s::Blake2s;
+
+use byteorder::{
+    ReadBytesExt,
+    WriteBytesExt,
+    LittleEndian
+};
+
+use std::io::{
+    self,
+    Read,
+    Write
+};
+
+use super::{
+    JubjubEngine,
+    JubjubParams,
+    TwistedEdwardsParams,
+    EdwardsPoint,
+    FixedGenerators,
+    FixedBaseTable,
+    PrimeOrder,
+    Unknown,
+    edwards,
+    montgomery
+};
+
+use super::edwards::batch_normalize;
+
+const MAGIC: &'static [u8; 8] = b"jubjubpp";
+const VERSION: u32 = 1;
+
+const FIXED_GENERATORS: [FixedGenerators; FixedGenerators::Max as usize] = [
+    FixedGenerators::ProofGenerationKey,
+    FixedGenerators::NoteCommitmentRandomness,
+    FixedGenerators::NullifierPosition,
+    FixedGenerators::ValueCommitmentValue,
+    FixedGenerators::ValueCommitmentRandomness,
+    FixedGenerators::SpendingKeyGenerator
+];
+
+/// What the file needs from a curve family. It's implemented by the
+/// prime order points of Jubjub and of Baby Jubjub, the tables being
+/// taken from the parameters they are used with.
+pub(crate) trait ParamsFilePoint<E: Engine>: EdwardsPoint<E> {
+    /// Returns the point if it's on the curve and in the prime order subgroup
+    fn from_xy_checked(x: E::Fr, y: E::Fr, params: &Self::Params) -> Option<Self>;
+    /// Returns the point without checking that it's on the curve
+    fn from_xy_unchecked(x: E::Fr, y: E::Fr) -> Self;
+    /// Returns the coordinates of the point on the Montgomery curve
+    fn montgomery_xy(&self, params: &Self::Params) -> Option<(E::Fr, E::Fr)>;
+    /// Returns the number of bits of the scalar field
+    fn scalar_bits() -> u32;
+
+    fn pedersen_hash_generators(params: &Self::Params) -> &[Self];
+    fn pedersen_hash_exp_table(params: &Self::Params) -> &[Vec<Vec<Self>>];
+    fn pedersen_hash_exp_window_size(params: &Self::Params) -> u32;
+    fn pedersen_hash_chunks_per_generator(params: &Self::Params) -> usize;
+    fn pedersen_circuit_generators(params: &Self::Params) -> &[Vec<Vec<(E::Fr, E::Fr)>>];
+    fn fixed_base_chunks_per_generator(params: &Self::Params) -> usize;
+    fn generator(params: &Self::Params, base: FixedGenerators) -> &Self;
+    fn circuit_generators(params: &Self::Params, base: FixedGenerators) -> &[Vec<(E::Fr, E::Fr)>];
+    /// Returns the bases and windows of the application specific tables
+    fn fixed_base_tables(params: &Self::Params) -> Vec<(&Self, &[Vec<(E::Fr, E::Fr)>])>;
+}
+
+impl<E: JubjubEngine> ParamsFilePoint<E> for edwards::Point<E, PrimeOrder> {
+    fn from_xy_checked(x: E::Fr, y: E::Fr, params: &E::Params) -> Option<Self> {
+        edwards::Point::<E, Unknown>::from_xy(x, y, params).and_then(|p| p.as_prime_order(params))
+    }
+
+    fn from_xy_unchecked(x: E::Fr, y: E::Fr) -> Self {
+        edwards::Point::from_xy_unchecked(x, y)
+    }
+
+    fn montgomery_xy(&self, params: &E::Params) -> Option<(E::Fr, E::Fr)> {
+        montgomery::Point::from_edwards(self, params).into_xy()
+    }
+
+    fn scalar_bits() -> u32 {
+        E::Fs::NUM_BITS
+    }
+
+    fn pedersen_hash_generators(params: &E::Params) -> &[Self] {
+        params.pedersen_hash_generators()
+    }
+
+    fn pedersen_hash_exp_table(params: &E::Params) -> &[Vec<Vec<Self>>] {
+        params.pedersen_hash_exp_table()
+    }
+
+    fn pedersen_hash_exp_window_size(params: &E::Params) -> u32 {
+        params.pedersen_hash_exp_window_size()
+    }
+
+    fn pedersen_hash_chunks_per_generator(params: &E::Params) -> usize {
+        params.pedersen_hash_chunks_per_generator()
+    }
+
+    fn pedersen_circuit_generators(params: &E::Params) -> &[Vec<Vec<(E::Fr, E::Fr)>>] {
+        params.pedersen_circuit_generators()
+    }
+
+    fn fixed_base_chunks_per_generator(params: &E::Params) -> usize {
+        params.fixed_base_chunks_per_generator()
+    }
+
+    fn generator(params: &E::Params, base: FixedGenerators) -> &Self {
+        params.generator(base)
+    }
+
+    fn circuit_generators(params: &E::Params, base: FixedGenerators) -> &[Vec<(E::Fr, E::Fr)>] {
+        params.circuit_generators(base)
+    }
+
+    fn fixed_base_tables(params: &E::Params) -> Vec<(&Self, &[Vec<(E::Fr, E::Fr)>])> {
+        params.fixed_base_tables().iter().map(|table| (table.base(), table.windows())).collect()
+    }
+}
+
+/// Generators and tables read from a file, to be moved into the
+/// parameters of the curve they were checked against
+pub(crate) struct ParamsData<E: Engine, P> {
+    pub pedersen_hash_generators: Vec<P>,
+    pub pedersen_hash_exp: Vec<Vec<Vec<P>>>,
+    pub pedersen_circuit_generators: Vec<Vec<Vec<(E::Fr, E::Fr)>>>,
+    pub fixed_base_generators: Vec<P>,
+    pub fixed_base_circuit_generators: Vec<Vec<Vec<(E::Fr, E::Fr)>>>,
+    /// Bases and windows of the application specific tables
+    pub fixed_base_tables: Vec<(P, Vec<Vec<(E::Fr, E::Fr)>>)>
+}
+
+/// Wraps the application specific tables read for Jubjub parameters
+pub(crate) fn fixed_base_tables<E: JubjubEngine>(
+    tables: Vec<(edwards::Point<E, PrimeOrder>, Vec<Vec<(E::Fr, E::Fr)>>)>
+) -> Vec<FixedBaseTable<E>>
+{
+    tables.into_iter().map(|(base, windows)| {
+        FixedBaseTable {
+            base: base,
+            windows: windows
+        }
+    }).collect()
+}
+
+struct HashingWriter<W: Write> {
+    writer: W,
+    hasher: Blake2s
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.writer.write(buf)?;
+        self.hasher.update(&buf[..len]);
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+struct HashingReader<R: Read> {
+    reader: R,
+    hasher: Blake2s
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.reader.read(buf)?;
+        self.hasher.update(&buf[..len]);
+
+        Ok(len)
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_len<W: Write>(mut writer: W, len: usize) -> io::Result<()> {
+    writer.write_u32::<LittleEndian>(len as u32)
+}
+
+/// Reads a length that has to be equal to the expected one. Lengths
+/// are checked before anything is allocated for them, so a corrupted
+/// file can't make the reader allocate more than it really contains.
+fn read_len<R: Read>(mut reader: R, expected: usize) -> io::Result<usize> {
+    let len = reader.read_u32::<LittleEndian>()? as usize;
+
+    if len != expected {
+        return Err(invalid_data("unexpected table size"));
+    }
+
+    Ok(len)
+}
+
+/// Reads a count that isn't known in advance. Nothing is preallocated
+/// for it, the items are collected as they are read.
+fn read_count<R: Read>(mut reader: R) -> io::Result<usize> {
+    Ok(reader.read_u32::<LittleEndian>()? as usize)
+}
+
+fn write_fr<F: PrimeField, W: Write>(writer: W, element: &F) -> io::Result<()> {
+    element.into_repr().write_le(writer)
+}
+
+fn read_fr<F: PrimeField, R: Read>(reader: R) -> io::Result<F> {
+    let mut repr = F::Repr::default();
+    repr.read_le(reader)?;
+
+    F::from_repr(repr).map_err(|_| invalid_data("element is not in field"))
+}
+
+fn write_coordinates<F: PrimeField, W: Write>(mut writer: W, coordinates: &(F, F)) -> io::Result<()> {
+    write_fr(&mut writer, &coordinates.0)?;
+    write_fr(&mut writer, &coordinates.1)
+}
+
+fn read_coordinates<F: PrimeField, R: Read>(mut reader: R) -> io::Result<(F, F)> {
+    let x = read_fr(&mut reader)?;
+    let y = read_fr(&mut reader)?;
+
+    Ok((x, y))
+}
+
+fn write_windows<F: PrimeField, W: Write>(mut writer: W, windows: &[Vec<(F, F)>]) -> io::Result<()> {
+    write_len(&mut writer, windows.len())?;
+    for window in windows {
+        write_len(&mut writer, window.len())?;
+        for coordinates in window {
+            write_coordinates(&mut writer, coordinates)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads `num_windows` windows of `window_size` points each
+fn read_windows<F: PrimeField, R: Read>(mut reader: R, num_windows: usize, window_size: usize) -> io::Result<Vec<Vec<(F, F)>>> {
+    read_len(&mut reader, num_windows)?;
+
+    let mut windows = Vec::with_capacity(num_windows);
+    for _ in 0..num_windows {
+        read_len(&mut reader, window_size)?;
+        let mut window = Vec::with_capacity(window_size);
+        for _ in 0..window_size {
+            window.push(read_coordinates(&mut reader)?);
+        }
+        windows.push(window);
+    }
+
+    Ok(windows)
+}
+
+/// Reads a generator, checking that it's a point of the prime order
+/// subgroup and not the identity
+fn read_generator<E: Engine, P: ParamsFilePoint<E>, R: Read>(reader: R, params: &P::Params) -> io::Result<P> {
+    let (x, y) = read_coordinates(reader)?;
+
+    let p = P::from_xy_checked(x, y, params).ok_or(invalid_data("generator is not a point of the prime order subgroup"))?;
+
+    if p == P::zero() {
+        return Err(invalid_data("generator is the identity"));
+    }
+
+    Ok(p)
+}
+
+/// Spot-checks the exp tables `[0, g, 2g, ...]` of a Pedersen hash
+/// generator, `g` being `2^window` times larger in every table
+fn check_exp_tables<E: Engine, P: ParamsFilePoint<E>>(generator: &P, tables: &[Vec<P>], window: u32, params: &P::Params) -> io::Result<()> {
+    let mut base = generator.clone();
+    for table in tables {
+        let last = table[table.len() - 2].add(&base, params);
+        if table[0] != P::zero() || table[1] != base || table[table.len() - 1] != last {
+            return Err(invalid_data("Pedersen hash exp table doesn't match the generator"));
+        }
+
+        for _ in 0..window {
+            base = base.double(params);
+        }
+    }
+
+    Ok(())
+}
+
+/// Spot-checks the circuit windows `[g, 2g, 3g, 4g]` of a Pedersen hash
+/// generator, `g` being 16 times larger in every window
+fn check_pedersen_windows<E: Engine, P: ParamsFilePoint<E>>(generator: &P, windows: &[Vec<(E::Fr, E::Fr)>], params: &P::Params) -> io::Result<()> {
+    let mut g = generator.clone();
+    for window in windows {
+        let last = g.double(params).double(params);
+        if Some(window[0]) != g.montgomery_xy(params) || Some(window[3]) != last.montgomery_xy(params) {
+            return Err(invalid_data("Pedersen hash circuit table doesn't match the generator"));
+        }
+
+        g = last.double(params).double(params);
+    }
+
+    Ok(())
+}
+
+/// Spot-checks the windows `[0, g, 2g, ..., 7g]` of a fixed-base table,
+/// `g` being 8 times larger in every window
+fn check_fixed_base_windows<E: Engine, P: ParamsFilePoint<E>>(base: &P, windows: &[Vec<(E::Fr, E::Fr)>], params: &P::Params) -> io::Result<()> {
+    let mut expected = Vec::with_capacity(windows.len() * 2);
+    let mut g = base.clone();
+    for _ in windows {
+        let next = g.double(params).double(params).double(params);
+        expected.push(g.clone());
+        expected.push(next.add(&g.negate(), params));
+        g = next;
+    }
+
+    let expected = batch_normalize(&expected);
+    for (window, expected) in windows.iter().zip(expected.chunks(2)) {
+        if window[0] != (E::Fr::zero(), E::Fr::one()) || window[1] != expected[0] || window[7] != expected[1] {
+            return Err(invalid_data("fixed-base table doesn't match the generator"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the generators and tables of `params`
+pub(crate) fn write<E: Engine, P: ParamsFilePoint<E>, W: Write>(params: &P::Params, writer: W) -> io::Result<()> {
+    let mut writer = HashingWriter {
+        writer: writer,
+        hasher: Blake2s::new(32)
+    };
+
+    writer.write_all(MAGIC)?;
+    writer.write_u32::<LittleEndian>(VERSION)?;
+    write_fr(&mut writer, params.edwards_a())?;
+    write_fr(&mut writer, params.edwards_d())?;
+    write_fr(&mut writer, params.montgomery_a())?;
+
+    write_len(&mut writer, P::pedersen_hash_generators(params).len())?;
+    for (generator, exp) in P::pedersen_hash_generators(params).iter().zip(P::pedersen_hash_exp_table(params).iter()) {
+        write_coordinates(&mut writer, &generator.edwards_xy())?;
+
+        write_len(&mut writer, exp.len())?;
+        for table in exp {
+            write_len(&mut writer, table.len())?;
+            for p in batch_normalize(table) {
+                write_coordinates(&mut writer, &p)?;
+            }
+        }
+    }
+
+    for windows in P::pedersen_circuit_generators(params) {
+        write_windows(&mut writer, windows)?;
+    }
+
+    for &base in FIXED_GENERATORS.iter() {
+        write_coordinates(&mut writer, &P::generator(params, base).edwards_xy())?;
+        write_windows(&mut writer, P::circuit_generators(params, base))?;
+    }
+
+    let fixed_base_tables = P::fixed_base_tables(params);
+    write_len(&mut writer, fixed_base_tables.len())?;
+    for (base, windows) in fixed_base_tables {
+        write_coordinates(&mut writer, &base.edwards_xy())?;
+        write_windows(&mut writer, windows)?;
+    }
+
+    let hash = writer.hasher.finalize();
+    writer.writer.write_all(hash.as_ref())
+}
+
+/// Reads the generators and tables written by `write`. `params` has to
+/// contain the curve constants only, they are checked against the ones
+/// in the file and used to validate the generators.
+pub(crate) fn read<E: Engine, P: ParamsFilePoint<E>, R: Read>(reader: R, params: &P::Params) -> io::Result<ParamsData<E, P>> {
+    let mut reader = HashingReader {
+        reader: reader,
+        hasher: Blake2s::new(32)
+    };
+
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a parameters file"));
+    }
+
+    if reader.read_u32::<LittleEndian>()? != VERSION {
+        return Err(invalid_data("unsupported parameters file version"));
+    }
+
+    let edwards_a: E::Fr = read_fr(&mut reader)?;
+    let edwards_d: E::Fr = read_fr(&mut reader)?;
+    let montgomery_a: E::Fr = read_fr(&mut reader)?;
+    if &edwards_a != params.edwards_a() || &edwards_d != params.edwards_d() || &montgomery_a != params.montgomery_a() {
+        return Err(invalid_data("parameters are for a different curve"));
+    }
+
+    let num_segments = read_count(&mut reader)?;
+    if num_segments == 0 {
+        return Err(invalid_data("there are no Pedersen hash generators"));
+    }
+
+    // Same number of tables as computed for the generators by `new`
+    let window = P::pedersen_hash_exp_window_size(params);
+    let num_tables = (P::scalar_bits() / window + 1) as usize;
+    let table_size = 1 << window;
+
+    let mut pedersen_hash_generators = vec![];
+    let mut pedersen_hash_exp = vec![];
+    for _ in 0..num_segments {
+        let generator = read_generator::<E, P, _>(&mut reader, params)?;
+
+        read_len(&mut reader, num_tables)?;
+        let mut tables = Vec::with_capacity(num_tables);
+        for _ in 0..num_tables {
+            read_len(&mut reader, table_size)?;
+            let mut table = Vec::with_capacity(table_size);
+            for _ in 0..table_size {
+                let (x, y) = read_coordinates(&mut reader)?;
+                table.push(P::from_xy_unchecked(x, y));
+            }
+            tables.push(table);
+        }
+
+        check_exp_tables(&generator, &tables, window, params)?;
+
+        pedersen_hash_generators.push(generator);
+        pedersen_hash_exp.push(tables);
+    }
+
+    let mut pedersen_circuit_generators = Vec::with_capacity(pedersen_hash_generators.len());
+    for generator in pedersen_hash_generators.iter() {
+        let windows = read_windows(&mut reader, P::pedersen_hash_chunks_per_generator(params), 4)?;
+        check_pedersen_windows(generator, &windows, params)?;
+
+        pedersen_circuit_generators.push(windows);
+    }
+
+    let mut fixed_base_generators = Vec::with_capacity(FIXED_GENERATORS.len());
+    let mut fixed_base_circuit_generators = Vec::with_capacity(FIXED_GENERATORS.len());
+    for _ in FIXED_GENERATORS.iter() {
+        let generator = read_generator::<E, P, _>(&mut reader, params)?;
+        let windows = read_windows(&mut reader, P::fixed_base_chunks_per_generator(params), 8)?;
+        check_fixed_base_windows(&generator, &windows, params)?;
+
+        fixed_base_generators.push(generator);
+        fixed_base_circuit_generators.push(windows);
+    }
+
+    let num_tables = read_count(&mut reader)?;
+    let mut fixed_base_tables = vec![];
+    for _ in 0..num_tables {
+        let base = read_generator::<E, P, _>(&mut reader, params)?;
+        let windows = read_windows(&mut reader, P::fixed_base_chunks_per_generator(params), 8)?;
+        check_fixed_base_windows(&base, &windows, params)?;
+
+        fixed_base_tables.push((base, windows));
+    }
+
+    let expected_hash = reader.hasher.finalize();
+    let mut hash = [0u8; 32];
+    reader.reader.read_exact(&mut hash)?;
+    if &hash[..] != expected_hash.as_ref() {
+        return Err(invalid_data("checksum mismatch"));
+    }
+
+    Ok(ParamsData {
+        pedersen_hash_generators: pedersen_hash_generators,
+        pedersen_hash_exp: pedersen_hash_exp,
+        pedersen_circuit_generators: pedersen_circuit_generators,
+        fixed_base_generators: fixed_base_generators,
+        fixed_base_circuit_generators: fixed_base_circuit_generators,
+        fixed_base_tables: fixed_base_tables
+    })
+}
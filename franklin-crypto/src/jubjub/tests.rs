@@ -1,7 +1,9 @@
 use super::{
     JubjubEngine,
     JubjubParams,
+    TwistedEdwardsParams,
     PrimeOrder,
+    Unknown,
     montgomery,
     edwards
 };
@@ -27,6 +29,8 @@ pub fn test_suite<E: JubjubEngine>(params: &E::Params) {
     test_mul_associativity::<E>(params);
     test_loworder::<E>(params);
     test_read_write::<E>(params);
+    test_wnaf_mul::<E>(params);
+    test_batch_normalize::<E>(params);
 }
 
 fn is_on_mont_curve<E: JubjubEngine, P: JubjubParams<E>>(
@@ -414,3 +418,55 @@ fn test_jubjub_params<E: JubjubEngine>(params: &E::Params) {
         assert!((params.fixed_base_chunks_per_generator() - 1) * 3 < E::Fs::NUM_BITS as usize);
     }
 }
+
+fn test_wnaf_mul<E: JubjubEngine>(params: &E::Params) {
+    use self::edwards::Point;
+    use bellman::pairing::ff::BitIterator;
+    let rng = &mut XorShiftRng::from_seed([0x4dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+    // Plain double-and-add for comparison
+    let double_and_add = |p: &Point<E, _>, s: <E::Fs as PrimeField>::Repr| {
+        let mut res = Point::zero();
+        for b in BitIterator::new(s) {
+            res = res.double(params);
+            if b {
+                res = res.add(p, params);
+            }
+        }
+        res
+    };
+
+    let mut scalars = vec![E::Fs::zero().into_repr(), E::Fs::one().into_repr(), E::Fs::char()];
+    let mut all_ones = E::Fs::char();
+    for limb in all_ones.as_mut() {
+        *limb = u64::max_value();
+    }
+    scalars.push(all_ones);
+    for _ in 0..50 {
+        scalars.push(E::Fs::rand(rng).into_repr());
+    }
+
+    let base = Point::<E, _>::rand(rng, params);
+    for s in scalars {
+        assert!(base.mul(s, params) == double_and_add(&base, s));
+    }
+}
+
+fn test_batch_normalize<E: JubjubEngine>(params: &E::Params) {
+    use self::edwards::Point;
+    let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+    assert!(Point::<E, Unknown>::batch_normalize(&[]).is_empty());
+
+    let mut points = vec![Point::<E, _>::zero()];
+    for _ in 0..20 {
+        let p = Point::<E, _>::rand(rng, params);
+        points.push(p.double(params).add(&p, params));
+    }
+
+    let affine = Point::batch_normalize(&points);
+    assert_eq!(affine.len(), points.len());
+    for (p, xy) in points.iter().zip(affine.iter()) {
+        assert!(p.into_xy() == *xy);
+    }
+}
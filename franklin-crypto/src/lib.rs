@@ -9,6 +9,8 @@ extern crate rand;
 extern crate byteorder;
 extern crate tiny_keccak;
 extern crate crypto;
+extern crate hex;
+extern crate num_bigint;
 
 #[cfg(test)]
 #[macro_use]
@@ -19,6 +21,7 @@ pub mod jubjub;
 pub mod alt_babyjubjub;
 pub mod baby_group_hash;
 pub mod group_hash;
+pub mod hash_to_curve;
 pub mod circuit;
 pub mod baby_pedersen_hash;
 pub mod pedersen_hash;
@@ -29,7 +32,9 @@ pub mod redjubjub;
 pub mod baby_util;
 pub mod util;
 pub mod eddsa;
+pub mod vrf;
 pub mod interpolation;
+pub mod polynomial;
 pub mod as_waksman;
 
 extern crate serde;
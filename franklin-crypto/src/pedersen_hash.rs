@@ -1,24 +1,56 @@
 use jubjub::*;
 use bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
+use group_hash::{find_group_hash, BlakeHasher};
+use constants::PEDERSEN_HASH_DOMAINS_PERSONALIZATION;
 
 #[derive(Copy, Clone)]
 pub enum Personalization {
     NoteCommitment,
-    MerkleTree(usize)
+    MerkleTree(usize),
+    BatchSignatureTranscript,
+    BatchSignatureChallenge,
+    InputCommitment,
+    AssetNoteCommitment
 }
 
+/// Merkle trees take the 6 bit personalizations below this depth.
+pub const MAX_MERKLE_TREE_DEPTH: usize = 63;
+
 impl Personalization {
     pub fn get_bits(&self) -> Vec<bool> {
-        match *self {
-            Personalization::NoteCommitment =>
-                vec![true, true, true, true, true, true],
+        let code = match *self {
             Personalization::MerkleTree(num) => {
-                assert!(num < 63);
+                assert!(num < MAX_MERKLE_TREE_DEPTH);
 
-                (0..6).map(|i| (num >> i) & 1 == 1).collect()
-            }
+                num
+            },
+            // The other domains share the note commitment code and
+            // are told apart by their domain generators
+            _ => 63
+        };
+
+        (0..6).map(|i| (code >> i) & 1 == 1).collect()
+    }
+
+    fn domain_tag(&self) -> Option<&'static [u8]> {
+        match *self {
+            Personalization::BatchSignatureTranscript => Some(b"batch_signature_transcript"),
+            Personalization::BatchSignatureChallenge => Some(b"batch_signature_challenge"),
+            Personalization::InputCommitment => Some(b"input_commitment"),
+            Personalization::AssetNoteCommitment => Some(b"asset_note_commitment"),
+            _ => None
         }
     }
+
+    /// The 6 bit codes are used up by the note commitments and the Merkle
+    /// tree levels. Other domains hash under the note commitment code and
+    /// add their own generator to the result, so a collision with another
+    /// domain gives a discrete log relation between independent generators.
+    pub fn domain_generator<E: JubjubEngine>(&self, params: &E::Params) -> Option<edwards::Point<E, PrimeOrder>> {
+        self.domain_tag().map(|tag| {
+            find_group_hash::<E, BlakeHasher, _>(tag, PEDERSEN_HASH_DOMAINS_PERSONALIZATION, params)
+        })
+    }
 }
 
 pub fn pedersen_hash<E, I>(
@@ -31,7 +63,7 @@ pub fn pedersen_hash<E, I>(
 {
     let mut bits = personalization.get_bits().into_iter().chain(bits.into_iter());
 
-    let mut result = edwards::Point::zero();
+    let mut result = personalization.domain_generator::<E>(params).unwrap_or(edwards::Point::zero());
     let mut generators = params.pedersen_hash_exp_table().iter();
 
     loop {
@@ -114,7 +146,7 @@ pub fn baby_pedersen_hash<E, I>(
 {
     let mut bits = personalization.get_bits().into_iter().chain(bits.into_iter());
 
-    let mut result = edwards::Point::zero();
+    let mut result = personalization.domain_generator::<E>(params).unwrap_or(edwards::Point::zero());
     let mut generators = params.pedersen_hash_exp_table().iter();
 
     loop {
@@ -184,3 +216,48 @@ pub fn baby_pedersen_hash<E, I>(
 
     result
 }
+
+#[test]
+fn test_personalizations_are_distinct() {
+    use bellman::pairing::bls12_381::Bls12;
+
+    let params = &JubjubBls12::new();
+
+    let mut all = vec![
+        Personalization::NoteCommitment,
+        Personalization::BatchSignatureTranscript,
+        Personalization::BatchSignatureChallenge,
+        Personalization::InputCommitment,
+        Personalization::AssetNoteCommitment
+    ];
+    all.extend((0..MAX_MERKLE_TREE_DEPTH).map(|i| Personalization::MerkleTree(i)));
+
+    let mut domains: Vec<(Vec<bool>, Option<(String, String)>)> = all.iter().map(|p| {
+        let generator = p.domain_generator::<Bls12>(params).map(|g| {
+            let (x, y) = g.into_xy();
+            (x.to_string(), y.to_string())
+        });
+
+        (p.get_bits(), generator)
+    }).collect();
+    domains.sort();
+    domains.dedup();
+    assert_eq!(domains.len(), all.len());
+}
+
+#[test]
+fn test_domain_generator() {
+    use bellman::pairing::bls12_381::Bls12;
+
+    let params = &JubjubBls12::new();
+    let input = vec![true, false, false, true, true];
+
+    // A domain's hash is the hash under the note commitment code plus
+    // the domain generator
+    let personalization = Personalization::BatchSignatureChallenge;
+    assert_eq!(personalization.get_bits(), Personalization::NoteCommitment.get_bits());
+
+    let expected = pedersen_hash::<Bls12, _>(Personalization::NoteCommitment, input.clone(), params)
+        .add(&personalization.domain_generator::<Bls12>(params).unwrap(), params);
+    assert!(pedersen_hash::<Bls12, _>(personalization, input, params) == expected);
+}
@@ -0,0 +1,709 @@
+use bellman::pairing::Engine;
+
+use bellman::pairing::ff::{
+    Field,
+    PrimeField
+};
+
+/// Below this size the schoolbook multiplication is faster than three FFTs
+const NAIVE_MULTIPLICATION_THRESHOLD: usize = 64;
+
+/// Multiplicative subgroup of the field of the power of two size,
+/// that is generated by the primitive root of unity of this order.
+/// Polynomials are given by coefficients from the lowest degree
+pub struct Domain<E: Engine> {
+    pub size: usize,
+    pub log_size: u32,
+    pub generator: E::Fr,
+    generator_inv: E::Fr,
+    size_inv: E::Fr
+}
+
+impl<E: Engine> Domain<E> {
+    /// Makes the smallest domain that has at least `min_size` elements,
+    /// returns `None` if the field doesn't have roots of unity of such order
+    pub fn new(min_size: usize) -> Option<Self> {
+        let mut size = 1;
+        let mut log_size = 0;
+        while size < min_size {
+            size <<= 1;
+            log_size += 1;
+
+            if log_size > E::Fr::S {
+                return None;
+            }
+        }
+
+        let mut generator = E::Fr::root_of_unity();
+        for _ in log_size..E::Fr::S {
+            generator.square();
+        }
+
+        let size_inv = E::Fr::from_str(&size.to_string())?.inverse()?;
+        let generator_inv = generator.inverse()?;
+
+        Some(Domain {
+            size,
+            log_size,
+            generator,
+            generator_inv,
+            size_inv
+        })
+    }
+
+    /// Elements of the domain in the order of powers of the generator
+    pub fn elements(&self) -> Vec<E::Fr> {
+        let mut result = Vec::with_capacity(self.size);
+        let mut element = E::Fr::one();
+        for _ in 0..self.size {
+            result.push(element);
+            element.mul_assign(&self.generator);
+        }
+
+        result
+    }
+
+    /// Evaluates the polynomial at all the elements of the domain
+    pub fn fft(&self, coeffs: &[E::Fr]) -> Vec<E::Fr> {
+        assert!(coeffs.len() <= self.size, "polynomial doesn't fit into the domain");
+        let mut values = coeffs.to_vec();
+        values.resize(self.size, E::Fr::zero());
+        serial_fft::<E>(&mut values, &self.generator, self.log_size);
+
+        values
+    }
+
+    /// Interpolates the polynomial of degree less than the size of the
+    /// domain from its values at all the elements of the domain
+    pub fn ifft(&self, values: &[E::Fr]) -> Vec<E::Fr> {
+        assert_eq!(values.len(), self.size);
+        let mut coeffs = values.to_vec();
+        serial_fft::<E>(&mut coeffs, &self.generator_inv, self.log_size);
+        for c in coeffs.iter_mut() {
+            c.mul_assign(&self.size_inv);
+        }
+
+        coeffs
+    }
+
+    /// Evaluates the polynomial at all the elements of the coset `shift * domain`
+    pub fn coset_fft(&self, coeffs: &[E::Fr], shift: &E::Fr) -> Vec<E::Fr> {
+        let mut shifted = coeffs.to_vec();
+        distribute_powers::<E>(&mut shifted, shift);
+
+        self.fft(&shifted)
+    }
+
+    /// Interpolates the polynomial from its values at the coset `shift * domain`
+    pub fn coset_ifft(&self, values: &[E::Fr], shift: &E::Fr) -> Option<Vec<E::Fr>> {
+        let mut coeffs = self.ifft(values);
+        distribute_powers::<E>(&mut coeffs, &shift.inverse()?);
+
+        Some(coeffs)
+    }
+
+    /// Evaluates the vanishing polynomial of the domain X^n - 1 at the point
+    pub fn evaluate_vanishing(&self, x: &E::Fr) -> E::Fr {
+        let mut result = x.pow(&[self.size as u64]);
+        result.sub_assign(&E::Fr::one());
+
+        result
+    }
+
+    /// Evaluates the polynomial given by its values on the domain at an arbitrary point
+    /// in O(n) using the barycentric formula
+    /// p(x) = (x^n - 1) / n * sum (v_i * w^i / (x - w^i))
+    pub fn barycentric_evaluate(&self, values: &[E::Fr], x: &E::Fr) -> E::Fr {
+        assert_eq!(values.len(), self.size);
+
+        let vanishing = self.evaluate_vanishing(x);
+        if vanishing.is_zero() {
+            // the point is in the domain itself
+            let elements = self.elements();
+            let idx = elements.iter().position(|e| e == x).expect("point must be in the domain");
+
+            return values[idx];
+        }
+
+        let elements = self.elements();
+        let mut denominators: Vec<E::Fr> = elements.iter().map(|e| {
+            let mut tmp = *x;
+            tmp.sub_assign(e);
+
+            tmp
+        }).collect();
+        batch_inversion::<E>(&mut denominators);
+
+        let mut result = E::Fr::zero();
+        for ((v, e), d) in values.iter().zip(elements.iter()).zip(denominators.iter()) {
+            let mut tmp = *v;
+            tmp.mul_assign(e);
+            tmp.mul_assign(d);
+            result.add_assign(&tmp);
+        }
+
+        result.mul_assign(&vanishing);
+        result.mul_assign(&self.size_inv);
+
+        result
+    }
+}
+
+fn serial_fft<E: Engine>(a: &mut [E::Fr], omega: &E::Fr, log_n: u32) {
+    fn bitreverse(mut n: u32, l: u32) -> u32 {
+        let mut r = 0;
+        for _ in 0..l {
+            r = (r << 1) | (n & 1);
+            n >>= 1;
+        }
+        r
+    }
+
+    let n = a.len() as u32;
+    assert_eq!(n, 1 << log_n);
+
+    for k in 0..n {
+        let rk = bitreverse(k, log_n);
+        if k < rk {
+            a.swap(rk as usize, k as usize);
+        }
+    }
+
+    let mut m = 1;
+    for _ in 0..log_n {
+        let w_m = omega.pow(&[(n / (2*m)) as u64]);
+
+        let mut k = 0;
+        while k < n {
+            let mut w = E::Fr::one();
+            for j in 0..m {
+                let mut t = a[(k+j+m) as usize];
+                t.mul_assign(&w);
+                let mut tmp = a[(k+j) as usize];
+                tmp.sub_assign(&t);
+                a[(k+j+m) as usize] = tmp;
+                a[(k+j) as usize].add_assign(&t);
+                w.mul_assign(&w_m);
+            }
+
+            k += 2*m;
+        }
+
+        m *= 2;
+    }
+}
+
+/// Multiplies the coefficient number `i` by `g^i`
+fn distribute_powers<E: Engine>(coeffs: &mut [E::Fr], g: &E::Fr) {
+    let mut power = E::Fr::one();
+    for c in coeffs.iter_mut() {
+        c.mul_assign(&power);
+        power.mul_assign(g);
+    }
+}
+
+/// Inverts all the elements with a single inversion. Elements must be non-zero
+pub fn batch_inversion<E: Engine>(elements: &mut [E::Fr]) {
+    let mut products = Vec::with_capacity(elements.len());
+    let mut accumulator = E::Fr::one();
+    for e in elements.iter() {
+        products.push(accumulator);
+        accumulator.mul_assign(e);
+    }
+
+    let mut inverse = accumulator.inverse().expect("elements must be non-zero");
+    for (e, p) in elements.iter_mut().zip(products.into_iter()).rev() {
+        let mut tmp = inverse;
+        tmp.mul_assign(&p);
+        inverse.mul_assign(e);
+        *e = tmp;
+    }
+}
+
+/// Evaluates the polynomial at the point using the Horner's rule
+pub fn evaluate<E: Engine>(coeffs: &[E::Fr], x: &E::Fr) -> E::Fr {
+    let mut result = E::Fr::zero();
+    for c in coeffs.iter().rev() {
+        result.mul_assign(x);
+        result.add_assign(c);
+    }
+
+    result
+}
+
+/// Multiplies two polynomials. Uses FFT for large inputs, so it's O(n log n)
+pub fn multiply<E: Engine>(a: &[E::Fr], b: &[E::Fr]) -> Vec<E::Fr> {
+    if a.is_empty() || b.is_empty() {
+        return vec![];
+    }
+
+    let result_len = a.len() + b.len() - 1;
+
+    if a.len().min(b.len()) < NAIVE_MULTIPLICATION_THRESHOLD {
+        let mut result = vec![E::Fr::zero(); result_len];
+        for (i, a) in a.iter().enumerate() {
+            for (j, b) in b.iter().enumerate() {
+                let mut tmp = *a;
+                tmp.mul_assign(b);
+                result[i + j].add_assign(&tmp);
+            }
+        }
+
+        return result;
+    }
+
+    let domain = Domain::<E>::new(result_len).expect("field must have roots of unity of large enough order");
+
+    let mut values = domain.fft(a);
+    for (v, b) in values.iter_mut().zip(domain.fft(b).into_iter()) {
+        v.mul_assign(&b);
+    }
+
+    let mut result = domain.ifft(&values);
+    result.truncate(result_len);
+
+    result
+}
+
+/// Divides the polynomial by the vanishing polynomial X^n - 1 of the
+/// domain of the size `n`. Returns `None` if the polynomial is not divisible
+pub fn divide_by_vanishing<E: Engine>(coeffs: &[E::Fr], n: usize) -> Option<Vec<E::Fr>> {
+    assert!(n > 0);
+    if coeffs.len() <= n {
+        return if coeffs.iter().all(|c| c.is_zero()) { Some(vec![]) } else { None };
+    }
+
+    // p(X) = q(X) * (X^n - 1), so q_i = p_(i+n) + q_(i+n) going from the top
+    let mut quotient = vec![E::Fr::zero(); coeffs.len() - n];
+    for i in (0..quotient.len()).rev() {
+        let mut tmp = coeffs[i + n];
+        if i + n < quotient.len() {
+            tmp.add_assign(&quotient[i + n]);
+        }
+        quotient[i] = tmp;
+    }
+
+    // remainder is p_i + q_i for the lowest coefficients
+    for i in 0..n {
+        let mut remainder = coeffs[i];
+        if i < quotient.len() {
+            remainder.add_assign(&quotient[i]);
+        }
+        if !remainder.is_zero() {
+            return None;
+        }
+    }
+
+    Some(quotient)
+}
+
+/// Divides `a` by `b`, returns the quotient and the remainder. Leading coefficient
+/// of `b` must be non-zero. Large divisions go through the inverse of the reversed
+/// divisor as a power series, so they cost a few FFT multiplications
+pub fn divide_with_remainder<E: Engine>(a: &[E::Fr], b: &[E::Fr]) -> (Vec<E::Fr>, Vec<E::Fr>) {
+    let leading = b.last().expect("divisor must not be empty");
+    let leading_inv = leading.inverse().expect("leading coefficient of the divisor must be non-zero");
+    if a.len() < b.len() {
+        return (vec![], a.to_vec());
+    }
+
+    let quotient_len = a.len() - b.len() + 1;
+
+    let quotient = if quotient_len.min(b.len()) < NAIVE_MULTIPLICATION_THRESHOLD {
+        let mut remainder = a.to_vec();
+        let mut quotient = vec![E::Fr::zero(); quotient_len];
+        for i in (0..quotient_len).rev() {
+            let mut coeff = remainder[i + b.len() - 1];
+            coeff.mul_assign(&leading_inv);
+            for (j, b) in b.iter().enumerate() {
+                let mut tmp = *b;
+                tmp.mul_assign(&coeff);
+                remainder[i + j].sub_assign(&tmp);
+            }
+            quotient[i] = coeff;
+        }
+
+        quotient
+    } else {
+        // rev(a) = rev(q) * rev(b) mod X^(deg q + 1)
+        let reversed_b: Vec<E::Fr> = b.iter().rev().cloned().collect();
+        let reversed_a: Vec<E::Fr> = a.iter().rev().take(quotient_len).cloned().collect();
+        let inverse = inverse_series::<E>(&reversed_b, quotient_len);
+
+        let mut quotient = multiply::<E>(&reversed_a, &inverse);
+        quotient.truncate(quotient_len);
+        quotient.reverse();
+
+        quotient
+    };
+
+    let mut remainder = a.to_vec();
+    for (r, p) in remainder.iter_mut().zip(multiply::<E>(&quotient, b).iter()) {
+        r.sub_assign(p);
+    }
+    remainder.truncate(b.len() - 1);
+
+    (quotient, remainder)
+}
+
+/// Power series `g` such that `f * g = 1 mod X^len` by the Newton iteration
+fn inverse_series<E: Engine>(f: &[E::Fr], len: usize) -> Vec<E::Fr> {
+    let mut two = E::Fr::one();
+    two.double();
+
+    let mut result = vec![f[0].inverse().expect("constant term must be non-zero")];
+    while result.len() < len {
+        let precision = (2 * result.len()).min(len);
+
+        // g = g * (2 - f * g) mod X^precision
+        let mut correction = multiply::<E>(&f[..precision.min(f.len())], &result);
+        correction.resize(precision, E::Fr::zero());
+        for c in correction.iter_mut() {
+            c.negate();
+        }
+        correction[0].add_assign(&two);
+
+        result = multiply::<E>(&result, &correction);
+        result.truncate(precision);
+    }
+
+    result
+}
+
+/// Tree of the products of (X - x_i) for a set of distinct points, where every level
+/// multiplies pairs of the polynomials of the level below. Gives the vanishing
+/// polynomial of the points, the evaluation at all the points and the interpolation
+/// in O(n log^2 n) instead of O(n^2)
+pub struct SubproductTree<E: Engine> {
+    // from the leaves (X - x_i) up to the single root
+    levels: Vec<Vec<Vec<E::Fr>>>,
+    // 1 / prod_(j != i) (x_i - x_j)
+    weights: Vec<E::Fr>
+}
+
+impl<E: Engine> SubproductTree<E> {
+    /// Builds the tree, returns `None` if the points are not distinct
+    pub fn new(points: &[E::Fr]) -> Option<Self> {
+        assert!(!points.is_empty(), "tree needs at least one point");
+
+        let leaves = points.iter().map(|x| {
+            let mut minus_x = *x;
+            minus_x.negate();
+
+            vec![minus_x, E::Fr::one()]
+        }).collect();
+
+        let mut levels: Vec<Vec<Vec<E::Fr>>> = vec![leaves];
+        while levels.last().expect("is never empty").len() > 1 {
+            let next = levels.last().expect("is never empty").chunks(2).map(|pair| {
+                if pair.len() == 2 {
+                    multiply::<E>(&pair[0], &pair[1])
+                } else {
+                    pair[0].clone()
+                }
+            }).collect();
+            levels.push(next);
+        }
+
+        let mut tree = SubproductTree::<E> {
+            levels,
+            weights: vec![]
+        };
+
+        // derivative of the vanishing polynomial is prod_(j != i) (x_i - x_j) at x_i
+        let mut derivative = vec![];
+        let mut degree = E::Fr::zero();
+        for c in tree.vanishing_polynomial().iter() {
+            let mut tmp = *c;
+            tmp.mul_assign(&degree);
+            derivative.push(tmp);
+            degree.add_assign(&E::Fr::one());
+        }
+        derivative.remove(0);
+
+        let mut weights = tree.evaluate(&derivative);
+        if weights.iter().any(|w| w.is_zero()) {
+            return None;
+        }
+        batch_inversion::<E>(&mut weights);
+        tree.weights = weights;
+
+        Some(tree)
+    }
+
+    /// Polynomial that is zero exactly at all the points
+    pub fn vanishing_polynomial(&self) -> &[E::Fr] {
+        &self.levels.last().expect("is never empty")[0]
+    }
+
+    /// Evaluates the polynomial at all the points by going down the tree
+    /// with the remainders of the division by every node
+    pub fn evaluate(&self, coeffs: &[E::Fr]) -> Vec<E::Fr> {
+        let mut remainders = vec![coeffs.to_vec()];
+        for level in self.levels.iter().rev() {
+            remainders = level.iter().enumerate().map(|(i, node)| {
+                divide_with_remainder::<E>(&remainders[i / 2], node).1
+            }).collect();
+        }
+
+        remainders.into_iter().map(|r| r.get(0).cloned().unwrap_or(E::Fr::zero())).collect()
+    }
+
+    /// Interpolates the polynomial of degree less than the number of points
+    /// from its values at all the points, as the sum of
+    /// v_i * w_i * prod_(j != i) (X - x_j) that is collected up the tree
+    pub fn interpolate(&self, values: &[E::Fr]) -> Vec<E::Fr> {
+        assert_eq!(values.len(), self.weights.len());
+
+        let mut sums: Vec<Vec<E::Fr>> = values.iter().zip(self.weights.iter()).map(|(v, w)| {
+            let mut tmp = *v;
+            tmp.mul_assign(w);
+
+            vec![tmp]
+        }).collect();
+
+        for level in self.levels.iter().take(self.levels.len() - 1) {
+            sums = sums.chunks(2).zip(level.chunks(2)).map(|(sum, node)| {
+                if sum.len() == 1 {
+                    return sum[0].clone();
+                }
+
+                let mut result = multiply::<E>(&sum[0], &node[1]);
+                let other = multiply::<E>(&sum[1], &node[0]);
+                if other.len() > result.len() {
+                    result.resize(other.len(), E::Fr::zero());
+                }
+                for (r, o) in result.iter_mut().zip(other.iter()) {
+                    r.add_assign(o);
+                }
+
+                result
+            }).collect();
+        }
+
+        let mut result = sums.pop().expect("is never empty");
+        result.resize(values.len(), E::Fr::zero());
+
+        result
+    }
+}
+
+/// Divides the polynomial by (X - z), returns the quotient and the remainder
+/// that is equal to the value of the polynomial at `z`
+pub fn divide_by_linear<E: Engine>(coeffs: &[E::Fr], z: &E::Fr) -> (Vec<E::Fr>, E::Fr) {
+    if coeffs.is_empty() {
+        return (vec![], E::Fr::zero());
+    }
+
+    let mut quotient = vec![E::Fr::zero(); coeffs.len() - 1];
+    let mut carry = E::Fr::zero();
+    for (i, c) in coeffs.iter().enumerate().rev() {
+        let mut tmp = carry;
+        tmp.mul_assign(z);
+        tmp.add_assign(c);
+        carry = tmp;
+        if i > 0 {
+            quotient[i - 1] = carry;
+        }
+    }
+
+    (quotient, carry)
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{XorShiftRng, SeedableRng, Rand};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use bellman::pairing::ff::{Field, PrimeField};
+    use super::*;
+
+    fn random_poly(rng: &mut XorShiftRng, len: usize) -> Vec<Fr> {
+        (0..len).map(|_| Fr::rand(rng)).collect()
+    }
+
+    #[test]
+    fn test_fft_and_ifft() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        for log_size in 0..8 {
+            let domain = Domain::<Bn256>::new(1 << log_size).unwrap();
+            assert_eq!(domain.size, 1 << log_size);
+            assert_eq!(domain.evaluate_vanishing(&domain.generator), Fr::zero());
+
+            let coeffs = random_poly(rng, domain.size);
+            let values = domain.fft(&coeffs);
+            for (x, v) in domain.elements().iter().zip(values.iter()) {
+                assert_eq!(evaluate::<Bn256>(&coeffs, x), *v);
+                assert_eq!(::interpolation::evaluate_at_x::<Bn256>(&coeffs, x), *v);
+            }
+            assert_eq!(domain.ifft(&values), coeffs);
+
+            let shift = Fr::multiplicative_generator();
+            let coset_values = domain.coset_fft(&coeffs, &shift);
+            for (x, v) in domain.elements().iter().zip(coset_values.iter()) {
+                let mut x = *x;
+                x.mul_assign(&shift);
+                assert_eq!(evaluate::<Bn256>(&coeffs, &x), *v);
+            }
+            assert_eq!(domain.coset_ifft(&coset_values, &shift).unwrap(), coeffs);
+        }
+
+        assert!(Domain::<Bn256>::new((1 << Fr::S) + 1).is_none());
+    }
+
+    #[test]
+    fn test_interpolation_matches_lagrange() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let domain = Domain::<Bn256>::new(16).unwrap();
+        let values = random_poly(rng, domain.size);
+
+        let points: Vec<(Fr, Fr)> = domain.elements().into_iter().zip(values.iter().cloned()).collect();
+        let lagrange = ::interpolation::interpolate::<Bn256>(&points).unwrap();
+
+        assert_eq!(domain.ifft(&values), lagrange);
+    }
+
+    #[test]
+    fn test_multiplication() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        for &(a_len, b_len) in [(1, 1), (3, 70), (64, 64), (100, 300), (2000, 3000)].iter() {
+            let a = random_poly(rng, a_len);
+            let b = random_poly(rng, b_len);
+            let product = multiply::<Bn256>(&a, &b);
+            assert_eq!(product.len(), a_len + b_len - 1);
+
+            for _ in 0..5 {
+                let x = Fr::rand(rng);
+                let mut expected = evaluate::<Bn256>(&a, &x);
+                expected.mul_assign(&evaluate::<Bn256>(&b, &x));
+                assert_eq!(evaluate::<Bn256>(&product, &x), expected);
+            }
+        }
+
+        assert!(multiply::<Bn256>(&[], &[Fr::one()]).is_empty());
+    }
+
+    #[test]
+    fn test_barycentric_evaluation() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let domain = Domain::<Bn256>::new(4096).unwrap();
+        let coeffs = random_poly(rng, domain.size);
+        let values = domain.fft(&coeffs);
+
+        for _ in 0..5 {
+            let x = Fr::rand(rng);
+            assert_eq!(domain.barycentric_evaluate(&values, &x), evaluate::<Bn256>(&coeffs, &x));
+        }
+
+        let elements = domain.elements();
+        assert_eq!(domain.barycentric_evaluate(&values, &elements[17]), values[17]);
+    }
+
+    #[test]
+    fn test_division() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let domain = Domain::<Bn256>::new(1024).unwrap();
+
+        // polynomial that is zero on the domain
+        let mut vanishing = vec![Fr::zero(); domain.size + 1];
+        vanishing[0].sub_assign(&Fr::one());
+        vanishing[domain.size] = Fr::one();
+        let quotient = random_poly(rng, 3 * domain.size + 5);
+        let product = multiply::<Bn256>(&quotient, &vanishing);
+
+        assert_eq!(divide_by_vanishing::<Bn256>(&product, domain.size).unwrap(), quotient);
+
+        let mut not_divisible = product.clone();
+        not_divisible[3].add_assign(&Fr::one());
+        assert!(divide_by_vanishing::<Bn256>(&not_divisible, domain.size).is_none());
+        assert!(divide_by_vanishing::<Bn256>(&[Fr::one()], domain.size).is_none());
+
+        // degree of the vanishing polynomial doesn't have to be a power of two
+        for &n in [1, 3, 100].iter() {
+            let mut vanishing = vec![Fr::zero(); n + 1];
+            vanishing[0].sub_assign(&Fr::one());
+            vanishing[n] = Fr::one();
+            let product = multiply::<Bn256>(&quotient, &vanishing);
+            assert_eq!(divide_by_vanishing::<Bn256>(&product, n).unwrap(), quotient);
+
+            let mut not_divisible = product.clone();
+            not_divisible[product.len() - 1].add_assign(&Fr::one());
+            assert!(divide_by_vanishing::<Bn256>(&not_divisible, n).is_none());
+        }
+
+        let z = Fr::rand(rng);
+        let (linear_quotient, remainder) = divide_by_linear::<Bn256>(&quotient, &z);
+        assert_eq!(remainder, evaluate::<Bn256>(&quotient, &z));
+
+        let mut minus_z = z;
+        minus_z.negate();
+        let mut restored = multiply::<Bn256>(&linear_quotient, &[minus_z, Fr::one()]);
+        restored[0].add_assign(&remainder);
+        assert_eq!(restored, quotient);
+    }
+
+    #[test]
+    fn test_division_with_remainder() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        for &(a_len, b_len) in [(1, 1), (5, 7), (10, 3), (300, 100), (1000, 999), (2000, 70)].iter() {
+            let a = random_poly(rng, a_len);
+            let b = random_poly(rng, b_len);
+            let (quotient, remainder) = divide_with_remainder::<Bn256>(&a, &b);
+            assert!(remainder.len() < b_len);
+
+            let mut restored = multiply::<Bn256>(&quotient, &b);
+            restored.resize(a_len, Fr::zero());
+            for (r, c) in restored.iter_mut().zip(remainder.iter()) {
+                r.add_assign(c);
+            }
+            assert_eq!(restored, a);
+        }
+    }
+
+    #[test]
+    fn test_subproduct_tree() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        for &size in [1, 2, 3, 17, 70, 300].iter() {
+            let points = random_poly(rng, size);
+            let tree = SubproductTree::<Bn256>::new(&points).unwrap();
+
+            let vanishing = tree.vanishing_polynomial();
+            assert_eq!(vanishing.len(), size + 1);
+            for x in points.iter() {
+                assert!(evaluate::<Bn256>(vanishing, x).is_zero());
+            }
+
+            let coeffs = random_poly(rng, 2 * size + 1);
+            let values = tree.evaluate(&coeffs);
+            for (x, v) in points.iter().zip(values.iter()) {
+                assert_eq!(evaluate::<Bn256>(&coeffs, x), *v);
+            }
+
+            let values = random_poly(rng, size);
+            let interpolated = tree.interpolate(&values);
+            assert_eq!(interpolated.len(), size);
+            for (x, v) in points.iter().zip(values.iter()) {
+                assert_eq!(evaluate::<Bn256>(&interpolated, x), *v);
+            }
+            if size >= 2 {
+                let points: Vec<(Fr, Fr)> = points.iter().cloned().zip(values.iter().cloned()).collect();
+                assert_eq!(::interpolation::interpolate::<Bn256>(&points).unwrap(), interpolated);
+            }
+        }
+
+        let mut points = random_poly(rng, 10);
+        points[7] = points[2];
+        assert!(SubproductTree::<Bn256>::new(&points).is_none());
+    }
+
+    #[test]
+    fn test_batch_inversion() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let elements = random_poly(rng, 100);
+        let mut inverted = elements.clone();
+        batch_inversion::<Bn256>(&mut inverted);
+
+        for (e, i) in elements.iter().zip(inverted.iter()) {
+            assert_eq!(e.inverse().unwrap(), *i);
+        }
+    }
+}
@@ -0,0 +1,339 @@
+//! Asset types for value commitments to several assets in one pool.
+//!
+//! The value generator of an asset type is the group hash of its 32 byte
+//! identifier, so commitments to different assets are independent and
+//! balance separately:
+//!
+//! `cv = [value] GH(identifier) + [rcv] ValueCommitmentRandomness`
+
+use bellman::pairing::ff::{
+    Field,
+    PrimeField
+};
+
+use byteorder::{
+    LittleEndian,
+    WriteBytesExt
+};
+
+use blake2_rfc::blake2s::Blake2s;
+
+use constants;
+
+use group_hash::{
+    generic_group_hash,
+    BlakeHasher
+};
+
+use pedersen_hash::{
+    pedersen_hash,
+    Personalization
+};
+
+use jubjub::{
+    JubjubEngine,
+    JubjubParams,
+    edwards,
+    PrimeOrder,
+    Unknown,
+    FixedGenerators
+};
+
+use super::{
+    nullifier,
+    ViewingKey
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AssetType {
+    identifier: [u8; 32]
+}
+
+impl AssetType {
+    /// Derives the asset type of `name`, as the first identifier
+    /// `BLAKE2s(name || i)` for a one byte counter `i` that has a
+    /// value generator.
+    pub fn new<E: JubjubEngine>(name: &[u8], params: &E::Params) -> Option<Self> {
+        for i in 0..=255u8 {
+            let mut h = Blake2s::with_params(32, &[], &[], constants::ASSET_IDENTIFIER_PERSONALIZATION);
+            h.update(name);
+            h.update(&[i]);
+
+            let mut identifier = [0u8; 32];
+            identifier.copy_from_slice(h.finalize().as_ref());
+
+            if let Some(asset_type) = Self::from_identifier::<E>(&identifier, params) {
+                return Some(asset_type);
+            }
+        }
+
+        None
+    }
+
+    /// Returns `None` if the identifier has no value generator.
+    pub fn from_identifier<E: JubjubEngine>(identifier: &[u8; 32], params: &E::Params) -> Option<Self> {
+        let asset_type = AssetType {
+            identifier: *identifier
+        };
+
+        asset_type.try_value_commitment_generator::<E>(params).map(|_| asset_type)
+    }
+
+    pub fn get_identifier(&self) -> &[u8; 32] {
+        &self.identifier
+    }
+
+    fn try_value_commitment_generator<E: JubjubEngine>(
+        &self,
+        params: &E::Params
+    ) -> Option<edwards::Point<E, PrimeOrder>>
+    {
+        generic_group_hash::<E, BlakeHasher, _>(&self.identifier, constants::ASSET_GENERATOR_PERSONALIZATION, params)
+    }
+
+    pub fn value_commitment_generator<E: JubjubEngine>(
+        &self,
+        params: &E::Params
+    ) -> edwards::Point<E, PrimeOrder>
+    {
+        self.try_value_commitment_generator(params)
+            .expect("asset types are only constructed with a generator")
+    }
+
+    /// The point hashed to by the group hash, before its cofactor is
+    /// cleared, as witnessed by the circuits.
+    pub(crate) fn unscaled_value_commitment_generator<E: JubjubEngine>(
+        &self,
+        params: &E::Params
+    ) -> edwards::Point<E, Unknown>
+    {
+        let mut h = Blake2s::with_params(32, &[], &[], constants::ASSET_GENERATOR_PERSONALIZATION);
+        h.update(constants::GH_FIRST_BLOCK);
+        h.update(&self.identifier);
+
+        edwards::Point::read(h.finalize().as_ref(), params)
+            .expect("asset types are only constructed with a generator")
+    }
+
+    pub fn value_commitment<E: JubjubEngine>(&self, value: u64, randomness: E::Fs) -> AssetValueCommitment<E> {
+        AssetValueCommitment {
+            asset_type: *self,
+            value: value,
+            randomness: randomness
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AssetValueCommitment<E: JubjubEngine> {
+    pub asset_type: AssetType,
+    pub value: u64,
+    pub randomness: E::Fs
+}
+
+impl<E: JubjubEngine> AssetValueCommitment<E> {
+    pub fn cm(
+        &self,
+        params: &E::Params
+    ) -> edwards::Point<E, PrimeOrder>
+    {
+        self.asset_type.value_commitment_generator(params)
+            .mul(self.value, params)
+            .add(
+                &params.generator(FixedGenerators::ValueCommitmentRandomness)
+                .mul(self.randomness, params),
+                params
+            )
+    }
+}
+
+/// Computes `sum [v_i] GH(asset_i)` for the signed values of the
+/// transparent value balance.
+pub fn value_balance_point<E: JubjubEngine>(
+    value_balance: &[(AssetType, i64)],
+    params: &E::Params
+) -> edwards::Point<E, Unknown>
+{
+    value_balance.iter().fold(edwards::Point::zero(), |acc, &(ref asset_type, value)| {
+        let magnitude = asset_type.value_commitment_generator::<E>(params)
+                                  .mul(value.unsigned_abs(), params);
+        let term = if value < 0 { magnitude.negate() } else { magnitude };
+
+        acc.add(&term.into(), params)
+    })
+}
+
+/// Computes `sum cv_spend - sum cv_output - value_balance_point(value_balance)`,
+/// which is `[sum rcv_spend - sum rcv_output] ValueCommitmentRandomness` if and
+/// only if the values of every asset balance.
+pub fn balance_residual<E: JubjubEngine>(
+    spends: &[edwards::Point<E, Unknown>],
+    outputs: &[edwards::Point<E, Unknown>],
+    value_balance: &[(AssetType, i64)],
+    params: &E::Params
+) -> edwards::Point<E, Unknown>
+{
+    let mut residual = spends.iter().fold(edwards::Point::zero(), |acc, cv| acc.add(cv, params));
+
+    for cv in outputs {
+        residual = residual.add(&cv.negate(), params);
+    }
+
+    residual.add(&value_balance_point(value_balance, params).negate(), params)
+}
+
+/// Checks that the values of every asset balance, given the
+/// sum of the randomness of the spends minus that of the outputs.
+pub fn check_balance<E: JubjubEngine>(
+    spends: &[edwards::Point<E, Unknown>],
+    outputs: &[edwards::Point<E, Unknown>],
+    value_balance: &[(AssetType, i64)],
+    rcv_sum: E::Fs,
+    params: &E::Params
+) -> bool
+{
+    let expected: edwards::Point<E, Unknown> = params.generator(FixedGenerators::ValueCommitmentRandomness)
+                                                     .mul(rcv_sum, params)
+                                                     .into();
+
+    balance_residual(spends, outputs, value_balance, params) == expected
+}
+
+/// A note of some asset type. The value generator of the asset is
+/// placed in the note contents before the value.
+pub struct AssetNote<E: JubjubEngine> {
+    /// The type of the asset
+    pub asset_type: AssetType,
+    /// The value of the note
+    pub value: u64,
+    /// The diversified base of the address, GH(d)
+    pub g_d: edwards::Point<E, PrimeOrder>,
+    /// The public key of the address, g_d^ivk
+    pub pk_d: edwards::Point<E, PrimeOrder>,
+    /// The commitment randomness
+    pub r: E::Fs
+}
+
+impl<E: JubjubEngine> AssetNote<E> {
+    /// Computes the note commitment, returning the full point.
+    fn cm_full_point(&self, params: &E::Params) -> edwards::Point<E, PrimeOrder>
+    {
+        // Calculate the note contents, as bytes
+        let mut note_contents = vec![];
+
+        // Write the value generator of the asset
+        self.asset_type.value_commitment_generator::<E>(params).write(&mut note_contents).unwrap();
+
+        // Writing the value in little endian
+        (&mut note_contents).write_u64::<LittleEndian>(self.value).unwrap();
+
+        // Write g_d
+        self.g_d.write(&mut note_contents).unwrap();
+
+        // Write pk_d
+        self.pk_d.write(&mut note_contents).unwrap();
+
+        assert_eq!(note_contents.len(), 32 + 8 + 32 + 32);
+
+        // Compute the Pedersen hash of the note contents, in a domain
+        // separate from the commitments of notes without an asset type
+        let hash_of_contents = pedersen_hash(
+            Personalization::AssetNoteCommitment,
+            note_contents.into_iter()
+                         .flat_map(|byte| {
+                            (0..8).map(move |i| ((byte >> i) & 1) == 1)
+                         }),
+            params
+        );
+
+        // Compute final commitment
+        params.generator(FixedGenerators::NoteCommitmentRandomness)
+              .mul(self.r, params)
+              .add(&hash_of_contents, params)
+    }
+
+    /// Computes the nullifier given the viewing key and
+    /// note position
+    pub fn nf(
+        &self,
+        viewing_key: &ViewingKey<E>,
+        position: u64,
+        params: &E::Params
+    ) -> Vec<u8>
+    {
+        nullifier(&self.cm_full_point(params), viewing_key, position, params)
+    }
+
+    /// Computes the note commitment
+    pub fn cm(&self, params: &E::Params) -> E::Fr
+    {
+        self.cm_full_point(params).into_xy().0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bellman::pairing::bls12_381::Bls12;
+    use bellman::pairing::bn256::Bn256;
+    use rand::{XorShiftRng, SeedableRng, Rng};
+
+    use jubjub::{JubjubBls12, fs};
+    use alt_babyjubjub::AltJubjubBn256;
+
+    use super::*;
+
+    #[test]
+    fn test_asset_generators() {
+        let params = &JubjubBls12::new();
+
+        let a = AssetType::new::<Bls12>(b"token a", params).unwrap();
+        let b = AssetType::new::<Bls12>(b"token b", params).unwrap();
+        assert!(a != b);
+        assert_eq!(AssetType::from_identifier::<Bls12>(a.get_identifier(), params), Some(a));
+
+        let g_a = a.value_commitment_generator::<Bls12>(params);
+        assert!(g_a != b.value_commitment_generator::<Bls12>(params));
+        assert!(&g_a != params.generator(FixedGenerators::ValueCommitmentValue));
+        assert!(a.unscaled_value_commitment_generator::<Bls12>(params).mul_by_cofactor(params) == g_a);
+
+        let params = &AltJubjubBn256::new();
+        let a = AssetType::new::<Bn256>(b"token a", params).unwrap();
+        assert!(a.unscaled_value_commitment_generator::<Bn256>(params).mul_by_cofactor(params)
+                == a.value_commitment_generator::<Bn256>(params));
+    }
+
+    #[test]
+    fn test_balance_per_asset() {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let a = AssetType::new::<Bls12>(b"token a", params).unwrap();
+        let b = AssetType::new::<Bls12>(b"token b", params).unwrap();
+
+        let spends = vec![a.value_commitment::<Bls12>(100, rng.gen()), b.value_commitment(50, rng.gen())];
+        let outputs = vec![a.value_commitment::<Bls12>(70, rng.gen()), b.value_commitment(50, rng.gen())];
+
+        let mut rcv_sum = fs::Fs::zero();
+        for cv in &spends {
+            rcv_sum.add_assign(&cv.randomness);
+        }
+        for cv in &outputs {
+            rcv_sum.sub_assign(&cv.randomness);
+        }
+
+        let spends: Vec<_> = spends.iter().map(|cv| cv.cm(params).into()).collect();
+        let outputs: Vec<_> = outputs.iter().map(|cv| cv.cm(params).into()).collect();
+
+        assert!(check_balance(&spends, &outputs, &[(a, 30)], rcv_sum, params));
+        assert!(check_balance(&spends, &outputs, &[(a, 30), (b, 0)], rcv_sum, params));
+
+        // Values of one asset can't pay for another
+        assert!(!check_balance(&spends, &outputs, &[(b, 30)], rcv_sum, params));
+        assert!(!check_balance(&spends, &outputs, &[(a, 31), (b, -1)], rcv_sum, params));
+        assert!(!check_balance(&spends, &outputs, &[(a, 30)], fs::Fs::zero(), params));
+
+        // Negative value balances mint into the pool
+        let outputs: Vec<_> = vec![a.value_commitment::<Bls12>(5, fs::Fs::zero()).cm(params).into()];
+        assert!(check_balance(&[], &outputs, &[(a, -5)], fs::Fs::zero(), params));
+    }
+}
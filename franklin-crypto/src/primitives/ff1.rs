@@ -0,0 +1,179 @@
+//! FF1 format-preserving encryption (NIST SP 800-38G) with AES-256,
+//! used by ZIP 32 to map diversifier indices to diversifiers.
+
+use crypto::aessafe::AesSafe256Encryptor;
+use crypto::symmetriccipher::BlockEncryptor;
+
+use num_bigint::BigUint;
+
+pub struct FF1 {
+    cipher: AesSafe256Encryptor,
+    radix: u32
+}
+
+fn big_to_u32(x: &BigUint) -> u32 {
+    x.to_bytes_be().iter().fold(0u32, |acc, &b| (acc << 8) | (b as u32))
+}
+
+fn num(x: &[u32], radix: u32) -> BigUint {
+    let radix = BigUint::from(radix);
+
+    x.iter().fold(BigUint::from(0u32), |acc, &d| acc * &radix + BigUint::from(d))
+}
+
+fn str_m(mut x: BigUint, m: usize, radix: u32) -> Vec<u32> {
+    let radix = BigUint::from(radix);
+
+    let mut res = vec![0; m];
+    for d in res.iter_mut().rev() {
+        *d = big_to_u32(&(&x % &radix));
+        x = x / &radix;
+    }
+
+    res
+}
+
+/// Writes `x` as a big-endian number of exactly `len` bytes.
+fn bytes_be(x: &BigUint, len: usize) -> Vec<u8> {
+    let bytes = x.to_bytes_be();
+    assert!(bytes.len() <= len);
+
+    let mut res = vec![0u8; len - bytes.len()];
+    res.extend(bytes);
+
+    res
+}
+
+impl FF1 {
+    pub fn new(key: &[u8; 32], radix: u32) -> Self {
+        assert!(radix >= 2 && radix <= (1 << 16));
+
+        FF1 {
+            cipher: AesSafe256Encryptor::new(key),
+            radix: radix
+        }
+    }
+
+    fn ciph(&self, block: &[u8]) -> [u8; 16] {
+        let mut out = [0u8; 16];
+        self.cipher.encrypt_block(block, &mut out);
+
+        out
+    }
+
+    /// CBC-MAC with a zero IV.
+    fn prf(&self, x: &[u8]) -> [u8; 16] {
+        assert_eq!(x.len() % 16, 0);
+
+        let mut y = [0u8; 16];
+        for block in x.chunks(16) {
+            for (y, b) in y.iter_mut().zip(block.iter()) {
+                *y ^= *b;
+            }
+            y = self.ciph(&y);
+        }
+
+        y
+    }
+
+    pub fn encrypt(&self, tweak: &[u8], x: &[u32]) -> Vec<u32> {
+        let radix = self.radix;
+        let n = x.len();
+        let t = tweak.len();
+        assert!(n >= 2);
+        assert!(x.iter().all(|&d| d < radix));
+
+        let u = n / 2;
+        let v = n - u;
+        let mut a = x[0..u].to_vec();
+        let mut b = x[u..].to_vec();
+
+        // b = ceil(ceil(v.log2(radix)) / 8)
+        let mut radix_v = BigUint::from(1u32);
+        for _ in 0..v {
+            radix_v = radix_v * BigUint::from(radix);
+        }
+        let beta = ((radix_v - BigUint::from(1u32)).bits() + 7) / 8;
+        let d = 4 * ((beta + 3) / 4) + 4;
+
+        let mut p = vec![1, 2, 1];
+        p.extend_from_slice(&bytes_be(&BigUint::from(radix), 3));
+        p.push(10);
+        p.push((u % 256) as u8);
+        p.extend_from_slice(&bytes_be(&BigUint::from(n as u32), 4));
+        p.extend_from_slice(&bytes_be(&BigUint::from(t as u32), 4));
+
+        for i in 0..10 {
+            let mut q = tweak.to_vec();
+            let zeros = (16 - ((t + beta + 1) % 16)) % 16;
+            q.extend(vec![0u8; zeros]);
+            q.push(i as u8);
+            q.extend(bytes_be(&num(&b, radix), beta));
+
+            let mut pq = p.clone();
+            pq.extend(q);
+            let r = self.prf(&pq);
+
+            let mut s = r.to_vec();
+            for j in 1..((d + 15) / 16) {
+                let mut block = r;
+                for (k, byte) in bytes_be(&BigUint::from(j as u32), 16).into_iter().enumerate() {
+                    block[k] ^= byte;
+                }
+                s.extend_from_slice(&self.ciph(&block));
+            }
+            s.truncate(d);
+
+            let y = BigUint::from_bytes_be(&s);
+            let m = if i % 2 == 0 { u } else { v };
+
+            let mut radix_m = BigUint::from(1u32);
+            for _ in 0..m {
+                radix_m = radix_m * BigUint::from(radix);
+            }
+
+            let c = (num(&a, radix) + y) % radix_m;
+
+            a = b;
+            b = str_m(c, m, radix);
+        }
+
+        a.extend(b);
+
+        a
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hex;
+    use super::FF1;
+
+    fn digits(s: &str) -> Vec<u32> {
+        s.chars().map(|c| c.to_digit(36).unwrap()).collect()
+    }
+
+    fn key() -> [u8; 32] {
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&hex::decode("2b7e151628aed2a6abf7158809cf4f3cef4359d8d580aa4f7f036d6f04fc6a94").unwrap());
+
+        key
+    }
+
+    #[test]
+    fn test_ff1_nist_samples() {
+        // NIST SP 800-38G samples 7 to 9
+        let ff1 = FF1::new(&key(), 10);
+        assert_eq!(ff1.encrypt(&[], &digits("0123456789")), digits("6657667009"));
+        assert_eq!(
+            ff1.encrypt(&hex::decode("39383736353433323130").unwrap(), &digits("0123456789")),
+            digits("1001623463")
+        );
+
+        let ff1 = FF1::new(&key(), 36);
+        assert_eq!(
+            ff1.encrypt(&hex::decode("3737373770717273373737").unwrap(), &digits("0123456789abcdefghi")),
+            digits("xs8a0azh2avyalyzuwd")
+        );
+    }
+}
@@ -0,0 +1,359 @@
+//! Sapling key components derived from a 32 byte spending key,
+//! as in the Zcash protocol specification:
+//!
+//! * `ask = ToScalar(PRF^expand(sk, [0]))`
+//! * `nsk = ToScalar(PRF^expand(sk, [1]))`
+//! * `ovk = truncate_32(PRF^expand(sk, [2]))`
+//!
+//! Diversifiers are found by encrypting consecutive 88 bit indices
+//! with FF1-AES256 under a diversifier key, as in ZIP 32.
+
+use bellman::pairing::ff::{
+    PrimeField,
+    PrimeFieldRepr
+};
+
+use std::io::{self, Read, Write};
+
+use rand::Rng;
+
+use blake2_rfc::blake2b::Blake2b;
+
+use jubjub::{
+    JubjubEngine,
+    JubjubParams,
+    FixedGenerators,
+    ToUniform,
+    edwards,
+    PrimeOrder,
+    Unknown
+};
+
+use super::{
+    Diversifier,
+    PaymentAddress,
+    ProofGenerationKey,
+    ViewingKey
+};
+
+use super::note_encryption::OutgoingViewingKey;
+use super::ff1::FF1;
+
+/// BLAKE2b Personalization for PRF^expand
+pub const PRF_EXPAND_PERSONALIZATION: &'static [u8; 16]
+          = b"Zcash_ExpandSeed";
+
+/// PRF^expand(sk, t) = BLAKE2b-512(sk || t)
+pub fn prf_expand(sk: &[u8], t: &[u8]) -> [u8; 64] {
+    prf_expand_vec(sk, &[t])
+}
+
+/// PRF^expand with `t` given in several parts.
+pub fn prf_expand_vec(sk: &[u8], ts: &[&[u8]]) -> [u8; 64] {
+    let mut h = Blake2b::with_params(64, &[], &[], PRF_EXPAND_PERSONALIZATION);
+    h.update(sk);
+    for t in ts {
+        h.update(t);
+    }
+
+    let mut res = [0u8; 64];
+    res.copy_from_slice(h.finalize().as_ref());
+
+    res
+}
+
+pub(crate) fn read_scalar<E: JubjubEngine, R: Read>(reader: R) -> io::Result<E::Fs> {
+    let mut repr = <E::Fs as PrimeField>::Repr::default();
+    repr.read_le(reader)?;
+
+    E::Fs::from_repr(repr).map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidData, "scalar is not in field")
+    })
+}
+
+pub(crate) fn read_prime_order_point<E: JubjubEngine, R: Read>(
+    reader: R,
+    params: &E::Params
+) -> io::Result<edwards::Point<E, PrimeOrder>>
+{
+    edwards::Point::<E, Unknown>::read(reader, params)?
+        .as_prime_order(params)
+        .ok_or(io::Error::new(io::ErrorKind::InvalidData, "point is not in the prime order subgroup"))
+}
+
+/// A Sapling spending key.
+#[derive(Copy, Clone)]
+pub struct SpendingKey(pub [u8; 32]);
+
+impl SpendingKey {
+    pub fn random<R: Rng>(rng: &mut R) -> Self {
+        SpendingKey(rng.gen())
+    }
+
+    pub fn expand<E: JubjubEngine>(&self) -> ExpandedSpendingKey<E> {
+        ExpandedSpendingKey::from_spending_key(&self.0)
+    }
+
+    /// The diversifier key of a spending key, as for a ZIP 32 master key.
+    pub fn diversifier_key(&self) -> DiversifierKey {
+        DiversifierKey::from_spending_key(&self.0)
+    }
+}
+
+pub struct ExpandedSpendingKey<E: JubjubEngine> {
+    pub ask: E::Fs,
+    pub nsk: E::Fs,
+    pub ovk: OutgoingViewingKey
+}
+
+impl<E: JubjubEngine> Clone for ExpandedSpendingKey<E> {
+    fn clone(&self) -> Self {
+        ExpandedSpendingKey {
+            ask: self.ask,
+            nsk: self.nsk,
+            ovk: self.ovk
+        }
+    }
+}
+
+impl<E: JubjubEngine> ExpandedSpendingKey<E> {
+    pub fn from_spending_key(sk: &[u8]) -> Self {
+        let ask = E::Fs::to_uniform(&prf_expand(sk, &[0x00]));
+        let nsk = E::Fs::to_uniform(&prf_expand(sk, &[0x01]));
+
+        let mut ovk = [0u8; 32];
+        ovk.copy_from_slice(&prf_expand(sk, &[0x02])[0..32]);
+
+        ExpandedSpendingKey {
+            ask: ask,
+            nsk: nsk,
+            ovk: OutgoingViewingKey(ovk)
+        }
+    }
+
+    pub fn proof_generation_key(&self, params: &E::Params) -> ProofGenerationKey<E> {
+        ProofGenerationKey {
+            ak: params.generator(FixedGenerators::SpendingKeyGenerator).mul(self.ask, params),
+            nsk: self.nsk
+        }
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let ask = read_scalar::<E, _>(&mut reader)?;
+        let nsk = read_scalar::<E, _>(&mut reader)?;
+
+        let mut ovk = [0u8; 32];
+        reader.read_exact(&mut ovk)?;
+
+        Ok(ExpandedSpendingKey {
+            ask: ask,
+            nsk: nsk,
+            ovk: OutgoingViewingKey(ovk)
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        self.ask.into_repr().write_le(&mut writer)?;
+        self.nsk.into_repr().write_le(&mut writer)?;
+        writer.write_all(&self.ovk.0)
+    }
+}
+
+pub struct FullViewingKey<E: JubjubEngine> {
+    pub vk: ViewingKey<E>,
+    pub ovk: OutgoingViewingKey
+}
+
+impl<E: JubjubEngine> Clone for FullViewingKey<E> {
+    fn clone(&self) -> Self {
+        FullViewingKey {
+            vk: ViewingKey {
+                ak: self.vk.ak.clone(),
+                nk: self.vk.nk.clone()
+            },
+            ovk: self.ovk
+        }
+    }
+}
+
+impl<E: JubjubEngine> FullViewingKey<E> {
+    pub fn from_expanded_spending_key(expsk: &ExpandedSpendingKey<E>, params: &E::Params) -> Self {
+        FullViewingKey {
+            vk: expsk.proof_generation_key(params).into_viewing_key(params),
+            ovk: expsk.ovk
+        }
+    }
+
+    pub fn read<R: Read>(mut reader: R, params: &E::Params) -> io::Result<Self> {
+        let ak = read_prime_order_point::<E, _>(&mut reader, params)?;
+        let nk = read_prime_order_point::<E, _>(&mut reader, params)?;
+
+        let mut ovk = [0u8; 32];
+        reader.read_exact(&mut ovk)?;
+
+        Ok(FullViewingKey {
+            vk: ViewingKey {
+                ak: ak,
+                nk: nk
+            },
+            ovk: OutgoingViewingKey(ovk)
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        self.vk.ak.write(&mut writer)?;
+        self.vk.nk.write(&mut writer)?;
+        writer.write_all(&self.ovk.0)
+    }
+}
+
+/// An 88 bit little-endian diversifier index.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DiversifierIndex(pub [u8; 11]);
+
+impl DiversifierIndex {
+    pub fn new() -> Self {
+        DiversifierIndex([0; 11])
+    }
+
+    /// Returns `false` on overflow.
+    pub fn increment(&mut self) -> bool {
+        for byte in self.0.iter_mut() {
+            *byte = byte.wrapping_add(1);
+            if *byte != 0 {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct DiversifierKey(pub [u8; 32]);
+
+impl DiversifierKey {
+    pub fn from_spending_key(sk: &[u8]) -> Self {
+        let mut dk = [0u8; 32];
+        dk.copy_from_slice(&prf_expand(sk, &[0x10])[0..32]);
+
+        DiversifierKey(dk)
+    }
+
+    /// d_j = FF1-AES256(dk, j), which is not always a valid diversifier.
+    pub fn diversifier(&self, j: DiversifierIndex) -> Diversifier {
+        let bits: Vec<u32> = j.0.iter()
+                                .flat_map(|&byte| (0..8).map(move |i| ((byte >> i) & 1) as u32))
+                                .collect();
+
+        let encrypted = FF1::new(&self.0, 2).encrypt(&[], &bits);
+
+        let mut d = [0u8; 11];
+        for (i, bit) in encrypted.into_iter().enumerate() {
+            d[i / 8] |= (bit as u8) << (i % 8);
+        }
+
+        Diversifier(d)
+    }
+
+    /// Returns the first valid diversifier with an index starting from `j`.
+    pub fn find_diversifier<E: JubjubEngine>(
+        &self,
+        mut j: DiversifierIndex,
+        params: &E::Params
+    ) -> Option<(DiversifierIndex, Diversifier)>
+    {
+        loop {
+            let d = self.diversifier(j);
+            if d.g_d::<E>(params).is_some() {
+                return Some((j, d));
+            }
+
+            if !j.increment() {
+                return None;
+            }
+        }
+    }
+
+    /// Returns the payment address for the first valid diversifier
+    /// with an index starting from `j`.
+    pub fn find_address<E: JubjubEngine>(
+        &self,
+        vk: &ViewingKey<E>,
+        j: DiversifierIndex,
+        params: &E::Params
+    ) -> Option<(DiversifierIndex, PaymentAddress<E>)>
+    {
+        let (j, d) = self.find_diversifier::<E>(j, params)?;
+        let address = vk.into_payment_address(d, params)?;
+
+        Some((j, address))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bellman::pairing::bls12_381::Bls12;
+    use rand::{XorShiftRng, SeedableRng};
+
+    use jubjub::JubjubBls12;
+
+    use super::*;
+
+    #[test]
+    fn test_spending_key_derivation() {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for _ in 0..10 {
+            let sk = SpendingKey::random(rng);
+
+            let expsk = sk.expand::<Bls12>();
+            let again = sk.expand::<Bls12>();
+            assert!(expsk.ask == again.ask && expsk.nsk == again.nsk && expsk.ovk == again.ovk);
+            assert!(expsk.ask != expsk.nsk);
+
+            let mut bytes = vec![];
+            expsk.write(&mut bytes).unwrap();
+            assert_eq!(bytes.len(), 96);
+            let decoded = ExpandedSpendingKey::<Bls12>::read(&bytes[..]).unwrap();
+            assert!(decoded.ask == expsk.ask && decoded.nsk == expsk.nsk && decoded.ovk == expsk.ovk);
+
+            let fvk = FullViewingKey::from_expanded_spending_key(&expsk, params);
+            let mut bytes = vec![];
+            fvk.write(&mut bytes).unwrap();
+            assert_eq!(bytes.len(), 96);
+            let decoded = FullViewingKey::<Bls12>::read(&bytes[..], params).unwrap();
+            assert!(decoded.vk.ak == fvk.vk.ak && decoded.vk.nk == fvk.vk.nk && decoded.ovk == fvk.ovk);
+
+            // The first valid diversifier gives an address of the viewing key
+            let dk = sk.diversifier_key();
+            let (j, address) = dk.find_address(&fvk.vk, DiversifierIndex::new(), params).unwrap();
+            assert!(address.pk_d == address.g_d(params).unwrap().mul(fvk.vk.ivk(), params));
+
+            // Every skipped index is invalid
+            let mut i = DiversifierIndex::new();
+            while i != j {
+                assert!(dk.diversifier(i).g_d::<Bls12>(params).is_none());
+                assert!(i.increment());
+            }
+
+            // The next one is different
+            let mut next = j;
+            next.increment();
+            let (k, d) = dk.find_diversifier::<Bls12>(next, params).unwrap();
+            assert!(k != j);
+            assert!(d.0 != address.diversifier.0);
+        }
+    }
+
+    #[test]
+    fn test_diversifier_index_increment() {
+        let mut j = DiversifierIndex([0xff, 0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(j.increment());
+        assert_eq!(j, DiversifierIndex([0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]));
+
+        let mut j = DiversifierIndex([0xff; 11]);
+        assert!(!j.increment());
+    }
+}
@@ -0,0 +1,203 @@
+//! An in-memory tree of note commitments, hashed as in the
+//! `Spend` circuit with the Pedersen hash personalized by the
+//! depth of the layer. Empty leaves are `Note::uncommitted()`.
+
+use bellman::pairing::ff::{
+    BitIterator,
+    PrimeField
+};
+
+use pedersen_hash::{
+    pedersen_hash,
+    Personalization,
+    MAX_MERKLE_TREE_DEPTH
+};
+
+use jubjub::JubjubEngine;
+
+use super::Note;
+
+/// MerkleCRH^Sapling of the nodes `lhs` and `rhs` at `depth`,
+/// counted from the leaves.
+pub fn merkle_hash<E: JubjubEngine>(
+    depth: usize,
+    lhs: &E::Fr,
+    rhs: &E::Fr,
+    params: &E::Params
+) -> E::Fr
+{
+    let lhs = BitIterator::new(lhs.into_repr()).collect::<Vec<_>>();
+    let rhs = BitIterator::new(rhs.into_repr()).collect::<Vec<_>>();
+
+    pedersen_hash::<E, _>(
+        Personalization::MerkleTree(depth),
+        lhs.into_iter().rev().take(E::Fr::NUM_BITS as usize)
+           .chain(rhs.into_iter().rev().take(E::Fr::NUM_BITS as usize)),
+        params
+    ).into_xy().0
+}
+
+/// The authentication path of a leaf, from the leaf to the root.
+/// The flag of each sibling is set if the path goes through the
+/// right node at that depth, as expected by the `Spend` circuit.
+#[derive(Clone)]
+pub struct MerklePath<E: JubjubEngine> {
+    pub auth_path: Vec<(E::Fr, bool)>,
+    pub position: u64
+}
+
+impl<E: JubjubEngine> MerklePath<E> {
+    /// Computes the root of the tree with `leaf` on this path.
+    pub fn root(&self, leaf: E::Fr, params: &E::Params) -> E::Fr {
+        self.auth_path.iter().enumerate().fold(leaf, |cur, (depth, &(ref sibling, is_right))| {
+            if is_right {
+                merkle_hash::<E>(depth, sibling, &cur, params)
+            } else {
+                merkle_hash::<E>(depth, &cur, sibling, params)
+            }
+        })
+    }
+}
+
+/// A tree of fixed depth, filled from the left. Every node is
+/// kept, so paths to any leaf can be computed.
+pub struct CommitmentTree<E: JubjubEngine> {
+    /// The filled nodes of each layer, starting from the leaves
+    layers: Vec<Vec<E::Fr>>,
+    /// The roots of empty subtrees of each depth
+    empty_roots: Vec<E::Fr>
+}
+
+impl<E: JubjubEngine> CommitmentTree<E> {
+    pub fn new(depth: usize, params: &E::Params) -> Self {
+        assert!(depth > 0 && depth <= MAX_MERKLE_TREE_DEPTH);
+
+        let mut empty_roots = vec![Note::<E>::uncommitted()];
+        for i in 0..depth {
+            let next = merkle_hash::<E>(i, &empty_roots[i], &empty_roots[i], params);
+            empty_roots.push(next);
+        }
+
+        CommitmentTree {
+            layers: vec![vec![]; depth + 1],
+            empty_roots: empty_roots
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.layers.len() - 1
+    }
+
+    pub fn size(&self) -> u64 {
+        self.layers[0].len() as u64
+    }
+
+    fn node(&self, depth: usize, index: usize) -> E::Fr {
+        self.layers[depth].get(index).cloned().unwrap_or(self.empty_roots[depth])
+    }
+
+    /// Appends a note commitment, returning its position, or `None`
+    /// if the tree is full.
+    pub fn append(&mut self, cm: E::Fr, params: &E::Params) -> Option<u64> {
+        let position = self.size();
+        if position == 1 << self.depth() {
+            return None;
+        }
+
+        self.layers[0].push(cm);
+
+        // Update the nodes on the path to the root
+        let mut index = position as usize;
+        for depth in 0..self.depth() {
+            let parent = merkle_hash::<E>(
+                depth,
+                &self.node(depth, index & !1),
+                &self.node(depth, index | 1),
+                params
+            );
+
+            index >>= 1;
+            if index < self.layers[depth + 1].len() {
+                self.layers[depth + 1][index] = parent;
+            } else {
+                self.layers[depth + 1].push(parent);
+            }
+        }
+
+        Some(position)
+    }
+
+    /// Returns the note commitment at `position`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the leaf at `position` is not filled.
+    pub fn leaf(&self, position: u64) -> E::Fr {
+        self.layers[0][position as usize]
+    }
+
+    pub fn root(&self) -> E::Fr {
+        self.node(self.depth(), 0)
+    }
+
+    /// Returns the path of the leaf at `position`, if it's filled.
+    pub fn path(&self, position: u64) -> Option<MerklePath<E>> {
+        if position >= self.size() {
+            return None;
+        }
+
+        let mut index = position as usize;
+        let mut auth_path = vec![];
+        for depth in 0..self.depth() {
+            auth_path.push((self.node(depth, index ^ 1), index & 1 == 1));
+            index >>= 1;
+        }
+
+        Some(MerklePath {
+            auth_path: auth_path,
+            position: position
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bellman::pairing::bls12_381::{Bls12, Fr};
+    use rand::{XorShiftRng, SeedableRng, Rng};
+
+    use jubjub::JubjubBls12;
+
+    use super::*;
+
+    #[test]
+    fn test_commitment_tree() {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let mut tree = CommitmentTree::<Bls12>::new(3, params);
+        let empty_root = tree.root();
+
+        let leaves: Vec<Fr> = (0..8).map(|_| rng.gen()).collect();
+        for (i, leaf) in leaves.iter().enumerate() {
+            assert_eq!(tree.append(*leaf, params), Some(i as u64));
+            assert!(tree.root() != empty_root);
+
+            // Every path leads to the current root
+            for j in 0..(i + 1) {
+                let path = tree.path(j as u64).unwrap();
+                assert_eq!(path.position, j as u64);
+                assert_eq!(path.root(leaves[j], params), tree.root());
+            }
+            assert!(tree.path(i as u64 + 1).is_none());
+        }
+
+        assert_eq!(tree.append(rng.gen(), params), None);
+
+        // The root matches a tree hashed layer by layer
+        let mut layer = leaves;
+        for depth in 0..3 {
+            layer = layer.chunks(2).map(|pair| merkle_hash::<Bls12>(depth, &pair[0], &pair[1], params)).collect();
+        }
+        assert_eq!(layer[0], tree.root());
+    }
+}
@@ -28,6 +28,16 @@ use jubjub::{
 
 use blake2_rfc::blake2s::Blake2s;
 
+pub mod note_encryption;
+pub mod asset;
+pub mod merkle_tree;
+pub mod transaction;
+pub mod state;
+pub mod keys;
+pub mod zip32;
+
+mod ff1;
+
 #[derive(Clone)]
 pub struct ValueCommitment<E: JubjubEngine> {
     pub value: u64,
@@ -66,6 +76,7 @@ impl<E: JubjubEngine> ProofGenerationKey<E> {
     }
 }
 
+#[derive(Clone)]
 pub struct ViewingKey<E: JubjubEngine> {
     pub ak: edwards::Point<E, PrimeOrder>,
     pub nk: edwards::Point<E, PrimeOrder>
@@ -229,23 +240,7 @@ impl<E: JubjubEngine> Note<E> {
         params: &E::Params
     ) -> Vec<u8>
     {
-        // Compute rho = cm + position.G
-        let rho = self
-            .cm_full_point(params)
-            .add(
-                &params.generator(FixedGenerators::NullifierPosition)
-                       .mul(position, params),
-                params
-            );
-
-        // Compute nf = BLAKE2s(nk | rho)
-        let mut nf_preimage = [0u8; 64];
-        viewing_key.nk.write(&mut nf_preimage[0..32]).unwrap();
-        rho.write(&mut nf_preimage[32..64]).unwrap();
-        let mut h = Blake2s::with_params(32, &[], &[], constants::PRF_NF_PERSONALIZATION);
-        h.update(&nf_preimage);
-        
-        h.finalize().as_ref().to_vec()
+        nullifier(&self.cm_full_point(params), viewing_key, position, params)
     }
 
     /// Computes the note commitment
@@ -256,3 +251,29 @@ impl<E: JubjubEngine> Note<E> {
         self.cm_full_point(params).into_xy().0
     }
 }
+
+/// Computes the nullifier of a note with the commitment `cm`
+/// given the viewing key and note position
+pub(crate) fn nullifier<E: JubjubEngine>(
+    cm: &edwards::Point<E, PrimeOrder>,
+    viewing_key: &ViewingKey<E>,
+    position: u64,
+    params: &E::Params
+) -> Vec<u8>
+{
+    // Compute rho = cm + position.G
+    let rho = cm.add(
+        &params.generator(FixedGenerators::NullifierPosition)
+               .mul(position, params),
+        params
+    );
+
+    // Compute nf = BLAKE2s(nk | rho)
+    let mut nf_preimage = [0u8; 64];
+    viewing_key.nk.write(&mut nf_preimage[0..32]).unwrap();
+    rho.write(&mut nf_preimage[32..64]).unwrap();
+    let mut h = Blake2s::with_params(32, &[], &[], constants::PRF_NF_PERSONALIZATION);
+    h.update(&nf_preimage);
+
+    h.finalize().as_ref().to_vec()
+}
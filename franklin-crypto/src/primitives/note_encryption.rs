@@ -0,0 +1,538 @@
+//! Sapling note encryption: the sender derives a key from the
+//! Diffie-Hellman agreement between `esk` and the recipient's `pk_d`,
+//! encrypts the note plaintext with ChaCha20-Poly1305 (RFC 8439, zero
+//! nonce, the keys are never reused) and encrypts `pk_d || esk` under
+//! a key derived from its outgoing viewing key, so that it can recover
+//! the notes it sent later on.
+//!
+//! The `epk`, `cv` and `cmu` used here are the values exposed by the
+//! `circuit::sapling::Output` circuit for the same `esk`.
+
+use bellman::pairing::ff::{
+    Field,
+    PrimeField,
+    PrimeFieldRepr
+};
+
+use byteorder::{
+    LittleEndian,
+    ReadBytesExt,
+    WriteBytesExt
+};
+
+use blake2_rfc::blake2b::Blake2b;
+
+use crypto::chacha20::ChaCha20;
+use crypto::mac::Mac;
+use crypto::poly1305::Poly1305;
+use crypto::symmetriccipher::SynchronousStreamCipher;
+use crypto::util::fixed_time_eq;
+
+use jubjub::{
+    JubjubEngine,
+    edwards,
+    PrimeOrder,
+    Unknown
+};
+
+use super::{
+    Diversifier,
+    Note,
+    PaymentAddress
+};
+
+/// BLAKE2b Personalization for the key derivation from the shared secret
+pub const KDF_SAPLING_PERSONALIZATION: &'static [u8; 16]
+          = b"Zcash_SaplingKDF";
+
+/// BLAKE2b Personalization for the outgoing cipher key
+pub const PRF_OCK_PERSONALIZATION: &'static [u8; 16]
+          = b"Zcash_Derive_ock";
+
+pub const MEMO_SIZE: usize = 512;
+pub const NOTE_PLAINTEXT_SIZE: usize = 1 + 11 + 8 + 32 + MEMO_SIZE;
+pub const OUT_PLAINTEXT_SIZE: usize = 32 + 32;
+pub const ENC_CIPHERTEXT_SIZE: usize = NOTE_PLAINTEXT_SIZE + 16;
+pub const OUT_CIPHERTEXT_SIZE: usize = OUT_PLAINTEXT_SIZE + 16;
+
+/// Lead byte of the note plaintext
+const NOTE_PLAINTEXT_LEAD_BYTE: u8 = 0x01;
+
+/// The memo field of a note, `0xF6` followed by zeros means "no memo".
+#[derive(Clone)]
+pub struct Memo(pub [u8; MEMO_SIZE]);
+
+impl Default for Memo {
+    fn default() -> Self {
+        let mut memo = [0u8; MEMO_SIZE];
+        memo[0] = 0xF6;
+
+        Memo(memo)
+    }
+}
+
+impl PartialEq for Memo {
+    fn eq(&self, other: &Memo) -> bool {
+        self.0[..] == other.0[..]
+    }
+}
+
+/// Key used by the sender to recover the notes it sent.
+#[derive(Copy, Clone, PartialEq)]
+pub struct OutgoingViewingKey(pub [u8; 32]);
+
+fn point_bytes<E: JubjubEngine, S>(p: &edwards::Point<E, S>) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    p.write(&mut bytes[..]).expect("has enough space");
+
+    bytes
+}
+
+/// KA^Sapling.Agree(sk, P) = [8][sk] P
+pub fn sapling_ka_agree<E: JubjubEngine>(
+    sk: &E::Fs,
+    p: &edwards::Point<E, Unknown>,
+    params: &E::Params
+) -> edwards::Point<E, PrimeOrder>
+{
+    p.mul(*sk, params).mul_by_cofactor(params)
+}
+
+/// KDF^Sapling(shared secret, epk) = BLAKE2b(shared secret || epk)
+pub fn kdf_sapling<E: JubjubEngine, S>(
+    dhsecret: &edwards::Point<E, PrimeOrder>,
+    epk: &edwards::Point<E, S>
+) -> [u8; 32]
+{
+    let mut h = Blake2b::with_params(32, &[], &[], KDF_SAPLING_PERSONALIZATION);
+    h.update(&point_bytes(dhsecret));
+    h.update(&point_bytes(epk));
+
+    let mut key = [0u8; 32];
+    key.copy_from_slice(h.finalize().as_ref());
+
+    key
+}
+
+/// PRF^ock(ovk, cv, cmu, epk) = BLAKE2b(ovk || cv || cmu || epk)
+pub fn prf_ock<E: JubjubEngine, S1, S2>(
+    ovk: &OutgoingViewingKey,
+    cv: &edwards::Point<E, S1>,
+    cmu: &E::Fr,
+    epk: &edwards::Point<E, S2>
+) -> [u8; 32]
+{
+    let mut cmu_bytes = [0u8; 32];
+    cmu.into_repr().write_le(&mut cmu_bytes[..]).expect("has enough space");
+
+    let mut h = Blake2b::with_params(32, &[], &[], PRF_OCK_PERSONALIZATION);
+    h.update(&ovk.0);
+    h.update(&point_bytes(cv));
+    h.update(&cmu_bytes);
+    h.update(&point_bytes(epk));
+
+    let mut key = [0u8; 32];
+    key.copy_from_slice(h.finalize().as_ref());
+
+    key
+}
+
+fn chacha20_poly1305_tag(
+    mac_key: &[u8],
+    aad: &[u8],
+    ciphertext: &[u8]
+) -> [u8; 16]
+{
+    fn pad16(mac: &mut Poly1305, len: usize) {
+        if len % 16 != 0 {
+            mac.input(&[0u8; 16][0..(16 - len % 16)]);
+        }
+    }
+
+    let mut mac = Poly1305::new(mac_key);
+    mac.input(aad);
+    pad16(&mut mac, aad.len());
+    mac.input(ciphertext);
+    pad16(&mut mac, ciphertext.len());
+
+    let mut lengths = vec![];
+    lengths.write_u64::<LittleEndian>(aad.len() as u64).unwrap();
+    lengths.write_u64::<LittleEndian>(ciphertext.len() as u64).unwrap();
+    mac.input(&lengths);
+
+    let mut tag = [0u8; 16];
+    mac.raw_result(&mut tag);
+
+    tag
+}
+
+/// ChaCha20-Poly1305 as in RFC 8439, returns `ciphertext || tag`.
+fn chacha20_poly1305_seal(
+    key: &[u8; 32],
+    nonce: &[u8; 12],
+    aad: &[u8],
+    plaintext: &[u8]
+) -> Vec<u8>
+{
+    // The first block of the key stream is the one-time Poly1305 key
+    let mut cipher = ChaCha20::new(key, nonce);
+    let mut mac_key = [0u8; 64];
+    cipher.process(&[0u8; 64], &mut mac_key);
+
+    let mut ciphertext = vec![0u8; plaintext.len()];
+    cipher.process(plaintext, &mut ciphertext);
+
+    let tag = chacha20_poly1305_tag(&mac_key[0..32], aad, &ciphertext);
+    ciphertext.extend_from_slice(&tag);
+
+    ciphertext
+}
+
+fn chacha20_poly1305_open(
+    key: &[u8; 32],
+    nonce: &[u8; 12],
+    aad: &[u8],
+    ciphertext: &[u8]
+) -> Option<Vec<u8>>
+{
+    if ciphertext.len() < 16 {
+        return None;
+    }
+    let (ciphertext, tag) = ciphertext.split_at(ciphertext.len() - 16);
+
+    let mut cipher = ChaCha20::new(key, nonce);
+    let mut mac_key = [0u8; 64];
+    cipher.process(&[0u8; 64], &mut mac_key);
+
+    if !fixed_time_eq(&chacha20_poly1305_tag(&mac_key[0..32], aad, ciphertext), tag) {
+        return None;
+    }
+
+    let mut plaintext = vec![0u8; ciphertext.len()];
+    cipher.process(ciphertext, &mut plaintext);
+
+    Some(plaintext)
+}
+
+/// Encrypts a note to its recipient and to the sender's outgoing viewing key.
+pub struct SaplingNoteEncryption<E: JubjubEngine> {
+    epk: edwards::Point<E, PrimeOrder>,
+    esk: E::Fs,
+    note: Note<E>,
+    to: PaymentAddress<E>,
+    memo: Memo,
+    ovk: OutgoingViewingKey
+}
+
+impl<E: JubjubEngine> SaplingNoteEncryption<E> {
+    /// `esk` has to be freshly sampled for every note, the same `esk`
+    /// is given to the `Output` circuit.
+    pub fn new(
+        ovk: OutgoingViewingKey,
+        note: Note<E>,
+        to: PaymentAddress<E>,
+        memo: Memo,
+        esk: E::Fs,
+        params: &E::Params
+    ) -> Self
+    {
+        let epk = note.g_d.mul(esk, params);
+
+        SaplingNoteEncryption {
+            epk: epk,
+            esk: esk,
+            note: note,
+            to: to,
+            memo: memo,
+            ovk: ovk
+        }
+    }
+
+    pub fn esk(&self) -> &E::Fs {
+        &self.esk
+    }
+
+    pub fn epk(&self) -> &edwards::Point<E, PrimeOrder> {
+        &self.epk
+    }
+
+    /// Produces the `ENC_CIPHERTEXT_SIZE` bytes of `C^enc`.
+    pub fn encrypt_note_plaintext(&self, params: &E::Params) -> Vec<u8> {
+        let shared_secret = sapling_ka_agree(&self.esk, &self.to.pk_d.clone().into(), params);
+        let key = kdf_sapling(&shared_secret, &self.epk);
+
+        let mut input = Vec::with_capacity(NOTE_PLAINTEXT_SIZE);
+        input.push(NOTE_PLAINTEXT_LEAD_BYTE);
+        input.extend_from_slice(&self.to.diversifier.0);
+        input.write_u64::<LittleEndian>(self.note.value).unwrap();
+        self.note.r.into_repr().write_le(&mut input).unwrap();
+        input.extend_from_slice(&self.memo.0);
+        assert_eq!(input.len(), NOTE_PLAINTEXT_SIZE);
+
+        chacha20_poly1305_seal(&key, &[0u8; 12], &[], &input)
+    }
+
+    /// Produces the `OUT_CIPHERTEXT_SIZE` bytes of `C^out`.
+    pub fn encrypt_outgoing_plaintext<S>(
+        &self,
+        cv: &edwards::Point<E, S>,
+        cmu: &E::Fr
+    ) -> Vec<u8>
+    {
+        let key = prf_ock(&self.ovk, cv, cmu, &self.epk);
+
+        let mut input = Vec::with_capacity(OUT_PLAINTEXT_SIZE);
+        self.note.pk_d.write(&mut input).unwrap();
+        self.esk.into_repr().write_le(&mut input).unwrap();
+        assert_eq!(input.len(), OUT_PLAINTEXT_SIZE);
+
+        chacha20_poly1305_seal(&key, &[0u8; 12], &[], &input)
+    }
+}
+
+/// Parses the note plaintext, returning the diversifier, value, commitment randomness and memo.
+fn parse_note_plaintext<E: JubjubEngine>(
+    plaintext: &[u8]
+) -> Option<(Diversifier, u64, E::Fs, Memo)>
+{
+    assert_eq!(plaintext.len(), NOTE_PLAINTEXT_SIZE);
+
+    if plaintext[0] != NOTE_PLAINTEXT_LEAD_BYTE {
+        return None;
+    }
+
+    let mut d = [0u8; 11];
+    d.copy_from_slice(&plaintext[1..12]);
+
+    let value = (&plaintext[12..20]).read_u64::<LittleEndian>().ok()?;
+
+    let mut rcm = <E::Fs as PrimeField>::Repr::default();
+    rcm.read_le(&plaintext[20..52]).ok()?;
+    let rcm = E::Fs::from_repr(rcm).ok()?;
+
+    let mut memo = [0u8; MEMO_SIZE];
+    memo.copy_from_slice(&plaintext[52..NOTE_PLAINTEXT_SIZE]);
+
+    Some((Diversifier(d), value, rcm, Memo(memo)))
+}
+
+/// Trial decryption of `C^enc` with the incoming viewing key. Returns
+/// `None` if the note is not addressed to `ivk` or doesn't match `cmu`.
+pub fn try_sapling_note_decryption<E: JubjubEngine>(
+    ivk: &E::Fs,
+    epk: &edwards::Point<E, Unknown>,
+    cmu: &E::Fr,
+    enc_ciphertext: &[u8],
+    params: &E::Params
+) -> Option<(Note<E>, PaymentAddress<E>, Memo)>
+{
+    if enc_ciphertext.len() != ENC_CIPHERTEXT_SIZE {
+        return None;
+    }
+
+    let shared_secret = sapling_ka_agree(ivk, epk, params);
+    let key = kdf_sapling(&shared_secret, epk);
+
+    let plaintext = chacha20_poly1305_open(&key, &[0u8; 12], &[], enc_ciphertext)?;
+    let (diversifier, value, rcm, memo) = parse_note_plaintext::<E>(&plaintext)?;
+
+    let g_d = diversifier.g_d::<E>(params)?;
+    let to = PaymentAddress {
+        pk_d: g_d.mul(*ivk, params),
+        diversifier: diversifier
+    };
+
+    let note = to.create_note(value, rcm, params)?;
+    if note.cm(params) != *cmu {
+        return None;
+    }
+
+    Some((note, to, memo))
+}
+
+/// Recovery of a sent note from `C^out` with the outgoing viewing key.
+/// Returns `None` if the ciphertexts were not produced with `ovk` or
+/// don't match the output description.
+pub fn try_sapling_output_recovery<E: JubjubEngine>(
+    ovk: &OutgoingViewingKey,
+    cv: &edwards::Point<E, Unknown>,
+    cmu: &E::Fr,
+    epk: &edwards::Point<E, Unknown>,
+    enc_ciphertext: &[u8],
+    out_ciphertext: &[u8],
+    params: &E::Params
+) -> Option<(Note<E>, PaymentAddress<E>, Memo)>
+{
+    if enc_ciphertext.len() != ENC_CIPHERTEXT_SIZE || out_ciphertext.len() != OUT_CIPHERTEXT_SIZE {
+        return None;
+    }
+
+    let ock = prf_ock(ovk, cv, cmu, epk);
+    let op = chacha20_poly1305_open(&ock, &[0u8; 12], &[], out_ciphertext)?;
+
+    let pk_d = edwards::Point::<E, Unknown>::read(&op[0..32], params).ok()?.as_prime_order(params)?;
+
+    let mut esk = <E::Fs as PrimeField>::Repr::default();
+    esk.read_le(&op[32..64]).ok()?;
+    let esk = E::Fs::from_repr(esk).ok()?;
+
+    let shared_secret = sapling_ka_agree(&esk, &pk_d.clone().into(), params);
+    let key = kdf_sapling(&shared_secret, epk);
+
+    let plaintext = chacha20_poly1305_open(&key, &[0u8; 12], &[], enc_ciphertext)?;
+    let (diversifier, value, rcm, memo) = parse_note_plaintext::<E>(&plaintext)?;
+
+    let to = PaymentAddress {
+        pk_d: pk_d,
+        diversifier: diversifier
+    };
+
+    // The sender must have used this esk for the output
+    let g_d = to.g_d(params)?;
+    if edwards::Point::<E, Unknown>::from(g_d.mul(esk, params)) != *epk {
+        return None;
+    }
+
+    let note = to.create_note(value, rcm, params)?;
+    if note.cm(params) != *cmu {
+        return None;
+    }
+
+    Some((note, to, memo))
+}
+
+#[cfg(test)]
+mod test {
+    use bellman::pairing::bls12_381::{Bls12, Fr};
+    use bellman::pairing::ff::Field;
+    use rand::{XorShiftRng, SeedableRng, Rng};
+    use hex;
+
+    use jubjub::{JubjubBls12, fs::Fs, edwards, Unknown};
+    use primitives::{ValueCommitment, ViewingKey};
+    use primitives::keys::{DiversifierIndex, FullViewingKey, SpendingKey};
+    use circuit::sapling::Output;
+    use circuit::test::TestConstraintSystem;
+    use bellman::Circuit;
+
+    use super::*;
+
+    #[test]
+    fn test_chacha20_poly1305_rfc8439() {
+        // RFC 8439, section 2.8.2
+        let key = hex::decode("808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f").unwrap();
+        let nonce = hex::decode("070000004041424344454647").unwrap();
+        let aad = hex::decode("50515253c0c1c2c3c4c5c6c7").unwrap();
+        let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+
+        let mut k = [0u8; 32];
+        k.copy_from_slice(&key);
+        let mut n = [0u8; 12];
+        n.copy_from_slice(&nonce);
+
+        let sealed = chacha20_poly1305_seal(&k, &n, &aad, plaintext);
+        assert_eq!(
+            hex::encode(&sealed),
+            "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d63dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b3692ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc3ff4def08e4b7a9de576d26586cec64b6116\
+1ae10b594f09e26a7e902ecbd0600691"
+        );
+
+        assert_eq!(chacha20_poly1305_open(&k, &n, &aad, &sealed).unwrap(), &plaintext[..]);
+
+        let mut tampered = sealed.clone();
+        tampered[0] ^= 1;
+        assert!(chacha20_poly1305_open(&k, &n, &aad, &tampered).is_none());
+    }
+
+    fn random_address<R: Rng>(rng: &mut R, params: &JubjubBls12) -> (ViewingKey<Bls12>, PaymentAddress<Bls12>) {
+        let sk = SpendingKey::random(rng);
+        let fvk = FullViewingKey::from_expanded_spending_key(&sk.expand::<Bls12>(), params);
+        let (_, address) = sk.diversifier_key().find_address(&fvk.vk, DiversifierIndex::new(), params).unwrap();
+
+        (fvk.vk, address)
+    }
+
+    #[test]
+    fn test_note_encryption() {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for _ in 0..5 {
+            let (viewing_key, to) = random_address(rng, params);
+            let ivk = viewing_key.ivk();
+            let ovk = OutgoingViewingKey(rng.gen());
+
+            let value_commitment = ValueCommitment::<Bls12> {
+                value: rng.gen(),
+                randomness: rng.gen()
+            };
+            let rcm: Fs = rng.gen();
+            let esk: Fs = rng.gen();
+
+            let note = to.create_note(value_commitment.value, rcm, params).unwrap();
+            let cmu = note.cm(params);
+            let cv: edwards::Point<Bls12, Unknown> = value_commitment.cm(params).into();
+
+            let mut memo = Memo::default();
+            memo.0[1] = 42;
+
+            let enc = SaplingNoteEncryption::new(ovk, note, to.clone(), memo.clone(), esk, params);
+            let epk: edwards::Point<Bls12, Unknown> = enc.epk().clone().into();
+            let enc_ciphertext = enc.encrypt_note_plaintext(params);
+            let out_ciphertext = enc.encrypt_outgoing_plaintext(&cv, &cmu);
+            assert_eq!(enc_ciphertext.len(), ENC_CIPHERTEXT_SIZE);
+            assert_eq!(out_ciphertext.len(), OUT_CIPHERTEXT_SIZE);
+
+            // The Output circuit exposes the same epk and cmu
+            {
+                let mut cs = TestConstraintSystem::<Bls12>::new();
+
+                Output {
+                    params: params,
+                    value_commitment: Some(value_commitment.clone()),
+                    payment_address: Some(to.clone()),
+                    commitment_randomness: Some(rcm),
+                    esk: Some(esk)
+                }.synthesize(&mut cs).unwrap();
+
+                assert!(cs.is_satisfied());
+                let (x, y) = epk.into_xy();
+                assert_eq!(cs.get_input(3, "epk/x/input variable"), x);
+                assert_eq!(cs.get_input(4, "epk/y/input variable"), y);
+                assert_eq!(cs.get_input(5, "commitment/input variable"), cmu);
+            }
+
+            // Recipient
+            let (note, address, decrypted_memo) = try_sapling_note_decryption(&ivk, &epk, &cmu, &enc_ciphertext, params).unwrap();
+            assert_eq!(note.value, value_commitment.value);
+            assert!(note.r == rcm);
+            assert!(address.pk_d == to.pk_d);
+            assert!(decrypted_memo == memo);
+
+            let mut wrong_ivk = ivk;
+            wrong_ivk.add_assign(&Fs::one());
+            assert!(try_sapling_note_decryption(&wrong_ivk, &epk, &cmu, &enc_ciphertext, params).is_none());
+
+            let mut wrong_cmu = cmu;
+            wrong_cmu.add_assign(&Fr::one());
+            assert!(try_sapling_note_decryption(&ivk, &epk, &wrong_cmu, &enc_ciphertext, params).is_none());
+
+            let mut tampered = enc_ciphertext.clone();
+            tampered[100] ^= 1;
+            assert!(try_sapling_note_decryption(&ivk, &epk, &cmu, &tampered, params).is_none());
+
+            // Sender
+            let (note, address, decrypted_memo) = try_sapling_output_recovery(&ovk, &cv, &cmu, &epk, &enc_ciphertext, &out_ciphertext, params).unwrap();
+            assert_eq!(note.value, value_commitment.value);
+            assert!(address.pk_d == to.pk_d);
+            assert!(decrypted_memo == memo);
+
+            let mut wrong_ovk = ovk;
+            wrong_ovk.0[0] ^= 1;
+            assert!(try_sapling_output_recovery(&wrong_ovk, &cv, &cmu, &epk, &enc_ciphertext, &out_ciphertext, params).is_none());
+
+            let wrong_cv: edwards::Point<Bls12, Unknown> = cv.double(params);
+            assert!(try_sapling_output_recovery(&ovk, &wrong_cv, &cmu, &epk, &enc_ciphertext, &out_ciphertext, params).is_none());
+        }
+    }
+}
@@ -0,0 +1,488 @@
+//! The state of a shielded pool: the tree of note commitments, the
+//! recent roots of the tree that spends may use as anchors, and the
+//! set of revealed nullifiers.
+//!
+//! The state can be written to and read from a file, which stores the
+//! leaves of the tree, so the nodes are recomputed when it's loaded.
+//! The file starts with a magic and a format version.
+
+use bellman::pairing::ff::{
+    PrimeField,
+    PrimeFieldRepr
+};
+
+use byteorder::{
+    LittleEndian,
+    ReadBytesExt,
+    WriteBytesExt
+};
+
+use std::collections::{BTreeSet, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use jubjub::JubjubEngine;
+
+use pedersen_hash::MAX_MERKLE_TREE_DEPTH;
+
+use super::merkle_tree::{
+    CommitmentTree,
+    MerklePath
+};
+
+use super::transaction::Transaction;
+
+/// The magic at the start of a state file
+const STATE_MAGIC: &[u8; 8] = b"shldstat";
+
+/// The version of the state file format
+const STATE_VERSION: u32 = 1;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// There is no room for more commitments in the tree
+    TreeFull,
+    /// The anchor is not one of the recent roots of the tree
+    UnknownAnchor,
+    /// The nullifier was already revealed
+    DoubleSpend
+}
+
+pub struct ShieldedState<E: JubjubEngine> {
+    tree: CommitmentTree<E>,
+    /// The recent roots of the tree, the current one last
+    anchors: VecDeque<E::Fr>,
+    max_anchors: usize,
+    nullifiers: BTreeSet<[u8; 32]>
+}
+
+fn read_fr<E: JubjubEngine, R: Read>(reader: R) -> io::Result<E::Fr> {
+    let mut repr = <E::Fr as PrimeField>::Repr::default();
+    repr.read_le(reader)?;
+
+    E::Fr::from_repr(repr).map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidData, "element is not in field")
+    })
+}
+
+impl<E: JubjubEngine> ShieldedState<E> {
+    /// An empty state with a tree of depth `tree_depth`, which keeps
+    /// the last `max_anchors` roots of the tree as anchors.
+    pub fn new(tree_depth: usize, max_anchors: usize, params: &E::Params) -> Self {
+        assert!(max_anchors > 0);
+
+        let tree = CommitmentTree::new(tree_depth, params);
+        let mut anchors = VecDeque::new();
+        anchors.push_back(tree.root());
+
+        ShieldedState {
+            tree: tree,
+            anchors: anchors,
+            max_anchors: max_anchors,
+            nullifiers: BTreeSet::new()
+        }
+    }
+
+    pub fn tree_depth(&self) -> usize {
+        self.tree.depth()
+    }
+
+    pub fn num_commitments(&self) -> u64 {
+        self.tree.size()
+    }
+
+    pub fn root(&self) -> E::Fr {
+        self.tree.root()
+    }
+
+    pub fn is_known_anchor(&self, anchor: &E::Fr) -> bool {
+        self.anchors.contains(anchor)
+    }
+
+    pub fn is_spent(&self, nullifier: &[u8; 32]) -> bool {
+        self.nullifiers.contains(nullifier)
+    }
+
+    fn push_anchor(&mut self) {
+        if self.anchors.len() == self.max_anchors {
+            self.anchors.pop_front();
+        }
+        self.anchors.push_back(self.tree.root());
+    }
+
+    /// Appends note commitments, returning their positions. The root
+    /// of the tree after all of them becomes the latest anchor, unless
+    /// it's the latest one already.
+    pub fn append_commitments(&mut self, cms: &[E::Fr], params: &E::Params) -> Result<Vec<u64>, Error> {
+        if self.tree.size() + cms.len() as u64 > 1 << self.tree.depth() {
+            return Err(Error::TreeFull);
+        }
+
+        let positions = cms.iter().map(|cm| {
+            self.tree.append(*cm, params).expect("there is room for the commitments")
+        }).collect();
+
+        // Spend-only transactions leave the root as it is,
+        // repeating it would push out older anchors
+        if self.anchors.back() != Some(&self.tree.root()) {
+            self.push_anchor();
+        }
+
+        Ok(positions)
+    }
+
+    /// Reveals the nullifiers, failing without changing the state if
+    /// any of them is revealed already, or twice.
+    pub fn spend(&mut self, nullifiers: &[[u8; 32]]) -> Result<(), Error> {
+        let mut revealed = BTreeSet::new();
+        for nf in nullifiers {
+            if self.is_spent(nf) || !revealed.insert(*nf) {
+                return Err(Error::DoubleSpend);
+            }
+        }
+
+        self.nullifiers.extend(revealed);
+
+        Ok(())
+    }
+
+    /// Applies a transaction, which is expected to be verified, by
+    /// revealing its nullifiers and appending its note commitments.
+    /// Returns the positions of the new notes.
+    pub fn apply_transaction(&mut self, tx: &Transaction<E>, params: &E::Params) -> Result<Vec<u64>, Error> {
+        if tx.spends.iter().any(|spend| !self.is_known_anchor(&spend.anchor)) {
+            return Err(Error::UnknownAnchor);
+        }
+
+        if self.tree.size() + tx.outputs.len() as u64 > 1 << self.tree.depth() {
+            return Err(Error::TreeFull);
+        }
+
+        let nullifiers: Vec<_> = tx.spends.iter().map(|spend| spend.nullifier).collect();
+        self.spend(&nullifiers)?;
+
+        let cms: Vec<_> = tx.outputs.iter().map(|output| output.cmu).collect();
+
+        self.append_commitments(&cms, params)
+    }
+
+    pub fn merkle_path(&self, position: u64) -> Option<MerklePath<E>> {
+        self.tree.path(position)
+    }
+
+    /// The authentication path of the note at `position` to the
+    /// current root, in the form of `Spend::auth_path`.
+    pub fn auth_path(&self, position: u64) -> Option<Vec<Option<(E::Fr, bool)>>> {
+        self.tree.path(position).map(|path| {
+            path.auth_path.into_iter().map(Some).collect()
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(STATE_MAGIC)?;
+        writer.write_u32::<LittleEndian>(STATE_VERSION)?;
+
+        writer.write_u8(self.tree.depth() as u8)?;
+        writer.write_u32::<LittleEndian>(self.max_anchors as u32)?;
+
+        writer.write_u64::<LittleEndian>(self.tree.size())?;
+        for position in 0..self.tree.size() {
+            self.tree.leaf(position).into_repr().write_le(&mut writer)?;
+        }
+
+        writer.write_u32::<LittleEndian>(self.anchors.len() as u32)?;
+        for anchor in &self.anchors {
+            anchor.into_repr().write_le(&mut writer)?;
+        }
+
+        writer.write_u64::<LittleEndian>(self.nullifiers.len() as u64)?;
+        for nf in &self.nullifiers {
+            writer.write_all(nf)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut reader: R, params: &E::Params) -> io::Result<Self> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != STATE_MAGIC {
+            return Err(invalid("not a shielded state file"));
+        }
+        if reader.read_u32::<LittleEndian>()? != STATE_VERSION {
+            return Err(invalid("unsupported state file version"));
+        }
+
+        let tree_depth = reader.read_u8()? as usize;
+        let max_anchors = reader.read_u32::<LittleEndian>()? as usize;
+        if tree_depth == 0 || tree_depth > MAX_MERKLE_TREE_DEPTH || max_anchors == 0 {
+            return Err(invalid("invalid state parameters"));
+        }
+
+        let mut tree = CommitmentTree::new(tree_depth, params);
+        let num_commitments = reader.read_u64::<LittleEndian>()?;
+        for _ in 0..num_commitments {
+            let cm = read_fr::<E, _>(&mut reader)?;
+            tree.append(cm, params).ok_or_else(|| invalid("too many commitments"))?;
+        }
+
+        let num_anchors = reader.read_u32::<LittleEndian>()? as usize;
+        if num_anchors == 0 || num_anchors > max_anchors {
+            return Err(invalid("invalid number of anchors"));
+        }
+        let mut anchors = VecDeque::new();
+        for _ in 0..num_anchors {
+            anchors.push_back(read_fr::<E, _>(&mut reader)?);
+        }
+        if anchors.back() != Some(&tree.root()) {
+            return Err(invalid("the latest anchor is not the root of the tree"));
+        }
+
+        let num_nullifiers = reader.read_u64::<LittleEndian>()?;
+        let mut nullifiers = BTreeSet::new();
+        for _ in 0..num_nullifiers {
+            let mut nf = [0u8; 32];
+            reader.read_exact(&mut nf)?;
+            nullifiers.insert(nf);
+        }
+
+        Ok(ShieldedState {
+            tree: tree,
+            anchors: anchors,
+            max_anchors: max_anchors,
+            nullifiers: nullifiers
+        })
+    }
+
+    /// Writes the state to a temporary file next to `path` and renames
+    /// it into place, so a crash during the write keeps the old state.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        {
+            let mut writer = BufWriter::new(File::create(&tmp)?);
+            self.write(&mut writer)?;
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+        }
+
+        fs::rename(&tmp, path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P, params: &E::Params) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?), params)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bellman::pairing::bls12_381::{Bls12, Fr, G1Affine, G2Affine};
+    use bellman::pairing::CurveAffine;
+    use bellman::groth16::Proof;
+    use bellman::{Circuit, ConstraintSystem};
+    use rand::{XorShiftRng, SeedableRng, Rng};
+
+    use circuit::sapling::Spend;
+    use circuit::test::TestConstraintSystem;
+    use jubjub::{JubjubBls12, edwards, fs};
+    use redjubjub::{PublicKey, Signature};
+
+    use primitives::ValueCommitment;
+    use primitives::keys::{SpendingKey, FullViewingKey, DiversifierIndex, DiversifierKey};
+    use primitives::transaction::{SpendDescription, OutputDescription};
+
+    use super::*;
+
+    fn dummy_proof() -> Proof<Bls12> {
+        Proof {
+            a: G1Affine::zero(),
+            b: G2Affine::zero(),
+            c: G1Affine::zero()
+        }
+    }
+
+    fn dummy_transaction(anchor: Fr, nullifiers: &[[u8; 32]], cms: &[Fr]) -> Transaction<Bls12> {
+        let signature = Signature::read(&[0u8; 64][..]).unwrap();
+
+        Transaction {
+            spends: nullifiers.iter().map(|nf| SpendDescription {
+                cv: edwards::Point::zero(),
+                anchor: anchor,
+                nullifier: *nf,
+                rk: PublicKey(edwards::Point::zero()),
+                zkproof: dummy_proof(),
+                spend_auth_sig: signature
+            }).collect(),
+            outputs: cms.iter().map(|cm| OutputDescription {
+                cv: edwards::Point::zero(),
+                cmu: *cm,
+                ephemeral_key: edwards::Point::zero(),
+                enc_ciphertext: vec![],
+                out_ciphertext: vec![],
+                zkproof: dummy_proof()
+            }).collect(),
+            value_balance: 0,
+            binding_sig: signature
+        }
+    }
+
+    #[test]
+    fn test_anchors_and_nullifiers() {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let mut state = ShieldedState::<Bls12>::new(3, 2, params);
+        let empty_root = state.root();
+        assert!(state.is_known_anchor(&empty_root));
+
+        let cms: Vec<Fr> = (0..3).map(|_| rng.gen()).collect();
+        assert_eq!(state.append_commitments(&cms, params), Ok(vec![0, 1, 2]));
+        let first_root = state.root();
+
+        // Only the last two roots are kept
+        assert!(state.is_known_anchor(&empty_root));
+        state.append_commitments(&[rng.gen()], params).unwrap();
+        assert!(!state.is_known_anchor(&empty_root));
+        assert!(state.is_known_anchor(&first_root));
+
+        // Spends must use a recent anchor
+        let nf: [u8; 32] = rng.gen();
+        assert_eq!(state.apply_transaction(&dummy_transaction(empty_root, &[nf], &[]), params), Err(Error::UnknownAnchor));
+
+        // A nullifier can be revealed once
+        let tx = dummy_transaction(first_root, &[nf], &[rng.gen(), rng.gen()]);
+        assert_eq!(state.apply_transaction(&tx, params), Ok(vec![4, 5]));
+        assert!(state.is_spent(&nf));
+        let tx = dummy_transaction(state.root(), &[nf], &[]);
+        assert_eq!(state.apply_transaction(&tx, params), Err(Error::DoubleSpend));
+        assert_eq!(state.spend(&[nf]), Err(Error::DoubleSpend));
+
+        // Failed transactions don't change the state
+        let other: [u8; 32] = rng.gen();
+        let root = state.root();
+        let tx = dummy_transaction(root, &[other, other], &[rng.gen()]);
+        assert_eq!(state.apply_transaction(&tx, params), Err(Error::DoubleSpend));
+        assert!(!state.is_spent(&other));
+        assert_eq!(state.root(), root);
+        assert_eq!(state.num_commitments(), 6);
+
+        let tx = dummy_transaction(root, &[other], &[rng.gen(), rng.gen(), rng.gen()]);
+        assert_eq!(state.apply_transaction(&tx, params), Err(Error::TreeFull));
+        assert!(!state.is_spent(&other));
+
+        // Every path leads to the current root
+        for (position, cm) in cms.iter().enumerate() {
+            let path = state.merkle_path(position as u64).unwrap();
+            assert_eq!(path.root(*cm, params), state.root());
+        }
+        assert!(state.auth_path(6).is_none());
+
+        // The state survives a round trip through a file
+        let file = ::std::env::temp_dir().join(format!("shielded_state_{}.bin", rng.gen::<u64>()));
+        state.save(&file).unwrap();
+        let loaded = ShieldedState::<Bls12>::load(&file, params).unwrap();
+        ::std::fs::remove_file(&file).unwrap();
+
+        assert_eq!(loaded.root(), state.root());
+        assert_eq!(loaded.num_commitments(), 6);
+        assert!(loaded.is_spent(&nf));
+        assert!(loaded.is_known_anchor(&root));
+
+        let mut bytes = vec![];
+        state.write(&mut bytes).unwrap();
+        let mut again = vec![];
+        loaded.write(&mut again).unwrap();
+        assert_eq!(bytes, again);
+
+        // Saving again replaces the file without leaving the temporary one
+        state.save(&file).unwrap();
+        state.save(&file).unwrap();
+        assert!(ShieldedState::<Bls12>::load(&file, params).is_ok());
+        let mut tmp = file.as_os_str().to_owned();
+        tmp.push(".tmp");
+        assert!(!::std::path::Path::new(&tmp).exists());
+        ::std::fs::remove_file(&file).unwrap();
+
+        // Truncated states are rejected
+        assert!(ShieldedState::<Bls12>::read(&bytes[0..bytes.len() - 1], params).is_err());
+
+        // So are files with another magic or version
+        let mut other = bytes.clone();
+        other[0] ^= 1;
+        assert_eq!(ShieldedState::<Bls12>::read(&other[..], params).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        let mut other = bytes.clone();
+        other[8] += 1;
+        assert_eq!(ShieldedState::<Bls12>::read(&other[..], params).err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_spend_only_transactions() {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let mut state = ShieldedState::<Bls12>::new(3, 2, params);
+        state.append_commitments(&[rng.gen()], params).unwrap();
+        let first_root = state.root();
+        state.append_commitments(&[rng.gen()], params).unwrap();
+        let second_root = state.root();
+
+        // Transactions without outputs don't push out the anchors
+        for _ in 0..3 {
+            let tx = dummy_transaction(first_root, &[rng.gen()], &[]);
+            assert_eq!(state.apply_transaction(&tx, params), Ok(vec![]));
+        }
+        assert_eq!(state.append_commitments(&[], params), Ok(vec![]));
+        assert!(state.is_known_anchor(&first_root));
+        assert!(state.is_known_anchor(&second_root));
+        assert_eq!(state.anchors.len(), 2);
+
+        let tx = dummy_transaction(first_root, &[rng.gen()], &[rng.gen()]);
+        assert_eq!(state.apply_transaction(&tx, params), Ok(vec![2]));
+        assert!(!state.is_known_anchor(&first_root));
+        assert!(state.is_known_anchor(&second_root));
+    }
+
+    #[test]
+    fn test_auth_path_in_spend_circuit() {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let expsk = SpendingKey::random(rng).expand::<Bls12>();
+        let proof_generation_key = expsk.proof_generation_key(params);
+        let vk = FullViewingKey::from_expanded_spending_key(&expsk, params).vk;
+        let (_, address) = DiversifierKey(rng.gen()).find_address(&vk, DiversifierIndex::new(), params).unwrap();
+
+        let mut state = ShieldedState::<Bls12>::new(8, 10, params);
+        state.append_commitments(&[rng.gen(), rng.gen()], params).unwrap();
+
+        let value_commitment = ValueCommitment::<Bls12> {
+            value: 100,
+            randomness: rng.gen()
+        };
+        let rcm: fs::Fs = rng.gen();
+        let note = address.create_note(value_commitment.value, rcm, params).unwrap();
+        let position = state.append_commitments(&[note.cm(params)], params).unwrap()[0];
+        state.append_commitments(&[rng.gen()], params).unwrap();
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        let instance = Spend {
+            params: params,
+            value_commitment: Some(value_commitment),
+            proof_generation_key: Some(proof_generation_key),
+            payment_address: Some(address),
+            commitment_randomness: Some(rcm),
+            ar: Some(rng.gen()),
+            auth_path: state.auth_path(position).unwrap(),
+            anchor: Some(state.root())
+        };
+        instance.synthesize(&mut cs).unwrap();
+
+        assert!(cs.is_satisfied());
+        assert_eq!(cs.get_input(5, "anchor/input variable"), state.root());
+    }
+}
@@ -0,0 +1,722 @@
+//! Shielded transactions made of Sapling spends and outputs.
+//!
+//! The builder proves every spend and output, signs the transaction
+//! with the randomized spend authorizing keys, and binds the value
+//! commitments to the value balance with the binding signature:
+//!
+//! `bsk = sum rcv_spend - sum rcv_output`
+//!
+//! `bvk = sum cv_spend - sum cv_output - [value_balance] ValueCommitmentValue`
+//!
+//! so `bvk = [bsk] ValueCommitmentRandomness` only if the values balance.
+
+use bellman::pairing::ff::{
+    Field,
+    PrimeField,
+    PrimeFieldRepr
+};
+
+use bellman::groth16::{
+    self,
+    Parameters,
+    PreparedVerifyingKey,
+    Proof
+};
+
+use bellman::SynthesisError;
+
+use byteorder::{
+    LittleEndian,
+    WriteBytesExt
+};
+
+use rand::Rng;
+
+use blake2_rfc::blake2b::Blake2b;
+
+use jubjub::{
+    JubjubEngine,
+    JubjubParams,
+    FixedGenerators,
+    edwards,
+    PrimeOrder,
+    Unknown
+};
+
+use circuit::multipack;
+use circuit::sapling::{
+    Spend,
+    Output
+};
+
+use redjubjub::{
+    PrivateKey,
+    PublicKey,
+    Signature
+};
+
+use super::{
+    Diversifier,
+    Note,
+    PaymentAddress,
+    ValueCommitment
+};
+
+use super::keys::ExpandedSpendingKey;
+use super::merkle_tree::MerklePath;
+use super::note_encryption::{
+    Memo,
+    OutgoingViewingKey,
+    SaplingNoteEncryption
+};
+
+/// BLAKE2b Personalization for the transaction signature hash
+pub const SIGHASH_PERSONALIZATION: &'static [u8; 16]
+          = b"Matter_SaplingTx";
+
+#[derive(Debug)]
+pub enum Error {
+    /// The spent notes are in trees with different roots
+    AnchorMismatch,
+    /// The note doesn't belong to the spending key
+    InvalidAddress,
+    /// The value balance is out of the range `[-i64::MAX, i64::MAX]`
+    InvalidAmount,
+    Synthesis(SynthesisError)
+}
+
+impl From<SynthesisError> for Error {
+    fn from(e: SynthesisError) -> Self {
+        Error::Synthesis(e)
+    }
+}
+
+/// Parameters of the `Spend` circuit for trees of some depth,
+/// and of the `Output` circuit.
+pub struct ProvingKeys<E: JubjubEngine> {
+    pub spend: Parameters<E>,
+    pub output: Parameters<E>
+}
+
+pub struct VerifyingKeys<E: JubjubEngine> {
+    pub spend: PreparedVerifyingKey<E>,
+    pub output: PreparedVerifyingKey<E>
+}
+
+impl<E: JubjubEngine> ProvingKeys<E> {
+    /// Generates random parameters, which is only suitable for tests
+    /// and local deployments, as the generator learns the trapdoor.
+    pub fn generate<R: Rng>(
+        tree_depth: usize,
+        params: &E::Params,
+        rng: &mut R
+    ) -> Result<Self, SynthesisError>
+    {
+        Ok(ProvingKeys {
+            spend: groth16::generate_random_parameters(Spend::<E>::blank(params, tree_depth), rng)?,
+            output: groth16::generate_random_parameters(Output::<E>::blank(params), rng)?
+        })
+    }
+
+    pub fn verifying_keys(&self) -> VerifyingKeys<E> {
+        VerifyingKeys {
+            spend: groth16::prepare_verifying_key(&self.spend.vk),
+            output: groth16::prepare_verifying_key(&self.output.vk)
+        }
+    }
+}
+
+pub struct SpendDescription<E: JubjubEngine> {
+    pub cv: edwards::Point<E, Unknown>,
+    pub anchor: E::Fr,
+    pub nullifier: [u8; 32],
+    pub rk: PublicKey<E>,
+    pub zkproof: Proof<E>,
+    pub spend_auth_sig: Signature
+}
+
+pub struct OutputDescription<E: JubjubEngine> {
+    pub cv: edwards::Point<E, Unknown>,
+    pub cmu: E::Fr,
+    pub ephemeral_key: edwards::Point<E, Unknown>,
+    pub enc_ciphertext: Vec<u8>,
+    pub out_ciphertext: Vec<u8>,
+    pub zkproof: Proof<E>
+}
+
+pub struct Transaction<E: JubjubEngine> {
+    pub spends: Vec<SpendDescription<E>>,
+    pub outputs: Vec<OutputDescription<E>>,
+    /// The value leaving the shielded pool, which is negative
+    /// if value enters the pool
+    pub value_balance: i64,
+    pub binding_sig: Signature
+}
+
+fn is_small_order<E: JubjubEngine>(p: &edwards::Point<E, Unknown>, params: &E::Params) -> bool {
+    p.mul_by_cofactor(params) == edwards::Point::zero()
+}
+
+/// Hashes everything but the signatures of the transaction.
+fn signature_hash<E: JubjubEngine>(
+    spends: &[(edwards::Point<E, Unknown>, E::Fr, [u8; 32], PublicKey<E>, Proof<E>)],
+    outputs: &[OutputDescription<E>],
+    value_balance: i64
+) -> [u8; 32]
+{
+    let mut data = vec![];
+
+    for &(ref cv, ref anchor, ref nullifier, ref rk, ref zkproof) in spends {
+        cv.write(&mut data).unwrap();
+        anchor.into_repr().write_le(&mut data).unwrap();
+        data.extend_from_slice(nullifier);
+        rk.write(&mut data).unwrap();
+        zkproof.write(&mut data).unwrap();
+    }
+
+    for output in outputs {
+        output.cv.write(&mut data).unwrap();
+        output.cmu.into_repr().write_le(&mut data).unwrap();
+        output.ephemeral_key.write(&mut data).unwrap();
+        data.extend_from_slice(&output.enc_ciphertext);
+        data.extend_from_slice(&output.out_ciphertext);
+        output.zkproof.write(&mut data).unwrap();
+    }
+
+    data.write_i64::<LittleEndian>(value_balance).unwrap();
+
+    let mut h = Blake2b::with_params(32, &[], &[], SIGHASH_PERSONALIZATION);
+    h.update(&data);
+
+    let mut res = [0u8; 32];
+    res.copy_from_slice(h.finalize().as_ref());
+
+    res
+}
+
+/// bvk = sum cv_spend - sum cv_output - [value_balance] ValueCommitmentValue
+fn binding_verification_key<E: JubjubEngine>(
+    spends: &[SpendDescription<E>],
+    outputs: &[OutputDescription<E>],
+    value_balance: i64,
+    params: &E::Params
+) -> PublicKey<E>
+{
+    let mut bvk = edwards::Point::zero();
+    for spend in spends {
+        bvk = bvk.add(&spend.cv, params);
+    }
+    for output in outputs {
+        bvk = bvk.add(&output.cv.negate(), params);
+    }
+
+    let balance = params.generator(FixedGenerators::ValueCommitmentValue)
+                        .mul(value_balance.unsigned_abs(), params);
+    let balance: edwards::Point<E, Unknown> = if value_balance < 0 { balance.negate().into() } else { balance.into() };
+
+    PublicKey(bvk.add(&balance.negate(), params))
+}
+
+impl<E: JubjubEngine> Transaction<E> {
+    pub fn signature_hash(&self) -> [u8; 32] {
+        let spends: Vec<_> = self.spends.iter().map(|s| {
+            (s.cv.clone(), s.anchor, s.nullifier, s.rk.clone(), s.zkproof.clone())
+        }).collect();
+
+        signature_hash(&spends, &self.outputs, self.value_balance)
+    }
+
+    /// Checks the proofs, the spend authorizing signatures and the
+    /// binding signature. Whether the anchors are known and the
+    /// nullifiers are unspent is up to the caller.
+    pub fn verify(&self, vks: &VerifyingKeys<E>, params: &E::Params) -> bool {
+        if checked_balance(Some(self.value_balance)).is_none() {
+            return false;
+        }
+
+        let sighash = self.signature_hash();
+
+        for spend in &self.spends {
+            if is_small_order(&spend.cv, params) || is_small_order(&spend.rk.0, params) {
+                return false;
+            }
+
+            if !spend.rk.verify(&sighash, &spend.spend_auth_sig, FixedGenerators::SpendingKeyGenerator, params) {
+                return false;
+            }
+
+            let mut public_inputs = vec![];
+            let (x, y) = spend.rk.0.into_xy();
+            public_inputs.extend_from_slice(&[x, y]);
+            let (x, y) = spend.cv.into_xy();
+            public_inputs.extend_from_slice(&[x, y]);
+            public_inputs.push(spend.anchor);
+            public_inputs.extend(multipack::compute_multipacking::<E>(
+                &multipack::bytes_to_bits_le(&spend.nullifier)
+            ));
+
+            match groth16::verify_proof(&vks.spend, &spend.zkproof, &public_inputs) {
+                Ok(true) => {},
+                _ => return false
+            }
+        }
+
+        for output in &self.outputs {
+            if is_small_order(&output.cv, params) || is_small_order(&output.ephemeral_key, params) {
+                return false;
+            }
+
+            let mut public_inputs = vec![];
+            let (x, y) = output.cv.into_xy();
+            public_inputs.extend_from_slice(&[x, y]);
+            let (x, y) = output.ephemeral_key.into_xy();
+            public_inputs.extend_from_slice(&[x, y]);
+            public_inputs.push(output.cmu);
+
+            match groth16::verify_proof(&vks.output, &output.zkproof, &public_inputs) {
+                Ok(true) => {},
+                _ => return false
+            }
+        }
+
+        binding_verification_key(&self.spends, &self.outputs, self.value_balance, params)
+            .verify(&sighash, &self.binding_sig, FixedGenerators::ValueCommitmentRandomness, params)
+    }
+}
+
+fn signed(value: u64) -> Option<i64> {
+    if value > i64::max_value() as u64 {
+        None
+    } else {
+        Some(value as i64)
+    }
+}
+
+/// Keeps the value balance symmetric around zero, `i64::MIN`
+/// has no positive counterpart.
+fn checked_balance(value_balance: Option<i64>) -> Option<i64> {
+    value_balance.and_then(|v| if v == i64::min_value() { None } else { Some(v) })
+}
+
+struct SpendInfo<E: JubjubEngine> {
+    expsk: ExpandedSpendingKey<E>,
+    diversifier: Diversifier,
+    note: Note<E>,
+    merkle_path: MerklePath<E>
+}
+
+struct OutputInfo<E: JubjubEngine> {
+    ovk: OutgoingViewingKey,
+    to: PaymentAddress<E>,
+    value: u64,
+    memo: Memo
+}
+
+/// Collects spends and outputs and builds a `Transaction` whose
+/// value balance is the difference of their values.
+pub struct Builder<'a, E: JubjubEngine> {
+    params: &'a E::Params,
+    anchor: Option<E::Fr>,
+    value_balance: i64,
+    spends: Vec<SpendInfo<E>>,
+    outputs: Vec<OutputInfo<E>>
+}
+
+impl<'a, E: JubjubEngine> Builder<'a, E> {
+    pub fn new(params: &'a E::Params) -> Self {
+        Builder {
+            params: params,
+            anchor: None,
+            value_balance: 0,
+            spends: vec![],
+            outputs: vec![]
+        }
+    }
+
+    /// Adds a note to spend, which is at `merkle_path` in the
+    /// tree. All of the spent notes must be in the same tree.
+    /// The value balance must stay in `[-i64::MAX, i64::MAX]`.
+    pub fn add_spend(
+        &mut self,
+        expsk: ExpandedSpendingKey<E>,
+        diversifier: Diversifier,
+        note: Note<E>,
+        merkle_path: MerklePath<E>
+    ) -> Result<(), Error>
+    {
+        let vk = expsk.proof_generation_key(self.params).into_viewing_key(self.params);
+        match vk.into_payment_address(diversifier, self.params) {
+            Some(ref address) if address.pk_d == note.pk_d => {},
+            _ => return Err(Error::InvalidAddress)
+        }
+
+        let anchor = merkle_path.root(note.cm(self.params), self.params);
+        if self.anchor.map_or(false, |existing| existing != anchor) {
+            return Err(Error::AnchorMismatch);
+        }
+
+        self.value_balance = checked_balance(signed(note.value).and_then(|v| self.value_balance.checked_add(v)))
+                                               .ok_or(Error::InvalidAmount)?;
+        self.anchor = Some(anchor);
+
+        self.spends.push(SpendInfo {
+            expsk: expsk,
+            diversifier: diversifier,
+            note: note,
+            merkle_path: merkle_path
+        });
+
+        Ok(())
+    }
+
+    pub fn add_output(
+        &mut self,
+        ovk: OutgoingViewingKey,
+        to: PaymentAddress<E>,
+        value: u64,
+        memo: Memo
+    ) -> Result<(), Error>
+    {
+        if to.g_d(self.params).is_none() {
+            return Err(Error::InvalidAddress);
+        }
+
+        self.value_balance = checked_balance(signed(value).and_then(|v| self.value_balance.checked_sub(v)))
+                                          .ok_or(Error::InvalidAmount)?;
+
+        self.outputs.push(OutputInfo {
+            ovk: ovk,
+            to: to,
+            value: value,
+            memo: memo
+        });
+
+        Ok(())
+    }
+
+    pub fn build<R: Rng>(self, keys: &ProvingKeys<E>, rng: &mut R) -> Result<Transaction<E>, Error> {
+        let params = self.params;
+
+        let value_balance = self.value_balance;
+
+        // bsk = sum rcv_spend - sum rcv_output
+        let mut bsk = E::Fs::zero();
+
+        let mut spends = vec![];
+        let mut spend_keys = vec![];
+        for spend in self.spends {
+            let rcv: E::Fs = rng.gen();
+            let ar: E::Fs = rng.gen();
+            bsk.add_assign(&rcv);
+
+            let value_commitment = ValueCommitment::<E> {
+                value: spend.note.value,
+                randomness: rcv
+            };
+            let cv = value_commitment.cm(params).into();
+
+            let proof_generation_key = spend.expsk.proof_generation_key(params);
+            let vk = proof_generation_key.into_viewing_key(params);
+            let rk = PublicKey(vk.rk(ar, params).into());
+
+            let mut nullifier = [0u8; 32];
+            nullifier.copy_from_slice(&spend.note.nf(&vk, spend.merkle_path.position, params));
+
+            let anchor = spend.merkle_path.root(spend.note.cm(params), params);
+
+            let instance = Spend {
+                params: params,
+                value_commitment: Some(value_commitment),
+                proof_generation_key: Some(proof_generation_key),
+                payment_address: vk.into_payment_address(spend.diversifier, params),
+                commitment_randomness: Some(spend.note.r),
+                ar: Some(ar),
+                auth_path: spend.merkle_path.auth_path.iter().map(|p| Some(*p)).collect(),
+                anchor: Some(anchor)
+            };
+            let zkproof = groth16::create_random_proof(instance, &keys.spend, rng)?;
+
+            spends.push((cv, anchor, nullifier, rk, zkproof));
+            spend_keys.push(PrivateKey::<E>(spend.expsk.ask).randomize(ar));
+        }
+
+        let mut outputs = vec![];
+        for output in self.outputs {
+            let rcv: E::Fs = rng.gen();
+            let rcm: E::Fs = rng.gen();
+            let esk: E::Fs = rng.gen();
+            bsk.sub_assign(&rcv);
+
+            let value_commitment = ValueCommitment::<E> {
+                value: output.value,
+                randomness: rcv
+            };
+            let cv: edwards::Point<E, Unknown> = value_commitment.cm(params).into();
+
+            let note = output.to.create_note(output.value, rcm, params).ok_or(Error::InvalidAddress)?;
+            let cmu = note.cm(params);
+
+            let encryptor = SaplingNoteEncryption::new(output.ovk, note, output.to.clone(), output.memo, esk, params);
+
+            let instance = Output {
+                params: params,
+                value_commitment: Some(value_commitment),
+                payment_address: Some(output.to),
+                commitment_randomness: Some(rcm),
+                esk: Some(esk)
+            };
+            let zkproof = groth16::create_random_proof(instance, &keys.output, rng)?;
+
+            outputs.push(OutputDescription {
+                ephemeral_key: encryptor.epk().clone().into(),
+                enc_ciphertext: encryptor.encrypt_note_plaintext(params),
+                out_ciphertext: encryptor.encrypt_outgoing_plaintext(&cv, &cmu),
+                cv: cv,
+                cmu: cmu,
+                zkproof: zkproof
+            });
+        }
+
+        let sighash = signature_hash(&spends, &outputs, value_balance);
+
+        let spends: Vec<_> = spends.into_iter().zip(spend_keys.iter()).map(|((cv, anchor, nullifier, rk, zkproof), rsk)| {
+            SpendDescription {
+                cv: cv,
+                anchor: anchor,
+                nullifier: nullifier,
+                rk: rk,
+                zkproof: zkproof,
+                spend_auth_sig: rsk.sign(&sighash, rng, FixedGenerators::SpendingKeyGenerator, params)
+            }
+        }).collect();
+
+        let bsk = PrivateKey::<E>(bsk);
+        let binding_sig = bsk.sign(&sighash, rng, FixedGenerators::ValueCommitmentRandomness, params);
+
+        // The values balance, so the binding key matches
+        debug_assert!(
+            binding_verification_key(&spends, &outputs, value_balance, params).0
+            == PublicKey::from_private(&bsk, FixedGenerators::ValueCommitmentRandomness, params).0
+        );
+
+        Ok(Transaction {
+            spends: spends,
+            outputs: outputs,
+            value_balance: value_balance,
+            binding_sig: binding_sig
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bellman::pairing::bls12_381::{Bls12, Fr};
+    use rand::{XorShiftRng, SeedableRng, Rng};
+
+    use jubjub::JubjubBls12;
+
+    use primitives::keys::{
+        SpendingKey,
+        FullViewingKey,
+        DiversifierIndex,
+        DiversifierKey
+    };
+    use primitives::merkle_tree::CommitmentTree;
+    use primitives::note_encryption::{
+        try_sapling_note_decryption,
+        try_sapling_output_recovery
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_build_and_verify() {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let tree_depth = 4;
+        let keys = ProvingKeys::<Bls12>::generate(tree_depth, params, rng).unwrap();
+        let vks = keys.verifying_keys();
+
+        let sk = SpendingKey::random(rng);
+        let expsk = sk.expand::<Bls12>();
+        let fvk = FullViewingKey::from_expanded_spending_key(&expsk, params);
+        let (_, address) = sk.diversifier_key().find_address(&fvk.vk, DiversifierIndex::new(), params).unwrap();
+
+        let recipient = SpendingKey::random(rng).expand::<Bls12>();
+        let recipient_vk = FullViewingKey::from_expanded_spending_key(&recipient, params).vk;
+        let (_, recipient_address) = DiversifierKey(rng.gen()).find_address(&recipient_vk, DiversifierIndex::new(), params).unwrap();
+
+        // Fund the sender in a local tree
+        let mut tree = CommitmentTree::<Bls12>::new(tree_depth, params);
+        tree.append(rng.gen(), params).unwrap();
+
+        let mut funded = vec![];
+        for &value in &[30, 20] {
+            let note = address.create_note(value, rng.gen(), params).unwrap();
+            let position = tree.append(note.cm(params), params).unwrap();
+            funded.push((note, position));
+        }
+        tree.append(rng.gen(), params).unwrap();
+
+        let mut builder = Builder::new(params);
+        for &(ref note, position) in &funded {
+            let note = address.create_note(note.value, note.r, params).unwrap();
+            builder.add_spend(expsk.clone(), address.diversifier, note, tree.path(position).unwrap()).unwrap();
+        }
+
+        let mut memo = Memo::default();
+        memo.0[0..5].copy_from_slice(b"hello");
+        builder.add_output(expsk.ovk, recipient_address.clone(), 45, memo.clone()).unwrap();
+
+        let mut tx = builder.build(&keys, rng).unwrap();
+        assert_eq!(tx.value_balance, 5);
+        assert!(tx.verify(&vks, params));
+
+        for (spend, &(ref note, position)) in tx.spends.iter().zip(funded.iter()) {
+            assert_eq!(spend.anchor, tree.root());
+            assert_eq!(spend.nullifier[..], note.nf(&fvk.vk, position, params)[..]);
+        }
+
+        // The recipient decrypts the note, and the sender recovers it
+        let output = &tx.outputs[0];
+        let (note, to, decrypted_memo) = try_sapling_note_decryption(
+            &recipient_vk.ivk(),
+            &output.ephemeral_key,
+            &output.cmu,
+            &output.enc_ciphertext,
+            params
+        ).unwrap();
+        assert_eq!(note.value, 45);
+        assert!(to.pk_d == recipient_address.pk_d);
+        assert!(decrypted_memo == memo);
+
+        let (note, _, _) = try_sapling_output_recovery(
+            &expsk.ovk,
+            &output.cv,
+            &output.cmu,
+            &output.ephemeral_key,
+            &output.enc_ciphertext,
+            &output.out_ciphertext,
+            params
+        ).unwrap();
+        assert_eq!(note.value, 45);
+
+        // Tampering with any part breaks the signatures or the proofs
+        tx.value_balance = 6;
+        assert!(!tx.verify(&vks, params));
+        tx.value_balance = 5;
+
+        tx.spends[1].nullifier[0] ^= 1;
+        assert!(!tx.verify(&vks, params));
+        tx.spends[1].nullifier[0] ^= 1;
+
+        let anchor = tx.spends[0].anchor;
+        tx.spends[0].anchor = Fr::one();
+        assert!(!tx.verify(&vks, params));
+        tx.spends[0].anchor = anchor;
+
+        tx.outputs[0].cmu = rng.gen();
+        assert!(!tx.verify(&vks, params));
+
+        // Shielding value into the pool
+        let mut builder = Builder::new(params);
+        builder.add_output(expsk.ovk, address.clone(), 10, Memo::default()).unwrap();
+        let tx = builder.build(&keys, rng).unwrap();
+        assert_eq!(tx.value_balance, -10);
+        assert!(tx.verify(&vks, params));
+    }
+
+    #[test]
+    fn test_builder_errors() {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let sk = SpendingKey::random(rng);
+        let expsk = sk.expand::<Bls12>();
+        let fvk = FullViewingKey::from_expanded_spending_key(&expsk, params);
+        let (_, address) = sk.diversifier_key().find_address(&fvk.vk, DiversifierIndex::new(), params).unwrap();
+
+        let mut tree = CommitmentTree::<Bls12>::new(4, params);
+        let note = address.create_note(10, rng.gen(), params).unwrap();
+        let first = tree.append(note.cm(params), params).unwrap();
+        let old_path = tree.path(first).unwrap();
+
+        let second_note = address.create_note(20, rng.gen(), params).unwrap();
+        let second = tree.append(second_note.cm(params), params).unwrap();
+
+        // The note must belong to the key
+        let mut builder = Builder::new(params);
+        let other = SpendingKey::random(rng).expand::<Bls12>();
+        match builder.add_spend(other, address.diversifier, note, old_path.clone()) {
+            Err(Error::InvalidAddress) => {},
+            _ => panic!("spend with another key must fail")
+        }
+
+        // A rejected spend doesn't set the anchor
+        let large = address.create_note(u64::max_value(), rng.gen(), params).unwrap();
+        match builder.add_spend(expsk.clone(), address.diversifier, large, old_path.clone()) {
+            Err(Error::InvalidAmount) => {},
+            _ => panic!("value out of range must fail")
+        }
+        assert!(builder.anchor.is_none());
+
+        // The notes must be spent from the same tree
+        builder.add_spend(expsk.clone(), address.diversifier, second_note, tree.path(second).unwrap()).unwrap();
+        let note = address.create_note(10, rng.gen(), params).unwrap();
+        match builder.add_spend(expsk.clone(), address.diversifier, note, old_path) {
+            Err(Error::AnchorMismatch) => {},
+            _ => panic!("spend from another tree must fail")
+        }
+
+        // The value balance must stay in [-i64::MAX, i64::MAX]
+        let mut builder = Builder::<Bls12>::new(params);
+        match builder.add_output(expsk.ovk, address.clone(), u64::max_value(), Memo::default()) {
+            Err(Error::InvalidAmount) => {},
+            _ => panic!("value out of range must fail")
+        }
+        builder.add_output(expsk.ovk, address.clone(), i64::max_value() as u64, Memo::default()).unwrap();
+        match builder.add_output(expsk.ovk, address.clone(), 1, Memo::default()) {
+            Err(Error::InvalidAmount) => {},
+            _ => panic!("value balance of i64::MIN must fail")
+        }
+        assert_eq!(builder.value_balance, -i64::max_value());
+    }
+
+    #[test]
+    fn test_extreme_value_balance() {
+        let params = &JubjubBls12::new();
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let tree_depth = 4;
+        let keys = ProvingKeys::<Bls12>::generate(tree_depth, params, rng).unwrap();
+        let vks = keys.verifying_keys();
+
+        let sk = SpendingKey::random(rng);
+        let expsk = sk.expand::<Bls12>();
+        let fvk = FullViewingKey::from_expanded_spending_key(&expsk, params);
+        let (_, address) = sk.diversifier_key().find_address(&fvk.vk, DiversifierIndex::new(), params).unwrap();
+
+        // Spending a note of i64::MAX into the transparent pool
+        let mut tree = CommitmentTree::<Bls12>::new(tree_depth, params);
+        let note = address.create_note(i64::max_value() as u64, rng.gen(), params).unwrap();
+        let position = tree.append(note.cm(params), params).unwrap();
+
+        let mut builder = Builder::new(params);
+        builder.add_spend(expsk.clone(), address.diversifier, note, tree.path(position).unwrap()).unwrap();
+        let tx = builder.build(&keys, rng).unwrap();
+        assert_eq!(tx.value_balance, i64::max_value());
+        assert!(tx.verify(&vks, params));
+
+        // Shielding i64::MAX from the transparent pool
+        let mut builder = Builder::new(params);
+        builder.add_output(expsk.ovk, address.clone(), i64::max_value() as u64, Memo::default()).unwrap();
+        let mut tx = builder.build(&keys, rng).unwrap();
+        assert_eq!(tx.value_balance, -i64::max_value());
+        assert!(tx.verify(&vks, params));
+
+        // i64::MIN is rejected before any arithmetic on it
+        tx.value_balance = i64::min_value();
+        assert!(!tx.verify(&vks, params));
+    }
+}
@@ -0,0 +1,560 @@
+//! Hierarchical deterministic derivation of Sapling keys (ZIP 32).
+//!
+//! Extended keys are encoded in 169 bytes: the depth, the tag of
+//! the parent full viewing key, the little-endian child index (with
+//! the top bit set for hardened children), the chain code, the key
+//! components and the diversifier key.
+
+use bellman::pairing::ff::{
+    Field,
+    PrimeField,
+    PrimeFieldRepr
+};
+
+use byteorder::{
+    LittleEndian,
+    ReadBytesExt,
+    WriteBytesExt
+};
+
+use std::io::{self, Read, Write};
+
+use blake2_rfc::blake2b::Blake2b;
+
+use jubjub::{
+    JubjubEngine,
+    JubjubParams,
+    FixedGenerators,
+    ToUniform
+};
+
+use super::{
+    PaymentAddress,
+    ViewingKey
+};
+
+use super::keys::{
+    prf_expand,
+    prf_expand_vec,
+    DiversifierIndex,
+    DiversifierKey,
+    ExpandedSpendingKey,
+    FullViewingKey
+};
+
+use super::note_encryption::OutgoingViewingKey;
+
+/// BLAKE2b Personalization for the master key generation
+pub const ZIP32_SAPLING_MASTER_PERSONALIZATION: &'static [u8; 16]
+          = b"ZcashIP32Sapling";
+
+/// BLAKE2b Personalization for the full viewing key fingerprint
+pub const ZIP32_SAPLING_FVFP_PERSONALIZATION: &'static [u8; 16]
+          = b"ZcashSaplingFVFP";
+
+/// Length of the extended key encodings.
+pub const EXTENDED_KEY_BYTES: usize = 169;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// Number of the child doesn't fit into 31 bits
+    InvalidChildIndex,
+    /// Depth of the child doesn't fit into a byte
+    MaxDepthExceeded,
+    /// Hardened children can't be derived from a full viewing key
+    HardenedFromViewingKey
+}
+
+/// Number of the child, which has to be less than 2^31 for both kinds
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ChildIndex {
+    NonHardened(u32),
+    Hardened(u32)
+}
+
+impl ChildIndex {
+    pub fn from_index(i: u32) -> Self {
+        if i >= (1 << 31) {
+            ChildIndex::Hardened(i - (1 << 31))
+        } else {
+            ChildIndex::NonHardened(i)
+        }
+    }
+
+    /// Index with the top bit set for hardened children
+    pub fn to_index(&self) -> Result<u32, Error> {
+        match *self {
+            ChildIndex::Hardened(i) if i < (1 << 31) => Ok(i | (1 << 31)),
+            ChildIndex::NonHardened(i) if i < (1 << 31) => Ok(i),
+            _ => Err(Error::InvalidChildIndex)
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ChainCode(pub [u8; 32]);
+
+/// The first 4 bytes of the fingerprint of a full viewing key.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FvkTag(pub [u8; 4]);
+
+impl FvkTag {
+    pub fn master() -> Self {
+        FvkTag([0u8; 4])
+    }
+}
+
+/// FVFP = BLAKE2b-256(ak || nk || ovk)
+pub fn fvk_fingerprint<E: JubjubEngine>(fvk: &FullViewingKey<E>) -> [u8; 32] {
+    let mut encoded = vec![];
+    fvk.write(&mut encoded).expect("writes to a vector");
+
+    let mut h = Blake2b::with_params(32, &[], &[], ZIP32_SAPLING_FVFP_PERSONALIZATION);
+    h.update(&encoded);
+
+    let mut res = [0u8; 32];
+    res.copy_from_slice(h.finalize().as_ref());
+
+    res
+}
+
+fn fvk_tag<E: JubjubEngine>(fvk: &FullViewingKey<E>) -> FvkTag {
+    let mut tag = [0u8; 4];
+    tag.copy_from_slice(&fvk_fingerprint(fvk)[0..4]);
+
+    FvkTag(tag)
+}
+
+fn derive_child_ovk(i_l: &[u8], ovk: &OutgoingViewingKey) -> OutgoingViewingKey {
+    let mut res = [0u8; 32];
+    res.copy_from_slice(&prf_expand_vec(i_l, &[&[0x15], &ovk.0])[0..32]);
+
+    OutgoingViewingKey(res)
+}
+
+fn derive_child_dk(i_l: &[u8], dk: &DiversifierKey) -> DiversifierKey {
+    let mut res = [0u8; 32];
+    res.copy_from_slice(&prf_expand_vec(i_l, &[&[0x16], &dk.0])[0..32]);
+
+    DiversifierKey(res)
+}
+
+fn split_i(i: [u8; 64]) -> ([u8; 32], ChainCode) {
+    let mut i_l = [0u8; 32];
+    let mut i_r = [0u8; 32];
+    i_l.copy_from_slice(&i[0..32]);
+    i_r.copy_from_slice(&i[32..64]);
+
+    (i_l, ChainCode(i_r))
+}
+
+fn read_header<R: Read>(mut reader: R) -> io::Result<(u8, FvkTag, ChildIndex, ChainCode)> {
+    let depth = reader.read_u8()?;
+
+    let mut tag = [0u8; 4];
+    reader.read_exact(&mut tag)?;
+
+    let i = reader.read_u32::<LittleEndian>()?;
+
+    let mut c = [0u8; 32];
+    reader.read_exact(&mut c)?;
+
+    Ok((depth, FvkTag(tag), ChildIndex::from_index(i), ChainCode(c)))
+}
+
+fn write_header<W: Write>(
+    mut writer: W,
+    depth: u8,
+    parent_fvk_tag: &FvkTag,
+    child_index: &ChildIndex,
+    chain_code: &ChainCode
+) -> io::Result<()>
+{
+    let index = child_index.to_index().map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid child index"))?;
+
+    writer.write_u8(depth)?;
+    writer.write_all(&parent_fvk_tag.0)?;
+    writer.write_u32::<LittleEndian>(index)?;
+    writer.write_all(&chain_code.0)
+}
+
+pub struct ExtendedSpendingKey<E: JubjubEngine> {
+    pub depth: u8,
+    pub parent_fvk_tag: FvkTag,
+    pub child_index: ChildIndex,
+    pub chain_code: ChainCode,
+    pub expsk: ExpandedSpendingKey<E>,
+    pub dk: DiversifierKey
+}
+
+impl<E: JubjubEngine> Clone for ExtendedSpendingKey<E> {
+    fn clone(&self) -> Self {
+        ExtendedSpendingKey {
+            depth: self.depth,
+            parent_fvk_tag: self.parent_fvk_tag,
+            child_index: self.child_index,
+            chain_code: self.chain_code,
+            expsk: self.expsk.clone(),
+            dk: self.dk
+        }
+    }
+}
+
+impl<E: JubjubEngine> ExtendedSpendingKey<E> {
+    pub fn master(seed: &[u8]) -> Self {
+        let mut h = Blake2b::with_params(64, &[], &[], ZIP32_SAPLING_MASTER_PERSONALIZATION);
+        h.update(seed);
+        let mut i = [0u8; 64];
+        i.copy_from_slice(h.finalize().as_ref());
+
+        let (sk_m, c_m) = split_i(i);
+
+        ExtendedSpendingKey {
+            depth: 0,
+            parent_fvk_tag: FvkTag::master(),
+            child_index: ChildIndex::NonHardened(0),
+            chain_code: c_m,
+            expsk: ExpandedSpendingKey::from_spending_key(&sk_m),
+            dk: DiversifierKey::from_spending_key(&sk_m)
+        }
+    }
+
+    /// Derives the key at `path` from the master key.
+    pub fn from_path(master: &Self, path: &[ChildIndex], params: &E::Params) -> Result<Self, Error> {
+        let mut xsk = master.clone();
+        for &i in path {
+            xsk = xsk.derive_child(i, params)?;
+        }
+
+        Ok(xsk)
+    }
+
+    pub fn derive_child(&self, i: ChildIndex, params: &E::Params) -> Result<Self, Error> {
+        let depth = self.depth.checked_add(1).ok_or(Error::MaxDepthExceeded)?;
+        let fvk = FullViewingKey::from_expanded_spending_key(&self.expsk, params);
+
+        let mut index = [0u8; 4];
+        (&mut index[..]).write_u32::<LittleEndian>(i.to_index()?).unwrap();
+
+        let tmp = match i {
+            ChildIndex::Hardened(_) => {
+                let mut encoded = vec![];
+                self.expsk.write(&mut encoded).expect("writes to a vector");
+                prf_expand_vec(&self.chain_code.0, &[&[0x11], &encoded, &self.dk.0, &index])
+            },
+            ChildIndex::NonHardened(_) => {
+                let mut encoded = vec![];
+                fvk.write(&mut encoded).expect("writes to a vector");
+                prf_expand_vec(&self.chain_code.0, &[&[0x12], &encoded, &self.dk.0, &index])
+            }
+        };
+        let (i_l, c_i) = split_i(tmp);
+
+        let mut ask = E::Fs::to_uniform(&prf_expand(&i_l, &[0x13]));
+        ask.add_assign(&self.expsk.ask);
+
+        let mut nsk = E::Fs::to_uniform(&prf_expand(&i_l, &[0x14]));
+        nsk.add_assign(&self.expsk.nsk);
+
+        Ok(ExtendedSpendingKey {
+            depth: depth,
+            parent_fvk_tag: fvk_tag(&fvk),
+            child_index: i,
+            chain_code: c_i,
+            expsk: ExpandedSpendingKey {
+                ask: ask,
+                nsk: nsk,
+                ovk: derive_child_ovk(&i_l, &self.expsk.ovk)
+            },
+            dk: derive_child_dk(&i_l, &self.dk)
+        })
+    }
+
+    pub fn to_extended_full_viewing_key(&self, params: &E::Params) -> ExtendedFullViewingKey<E> {
+        ExtendedFullViewingKey {
+            depth: self.depth,
+            parent_fvk_tag: self.parent_fvk_tag,
+            child_index: self.child_index,
+            chain_code: self.chain_code,
+            fvk: FullViewingKey::from_expanded_spending_key(&self.expsk, params),
+            dk: self.dk
+        }
+    }
+
+    /// Returns the address with the first valid diversifier.
+    pub fn default_address(&self, params: &E::Params) -> (DiversifierIndex, PaymentAddress<E>) {
+        self.to_extended_full_viewing_key(params).default_address(params)
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let (depth, parent_fvk_tag, child_index, chain_code) = read_header(&mut reader)?;
+        let expsk = ExpandedSpendingKey::read(&mut reader)?;
+
+        let mut dk = [0u8; 32];
+        reader.read_exact(&mut dk)?;
+
+        Ok(ExtendedSpendingKey {
+            depth: depth,
+            parent_fvk_tag: parent_fvk_tag,
+            child_index: child_index,
+            chain_code: chain_code,
+            expsk: expsk,
+            dk: DiversifierKey(dk)
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write_header(&mut writer, self.depth, &self.parent_fvk_tag, &self.child_index, &self.chain_code)?;
+        self.expsk.write(&mut writer)?;
+        writer.write_all(&self.dk.0)
+    }
+}
+
+pub struct ExtendedFullViewingKey<E: JubjubEngine> {
+    pub depth: u8,
+    pub parent_fvk_tag: FvkTag,
+    pub child_index: ChildIndex,
+    pub chain_code: ChainCode,
+    pub fvk: FullViewingKey<E>,
+    pub dk: DiversifierKey
+}
+
+impl<E: JubjubEngine> Clone for ExtendedFullViewingKey<E> {
+    fn clone(&self) -> Self {
+        ExtendedFullViewingKey {
+            depth: self.depth,
+            parent_fvk_tag: self.parent_fvk_tag,
+            child_index: self.child_index,
+            chain_code: self.chain_code,
+            fvk: self.fvk.clone(),
+            dk: self.dk
+        }
+    }
+}
+
+impl<E: JubjubEngine> ExtendedFullViewingKey<E> {
+    /// Derives a non-hardened child, hardened ones need the spending key.
+    pub fn derive_child(&self, i: ChildIndex, params: &E::Params) -> Result<Self, Error> {
+        if let ChildIndex::Hardened(_) = i {
+            return Err(Error::HardenedFromViewingKey);
+        }
+        let index = i.to_index()?;
+        let depth = self.depth.checked_add(1).ok_or(Error::MaxDepthExceeded)?;
+
+        let mut index_bytes = [0u8; 4];
+        (&mut index_bytes[..]).write_u32::<LittleEndian>(index).unwrap();
+
+        let mut encoded = vec![];
+        self.fvk.write(&mut encoded).expect("writes to a vector");
+        let (i_l, c_i) = split_i(prf_expand_vec(&self.chain_code.0, &[&[0x12], &encoded, &self.dk.0, &index_bytes]));
+
+        let i_ask = E::Fs::to_uniform(&prf_expand(&i_l, &[0x13]));
+        let i_nsk = E::Fs::to_uniform(&prf_expand(&i_l, &[0x14]));
+
+        let ak = params.generator(FixedGenerators::SpendingKeyGenerator)
+                       .mul(i_ask, params)
+                       .add(&self.fvk.vk.ak, params);
+        let nk = params.generator(FixedGenerators::ProofGenerationKey)
+                       .mul(i_nsk, params)
+                       .add(&self.fvk.vk.nk, params);
+
+        Ok(ExtendedFullViewingKey {
+            depth: depth,
+            parent_fvk_tag: fvk_tag(&self.fvk),
+            child_index: i,
+            chain_code: c_i,
+            fvk: FullViewingKey {
+                vk: ViewingKey {
+                    ak: ak,
+                    nk: nk
+                },
+                ovk: derive_child_ovk(&i_l, &self.fvk.ovk)
+            },
+            dk: derive_child_dk(&i_l, &self.dk)
+        })
+    }
+
+    /// Returns the address for the diversifier index `j`, if it's valid.
+    pub fn address(&self, j: DiversifierIndex, params: &E::Params) -> Option<PaymentAddress<E>> {
+        self.fvk.vk.into_payment_address(self.dk.diversifier(j), params)
+    }
+
+    /// Returns the address with the first valid diversifier.
+    pub fn default_address(&self, params: &E::Params) -> (DiversifierIndex, PaymentAddress<E>) {
+        self.dk.find_address(&self.fvk.vk, DiversifierIndex::new(), params)
+               .expect("a valid diversifier exists")
+    }
+
+    pub fn read<R: Read>(mut reader: R, params: &E::Params) -> io::Result<Self> {
+        let (depth, parent_fvk_tag, child_index, chain_code) = read_header(&mut reader)?;
+        let fvk = FullViewingKey::read(&mut reader, params)?;
+
+        let mut dk = [0u8; 32];
+        reader.read_exact(&mut dk)?;
+
+        Ok(ExtendedFullViewingKey {
+            depth: depth,
+            parent_fvk_tag: parent_fvk_tag,
+            child_index: child_index,
+            chain_code: chain_code,
+            fvk: fvk,
+            dk: DiversifierKey(dk)
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write_header(&mut writer, self.depth, &self.parent_fvk_tag, &self.child_index, &self.chain_code)?;
+        self.fvk.write(&mut writer)?;
+        writer.write_all(&self.dk.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bellman::pairing::bls12_381::Bls12;
+
+    use jubjub::JubjubBls12;
+
+    use super::*;
+
+    #[test]
+    fn test_child_derivation() {
+        let params = &JubjubBls12::new();
+        let seed: Vec<u8> = (0..32).collect();
+
+        let m = ExtendedSpendingKey::<Bls12>::master(&seed);
+        let m_fvk = m.to_extended_full_viewing_key(params);
+        assert_eq!(m.depth, 0);
+        assert_eq!(m.parent_fvk_tag, FvkTag::master());
+
+        // Non-hardened children can be derived from the viewing key alone
+        for i in 0..3 {
+            let i = ChildIndex::NonHardened(i);
+            let xsk = m.derive_child(i, params).unwrap().to_extended_full_viewing_key(params);
+            let xfvk = m_fvk.derive_child(i, params).unwrap();
+
+            assert_eq!(xsk.depth, 1);
+            assert_eq!(xsk.child_index, i);
+            assert_eq!(xsk.parent_fvk_tag, xfvk.parent_fvk_tag);
+            assert_eq!(xsk.chain_code, xfvk.chain_code);
+            assert!(xsk.fvk.vk.ak == xfvk.fvk.vk.ak);
+            assert!(xsk.fvk.vk.nk == xfvk.fvk.vk.nk);
+            assert!(xsk.fvk.ovk == xfvk.fvk.ovk);
+            assert!(xsk.dk == xfvk.dk);
+
+            let (j, address) = xfvk.default_address(params);
+            assert!(xfvk.address(j, params).unwrap().pk_d == address.pk_d);
+        }
+
+        assert_eq!(m_fvk.derive_child(ChildIndex::Hardened(0), params).err(), Some(Error::HardenedFromViewingKey));
+
+        // Hardened and non-hardened children with the same number differ
+        let hardened = m.derive_child(ChildIndex::Hardened(0), params).unwrap();
+        let non_hardened = m.derive_child(ChildIndex::NonHardened(0), params).unwrap();
+        assert!(hardened.expsk.ask != non_hardened.expsk.ask);
+        assert!(hardened.chain_code != non_hardened.chain_code);
+
+        // m/32'/133'/0'
+        let path = [ChildIndex::Hardened(32), ChildIndex::Hardened(133), ChildIndex::Hardened(0)];
+        let account = ExtendedSpendingKey::from_path(&m, &path, params).unwrap();
+        assert_eq!(account.depth, 3);
+        assert_eq!(account.child_index.to_index(), Ok(1 << 31));
+
+        let parent = ExtendedSpendingKey::from_path(&m, &path[0..2], params).unwrap();
+        assert_eq!(account.parent_fvk_tag, fvk_tag(&parent.to_extended_full_viewing_key(params).fvk));
+    }
+
+    #[test]
+    fn test_extended_key_serialization() {
+        let params = &JubjubBls12::new();
+
+        let m = ExtendedSpendingKey::<Bls12>::master(b"a seed of at least 32 bytes long");
+        let xsk = m.derive_child(ChildIndex::Hardened(7), params).unwrap()
+                   .derive_child(ChildIndex::NonHardened(3), params).unwrap();
+
+        let mut bytes = vec![];
+        xsk.write(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), EXTENDED_KEY_BYTES);
+
+        let decoded = ExtendedSpendingKey::<Bls12>::read(&bytes[..]).unwrap();
+        let mut again = vec![];
+        decoded.write(&mut again).unwrap();
+        assert_eq!(bytes, again);
+        assert_eq!(decoded.child_index, ChildIndex::NonHardened(3));
+
+        let xfvk = xsk.to_extended_full_viewing_key(params);
+        let mut bytes = vec![];
+        xfvk.write(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), EXTENDED_KEY_BYTES);
+
+        let decoded = ExtendedFullViewingKey::<Bls12>::read(&bytes[..], params).unwrap();
+        let mut again = vec![];
+        decoded.write(&mut again).unwrap();
+        assert_eq!(bytes, again);
+
+        // Truncated encodings are rejected
+        assert!(ExtendedFullViewingKey::<Bls12>::read(&bytes[0..100], params).is_err());
+    }
+
+    #[test]
+    fn test_derivation_paths() {
+        // Derives m, m/1, m/1/2' and m/1/2'/3, the last one both from the
+        // spending key and from the full viewing key of m/1/2'
+        let params = &JubjubBls12::new();
+        let seed: Vec<u8> = (0..32).collect();
+
+        let i1 = ChildIndex::NonHardened(1);
+        let i2h = ChildIndex::Hardened(2);
+        let i3 = ChildIndex::NonHardened(3);
+
+        let m = ExtendedSpendingKey::<Bls12>::master(&seed);
+        let m_1 = m.derive_child(i1, params).unwrap();
+        let m_1_2h = ExtendedSpendingKey::from_path(&m, &[i1, i2h], params).unwrap();
+        let m_1_2hv_3 = m_1_2h.to_extended_full_viewing_key(params).derive_child(i3, params).unwrap();
+        let m_1_2h_3 = m_1_2h.derive_child(i3, params).unwrap().to_extended_full_viewing_key(params);
+
+        assert_eq!(m_1.parent_fvk_tag, fvk_tag(&m.to_extended_full_viewing_key(params).fvk));
+        assert_eq!(m_1_2h.depth, 2);
+        assert_eq!(m_1_2h.child_index.to_index(), Ok((1 << 31) + 2));
+
+        let mut expected = vec![];
+        m_1_2h_3.write(&mut expected).unwrap();
+        let mut encoded = vec![];
+        m_1_2hv_3.write(&mut encoded).unwrap();
+        assert_eq!(encoded, expected);
+
+        // The header of the encoding stores the index with the top bit set
+        let mut encoded = vec![];
+        m_1_2h.write(&mut encoded).unwrap();
+        assert_eq!(encoded[0], 2);
+        assert_eq!(&encoded[5..9], &[2, 0, 0, 0x80]);
+    }
+
+    #[test]
+    fn test_invalid_derivation() {
+        let params = &JubjubBls12::new();
+        let m = ExtendedSpendingKey::<Bls12>::master(b"a seed of at least 32 bytes long");
+
+        // Numbers of at least 2^31 don't fit next to the hardened flag
+        assert_eq!(ChildIndex::Hardened(1 << 31).to_index(), Err(Error::InvalidChildIndex));
+        assert_eq!(ChildIndex::NonHardened(1 << 31).to_index(), Err(Error::InvalidChildIndex));
+        assert_eq!(ChildIndex::Hardened((1 << 31) - 1).to_index(), Ok(u32::max_value()));
+        assert_eq!(m.derive_child(ChildIndex::Hardened(1 << 31), params).err(), Some(Error::InvalidChildIndex));
+
+        let m_fvk = m.to_extended_full_viewing_key(params);
+        assert_eq!(m_fvk.derive_child(ChildIndex::NonHardened(u32::max_value()), params).err(), Some(Error::InvalidChildIndex));
+
+        let mut invalid = m.clone();
+        invalid.child_index = ChildIndex::Hardened(1 << 31);
+        assert!(invalid.write(&mut vec![]).is_err());
+
+        // The depth is encoded in a single byte
+        let mut deepest = m.clone();
+        deepest.depth = u8::max_value();
+        assert_eq!(deepest.derive_child(ChildIndex::Hardened(0), params).err(), Some(Error::MaxDepthExceeded));
+
+        let mut deepest_fvk = m_fvk.clone();
+        deepest_fvk.depth = u8::max_value();
+        assert_eq!(deepest_fvk.derive_child(ChildIndex::NonHardened(0), params).err(), Some(Error::MaxDepthExceeded));
+    }
+}
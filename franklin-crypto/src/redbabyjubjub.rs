@@ -1,12 +1,21 @@
 //! Implementation of RedJubjub, a specialization of RedDSA to the Jubjub curve.
 //! See section 5.4.6 of the Sapling protocol specification.
+//!
+//! Signatures and public keys use the same canonical encoding and strict
+//! decoding rules as `eddsa`. `verify` and `batch_verify` decode `R`
+//! strictly, so signatures whose `R` is not a prime order point, which
+//! the cofactored verification equation used to accept, are rejected.
 
 use bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
 use rand::{Rng, Rand};
 use std::io::{self, Read, Write};
 
+use hex;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+
 use babyjubjub::{FixedGenerators, JubjubEngine, JubjubParams, Unknown, edwards::Point};
 use baby_util::{hash_to_scalar};
+use util::{self, decode_hex, deserialize_hex};
 
 fn read_scalar<E: JubjubEngine, R: Read>(reader: R) -> io::Result<E::Fs> {
     let mut s_repr = <E::Fs as PrimeField>::Repr::default();
@@ -25,11 +34,29 @@ fn write_scalar<E: JubjubEngine, W: Write>(s: &E::Fs, writer: W) -> io::Result<(
     s.into_repr().write_le(writer)
 }
 
+fn read_point_strict<E: JubjubEngine, R: Read>(
+    reader: R,
+    params: &E::Params
+) -> io::Result<Point<E, Unknown>> {
+    util::read_point_strict(
+        reader,
+        |bytes| Point::read(bytes, params),
+        |p, bytes| p.write(bytes),
+        |p| p.mul_by_cofactor(params) != Point::zero() && p.as_prime_order(params).is_some()
+    )
+}
+
 fn h_star<E: JubjubEngine>(a: &[u8], b: &[u8]) -> E::Fs {
     hash_to_scalar::<E>(b"Zcash_RedJubjubH", a, b)
 }
 
-#[derive(Copy, Clone)]
+/// Length of the canonical signature encoding `R || s`.
+pub const SIGNATURE_BYTES: usize = 64;
+
+/// Length of the canonical compressed public key encoding.
+pub const PUBLIC_KEY_BYTES: usize = 32;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub struct Signature {
     rbar: [u8; 32],
     sbar: [u8; 32],
@@ -52,6 +79,42 @@ impl Signature {
         writer.write_all(&self.rbar)?;
         writer.write_all(&self.sbar)
     }
+
+    pub fn from_bytes(bytes: &[u8; SIGNATURE_BYTES]) -> Self {
+        let mut rbar = [0u8; 32];
+        let mut sbar = [0u8; 32];
+        rbar.copy_from_slice(&bytes[..32]);
+        sbar.copy_from_slice(&bytes[32..]);
+        Signature { rbar, sbar }
+    }
+
+    pub fn to_bytes(&self) -> [u8; SIGNATURE_BYTES] {
+        let mut bytes = [0u8; SIGNATURE_BYTES];
+        bytes[..32].copy_from_slice(&self.rbar);
+        bytes[32..].copy_from_slice(&self.sbar);
+        bytes
+    }
+
+    pub fn from_hex(s: &str) -> io::Result<Self> {
+        let bytes = decode_hex(s, SIGNATURE_BYTES)?;
+        Self::read(&bytes[..])
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(&self.to_bytes()[..])
+    }
+}
+
+impl Serialize for Signature {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for Signature {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_hex(deserializer, "a hex encoded 64 byte signature", Signature::from_hex)
+    }
 }
 
 impl<E: JubjubEngine> PrivateKey<E> {
@@ -124,6 +187,20 @@ impl<E: JubjubEngine> PublicKey<E> {
         self.0.write(writer)
     }
 
+    /// Strictly decodes a public key, see the `eddsa` module documentation.
+    pub fn from_bytes(bytes: &[u8; PUBLIC_KEY_BYTES], params: &E::Params) -> io::Result<Self> {
+        let p = read_point_strict::<E, _>(&bytes[..], params)?;
+        Ok(PublicKey(p))
+    }
+
+    pub fn to_bytes(&self) -> [u8; PUBLIC_KEY_BYTES] {
+        let mut bytes = [0u8; PUBLIC_KEY_BYTES];
+        self.0.write(&mut bytes[..]).expect("points should serialize to 32 bytes");
+        bytes
+    }
+
+    /// Verifies the signature with the cofactored equation. `R` must be
+    /// the canonical encoding of a prime order point.
     pub fn verify(
         &self,
         msg: &[u8],
@@ -135,8 +212,8 @@ impl<E: JubjubEngine> PublicKey<E> {
         let c = h_star::<E>(&sig.rbar[..], msg);
 
         // Signature checks:
-        // R != invalid
-        let r = match Point::read(&sig.rbar[..], params) {
+        // R != invalid, canonical and of prime order
+        let r = match read_point_strict::<E, &[u8]>(&sig.rbar[..], params) {
             Ok(r) => r,
             Err(_) => return false,
         };
@@ -160,6 +237,8 @@ pub struct BatchEntry<'a, E: JubjubEngine> {
     sig: Signature,
 }
 
+/// Verifies the signatures at once. As in `PublicKey::verify`, every
+/// `R` must be the canonical encoding of a prime order point.
 // TODO: #82: This is a naive implementation currently,
 // and doesn't use multiexp.
 pub fn batch_verify<'a, E: JubjubEngine, R: Rng>(
@@ -172,7 +251,7 @@ pub fn batch_verify<'a, E: JubjubEngine, R: Rng>(
     let mut acc = Point::<E, Unknown>::zero();
 
     for entry in batch {
-        let mut r = match Point::<E, Unknown>::read(&entry.sig.rbar[..], params) {
+        let mut r = match read_point_strict::<E, &[u8]>(&entry.sig.rbar[..], params) {
             Ok(r) => r,
             Err(_) => return false,
         };
@@ -204,7 +283,7 @@ pub fn batch_verify<'a, E: JubjubEngine, R: Rng>(
 
 #[cfg(test)]
 mod tests {
-    use bellman::pairing::bn256::Bn256;
+    use bellman::pairing::bn256::{Bn256, Fr};
     use rand::thread_rng;
 
     use babyjubjub::{JubjubBn256, fs::Fs, edwards};
@@ -306,6 +385,203 @@ mod tests {
         }
     }
 
+    #[test]
+    fn strict_decoding() {
+        let rng = &mut thread_rng();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+        let params = &JubjubBn256::new();
+
+        let sk = PrivateKey::<Bn256>(rng.gen());
+        let vk = PublicKey::from_private(&sk, p_g, params);
+        let msg = b"Foo bar";
+        let sig = sk.sign(msg, rng, p_g, params);
+
+        let sig_bytes = sig.to_bytes();
+        assert_eq!(Signature::from_bytes(&sig_bytes), sig);
+        assert_eq!(Signature::from_hex(&sig.to_hex()).unwrap(), sig);
+        assert!(PublicKey::<Bn256>::from_bytes(&vk.to_bytes(), params).unwrap().0 == vk.0);
+
+        // s + order(G) is not canonical
+        let mut s_repr = <Fs as PrimeField>::Repr::default();
+        s_repr.read_le(&sig_bytes[32..]).unwrap();
+        s_repr.add_nocarry(&Fs::char());
+        let mut malleated = sig_bytes;
+        s_repr.write_le(&mut malleated[32..]).unwrap();
+        assert!(!vk.verify(msg, &Signature::from_bytes(&malleated), p_g, params));
+
+        // the identity as R, with and without a redundant sign bit
+        let mut identity = [0u8; PUBLIC_KEY_BYTES];
+        identity[0] = 1;
+        assert!(PublicKey::<Bn256>::from_bytes(&identity, params).is_err());
+        let mut malformed = sig_bytes;
+        malformed[..32].copy_from_slice(&identity);
+        assert!(!vk.verify(msg, &Signature::from_bytes(&malformed), p_g, params));
+        malformed[31] |= 0x80;
+        assert!(!vk.verify(msg, &Signature::from_bytes(&malformed), p_g, params));
+
+        // mixed order public key
+        let zero = edwards::Point::zero();
+        let p8 = loop {
+            let r = edwards::Point::<Bn256, _>::rand(rng, params).mul(Fs::char(), params);
+            if r.double(params).double(params) != zero && r.double(params).double(params).double(params) == zero {
+                break r;
+            }
+        };
+        let mixed = PublicKey::<Bn256>(vk.0.add(&p8, params));
+        assert!(PublicKey::<Bn256>::from_bytes(&mixed.to_bytes(), params).is_err());
+    }
+
+    #[test]
+    fn small_order_r_is_rejected() {
+        let rng = &mut thread_rng();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+        let params = &JubjubBn256::new();
+        let zero = edwards::Point::zero();
+
+        let p8 = loop {
+            let r = edwards::Point::<Bn256, _>::rand(rng, params).mul(Fs::char(), params);
+            if r.double(params).double(params) != zero && r.double(params).double(params).double(params) == zero {
+                break r;
+            }
+        };
+
+        let sk = PrivateKey::<Bn256>(rng.gen());
+        let vk = PublicKey::from_private(&sk, p_g, params);
+        let msg = b"Foo bar";
+
+        // R of small order and R with a small order component, with s
+        // computed for them, satisfy the cofactored equation
+        let r: Fs = rng.gen();
+        let r_g: edwards::Point<Bn256, Unknown> = params.generator(p_g).mul(r, params).into();
+        for (r_point, mut s) in vec![(p8.clone(), Fs::zero()), (r_g.add(&p8, params), r)] {
+            let mut rbar = [0u8; 32];
+            r_point.write(&mut rbar[..]).unwrap();
+            let mut c = h_star::<Bn256>(&rbar[..], msg);
+            c.mul_assign(&sk.0);
+            s.add_assign(&c);
+            let mut sbar = [0u8; 32];
+            write_scalar::<Bn256, &mut [u8]>(&s, &mut sbar[..]).unwrap();
+            let sig = Signature { rbar, sbar };
+
+            let c = h_star::<Bn256>(&rbar[..], msg);
+            assert!(vk.0.mul(c, params).add(&r_point, params).add(
+                &params.generator(p_g).mul(s, params).negate().into(),
+                params
+            ).mul_by_cofactor(params) == edwards::Point::zero());
+
+            assert!(!vk.verify(msg, &sig, p_g, params));
+
+            let batch = vec![BatchEntry { vk: PublicKey(vk.0.clone()), msg: msg, sig: sig }];
+            assert!(!batch_verify(rng, &batch, p_g, params));
+        }
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        use serde::de::IntoDeserializer;
+        use serde::de::value::{StrDeserializer, Error as ValueError};
+
+        let rng = &mut thread_rng();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+        let params = &JubjubBn256::new();
+
+        let sk = PrivateKey::<Bn256>(rng.gen());
+        let sig = sk.sign(b"Foo bar", rng, p_g, params);
+
+        let hex = sig.to_hex();
+        let deserializer: StrDeserializer<ValueError> = hex.as_str().into_deserializer();
+        assert_eq!(Signature::deserialize(deserializer).unwrap(), sig);
+
+        let deserializer: StrDeserializer<ValueError> = "00".into_deserializer();
+        assert!(Signature::deserialize(deserializer).is_err());
+    }
+
+    #[test]
+    fn circomlib_vectors() {
+        // Vectors of circomlib's Baby Jubjub tests (test/babyjub.js in
+        // https://github.com/iden3/circomlib), which uses the same curve
+        // form. circomlib signs with other hashes and generators, so the
+        // cross-check covers the curve arithmetic and the key encoding.
+        fn point(x: &str, y: &str, params: &JubjubBn256) -> edwards::Point<Bn256, Unknown> {
+            edwards::Point::from_xy(Fr::from_str(x).unwrap(), Fr::from_str(y).unwrap(), params).unwrap()
+        }
+
+        fn xy(p: &edwards::Point<Bn256, Unknown>) -> (String, String) {
+            let (x, y) = p.into_xy();
+            (to_decimal(x), to_decimal(y))
+        }
+
+        fn to_decimal(f: Fr) -> String {
+            use num_bigint::BigUint;
+
+            let mut bytes = vec![];
+            f.into_repr().write_be(&mut bytes).unwrap();
+            BigUint::from_bytes_be(&bytes).to_string()
+        }
+
+        let params = &JubjubBn256::new();
+
+        let p1 = point(
+            "17777552123799933955779906779655732241715742912184938656739573121738514868268",
+            "2626589144620713026669568689430873010625803728049924121243784502389097019475",
+            params
+        );
+        let p2 = point(
+            "16540640123574156134436876038791482806971768689494387082833631921987005038935",
+            "20819045374670962167435360035096875258406992893633759881276124905556507972311",
+            params
+        );
+        let p1_double = (
+            "6890855772600357754907169075114257697580319025794532037257385534741338397365".to_string(),
+            "4338620300185947561074059802482547481416142213883829469920100239455078257889".to_string()
+        );
+
+        // "Should add 2 same numbers", "Should add 2 different numbers"
+        assert_eq!(xy(&p1.add(&p1, params)), p1_double);
+        assert_eq!(xy(&p1.double(params)), p1_double);
+        assert_eq!(xy(&p1.add(&p2, params)), (
+            "7916061937171219682591368294088513039687205273691143098332585753343424131937".to_string(),
+            "14035240266687799601661095864649209771790948434046947201833777492504781204499".to_string()
+        ));
+
+        // "should inSubgroup 1", the scalars below are reduced by the order
+        let p1_prime = p1.as_prime_order(params).unwrap();
+        let p1_double = point(&p1_double.0, &p1_double.1, params).as_prime_order(params).unwrap();
+
+        // "should mulPointEscalar 0", "1" and "2"
+        assert_eq!(xy(&p1_prime.mul(Fs::from_str("3").unwrap(), params).into()), (
+            "19372461775513343691590086534037741906533799473648040012278229434133483800898".to_string(),
+            "9458658722007214007257525444427903161243386465067105737478306991484593958249".to_string()
+        ));
+        let s = Fs::from_str("14035240266687799601661095864649209771790948434046947201833777492504781204499").unwrap();
+        assert_eq!(xy(&p1_prime.mul(s, params).into()), (
+            "17070357974431721403481313912716834497662307308519659060910483826664480189605".to_string(),
+            "4014745322800118607127020275658861516666525056516280575712425373174125159339".to_string()
+        ));
+        let s = Fs::from_str("20819045374670962167435360035096875258406992893633759881276124905556507972311").unwrap();
+        assert_eq!(xy(&p1_double.mul(s, params).into()), (
+            "13563888653650925984868671744672725781658357821216877865297235725727006259983".to_string(),
+            "8442587202676550862664528699803615547505326611544120184665036919364004251662".to_string()
+        ));
+
+        // "should packPoint - unpackPoint 1" and "2". circomlib sets the
+        // top bit for x > (p - 1) / 2, this encoding for odd x, so only
+        // the bytes of y are shared.
+        for &(ref p, packed) in [
+            (p1_prime.clone(), "53b81ed5bffe9545b54016234682e7b2f699bd42a5e9eae27ff4051bc698ce85"),
+            (p1_double.clone(), "e114eb17eddf794f063a68fecac515e3620e131976108555735c8b0773929709")
+        ].iter() {
+            let packed = hex::decode(packed).unwrap();
+            let vk = PublicKey::<Bn256>(p.clone().into());
+            let encoding = vk.to_bytes();
+            assert_eq!(encoding[..31], packed[..31]);
+            assert_eq!(encoding[31] & 0x7f, packed[31] & 0x7f);
+
+            let decoded = PublicKey::<Bn256>::from_bytes(&encoding, params).unwrap();
+            assert!(decoded.0 == vk.0);
+        }
+    }
+
     #[test]
     fn random_signatures() {
         let rng = &mut thread_rng();
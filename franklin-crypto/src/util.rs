@@ -2,6 +2,13 @@ use blake2_rfc::blake2b::Blake2b;
 use blake2_rfc::blake2s::Blake2s;
 use crypto::sha2::Sha256;
 
+use std::fmt;
+use std::io::{self, Read};
+
+use hex;
+use serde::Deserializer;
+use serde::de::{self, Visitor};
+
 use jubjub::{JubjubEngine, ToUniform};
 
 
@@ -30,4 +37,84 @@ pub fn sha256_hash_to_scalar<E: JubjubEngine>(persona: &[u8], a: &[u8], b: &[u8]
     let mut bytes = [0u8; 32];
     hasher.result(&mut bytes[..]);
     E::Fs::to_uniform_32(bytes.as_ref())
-}
\ No newline at end of file
+}
+
+/// Reads a 32 byte compressed point, rejecting every encoding that is
+/// not the unique canonical encoding of a prime order point. `read` and
+/// `write` are the encoding of the curve, and `is_prime_order` must hold
+/// exactly for the points of the prime order subgroup other than the
+/// identity. Shared by `eddsa` and `redbabyjubjub`, whose curves have
+/// separate point types.
+pub(crate) fn read_point_strict<P, R, D, W, C>(
+    mut reader: R,
+    read: D,
+    write: W,
+    is_prime_order: C
+) -> io::Result<P>
+    where R: Read,
+          D: FnOnce(&[u8]) -> io::Result<P>,
+          W: FnOnce(&P, &mut [u8]) -> io::Result<()>,
+          C: FnOnce(&P) -> bool
+{
+    let mut bytes = [0u8; 32];
+    reader.read_exact(&mut bytes)?;
+
+    // checks that y is in the field and that the point is on curve
+    let p = read(&bytes[..])?;
+
+    // x = 0 can be encoded with both signs
+    let mut encoding = [0u8; 32];
+    write(&p, &mut encoding[..])?;
+    if encoding != bytes {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "non-canonical point encoding"));
+    }
+
+    if !is_prime_order(&p) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "point is not of prime order"));
+    }
+
+    Ok(p)
+}
+
+pub(crate) fn decode_hex(s: &str, expected_len: usize) -> io::Result<Vec<u8>> {
+    let bytes = hex::decode(s).map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidInput, "invalid hex string")
+    })?;
+
+    if bytes.len() != expected_len {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid encoding length"));
+    }
+
+    Ok(bytes)
+}
+
+/// Deserializes a value from the hex string decoded by `from_hex`.
+pub(crate) fn deserialize_hex<'de, D, T>(
+    deserializer: D,
+    expecting: &'static str,
+    from_hex: fn(&str) -> io::Result<T>
+) -> Result<T, D::Error>
+    where D: Deserializer<'de>
+{
+    struct HexVisitor<T> {
+        expecting: &'static str,
+        from_hex: fn(&str) -> io::Result<T>
+    }
+
+    impl<'de, T> Visitor<'de> for HexVisitor<T> {
+        type Value = T;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str(self.expecting)
+        }
+
+        fn visit_str<Err: de::Error>(self, v: &str) -> Result<Self::Value, Err> {
+            (self.from_hex)(v).map_err(de::Error::custom)
+        }
+    }
+
+    deserializer.deserialize_str(HexVisitor {
+        expecting: expecting,
+        from_hex: from_hex
+    })
+}
@@ -0,0 +1,266 @@
+//! This is an ECVRF-style verifiable random function on top of
+//! `eddsa` keys, following the structure of RFC 9381 with choices
+//! that keep the verifier cheap inside a circuit (see `circuit::vrf`):
+//!
+//! * the input is mapped to the curve as `H = hash_to_curve(Y || alpha)`
+//!   with Elligator 2 instead of try-and-increment;
+//! * the challenge is the first 128 bits of BLAKE2s over the encodings of
+//!   `Y, H, Gamma, U, V`;
+//! * the output is BLAKE2s over the encoding of `cofactor . Gamma`.
+//!
+//! A proof is 80 bytes: the encoding of `Gamma`, the little-endian
+//! 16 byte challenge `c` and the little-endian scalar `s`.
+
+use bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
+use std::io::{self, Read, Write};
+
+use blake2_rfc::blake2s::Blake2s;
+
+use jubjub::{
+    FixedGenerators,
+    JubjubEngine,
+    JubjubParams,
+    PrimeOrder,
+    Unknown,
+    edwards::Point
+};
+
+use eddsa::{PrivateKey, PublicKey};
+use hash_to_curve::hash_to_curve;
+use util::hash_to_scalar;
+
+use ::constants::{
+    VRF_HASH_TO_CURVE_PERSONALIZATION,
+    VRF_CHALLENGE_PERSONALIZATION,
+    VRF_OUTPUT_PERSONALIZATION
+};
+
+/// Length of the challenge in bits.
+pub const CHALLENGE_BITS: usize = 128;
+
+/// Length of the proof encoding `Gamma || c || s`.
+pub const PROOF_BYTES: usize = 80;
+
+/// Length of the VRF output.
+pub const OUTPUT_BYTES: usize = 32;
+
+#[derive(Clone)]
+pub struct Proof<E: JubjubEngine> {
+    pub gamma: Point<E, Unknown>,
+    pub c: E::Fs,
+    pub s: E::Fs
+}
+
+fn point_bytes<E: JubjubEngine, S>(p: &Point<E, S>) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    p.write(&mut bytes[..]).expect("has enough space");
+
+    bytes
+}
+
+/// Maps the public key and the VRF input to the curve.
+pub fn hash_to_point<E: JubjubEngine>(
+    pk: &PublicKey<E>,
+    alpha: &[u8],
+    params: &E::Params
+) -> Point<E, PrimeOrder>
+{
+    let mut input = point_bytes(&pk.0).to_vec();
+    input.extend_from_slice(alpha);
+
+    hash_to_curve::<E>(VRF_HASH_TO_CURVE_PERSONALIZATION, &input, params)
+}
+
+fn read_challenge<E: JubjubEngine>(bytes: &[u8]) -> E::Fs {
+    let mut padded = [0u8; 32];
+    padded[0..(CHALLENGE_BITS / 8)].copy_from_slice(&bytes[0..(CHALLENGE_BITS / 8)]);
+
+    let mut c = <E::Fs as PrimeField>::Repr::default();
+    c.read_le(&padded[..]).expect("has enough space");
+
+    E::Fs::from_repr(c).expect("128 bits are in the field")
+}
+
+fn challenge<E: JubjubEngine>(points: &[&Point<E, Unknown>]) -> E::Fs {
+    let mut h = Blake2s::with_params(32, &[], &[], VRF_CHALLENGE_PERSONALIZATION);
+    for p in points {
+        h.update(&point_bytes(*p));
+    }
+
+    read_challenge::<E>(h.finalize().as_ref())
+}
+
+impl<E: JubjubEngine> Proof<E> {
+    pub fn read<R: Read>(mut reader: R, params: &E::Params) -> io::Result<Self> {
+        let gamma = Point::read(&mut reader, params)?;
+
+        let mut c_bytes = [0u8; CHALLENGE_BITS / 8];
+        reader.read_exact(&mut c_bytes)?;
+        let c = read_challenge::<E>(&c_bytes);
+
+        let mut s = <E::Fs as PrimeField>::Repr::default();
+        s.read_le(&mut reader)?;
+        let s = E::Fs::from_repr(s).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "scalar is not in field")
+        })?;
+
+        Ok(Proof { gamma, c, s })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        self.gamma.write(&mut writer)?;
+
+        let mut c_bytes = vec![];
+        self.c.into_repr().write_le(&mut c_bytes)?;
+        writer.write_all(&c_bytes[0..(CHALLENGE_BITS / 8)])?;
+
+        self.s.into_repr().write_le(&mut writer)
+    }
+
+    /// Computes the VRF output of the proof without verifying it.
+    pub fn to_hash(&self, params: &E::Params) -> [u8; OUTPUT_BYTES] {
+        let gamma = self.gamma.mul_by_cofactor(params);
+
+        let mut h = Blake2s::with_params(32, &[], &[], VRF_OUTPUT_PERSONALIZATION);
+        h.update(&point_bytes(&gamma));
+
+        let mut output = [0u8; OUTPUT_BYTES];
+        output.copy_from_slice(h.finalize().as_ref());
+
+        output
+    }
+}
+
+/// Evaluates the VRF on `alpha`, returning the proof and the output.
+/// The nonce is derived deterministically from the key and the input.
+pub fn prove<E: JubjubEngine>(
+    sk: &PrivateKey<E>,
+    alpha: &[u8],
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> (Proof<E>, [u8; OUTPUT_BYTES])
+{
+    let pk = PublicKey::from_private(sk, p_g, params);
+    let h = hash_to_point(&pk, alpha, params);
+
+    // Gamma = sk . H
+    let gamma: Point<E, Unknown> = h.mul(sk.0, params).into();
+
+    // k = H*(sk || H)
+    let mut sk_bytes = vec![];
+    sk.write(&mut sk_bytes).expect("writes to a vector");
+    let k = hash_to_scalar::<E>(b"Matter_VRF_Nonce", &sk_bytes, &point_bytes(&h));
+
+    let u: Point<E, Unknown> = params.generator(p_g).mul(k, params).into();
+    let v: Point<E, Unknown> = h.mul(k, params).into();
+    let h: Point<E, Unknown> = h.into();
+
+    let c = challenge::<E>(&[&pk.0, &h, &gamma, &u, &v]);
+
+    // s = k + c . sk
+    let mut s = c;
+    s.mul_assign(&sk.0);
+    s.add_assign(&k);
+
+    let proof = Proof { gamma, c, s };
+    let output = proof.to_hash(params);
+
+    (proof, output)
+}
+
+/// Verifies the proof for `alpha`, returning the VRF output if it's valid.
+pub fn verify<E: JubjubEngine>(
+    pk: &PublicKey<E>,
+    alpha: &[u8],
+    proof: &Proof<E>,
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> Option<[u8; OUTPUT_BYTES]>
+{
+    let h: Point<E, Unknown> = hash_to_point(pk, alpha, params).into();
+
+    // U = s . G - c . Y
+    let u: Point<E, Unknown> = params.generator(p_g).mul(proof.s, params).into();
+    let u = u.add(&pk.0.mul(proof.c, params).negate(), params);
+
+    // V = s . H - c . Gamma
+    let v = h.mul(proof.s, params).add(&proof.gamma.mul(proof.c, params).negate(), params);
+
+    if challenge::<E>(&[&pk.0, &h, &proof.gamma, &u, &v]) == proof.c {
+        Some(proof.to_hash(params))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bellman::pairing::bn256::Bn256;
+    use rand::{XorShiftRng, SeedableRng, Rand, Rng};
+    use hex;
+
+    use alt_babyjubjub::{AltJubjubBn256, fs::Fs};
+
+    use super::*;
+
+    #[test]
+    fn test_prove_verify() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = &AltJubjubBn256::new();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+
+        for _ in 0..10 {
+            let sk = PrivateKey::<Bn256>(rng.gen());
+            let pk = PublicKey::from_private(&sk, p_g, params);
+            let alpha: Vec<u8> = (0..rng.gen_range(0, 100)).map(|_| rng.gen()).collect();
+
+            let (proof, output) = prove(&sk, &alpha, p_g, params);
+            assert_eq!(verify(&pk, &alpha, &proof, p_g, params), Some(output));
+
+            // Deterministic
+            let (_, again) = prove(&sk, &alpha, p_g, params);
+            assert_eq!(output, again);
+
+            // Round trip
+            let mut bytes = vec![];
+            proof.write(&mut bytes).unwrap();
+            assert_eq!(bytes.len(), PROOF_BYTES);
+            let decoded = Proof::<Bn256>::read(&bytes[..], params).unwrap();
+            assert_eq!(verify(&pk, &alpha, &decoded, p_g, params), Some(output));
+
+            // Wrong input, wrong key and tampered proofs
+            let mut other_alpha = alpha.clone();
+            other_alpha.push(0);
+            assert!(verify(&pk, &other_alpha, &proof, p_g, params).is_none());
+
+            let other_pk = PublicKey::from_private(&PrivateKey::<Bn256>(rng.gen()), p_g, params);
+            assert!(verify(&other_pk, &alpha, &proof, p_g, params).is_none());
+
+            let mut tampered = proof.clone();
+            tampered.s.add_assign(&Fs::one());
+            assert!(verify(&pk, &alpha, &tampered, p_g, params).is_none());
+
+            let mut tampered = proof.clone();
+            tampered.gamma = tampered.gamma.add(&params.generator(p_g).clone().into(), params);
+            assert!(verify(&pk, &alpha, &tampered, p_g, params).is_none());
+        }
+    }
+
+    #[test]
+    fn test_vrf_vector() {
+        let params = &AltJubjubBn256::new();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+
+        let sk = PrivateKey::<Bn256>(Fs::from_str("42").unwrap());
+        let pk = PublicKey::from_private(&sk, p_g, params);
+
+        let (proof, output) = prove(&sk, b"lottery round 1", p_g, params);
+
+        let mut bytes = vec![];
+        proof.write(&mut bytes).unwrap();
+
+        assert_eq!(hex::encode(&bytes), "5067a7e8a18c4b6165f76d9f9ab4956d70b874b7e5b45074700a4dac304dd2987058a1faddce25c4f2a61e70a6ade8071a44b4789b35584e34da1b1f60df974ac00a0f77d58cef0da4db5601e9553c03");
+        assert_eq!(hex::encode(&output[..]), "d519b2d7f346fce42ac9a250d58cbf9c45e9d846cf7f8a47f858dfd97faa6142");
+        assert_eq!(verify(&pk, b"lottery round 1", &proof, p_g, params), Some(output));
+    }
+}